@@ -1,9 +1,38 @@
-use clap::{Parser, Subcommand};
-use std::env;
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand};
+use flate2::read::GzDecoder;
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    process::ExitCode,
+};
+use yoink::collection::archive::{is_collection, original_name, CollectionArchive, EntryFilter};
 use yoink::collection::collecter::Collecter;
+use yoink::collection::parsers::evtx_parser::{EventFilter, EvtxFile};
+use yoink::collection::parsers::history_parser::{history_shell, parse_history, HistoryEntry};
+use yoink::collection::parsers::journal_parser::JournalFile;
+use yoink::collection::parsers::jumplist_parser::{is_jumplist_file, parse_jumplist};
+use yoink::collection::parsers::lnk_parser::parse_lnk;
+use yoink::collection::parsers::login_parser::{parse_lastlog, parse_passwd, parse_utmp};
+use yoink::collection::parsers::mft_parser::{parse_collection_mft, parse_mft, MftEntry};
+use yoink::collection::parsers::output::{OutputFormat, RecordWriter};
+use yoink::collection::parsers::prefetch_parser::parse_prefetch;
+use yoink::collection::parsers::registry_artefacts::extract_artefacts;
+use yoink::collection::parsers::registry_parser::Hive;
+use yoink::collection::parsers::usn_parser::{
+    parse_collection_usn, MftPaths, UsnEntry, UsnJournal,
+};
 use yoink::collection::rules::{
     get_rule_name, get_rule_platform, get_rules_from_dir, CollectionRule,
 };
+use yoink::collection::timeline::{
+    mactime, parse_time_bound, parse_time_end, write_bodyfile, BodyfileEntry,
+};
+use yoink::collection::uploaders::http_uploader::upload_archive;
+use yoink::collection::uploaders::sftp_uploader::SftpUploader;
 
 #[cfg(target_os = "windows")]
 const HOSTNAME_ENV: &str = "COMPUTERNAME";
@@ -37,12 +66,944 @@ enum Commands {
         #[clap(short, long, default_value_t = format!("{0}_{1}.zip", env::var(HOSTNAME_ENV).unwrap_or("localhost".to_string()), chrono::Utc::now().timestamp_millis()))]
         /// path the to the output file, must end in .zip e.g. /path/to/output.zip
         output: String,
-        /// the name of the rules to use for collection
+        #[clap(short, long, default_value_t = String::from(""))]
+        /// upload the collection, either a http(s):// URL (e.g. a pre-signed URL), s3://bucket/key or sftp://user@host/path
+        upload: String,
+        #[command(flatten)]
+        upload_options: UploadOptions,
+        #[clap(short, long, default_value_t = String::from(""))]
+        /// collect from a raw (dd), E01, VHD or VHDX disk or filesystem image instead of the live host
+        image: String,
+        #[clap(long, default_value_t = false)]
+        /// read artefacts straight from the block device, bypassing the kernel (ext2/3/4 on Linux)
+        raw: bool,
+        #[clap(long, default_value_t = false)]
+        /// store the freed start of $UsnJrnl:$J as zeros instead of leaving it out
+        keep_sparse: bool,
+        #[clap(long, default_value_t = false)]
+        /// find files on NTFS volumes by parsing $MFT instead of walking directories, this also finds files the OS hides or denies access to
+        mft: bool,
+        /// the name of the rules to use for collection, on Linux rule paths also match hidden files and directories such as .bash_history
         rules: Vec<String>,
     },
+    /// verify the integrity of a collection against its manifest
+    Verify {
+        #[clap(short, long, default_value_t = String::from(""))]
+        /// the password the collection was encrypted with
+        encryption_key: String,
+        /// path to the collection to verify
+        archive: String,
+    },
+    /// list the artefacts in a collection
+    Ls {
+        #[command(flatten)]
+        selection: EntrySelection,
+        /// path to the collection to list
+        archive: String,
+    },
+    /// extract artefacts from a collection, restoring their original names and timestamps
+    Extract {
+        #[command(flatten)]
+        selection: EntrySelection,
+        #[clap(short, long, default_value_t = String::from("."))]
+        /// the directory to extract the artefacts to
+        output: String,
+        /// path to the collection to extract
+        archive: String,
+    },
+    /// parse collected artefacts into records, from a collection or a standalone file
+    Parse {
+        #[command(subcommand)]
+        artefact: ParseCommands,
+    },
+    /// build a mactime style timeline from the metadata of every artefact in a collection and its $MFT records
+    Timeline {
+        #[clap(short, long, default_value_t = String::from(""))]
+        /// the password the collection was encrypted with
+        encryption_key: String,
+        #[clap(short, long, default_value_t = String::from(""))]
+        /// the file to write the timeline to, it is printed when no file is given
+        output: String,
+        #[clap(short, long, default_value_t = String::from(""))]
+        /// also write the Sleuth Kit bodyfile the timeline is built from to this file
+        bodyfile: String,
+        #[command(flatten)]
+        window: TimeWindow,
+        /// path to the collection to build the timeline from
+        archive: String,
+    },
+    /// upload an existing collection, resuming any interrupted s3:// upload
+    Upload {
+        #[command(flatten)]
+        upload_options: UploadOptions,
+        /// path to the collection to upload
+        archive: String,
+        /// a http(s):// URL (e.g. a pre-signed URL), s3://bucket/key or sftp://user@host/path
+        destination: String,
+    },
 }
 
-fn main() {
+#[derive(Subcommand)]
+enum ParseCommands {
+    /// parse $MFT into one record per file with its full path, timestamps and streams
+    Mft(ParseOptions),
+    /// parse the USN journal ($UsnJrnl:$J) into one record per change to a file
+    Usn {
+        #[command(flatten)]
+        options: ParseOptions,
+        #[clap(long, default_value_t = String::from(""))]
+        /// a standalone $MFT to resolve full paths against, a collection's own $MFT is used otherwise
+        mft: String,
+    },
+    /// parse systemd journal files into one record per entry
+    Journal {
+        #[command(flatten)]
+        options: ParseOptions,
+        #[command(flatten)]
+        window: TimeWindow,
+    },
+    /// parse utmp, wtmp and btmp into one record per login, logout, reboot or failed login
+    Utmp(ParseOptions),
+    /// parse lastlog into the last login of every user
+    Lastlog {
+        #[command(flatten)]
+        options: ParseOptions,
+        #[clap(long, default_value_t = String::from(""))]
+        /// a standalone /etc/passwd to name the users by, a collection's own /etc/passwd is used otherwise
+        passwd: String,
+    },
+    /// parse EVTX event logs into one record per event
+    Evtx {
+        #[command(flatten)]
+        options: ParseOptions,
+        #[clap(long, value_delimiter = ',')]
+        /// only export events with these ids e.g. 4624,4625, instead of the event ids of the rule that collected each log
+        event_ids: Vec<u32>,
+        #[clap(long, value_delimiter = ',')]
+        /// only export events of these channels e.g. Security,System, instead of the channels of the rule that collected each log
+        channels: Vec<String>,
+        #[clap(short, long, default_value_t = String::from(""))]
+        /// directory with the custom rules a collection was made with, to apply their event filters
+        rule_dir: String,
+    },
+    /// parse SYSTEM, SOFTWARE, NTUSER.DAT and Amcache.hve hives into their Run keys, services, UserAssist, ShimCache, AmCache and BAM entries
+    Registry(ParseOptions),
+    /// parse shell and REPL history files into one timeline of the commands every user ran
+    History {
+        #[command(flatten)]
+        options: ParseOptions,
+        #[clap(long, default_value_t = String::from(""))]
+        /// a standalone /etc/passwd to name the users by, a collection's own /etc/passwd is used otherwise
+        passwd: String,
+        #[clap(long, default_value_t = String::from(""))]
+        /// the shell that wrote a standalone history file (bash, zsh, fish, ash, sh, mysql, python or psql), taken from its name otherwise
+        shell: String,
+    },
+    /// parse Prefetch files into the run count, last run times and volumes of every program that ran
+    Prefetch(ParseOptions),
+    /// parse LNK shortcut files into their target paths, target times and volumes
+    Lnk(ParseOptions),
+    /// parse automaticDestinations-ms and customDestinations-ms JumpLists into one record per link
+    Jumplist(ParseOptions),
+}
+
+type TimeBound = Option<DateTime<Utc>>;
+
+#[derive(Args)]
+struct TimeWindow {
+    #[clap(long, default_value_t = String::from(""))]
+    /// only include times from this one on, e.g. 2024-01-31 or 2024-01-31T12:00:00Z
+    start: String,
+    #[clap(long, default_value_t = String::from(""))]
+    /// only include times up to this one, e.g. 2024-02-01 (up to the end of that day) or 2024-01-31T18:00:00Z
+    end: String,
+}
+
+impl TimeWindow {
+    /// Returns the start and end of the window, either of which may be open.
+    fn bounds(&self) -> Result<(TimeBound, TimeBound), Box<dyn Error>> {
+        let bound = |bound: &str, parse: fn(&str) -> Result<_, String>| {
+            if bound.is_empty() {
+                Ok(None)
+            } else {
+                parse(bound).map(Some)
+            }
+        };
+        Ok((
+            bound(&self.start, parse_time_bound)?,
+            bound(&self.end, parse_time_end)?,
+        ))
+    }
+}
+
+#[derive(Args)]
+struct ParseOptions {
+    #[clap(short, long, default_value_t = String::from(""))]
+    /// the password the collection was encrypted with
+    encryption_key: String,
+    #[clap(short, long, default_value = "jsonl")]
+    /// the format to write the records in, jsonl or csv
+    format: OutputFormat,
+    #[clap(short, long, default_value_t = String::from(""))]
+    /// the file to write the records to, they are printed when no file is given
+    output: String,
+    /// path to a collection or a standalone artefact
+    input: String,
+}
+
+impl ParseOptions {
+    fn writer(&self) -> Result<RecordWriter<Box<dyn Write>>, Box<dyn Error>> {
+        let writer: Box<dyn Write> = if self.output.is_empty() {
+            Box::new(io::stdout().lock())
+        } else {
+            Box::new(BufWriter::new(File::create(&self.output)?))
+        };
+        Ok(RecordWriter::new(writer, self.format))
+    }
+
+    /// Opens the input when it is a collection, `None` when it is a standalone artefact.
+    fn open_collection(&self) -> Result<Option<CollectionArchive>, Box<dyn Error>> {
+        if !is_collection(&self.input) {
+            return Ok(None);
+        }
+        let encryption_key = if self.encryption_key.is_empty() {
+            None
+        } else {
+            Some(self.encryption_key.to_string())
+        };
+        Ok(Some(CollectionArchive::open(&self.input, encryption_key)?))
+    }
+}
+
+/// Decompresses rotated logs that were gzipped, e.g. `wtmp.1.gz`.
+fn decompressed<'a>(path: &str, reader: &'a mut dyn Read) -> Box<dyn Read + 'a> {
+    if path.ends_with(".gz") {
+        Box::new(GzDecoder::new(reader))
+    } else {
+        Box::new(reader)
+    }
+}
+
+/// Returns whether a file is utmp, wtmp or btmp, including rotated copies such as `wtmp.1` or
+/// `btmp-20240101.gz`.
+fn is_login_file(name: &str) -> bool {
+    name == "utmp" || name.starts_with("wtmp") || name.starts_with("btmp")
+}
+
+/// Returns whether a file is a journal, either closed cleanly or not (`.journal~`).
+fn is_journal_file(name: &str) -> bool {
+    name.ends_with(".journal") || name.ends_with(".journal~")
+}
+
+fn parse_journal_records(
+    options: &ParseOptions,
+    window: &TimeWindow,
+) -> Result<usize, Box<dyn Error>> {
+    let (start, end) = window.bounds()?;
+    let mut writer = options.writer()?;
+    let mut parsed = 0;
+    let mut write = |path: &str, reader: &mut dyn Read| -> Result<(), Box<dyn Error>> {
+        // a damaged journal still gives the entries before the damage
+        let journal = match JournalFile::from_reader(reader, path) {
+            Ok(journal) => journal,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Ok(());
+            }
+        };
+        for entry in journal.entries_between(start, end) {
+            match entry {
+                Ok(entry) => {
+                    writer.write(&entry)?;
+                    parsed += 1;
+                }
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        Ok(())
+    };
+    match options.open_collection()? {
+        Some(mut collection) => {
+            collection.for_each_matching("journal", is_journal_file, &mut write)?
+        }
+        None => write(
+            &options.input,
+            &mut BufReader::new(File::open(&options.input)?),
+        )?,
+    }
+    writer.flush()?;
+    Ok(parsed)
+}
+
+fn parse_login_records(options: &ParseOptions) -> Result<usize, Box<dyn Error>> {
+    let mut writer = options.writer()?;
+    let mut parsed = 0;
+    let mut write = |path: &str, reader: &mut dyn Read| -> Result<(), Box<dyn Error>> {
+        for record in parse_utmp(decompressed(path, reader), path)? {
+            writer.write(&record)?;
+            parsed += 1;
+        }
+        Ok(())
+    };
+    match options.open_collection()? {
+        Some(mut collection) => {
+            collection.for_each_matching("utmp, wtmp or btmp", is_login_file, &mut write)?
+        }
+        None => write(
+            &options.input,
+            &mut BufReader::new(File::open(&options.input)?),
+        )?,
+    }
+    writer.flush()?;
+    Ok(parsed)
+}
+
+fn parse_lastlog_records(options: &ParseOptions, passwd: &str) -> Result<usize, Box<dyn Error>> {
+    let mut writer = options.writer()?;
+    let mut parsed = 0;
+    let mut users = if passwd.is_empty() {
+        HashMap::new()
+    } else {
+        parse_passwd(&std::fs::read_to_string(passwd)?)
+    };
+    let mut write = |path: &str,
+                     reader: &mut dyn Read,
+                     users: &HashMap<u32, String>|
+     -> Result<(), Box<dyn Error>> {
+        for record in parse_lastlog(reader, path, users)? {
+            writer.write(&record)?;
+            parsed += 1;
+        }
+        Ok(())
+    };
+    match options.open_collection()? {
+        Some(mut collection) => {
+            // user names are taken from the collection's own /etc/passwd when it holds one
+            if passwd.is_empty() {
+                users.extend(collection_users(&mut collection)?);
+            }
+            collection.for_each_artefact("lastlog", |path, reader| write(path, reader, &users))?
+        }
+        None => write(
+            &options.input,
+            &mut BufReader::new(File::open(&options.input)?),
+            &users,
+        )?,
+    }
+    writer.flush()?;
+    Ok(parsed)
+}
+
+/// Reads the users of the collection's own `/etc/passwd`, when it holds one.
+fn collection_users(
+    collection: &mut CollectionArchive,
+) -> Result<HashMap<u32, String>, Box<dyn Error>> {
+    let mut users = HashMap::new();
+    for entry in collection.find(|name| name == "passwd") {
+        if entry.original_path.ends_with("/etc/passwd") {
+            let mut contents = String::new();
+            collection
+                .entry(&entry.archive_path)?
+                .read_to_string(&mut contents)?;
+            users.extend(parse_passwd(&contents));
+        }
+    }
+    Ok(users)
+}
+
+/// Returns the uid that owns a standalone artefact.
+#[cfg(target_os = "linux")]
+fn file_owner(path: &str) -> Option<u32> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|metadata| metadata.uid())
+}
+
+#[cfg(target_os = "windows")]
+fn file_owner(_path: &str) -> Option<u32> {
+    None
+}
+
+/// Parses every history file into a single timeline ordered by when each command was run, the
+/// commands of files without timestamps come first in the order they were written.
+fn parse_history_records(
+    options: &ParseOptions,
+    passwd: &str,
+    shell: &str,
+) -> Result<usize, Box<dyn Error>> {
+    let mut users = if passwd.is_empty() {
+        HashMap::new()
+    } else {
+        parse_passwd(&std::fs::read_to_string(passwd)?)
+    };
+    let mut commands: Vec<HistoryEntry> = Vec::new();
+    match options.open_collection()? {
+        Some(mut collection) => {
+            if passwd.is_empty() {
+                users.extend(collection_users(&mut collection)?);
+            }
+            let platform = collection.manifest().platform.clone();
+            let entries = collection.find(|name| history_shell(name).is_some());
+            if entries.is_empty() {
+                return Err("The collection does not hold any shell history artefacts".into());
+            }
+            for entry in entries {
+                let path = original_name(&platform, &entry.original_path);
+                let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+                let Some(shell) = history_shell(file_name) else {
+                    continue;
+                };
+                let reader = collection.entry(&entry.archive_path)?;
+                commands.extend(parse_history(
+                    reader,
+                    path,
+                    shell,
+                    entry.metadata.uid,
+                    &users,
+                )?);
+            }
+        }
+        None => {
+            let file_name = std::path::Path::new(&options.input)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let shell = match shell {
+                "" => history_shell(&file_name).ok_or(
+                    "The shell that wrote the history file is not known, give it with --shell",
+                )?,
+                shell => shell,
+            };
+            let reader = BufReader::new(File::open(&options.input)?);
+            let uid = file_owner(&options.input);
+            commands.extend(parse_history(reader, &options.input, shell, uid, &users)?);
+        }
+    }
+
+    // RFC3339 timestamps in UTC sort in time order
+    commands.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+    let mut writer = options.writer()?;
+    for command in &commands {
+        writer.write(command)?;
+    }
+    writer.flush()?;
+    Ok(commands.len())
+}
+
+fn parse_evtx_records(
+    options: &ParseOptions,
+    filter: EventFilter,
+    rule_dir: &str,
+) -> Result<usize, Box<dyn Error>> {
+    let mut writer = options.writer()?;
+    let mut parsed = 0;
+    // a record is written when any of the filters matches it
+    let mut write = |path: &str,
+                     reader: &mut dyn Read,
+                     filters: &[EventFilter]|
+     -> Result<(), Box<dyn Error>> {
+        let log = match EvtxFile::from_reader(reader, path) {
+            Ok(log) => log,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Ok(());
+            }
+        };
+        for record in log.records() {
+            match record {
+                Ok(record) if filters.iter().any(|filter| filter.matches(&record)) => {
+                    writer.write(&record)?;
+                    parsed += 1;
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        Ok(())
+    };
+    match options.open_collection()? {
+        Some(mut collection) => {
+            // each log is filtered by the rules that collected it unless a filter is given, a log
+            // that any rule collected without a filter is not filtered
+            let mut rules = CollectionRule::get_all()?;
+            if !rule_dir.is_empty() {
+                rules.extend(get_rules_from_dir(rule_dir.to_string())?);
+            }
+            let rule_filters: HashMap<String, EventFilter> = rules
+                .into_iter()
+                .filter_map(|rule| match rule {
+                    CollectionRule::FileRule(rule) => Some((
+                        rule.name,
+                        EventFilter {
+                            event_ids: rule.event_ids,
+                            channels: rule.channels,
+                        },
+                    )),
+                    _ => None,
+                })
+                .collect();
+            let platform = collection.manifest().platform.clone();
+            let logs = collection.find(|name| name.to_ascii_lowercase().ends_with(".evtx"));
+            if logs.is_empty() {
+                return Err("The collection does not hold any EVTX artefacts".into());
+            }
+            for log in logs {
+                let log_filters: Vec<EventFilter> = if filter == EventFilter::default() {
+                    log.rules()
+                        .map(|rule| rule_filters.get(rule).cloned().unwrap_or_default())
+                        .collect()
+                } else {
+                    vec![filter.clone()]
+                };
+                let mut file = collection.entry(&log.archive_path)?;
+                write(
+                    original_name(&platform, &log.original_path),
+                    &mut file,
+                    &log_filters,
+                )?;
+            }
+        }
+        None => write(
+            &options.input,
+            &mut BufReader::new(File::open(&options.input)?),
+            &[filter],
+        )?,
+    }
+    writer.flush()?;
+    Ok(parsed)
+}
+
+/// Returns whether a file is a hive that registry artefacts are extracted from.
+fn is_hive_file(name: &str) -> bool {
+    ["SYSTEM", "SOFTWARE", "NTUSER.DAT", "Amcache.hve"]
+        .iter()
+        .any(|hive| name.eq_ignore_ascii_case(hive))
+}
+
+/// The names of the transaction logs of a hive.
+fn hive_logs(path: &str) -> [String; 2] {
+    [format!("{}.LOG1", path), format!("{}.LOG2", path)]
+}
+
+fn parse_registry_records(options: &ParseOptions) -> Result<usize, Box<dyn Error>> {
+    let mut writer = options.writer()?;
+    let mut parsed = 0;
+    let mut write = |path: &str,
+                     reader: &mut dyn Read,
+                     logs: &[Vec<u8>]|
+     -> Result<(), Box<dyn Error>> {
+        let mut hive = Hive::from_reader(reader, path)?;
+        if hive.is_dirty() {
+            match hive.recover(logs)? {
+                0 => eprintln!(
+                    "{} was not written back cleanly and no transaction log could be replayed, its latest changes may be missing",
+                    path
+                ),
+                applied => eprintln!(
+                    "Replayed {0} changes from the transaction logs into {1}",
+                    applied, path
+                ),
+            }
+        }
+        for artefact in extract_artefacts(&hive) {
+            writer.write(&artefact)?;
+            parsed += 1;
+        }
+        Ok(())
+    };
+    match options.open_collection()? {
+        Some(mut collection) => {
+            let platform = collection.manifest().platform.clone();
+            let hives = collection.find(is_hive_file);
+            if hives.is_empty() {
+                return Err("The collection does not hold any registry hive artefacts".into());
+            }
+            for hive in hives {
+                let path = original_name(&platform, &hive.original_path).to_string();
+                let log_names = hive_logs(&path);
+                let mut logs = Vec::new();
+                for log in collection.find(|name| name.to_ascii_uppercase().contains(".LOG")) {
+                    let log_path = original_name(&platform, &log.original_path);
+                    if log_names
+                        .iter()
+                        .any(|name| name.eq_ignore_ascii_case(log_path))
+                    {
+                        let mut contents = Vec::new();
+                        collection
+                            .entry(&log.archive_path)?
+                            .read_to_end(&mut contents)?;
+                        logs.push(contents);
+                    }
+                }
+                // a damaged hive should not stop the others from being parsed
+                let mut file = collection.entry(&hive.archive_path)?;
+                if let Err(e) = write(&path, &mut file, &logs) {
+                    eprintln!("{}", e);
+                }
+            }
+        }
+        None => {
+            let mut logs = Vec::new();
+            for log in hive_logs(&options.input) {
+                // logs extracted from a collection keep the case Windows gave them
+                let lowercase = log.replace(".LOG", ".log");
+                if let Ok(contents) = std::fs::read(&log).or_else(|_| std::fs::read(&lowercase)) {
+                    logs.push(contents);
+                }
+            }
+            write(
+                &options.input,
+                &mut BufReader::new(File::open(&options.input)?),
+                &logs,
+            )?
+        }
+    }
+    writer.flush()?;
+    Ok(parsed)
+}
+
+fn parse_prefetch_records(options: &ParseOptions) -> Result<usize, Box<dyn Error>> {
+    let mut writer = options.writer()?;
+    let mut parsed = 0;
+    let mut write = |path: &str, reader: &mut dyn Read| -> Result<(), Box<dyn Error>> {
+        writer.write(&parse_prefetch(reader, path)?)?;
+        parsed += 1;
+        Ok(())
+    };
+    match options.open_collection()? {
+        Some(mut collection) => collection.for_each_matching(
+            "Prefetch",
+            |name| name.to_ascii_lowercase().ends_with(".pf"),
+            // a damaged prefetch file should not stop the others from being parsed
+            |path, reader| {
+                if let Err(e) = write(path, reader) {
+                    eprintln!("{}", e);
+                }
+                Ok(())
+            },
+        )?,
+        None => write(
+            &options.input,
+            &mut BufReader::new(File::open(&options.input)?),
+        )?,
+    }
+    writer.flush()?;
+    Ok(parsed)
+}
+
+fn parse_lnk_records(options: &ParseOptions) -> Result<usize, Box<dyn Error>> {
+    let mut writer = options.writer()?;
+    let mut parsed = 0;
+    let mut write = |path: &str, reader: &mut dyn Read| -> Result<(), Box<dyn Error>> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        writer.write(&parse_lnk(&data, path)?)?;
+        parsed += 1;
+        Ok(())
+    };
+    match options.open_collection()? {
+        Some(mut collection) => collection.for_each_matching(
+            "LNK",
+            |name| name.to_ascii_lowercase().ends_with(".lnk"),
+            |path, reader| {
+                if let Err(e) = write(path, reader) {
+                    eprintln!("{}", e);
+                }
+                Ok(())
+            },
+        )?,
+        None => write(
+            &options.input,
+            &mut BufReader::new(File::open(&options.input)?),
+        )?,
+    }
+    writer.flush()?;
+    Ok(parsed)
+}
+
+fn parse_jumplist_records(options: &ParseOptions) -> Result<usize, Box<dyn Error>> {
+    let mut writer = options.writer()?;
+    let mut parsed = 0;
+    let mut write = |path: &str, reader: &mut dyn Read| -> Result<(), Box<dyn Error>> {
+        // a link that cannot be read still leaves the rest of its JumpList
+        let links = match parse_jumplist(reader, path) {
+            Ok(links) => links,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Ok(());
+            }
+        };
+        for link in links {
+            match link {
+                Ok(link) => {
+                    writer.write(&link)?;
+                    parsed += 1;
+                }
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        Ok(())
+    };
+    match options.open_collection()? {
+        Some(mut collection) => {
+            collection.for_each_matching("JumpList", is_jumplist_file, &mut write)?
+        }
+        None => write(
+            &options.input,
+            &mut BufReader::new(File::open(&options.input)?),
+        )?,
+    }
+    writer.flush()?;
+    Ok(parsed)
+}
+
+fn parse_mft_records(options: &ParseOptions) -> Result<usize, Box<dyn Error>> {
+    let mut writer = options.writer()?;
+    let mut parsed = 0;
+    let mut write = |entry: MftEntry| -> Result<(), Box<dyn Error>> {
+        writer.write(&entry)?;
+        parsed += 1;
+        Ok(())
+    };
+    match options.open_collection()? {
+        // the paths of records from a collection are prefixed with the volume they came from
+        Some(mut collection) => parse_collection_mft(&mut collection, &mut write)?,
+        None => {
+            for entry in parse_mft(BufReader::new(File::open(&options.input)?))? {
+                write(entry)?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(parsed)
+}
+
+fn parse_usn_records(options: &ParseOptions, mft: &str) -> Result<usize, Box<dyn Error>> {
+    let mut writer = options.writer()?;
+    let mut parsed = 0;
+    let mut write = |entry: UsnEntry| -> Result<(), Box<dyn Error>> {
+        writer.write(&entry)?;
+        parsed += 1;
+        Ok(())
+    };
+    let mft = if mft.is_empty() {
+        None
+    } else {
+        Some(parse_mft(BufReader::new(File::open(mft)?))?)
+    };
+    match options.open_collection()? {
+        Some(mut collection) => parse_collection_usn(&mut collection, mft, &mut write)?,
+        None => {
+            let paths = MftPaths::new(mft.unwrap_or_default(), "");
+            for entry in UsnJournal::new(BufReader::new(File::open(&options.input)?)) {
+                let mut entry = entry?;
+                paths.resolve(&mut entry);
+                write(entry)?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(parsed)
+}
+
+fn build_timeline(
+    archive: &str,
+    encryption_key: &str,
+    output: &str,
+    bodyfile: &str,
+    window: &TimeWindow,
+) -> Result<usize, Box<dyn Error>> {
+    let (start, end) = window.bounds()?;
+    let options = ParseOptions {
+        encryption_key: encryption_key.to_string(),
+        format: OutputFormat::Csv,
+        output: output.to_string(),
+        input: archive.to_string(),
+    };
+    let mut collection = options
+        .open_collection()?
+        .ok_or(format!("{} is not a collection", archive))?;
+
+    let platform = collection.manifest().platform.clone();
+    let mut entries: Vec<BodyfileEntry> = collection
+        .manifest()
+        .entries
+        .iter()
+        .map(|entry| BodyfileEntry::from_manifest(&platform, entry))
+        .collect();
+    // a collection without an $MFT still has the metadata of its artefacts
+    if collection.has_artefact("$MFT") {
+        parse_collection_mft(&mut collection, |entry| {
+            entries.extend(BodyfileEntry::from_mft(&entry));
+            Ok(())
+        })?;
+    }
+    if !bodyfile.is_empty() {
+        write_bodyfile(&entries, BufWriter::new(File::create(bodyfile)?))?;
+    }
+
+    let rows = mactime(&entries, start, end);
+    let mut writer = options.writer()?;
+    for row in &rows {
+        writer.write(row)?;
+    }
+    writer.flush()?;
+    Ok(rows.len())
+}
+
+#[derive(Args)]
+struct EntrySelection {
+    #[clap(short, long, default_value_t = String::from(""))]
+    /// the password the collection was encrypted with
+    encryption_key: String,
+    #[clap(short, long)]
+    /// only select artefacts collected by this rule, can be given multiple times
+    rule: Vec<String>,
+    #[clap(short, long)]
+    /// only select artefacts whose path matches this glob e.g. "**/Prefetch/*.pf", Windows paths are matched with / separators, can be given multiple times
+    glob: Vec<String>,
+}
+
+impl EntrySelection {
+    fn open(&self, archive: &str) -> Result<(CollectionArchive, EntryFilter), Box<dyn Error>> {
+        let encryption_key = if self.encryption_key.is_empty() {
+            None
+        } else {
+            Some(self.encryption_key.to_string())
+        };
+        Ok((
+            CollectionArchive::open(archive, encryption_key)?,
+            EntryFilter::new(self.rule.clone(), self.glob.clone())?,
+        ))
+    }
+}
+
+fn list_collection(archive: &str, selection: &EntrySelection) -> Result<(), Box<dyn Error>> {
+    let (collection, filter) = selection.open(archive)?;
+    let platform = collection.manifest().platform.clone();
+    for entry in collection.list(&filter) {
+        let last_modified = chrono::DateTime::from_timestamp(entry.last_modified, 0)
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        println!(
+            "{0:>12}  {1}  {2:<20}  {3}",
+            entry.size,
+            last_modified,
+            entry.rule,
+            original_name(&platform, &entry.original_path)
+        );
+    }
+    Ok(())
+}
+
+#[derive(Args)]
+struct UploadOptions {
+    #[clap(long, default_value_t = String::from(""))]
+    /// the S3-compatible endpoint used for s3:// uploads e.g. http://localhost:9000
+    s3_endpoint: String,
+    #[clap(long, default_value_t = 5)]
+    /// the number of times a failed upload request is retried
+    upload_retries: u32,
+    #[clap(long, default_value_t = String::from(""))]
+    /// the private key used to authenticate sftp:// uploads
+    sftp_key: String,
+    #[clap(long, default_value_t = String::from(""))]
+    /// the SHA256 fingerprint the SFTP server's host key must match e.g. SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s
+    sftp_host_key: String,
+}
+
+fn verify_collection(archive: &str, encryption_key: &str) -> Result<bool, Box<dyn Error>> {
+    let encryption_key = if encryption_key.is_empty() {
+        None
+    } else {
+        Some(encryption_key.to_string())
+    };
+    let report = CollectionArchive::open(archive, encryption_key)?.verify()?;
+
+    for path in &report.missing {
+        println!("Missing: {}", path);
+    }
+    for path in &report.extra {
+        println!("Extra: {}", path);
+    }
+    for (path, reason) in &report.corrupted {
+        println!("Corrupted: {0}, {1}", path, reason);
+    }
+    println!(
+        "Verified {0} entries, {1} missing, {2} extra, {3} corrupted",
+        report.verified.len(),
+        report.missing.len(),
+        report.extra.len(),
+        report.corrupted.len()
+    );
+    Ok(report.is_valid())
+}
+
+fn upload_collection(
+    archive: &str,
+    destination: &str,
+    options: &UploadOptions,
+) -> Result<(), Box<dyn Error>> {
+    if destination.is_empty() {
+        return Ok(());
+    }
+    if destination.starts_with("sftp://") {
+        SftpUploader::connect(destination, &options.sftp_key, &options.sftp_host_key)?
+            .upload(archive)?;
+    } else {
+        upload_archive(
+            archive,
+            destination,
+            &options.s3_endpoint,
+            options.upload_retries,
+        )?;
+    }
+    println!("Collection uploaded to {}", destination);
+    Ok(())
+}
+
+fn write_collection(
+    collector: &mut Collecter,
+    output: &str,
+    upload: &str,
+    upload_options: &UploadOptions,
+) -> Result<(), Box<dyn Error>> {
+    // sftp destinations receive the archive as it is written rather than a finished local copy
+    if upload.starts_with("sftp://") {
+        let result = SftpUploader::connect(
+            upload,
+            &upload_options.sftp_key,
+            &upload_options.sftp_host_key,
+        )
+        .and_then(|uploader| {
+            // a remote file that holds no artefacts, or only part of the archive, is removed
+            match collector.compress_collection_to(uploader.create()?) {
+                Ok(0) => {
+                    uploader.remove()?;
+                    Err(
+                        "No artefacts could be collected, nothing was left at the destination"
+                            .into(),
+                    )
+                }
+                Ok(written) => Ok(written),
+                Err(e) => {
+                    uploader.remove().ok();
+                    Err(e)
+                }
+            }
+        });
+        println!(
+            "Collection of {0} artefacts streamed to {1}",
+            result?, upload
+        );
+        return Ok(());
+    }
+
+    collector.compress_collection(output)?;
+    println!("Collection compressed to {}", output);
+    upload_collection(output, upload, upload_options)
+}
+
+fn main() -> ExitCode {
     let cli = Cli::parse();
 
     match &cli.command {
@@ -52,11 +1013,17 @@ fn main() {
             all,
             encryption_key,
             output,
+            upload,
+            upload_options,
+            image,
+            raw,
+            keep_sparse,
+            mft,
             rules,
         }) => {
             if !output.ends_with(".zip") {
                 print!("Output file must end in .zip, currently: {}", output);
-                return;
+                return ExitCode::SUCCESS;
             }
             if *list {
                 let mut rules =
@@ -107,21 +1074,31 @@ fn main() {
                     }
                 }
 
-                return;
+                return ExitCode::SUCCESS;
             }
 
             let mut collector: Collecter;
+            let encryption_key = if encryption_key.is_empty() {
+                None
+            } else {
+                Some(encryption_key.to_string())
+            };
 
-            if encryption_key.is_empty() {
-                collector = Collecter::new(env::consts::OS.to_string(), None)
-                    .expect("Failed to create collector");
+            if !image.is_empty() {
+                collector = match Collecter::from_image(image, encryption_key) {
+                    Ok(collector) => collector,
+                    Err(e) => {
+                        println!("Failed to open image {0}: {1}", image, e);
+                        return ExitCode::FAILURE;
+                    }
+                };
             } else {
-                collector = Collecter::new(
-                    env::consts::OS.to_string(),
-                    Some(encryption_key.to_string()),
-                )
-                .expect("Failed to create collector");
+                collector = Collecter::new(env::consts::OS.to_string(), encryption_key)
+                    .expect("Failed to create collector");
             }
+            collector.set_raw_access(*raw);
+            collector.set_keep_sparse(*keep_sparse);
+            collector.set_mft_search(*mft);
 
             if !rule_dir.is_empty() {
                 std::fs::read_dir(rule_dir)
@@ -129,7 +1106,7 @@ fn main() {
                     .filter_map(|entry| entry.ok())
                     .filter(|entry| {
                         entry.path().is_file()
-                            && entry.path().extension().map_or(false, |ext| ext == "yaml")
+                            && entry.path().extension().is_some_and(|ext| ext == "yaml")
                     })
                     .for_each(|entry| {
                         match collector.add_rule_from_file(
@@ -149,16 +1126,16 @@ fn main() {
                     .collect_all()
                     .expect("Failed to collect artefacts");
 
-                match collector.compress_collection(output) {
-                    Ok(_) => println!("Collection compressed to {}", output),
-                    Err(e) => println!("{}", e),
+                if let Err(e) = write_collection(&mut collector, output, upload, upload_options) {
+                    println!("{}", e);
+                    return ExitCode::FAILURE;
                 }
-                return;
+                return ExitCode::SUCCESS;
             }
 
             if rules.is_empty() {
                 println!("No rules specified, use -l to list available rules");
-                return;
+                return ExitCode::SUCCESS;
             }
 
             for rule in rules {
@@ -175,11 +1152,116 @@ fn main() {
                 }
             }
 
-            match collector.compress_collection(output) {
-                Ok(_) => println!("Collection compressed to {}", output),
-                Err(e) => println!("{}", e),
+            if let Err(e) = write_collection(&mut collector, output, upload, upload_options) {
+                println!("{}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Some(Commands::Parse { artefact }) => {
+            let (options, result) = match artefact {
+                ParseCommands::Mft(options) => (options, parse_mft_records(options)),
+                ParseCommands::Usn { options, mft } => (options, parse_usn_records(options, mft)),
+                ParseCommands::Journal { options, window } => {
+                    (options, parse_journal_records(options, window))
+                }
+                ParseCommands::Utmp(options) => (options, parse_login_records(options)),
+                ParseCommands::Lastlog { options, passwd } => {
+                    (options, parse_lastlog_records(options, passwd))
+                }
+                ParseCommands::Evtx {
+                    options,
+                    event_ids,
+                    channels,
+                    rule_dir,
+                } => {
+                    let filter = EventFilter {
+                        event_ids: event_ids.clone(),
+                        channels: channels.clone(),
+                    };
+                    (options, parse_evtx_records(options, filter, rule_dir))
+                }
+                ParseCommands::Registry(options) => (options, parse_registry_records(options)),
+                ParseCommands::History {
+                    options,
+                    passwd,
+                    shell,
+                } => (options, parse_history_records(options, passwd, shell)),
+                ParseCommands::Prefetch(options) => (options, parse_prefetch_records(options)),
+                ParseCommands::Lnk(options) => (options, parse_lnk_records(options)),
+                ParseCommands::Jumplist(options) => (options, parse_jumplist_records(options)),
+            };
+            match result {
+                Ok(parsed) if !options.output.is_empty() => {
+                    println!("Parsed {0} records to {1}", parsed, options.output)
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    println!("Failed to parse {0}: {1}", options.input, e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        Some(Commands::Timeline {
+            encryption_key,
+            output,
+            bodyfile,
+            window,
+            archive,
+        }) => match build_timeline(archive, encryption_key, output, bodyfile, window) {
+            Ok(rows) if !output.is_empty() => {
+                println!("Wrote a timeline of {0} rows to {1}", rows, output)
+            }
+            Ok(_) => {}
+            Err(e) => {
+                println!("Failed to build a timeline of {0}: {1}", archive, e);
+                return ExitCode::FAILURE;
+            }
+        },
+        Some(Commands::Upload {
+            upload_options,
+            archive,
+            destination,
+        }) => {
+            if let Err(e) = upload_collection(archive, destination, upload_options) {
+                println!("{}", e);
+                return ExitCode::FAILURE;
             }
         }
+        Some(Commands::Ls { selection, archive }) => {
+            if let Err(e) = list_collection(archive, selection) {
+                println!("Failed to list {0}: {1}", archive, e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Some(Commands::Extract {
+            selection,
+            output,
+            archive,
+        }) => match selection
+            .open(archive)
+            .and_then(|(mut collection, filter)| collection.extract(output, &filter))
+        {
+            Ok(extracted) => println!("Extracted {0} artefacts to {1}", extracted, output),
+            Err(e) => {
+                println!("Failed to extract {0}: {1}", archive, e);
+                return ExitCode::FAILURE;
+            }
+        },
+        Some(Commands::Verify {
+            encryption_key,
+            archive,
+        }) => match verify_collection(archive, encryption_key) {
+            Ok(true) => println!("Collection {} is intact", archive),
+            Ok(false) => {
+                println!("Collection {} failed verification", archive);
+                return ExitCode::FAILURE;
+            }
+            Err(e) => {
+                println!("Failed to verify {0}: {1}", archive, e);
+                return ExitCode::FAILURE;
+            }
+        },
         None => println!("Unsupported!"),
     }
+    ExitCode::SUCCESS
 }
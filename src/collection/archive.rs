@@ -0,0 +1,392 @@
+use super::manifest::{HashingWriter, Manifest, ManifestEntry, MANIFEST_NAME};
+use super::metadata::parse_timestamp;
+use filetime::FileTime;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Read},
+    path::{Path, PathBuf},
+};
+use zip::{read::ZipFile, ZipArchive};
+
+/// A collection produced by `Collecter`, opened for reading.
+pub struct CollectionArchive {
+    archive: ZipArchive<File>,
+    encryption_key: Option<String>,
+    manifest: Manifest,
+}
+
+#[derive(Debug, Default)]
+pub struct VerificationReport {
+    pub verified: Vec<String>,
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+    pub corrupted: Vec<(String, String)>,
+}
+
+impl VerificationReport {
+    pub fn is_valid(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.corrupted.is_empty()
+    }
+}
+
+/// Selects entries by the rule that collected them and/or by globs matched against both the
+/// original path and the path inside the archive. An empty filter selects everything.
+#[derive(Default)]
+pub struct EntryFilter {
+    rules: Vec<String>,
+    globs: Option<GlobSet>,
+}
+
+impl EntryFilter {
+    pub fn new(rules: Vec<String>, globs: Vec<String>) -> Result<Self, Box<dyn Error>> {
+        let globs = if globs.is_empty() {
+            None
+        } else {
+            let mut builder = GlobSetBuilder::new();
+            for glob in globs {
+                builder.add(Glob::new(&glob)?);
+            }
+            Some(builder.build()?)
+        };
+        Ok(EntryFilter { rules, globs })
+    }
+
+    pub fn matches(&self, platform: &str, entry: &ManifestEntry) -> bool {
+        if !self.rules.is_empty() && !entry.rules().any(|rule| self.rules.contains(rule)) {
+            return false;
+        }
+        match &self.globs {
+            Some(globs) => {
+                globs.is_match(glob_path(platform, &entry.archive_path).as_ref())
+                    || globs.is_match(
+                        glob_path(platform, original_name(platform, &entry.original_path)).as_ref(),
+                    )
+            }
+            None => true,
+        }
+    }
+}
+
+/// Windows paths are matched with `/` separators, so `C:/Windows/**` selects both `C:\Windows\...`
+/// and the `C\Windows\...` entries in the archive.
+fn glob_path<'a>(platform: &str, path: &'a str) -> Cow<'a, str> {
+    if platform == "windows" {
+        Cow::Owned(path.replace('\\', "/"))
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+/// Returns the name an artefact had on the host it was collected from, undoing the empty stream
+/// suffix that is appended to every path found while searching NTFS volumes.
+pub fn original_name<'a>(platform: &str, original_path: &'a str) -> &'a str {
+    if platform == "windows" {
+        original_path.strip_suffix(':').unwrap_or(original_path)
+    } else {
+        original_path
+    }
+}
+
+/// Maps an artefact's original path to a relative path it can be extracted to. Windows drive
+/// letters and disk image partitions become the top level directory (`C:\Windows` becomes
+/// `C/Windows`, `partition2:/etc` becomes `partition2/etc`) and alternate data
+/// streams keep their `file:stream` name, which is created as a stream when extracting on
+/// Windows. Any components that would escape the destination are dropped, and colons anywhere
+/// else are replaced so no component can name another drive.
+pub fn extraction_path(platform: &str, original_path: &str) -> PathBuf {
+    let name = original_name(platform, original_path);
+    let mut path = PathBuf::new();
+
+    if platform == "windows" {
+        let components: Vec<&str> = name.split(['\\', '/']).filter(|c| !c.is_empty()).collect();
+        for (position, component) in components.iter().enumerate() {
+            if *component == "." || *component == ".." {
+                continue;
+            }
+            match component.strip_suffix(':') {
+                Some(volume) if position == 0 && is_volume_name(volume) => path.push(volume),
+                _ => path.push(escape_colons(component, position == components.len() - 1)),
+            }
+        }
+    } else {
+        let components: Vec<_> = Path::new(name).components().collect();
+        for (position, component) in components.iter().enumerate() {
+            if let std::path::Component::Normal(component) = component {
+                // artefacts from a partition of a disk image are named partition2:/etc/passwd
+                let component = component.to_string_lossy();
+                match component.strip_suffix(':') {
+                    Some(partition) if position == 0 && is_volume_name(partition) => {
+                        path.push(partition)
+                    }
+                    // a colon is part of a Linux file name, but a path on Windows
+                    _ if cfg!(windows) => {
+                        path.push(escape_colons(&component, position == components.len() - 1))
+                    }
+                    _ => path.push(component.as_ref()),
+                }
+            }
+        }
+    }
+
+    path
+}
+
+fn is_volume_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Replaces the colons of a path component with `_`, unless it is the `file:stream` name of an
+/// alternate data stream at the end of the path. A name such as `D:` or `D:evil` would otherwise
+/// be read as a drive on Windows and replace the whole path it is pushed onto.
+fn escape_colons(component: &str, last: bool) -> String {
+    match component.split_once(':') {
+        Some((file, _)) if last && file.len() > 1 => component.to_string(),
+        Some(_) => component.replace(':', "_"),
+        None => component.to_string(),
+    }
+}
+
+/// Returns whether a file is a collection, a zip archive holding a manifest, rather than a
+/// standalone artefact.
+pub fn is_collection(path: &str) -> bool {
+    File::open(path)
+        .ok()
+        .and_then(|file| ZipArchive::new(file).ok())
+        .is_some_and(|archive| archive.index_for_name(MANIFEST_NAME).is_some())
+}
+
+impl CollectionArchive {
+    pub fn open(path: &str, encryption_key: Option<String>) -> Result<Self, Box<dyn Error>> {
+        let archive = ZipArchive::new(File::open(path)?)?;
+        let mut collection = CollectionArchive {
+            archive,
+            encryption_key,
+            manifest: Manifest::default(),
+        };
+
+        let mut manifest = String::new();
+        collection
+            .entry(MANIFEST_NAME)
+            .map_err(|e| format!("Failed to read {0}: {1}", MANIFEST_NAME, e))?
+            .read_to_string(&mut manifest)?;
+        collection.manifest = Manifest::from_json(&manifest)?;
+
+        Ok(collection)
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    pub fn entry_names(&self) -> Vec<String> {
+        self.archive
+            .file_names()
+            .filter(|name| *name != MANIFEST_NAME)
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    pub fn entry(&mut self, name: &str) -> Result<ZipFile<'_, File>, Box<dyn Error>> {
+        let encrypted = self
+            .archive
+            .index_for_name(name)
+            .and_then(|index| self.archive.by_index_raw(index).ok())
+            .ok_or(format!("{} is not in the archive", name))?
+            .encrypted();
+
+        if !encrypted {
+            return Ok(self.archive.by_name(name)?);
+        }
+        let encryption_key = self
+            .encryption_key
+            .as_deref()
+            .ok_or("The collection is encrypted, an encryption key is required")?;
+        Ok(self
+            .archive
+            .by_name_decrypt(name, encryption_key.as_bytes())?)
+    }
+
+    pub fn list(&self, filter: &EntryFilter) -> Vec<&ManifestEntry> {
+        self.manifest
+            .entries
+            .iter()
+            .filter(|entry| filter.matches(&self.manifest.platform, entry))
+            .collect()
+    }
+
+    /// Returns the entries whose original file name, the last component of their path including
+    /// any stream such as `$UsnJrnl:$J`, is accepted by `matches`.
+    pub fn find<F>(&self, matches: F) -> Vec<ManifestEntry>
+    where
+        F: Fn(&str) -> bool,
+    {
+        self.manifest
+            .entries
+            .iter()
+            .filter(|entry| {
+                original_name(&self.manifest.platform, &entry.original_path)
+                    .rsplit(['\\', '/'])
+                    .next()
+                    .is_some_and(&matches)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Returns whether the collection holds an artefact named `file_name`.
+    pub fn has_artefact(&self, file_name: &str) -> bool {
+        !self
+            .find(|name| name.eq_ignore_ascii_case(file_name))
+            .is_empty()
+    }
+
+    /// Calls `parse` with the original path and contents of every artefact named `file_name`,
+    /// failing when there are none.
+    pub fn for_each_artefact<F>(&mut self, file_name: &str, parse: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(&str, &mut dyn Read) -> Result<(), Box<dyn Error>>,
+    {
+        self.for_each_matching(
+            file_name,
+            |name| name.eq_ignore_ascii_case(file_name),
+            parse,
+        )
+    }
+
+    /// Calls `parse` with the original path and contents of every artefact whose file name is
+    /// accepted by `matches`, failing when there are none.
+    pub fn for_each_matching<M, F>(
+        &mut self,
+        description: &str,
+        matches: M,
+        mut parse: F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        M: Fn(&str) -> bool,
+        F: FnMut(&str, &mut dyn Read) -> Result<(), Box<dyn Error>>,
+    {
+        let platform = self.manifest.platform.clone();
+        let entries = self.find(matches);
+        if entries.is_empty() {
+            return Err(
+                format!("The collection does not hold any {} artefacts", description).into(),
+            );
+        }
+        for entry in entries {
+            let mut file = self.entry(&entry.archive_path)?;
+            parse(original_name(&platform, &entry.original_path), &mut file)?;
+        }
+        Ok(())
+    }
+
+    /// Extracts the selected entries below `destination` and restores the access and modified
+    /// times that were recorded in the manifest. Returns the number of entries extracted, or an
+    /// error counting those that failed once every other entry has been extracted.
+    pub fn extract(
+        &mut self,
+        destination: &str,
+        filter: &EntryFilter,
+    ) -> Result<usize, Box<dyn Error>> {
+        let platform = self.manifest.platform.clone();
+        let mut extracted = 0;
+        let mut failed = 0;
+
+        for entry in self.list(filter).into_iter().cloned().collect::<Vec<_>>() {
+            let output_path =
+                Path::new(destination).join(extraction_path(&platform, &entry.original_path));
+            let result = self.entry(&entry.archive_path).and_then(|mut file| {
+                if let Some(parent) = output_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let mut writer = BufWriter::new(File::create(&output_path)?);
+                io::copy(&mut file, &mut writer)?;
+                writer.into_inner().map_err(|e| e.into_error())?;
+
+                let to_file_time = |timestamp: &Option<String>| {
+                    timestamp
+                        .as_deref()
+                        .and_then(parse_timestamp)
+                        .map(|time| {
+                            FileTime::from_unix_time(
+                                time.timestamp(),
+                                time.timestamp_subsec_nanos(),
+                            )
+                        })
+                        .unwrap_or(FileTime::from_unix_time(entry.last_modified, 0))
+                };
+                filetime::set_file_times(
+                    &output_path,
+                    to_file_time(&entry.metadata.accessed),
+                    to_file_time(&entry.metadata.modified),
+                )?;
+                Ok(())
+            });
+
+            match result {
+                Ok(_) => {
+                    println!("Extracted {}", output_path.display());
+                    extracted += 1;
+                }
+                Err(e) => {
+                    println!("Failed to extract {0}, {1}", entry.archive_path, e);
+                    failed += 1;
+                }
+            }
+        }
+
+        if failed > 0 {
+            return Err(format!(
+                "{0} artefacts could not be extracted, {1} were extracted to {2}",
+                failed, extracted, destination
+            )
+            .into());
+        }
+        Ok(extracted)
+    }
+
+    /// Recomputes the SHA256 of every entry in the archive and compares it against the manifest.
+    pub fn verify(&mut self) -> Result<VerificationReport, Box<dyn Error>> {
+        let mut report = VerificationReport::default();
+        let names: HashSet<String> = self.entry_names().into_iter().collect();
+
+        for entry in self.manifest.entries.clone() {
+            if !names.contains(&entry.archive_path) {
+                report.missing.push(entry.archive_path);
+                continue;
+            }
+
+            let hashed = self.entry(&entry.archive_path).and_then(|mut file| {
+                let mut writer = HashingWriter::new(io::sink());
+                io::copy(&mut file, &mut writer)?;
+                Ok(writer.finish())
+            });
+
+            match hashed {
+                Ok((sha256, size)) if sha256 == entry.sha256 && size == entry.size => {
+                    report.verified.push(entry.archive_path)
+                }
+                Ok((sha256, size)) => report.corrupted.push((
+                    entry.archive_path,
+                    format!(
+                        "expected {0} ({1} bytes) but found {2} ({3} bytes)",
+                        entry.sha256, entry.size, sha256, size
+                    ),
+                )),
+                Err(e) => report.corrupted.push((entry.archive_path, e.to_string())),
+            }
+        }
+
+        for name in names {
+            if self.manifest.get_entry(&name).is_none() && !name.ends_with('/') {
+                report.extra.push(name);
+            }
+        }
+        report.extra.sort();
+
+        Ok(report)
+    }
+}
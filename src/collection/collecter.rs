@@ -1,34 +1,123 @@
+use super::image::DiskImage;
+use super::manifest::{HashingWriter, Manifest, ManifestEntry, MANIFEST_NAME};
+use super::metadata::FileMetadata;
 use super::{file::FileCollecter, memory::MemoryCollecter, rules::CollectionRule};
 use chrono::NaiveDateTime;
-use chrono::{DateTime, Local};
-use std::io::{BufRead, BufReader, Write};
+use chrono::{DateTime, Utc};
+use std::io::{BufRead, BufReader, Seek, Write};
 use std::{error::Error, fs::File};
 use zip::{
+    unstable::path_to_string,
     write::{FileOptions, SimpleFileOptions},
     AesMode::Aes256,
     CompressionMethod, ZipWriter,
 };
 
+#[cfg(target_os = "linux")]
+use super::readers::ext4_reader::{find_mount, open_device, Ext4};
 #[cfg(target_os = "windows")]
-use super::readers::ntfs_reader::{copy_file, get_lastmodified, parse_stream};
+use super::readers::ntfs_reader::{
+    copy_file, deleted_archive_path, deleted_record, get_lastmodified, get_metadata,
+    get_recovery_confidence, parse_stream,
+};
+#[cfg(target_os = "linux")]
+use super::walker::open_noatime;
+#[cfg(target_os = "linux")]
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    path::Path,
+};
+
+/// Removes the entry that was being written when its artefact failed to be copied, so the
+/// archive never holds a partial file that the manifest does not list.
+fn abort_entry<W: Write + Seek>(zip: &mut ZipWriter<W>, error: Box<dyn Error>) -> Box<dyn Error> {
+    match zip.abort_file() {
+        Ok(()) => error,
+        Err(e) => format!(
+            "{0}, and the partial entry could not be removed: {1}",
+            error, e
+        )
+        .into(),
+    }
+}
+
+fn copy_buffered<R: BufRead, W: Write>(reader: &mut R, writer: &mut W) -> std::io::Result<()> {
+    loop {
+        let length = {
+            let buffer = reader.fill_buf()?;
+            writer.write_all(buffer)?;
+            buffer.len()
+        };
+        if length == 0 {
+            return Ok(());
+        }
+        reader.consume(length);
+    }
+}
 
 pub struct Collecter {
+    platform: String,
     encryption_key: Option<String>,
     artefacts: Vec<String>,
     file: FileCollecter,
     memory: MemoryCollecter,
+    /// Write the freed start of `$UsnJrnl:$J` as zeros rather than leaving it out.
+    keep_sparse: bool,
+    /// The block devices opened to read artefacts raw, keyed by device path, so each is only
+    /// opened once per collection.
+    #[cfg(target_os = "linux")]
+    devices: HashMap<String, Ext4<BufReader<File>>>,
 }
 
 impl Collecter {
     pub fn new(platform: String, encryption_key: Option<String>) -> Result<Self, Box<dyn Error>> {
         Ok(Collecter {
+            platform: platform.clone(),
             encryption_key,
             artefacts: Vec::new(),
             file: FileCollecter::new(platform.clone())?,
             memory: MemoryCollecter::new(platform.clone())?,
+            keep_sparse: false,
+            #[cfg(target_os = "linux")]
+            devices: HashMap::new(),
         })
     }
 
+    /// Creates a collecter that resolves file rules inside the filesystem of a raw disk image
+    /// rather than on the live host. The rules used are those for the platform the filesystem
+    /// belongs to, so a Windows image can be collected from on Linux with the Windows rules.
+    pub fn from_image(
+        image_path: &str,
+        encryption_key: Option<String>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let image = DiskImage::open(image_path)?;
+        let mut collecter = Collecter::new(image.platform().to_string(), encryption_key)?;
+        println!(
+            "Collecting from {0} as a {1} filesystem",
+            image.path(),
+            image.platform()
+        );
+        collecter.file.image = Some(image);
+        Ok(collecter)
+    }
+
+    /// Reads artefacts from the raw block device of the filesystem they are stored on instead of
+    /// through the kernel, so hooked system calls cannot hide or alter them. Only ext2/3/4 is
+    /// supported on Linux, artefacts on other filesystems are read normally. NTFS volumes on
+    /// Windows are always read raw.
+    pub fn set_raw_access(&mut self, raw_access: bool) {
+        self.file.raw_access = raw_access;
+    }
+
+    /// Finds files on NTFS volumes by reading `$MFT` instead of walking the directory tree.
+    pub fn set_mft_search(&mut self, mft_search: bool) {
+        self.file.mft_search = mft_search;
+    }
+
+    pub fn set_keep_sparse(&mut self, keep_sparse: bool) {
+        self.keep_sparse = keep_sparse;
+    }
+
     pub fn add_rule_from_file(&mut self, file_path: &str) -> Result<(), Box<dyn Error>> {
         let new_rule = CollectionRule::from_yaml_file(file_path)?;
 
@@ -47,6 +136,10 @@ impl Collecter {
         if let Ok(collected) = self.file.collect_by_rulename(rule_name) {
             return Ok(collected);
         }
+        // memory is only ever collected from the live host
+        if self.file.image.is_some() {
+            return Err("Only file rules can be collected from an image".into());
+        }
         if let Ok(collected) = self.memory.collect_by_rulename(rule_name) {
             return Ok(collected);
         }
@@ -55,7 +148,9 @@ impl Collecter {
 
     pub fn collect_all(&mut self) -> Result<(), Box<dyn Error>> {
         self.file.collect_all()?;
-        self.memory.collect_all()?;
+        if self.file.image.is_none() {
+            self.memory.collect_all()?;
+        }
         Ok(())
     }
 
@@ -63,32 +158,75 @@ impl Collecter {
         &mut self,
         last_modified: NaiveDateTime,
     ) -> Result<FileOptions<'_, ()>, Box<dyn Error>> {
+        // zip can only store times between 1980 and 2107, the real time is kept in the manifest
+        let last_modified: zip::DateTime = last_modified.try_into().unwrap_or_default();
         if self.encryption_key.is_some() {
             Ok(SimpleFileOptions::default()
                 .compression_method(zip::CompressionMethod::BZIP2)
-                .last_modified_time(last_modified.try_into()?)
+                .last_modified_time(last_modified)
                 .large_file(true)
                 .with_aes_encryption(Aes256, self.encryption_key.as_deref().unwrap()))
         } else {
             Ok(SimpleFileOptions::default()
                 .compression_method(CompressionMethod::BZIP2)
                 .large_file(true)
-                .last_modified_time(last_modified.try_into()?))
+                .last_modified_time(last_modified))
         }
     }
 
+    fn compress_image_file<W: Write + Seek>(
+        &mut self,
+        zip: &mut ZipWriter<W>,
+        file_path: String,
+    ) -> Result<ManifestEntry, Box<dyn Error>> {
+        let image = self.file.image.as_mut().ok_or("No image to collect from")?;
+        let zip_path = image.archive_path(&file_path);
+        let file = image.open_file(&file_path)?;
+        let last_modified = image.get_lastmodified(&file)?;
+        let metadata = image.get_metadata(&file).unwrap_or_default();
+        let recovery_confidence = image.recovery_confidence(&file)?;
+        let options = self.get_zip_options(last_modified)?;
+
+        zip.start_file(zip_path.clone(), options)?;
+        let mut writer = HashingWriter::new(&mut *zip);
+        // looked up again as get_zip_options needs to borrow the whole collecter
+        let image = self.file.image.as_mut().ok_or("No image to collect from")?;
+        let start_offset = match image.copy_file(&file, &mut writer, self.keep_sparse) {
+            Ok(start_offset) => start_offset,
+            Err(e) => return Err(abort_entry(zip, e)),
+        };
+
+        let (sha256, size) = writer.finish();
+        Ok(ManifestEntry {
+            rule: String::new(),
+            other_rules: Vec::new(),
+            archive_path: zip_path,
+            original_path: file_path,
+            size,
+            sha256,
+            last_modified: last_modified.and_utc().timestamp(),
+            // nothing in an image is ever accessed through a mounted filesystem
+            atime_preserved: true,
+            metadata,
+            recovery_confidence,
+            start_offset: (start_offset > 0).then_some(start_offset),
+        })
+    }
+
     #[cfg(target_os = "windows")]
-    fn compress_file(
+    fn compress_file<W: Write + Seek>(
         &mut self,
-        zip: &mut ZipWriter<File>,
+        zip: &mut ZipWriter<W>,
         file_path: String,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<ManifestEntry, Box<dyn Error>> {
         use std::path::Path;
 
         let (path, stream_name) = parse_stream(file_path.as_str());
         let zip_path: String;
 
-        if self.memory.get_memory_dumps().contains(&file_path) {
+        if deleted_record(&path).is_some() {
+            zip_path = deleted_archive_path(&path);
+        } else if self.memory.get_memory_dumps().contains(&file_path) {
             zip_path = format!(
                 "memory/{}",
                 Path::new(&file_path)
@@ -102,73 +240,207 @@ impl Collecter {
             zip_path = format!("{0}_{1}", path.replace(":", ""), stream_name);
         }
 
-        if let Ok(last_modified) = get_lastmodified(path.clone()) {
+        let zip_path = path_to_string(zip_path).to_string();
+        let last_modified: NaiveDateTime;
+        let metadata: FileMetadata;
+        let atime_preserved: bool;
+        let mut recovery_confidence = None;
+        let mut start_offset = None;
+        let mut writer: HashingWriter<&mut ZipWriter<W>>;
+
+        if let Ok(ntfs_last_modified) = get_lastmodified(path.clone()) {
+            last_modified = ntfs_last_modified;
+            metadata = get_metadata(path.clone()).unwrap_or_default();
+            // reading the raw volume bypasses the filesystem so access times are never touched
+            atime_preserved = true;
+            recovery_confidence = get_recovery_confidence(path.clone())?;
             let options = self.get_zip_options(last_modified)?;
-            zip.start_file_from_path(zip_path, options)?;
-            copy_file(file_path, zip)?;
+            zip.start_file(zip_path.clone(), options)?;
+            writer = HashingWriter::new(&mut *zip);
+            match copy_file(file_path.clone(), &mut writer, self.keep_sparse) {
+                Ok(copied) if copied.start_offset > 0 => start_offset = Some(copied.start_offset),
+                Ok(_) => {}
+                Err(e) => return Err(abort_entry(zip, e)),
+            }
         } else {
             let file = File::options()
                 .read(true)
                 .write(false)
                 .open(file_path.clone())?;
-            let last_modified = file.metadata()?.modified()?;
+            metadata = FileMetadata::from_file(&file, &file_path).unwrap_or_default();
+            let accessed = file.metadata()?.accessed().ok();
+            let file_last_modified = file.metadata()?.modified()?;
             let mut reader = BufReader::new(file);
-            let last_modified = DateTime::<Local>::from(last_modified).naive_utc();
+            last_modified = DateTime::<Utc>::from(file_last_modified).naive_utc();
             let options = self.get_zip_options(last_modified)?;
 
-            zip.start_file_from_path(zip_path, options)?;
+            zip.start_file(zip_path.clone(), options)?;
+            writer = HashingWriter::new(&mut *zip);
+            if let Err(e) = copy_buffered(&mut reader, &mut writer) {
+                return Err(abort_entry(zip, e.into()));
+            }
+            atime_preserved = reader.get_ref().metadata()?.accessed().ok() == accessed;
+        }
 
-            loop {
-                let length = {
-                    let buffer = reader.fill_buf()?;
-                    zip.write_all(buffer)?;
-                    buffer.len()
-                };
-                if length == 0 {
-                    break;
-                }
-                reader.consume(length);
+        let (sha256, size) = writer.finish();
+        Ok(ManifestEntry {
+            rule: String::new(),
+            other_rules: Vec::new(),
+            archive_path: zip_path,
+            original_path: file_path,
+            size,
+            sha256,
+            last_modified: last_modified.and_utc().timestamp(),
+            atime_preserved,
+            metadata,
+            recovery_confidence,
+            start_offset,
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn compress_raw_file<W: Write + Seek>(
+        &mut self,
+        zip: &mut ZipWriter<W>,
+        file_path: String,
+    ) -> Result<Option<ManifestEntry>, Box<dyn Error>> {
+        let mount = match find_mount(&file_path) {
+            Some(mount) => mount,
+            None => return Ok(None),
+        };
+        // anything that stops the file being read raw falls back to reading it normally
+        let filesystem = match self.devices.entry(mount.device.clone()) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                open_device(&mount.device).map(|filesystem| entry.insert(filesystem))
+            }
+        };
+        let inode = match filesystem
+            .and_then(|filesystem| filesystem.open_file(mount.relative_path(&file_path)))
+        {
+            Ok(inode) => inode,
+            Err(e) => {
+                println!(
+                    "Failed to read {0} from {1}, {2}",
+                    file_path, mount.device, e
+                );
+                return Ok(None);
             }
+        };
+        let last_modified = DateTime::<Utc>::from_timestamp(inode.modified.0, inode.modified.1)
+            .ok_or("Invalid modified time")?
+            .naive_utc();
+        let options = self.get_zip_options(last_modified)?;
+        let zip_path = path_to_string(&file_path).to_string();
+
+        zip.start_file(zip_path.clone(), options)?;
+        let mut writer = HashingWriter::new(&mut *zip);
+        // looked up again as get_zip_options needs to borrow the whole collecter
+        let filesystem = self
+            .devices
+            .get_mut(&mount.device)
+            .ok_or("No filesystem for the device")?;
+        if let Err(e) = filesystem.copy_inode(&inode, &mut writer) {
+            return Err(abort_entry(zip, e));
         }
 
-        Ok(())
+        let (sha256, size) = writer.finish();
+        Ok(Some(ManifestEntry {
+            rule: String::new(),
+            other_rules: Vec::new(),
+            archive_path: zip_path,
+            original_path: file_path,
+            size,
+            sha256,
+            last_modified: last_modified.and_utc().timestamp(),
+            // the block device is read directly so the file's access time is never touched
+            atime_preserved: true,
+            metadata: inode.metadata(),
+            recovery_confidence: None,
+            start_offset: None,
+        }))
     }
 
     #[cfg(target_os = "linux")]
-    fn compress_file(
+    fn compress_file<W: Write + Seek>(
         &mut self,
-        zip: &mut ZipWriter<File>,
+        zip: &mut ZipWriter<W>,
         file_path: String,
-    ) -> Result<(), Box<dyn Error>> {
-        let file = File::options()
-            .read(true)
-            .write(false)
-            .open(file_path.clone())?;
+    ) -> Result<ManifestEntry, Box<dyn Error>> {
+        if self.file.raw_access && !self.memory.get_memory_dumps().contains(&file_path) {
+            if let Some(entry) = self.compress_raw_file(zip, file_path.clone())? {
+                return Ok(entry);
+            }
+        }
+
+        let (file, noatime) = open_noatime(Path::new(&file_path))?;
+        if !noatime {
+            println!(
+                "Not permitted to open {} with O_NOATIME, reading it may update its access time",
+                file_path
+            );
+        }
+        let metadata = FileMetadata::from_file(&file, &file_path).unwrap_or_default();
+        let accessed = file.metadata()?.accessed().ok();
         let last_modified = file.metadata()?.modified()?;
         let mut reader = BufReader::new(file);
-        let last_modified = DateTime::<Local>::from(last_modified).naive_utc();
+        let last_modified = DateTime::<Utc>::from(last_modified).naive_utc();
         let options = self.get_zip_options(last_modified)?;
+        let zip_path = path_to_string(&file_path).to_string();
 
-        zip.start_file_from_path(file_path, options)?;
-
-        loop {
-            let length = {
-                let buffer = reader.fill_buf()?;
-                zip.write_all(buffer)?;
-                buffer.len()
-            };
-            if length == 0 {
-                break;
-            }
-            reader.consume(length);
+        zip.start_file(zip_path.clone(), options)?;
+        let mut writer = HashingWriter::new(&mut *zip);
+        if let Err(e) = copy_buffered(&mut reader, &mut writer) {
+            return Err(abort_entry(zip, e.into()));
         }
 
-        Ok(())
+        let (sha256, size) = writer.finish();
+        Ok(ManifestEntry {
+            rule: String::new(),
+            other_rules: Vec::new(),
+            archive_path: zip_path,
+            original_path: file_path,
+            size,
+            sha256,
+            last_modified: last_modified.and_utc().timestamp(),
+            atime_preserved: reader.get_ref().metadata()?.accessed().ok() == accessed,
+            metadata,
+            recovery_confidence: None,
+            start_offset: None,
+        })
     }
 
     pub fn compress_collection(&mut self, output_file: &str) -> Result<(), Box<dyn Error>> {
+        let zip_file = File::create(output_file)?;
+        match self.compress_collection_to(zip_file) {
+            Ok(0) => {
+                std::fs::remove_file(output_file)?;
+                Ok(())
+            }
+            Ok(_) => Ok(()),
+            Err(e) => {
+                std::fs::remove_file(output_file).unwrap_or_default();
+                Err(e)
+            }
+        }
+    }
+
+    /// Compresses every collected artefact into a zip written to `writer`, this allows the
+    /// collection to be streamed straight to a remote destination instead of a local file.
+    /// Returns the number of artefacts that were successfully compressed.
+    pub fn compress_collection_to<W: Write + Seek>(
+        &mut self,
+        writer: W,
+    ) -> Result<usize, Box<dyn Error>> {
         self.artefacts.append(&mut self.file.files);
         self.artefacts.append(&mut self.memory.get_memory_dumps());
+        let mut artefact_rules = self.file.file_rules.clone();
+        artefact_rules.extend(
+            self.memory
+                .get_memory_dump_rules()
+                .into_iter()
+                .map(|(artefact, rule)| (artefact, vec![rule])),
+        );
 
         // remove any duplicates
         let mut unique_artefacts = std::collections::HashSet::new();
@@ -180,13 +452,24 @@ impl Collecter {
             return Err("No artefacts to compress".into());
         }
 
-        let zip_file = File::create(output_file)?;
-        let mut zip: ZipWriter<File> = ZipWriter::new(zip_file);
+        let mut zip = ZipWriter::new(writer);
+        let mut manifest = Manifest::new(self.platform.clone());
         let mut processed = 0;
         for artefact in unique_artefacts {
-            match self.compress_file(&mut zip, artefact.clone()) {
-                Ok(_) => {
+            let compressed = if self.file.image.is_some() {
+                self.compress_image_file(&mut zip, artefact.clone())
+            } else {
+                self.compress_file(&mut zip, artefact.clone())
+            };
+            match compressed {
+                Ok(mut entry) => {
                     println!("Compressed artefact: {}", artefact);
+                    let mut rules = artefact_rules.remove(&artefact).unwrap_or_default();
+                    if !rules.is_empty() {
+                        entry.rule = rules.remove(0);
+                    }
+                    entry.other_rules = rules;
+                    manifest.entries.push(entry);
                     processed += 1;
                     continue;
                 }
@@ -196,10 +479,12 @@ impl Collecter {
                 }
             }
         }
-        zip.finish()?;
-        if processed == 0 {
-            std::fs::remove_file(output_file)?;
-        }
-        Ok(())
+
+        let options = self.get_zip_options(chrono::Utc::now().naive_utc())?;
+        zip.start_file(MANIFEST_NAME, options)?;
+        zip.write_all(manifest.to_json()?.as_bytes())?;
+
+        zip.finish()?.flush()?;
+        Ok(processed)
     }
 }
@@ -1,27 +1,46 @@
+use super::image::DiskImage;
 use super::rules::CollectionRule;
 use super::rules::FileRule;
-use ignore::{WalkBuilder, WalkState};
-use num_cpus;
 use regex::Regex;
+use std::collections::HashMap;
+use std::error::Error;
+
+#[cfg(target_os = "linux")]
+use super::readers::ext4_reader::{get_mounts, open_device};
+#[cfg(target_os = "windows")]
+use super::readers::ntfs_reader::{
+    does_file_exist, expand_streams, list_streams, parse_stream, walk_deleted, walk_mft,
+};
+#[cfg(target_os = "linux")]
+use super::walker::walk;
+#[cfg(target_os = "windows")]
+use ignore::{WalkBuilder, WalkState};
+#[cfg(target_os = "windows")]
 use std::{
     cmp,
     sync::mpsc::{self, Sender},
 };
-use std::{env, error::Error};
-
-#[cfg(target_os = "windows")]
-use super::readers::ntfs_reader::{does_file_exist, parse_stream};
 #[cfg(target_os = "windows")]
 use windows::Win32::Storage::FileSystem::GetLogicalDriveStringsA;
 
 pub struct FileCollecter {
+    platform: String,
     rules: Vec<FileRule>,
     pub files: Vec<String>,
+    /// Every rule that collected a file, in the order they were run.
+    pub file_rules: HashMap<String, Vec<String>>,
+    /// Search and read artefacts from the raw block device rather than through the kernel.
+    pub raw_access: bool,
+    /// Find files on NTFS volumes by reading `$MFT` instead of walking the directory tree.
+    pub mft_search: bool,
+    /// Rules are resolved inside this image instead of on the live host when it is set.
+    pub image: Option<DiskImage>,
 }
 
 impl FileCollecter {
     pub fn new(platform: String) -> Result<Self, Box<dyn Error>> {
         Ok(FileCollecter {
+            platform: platform.clone(),
             rules: CollectionRule::get_rules_by_platform_and_type(platform.as_str(), "file")?
                 .into_iter()
                 .filter_map(|rule| {
@@ -33,12 +52,16 @@ impl FileCollecter {
                 })
                 .collect(),
             files: Vec::new(),
+            file_rules: HashMap::new(),
+            raw_access: false,
+            mft_search: false,
+            image: None,
         })
     }
 
     pub fn add_rule(&mut self, new_rule: CollectionRule) -> Result<(), Box<dyn Error>> {
         if let CollectionRule::FileRule(rule) = new_rule {
-            if rule.platform != env::consts::OS {
+            if rule.platform != self.platform {
                 return Err("Rule platform does not match current platform".into());
             }
             if self
@@ -55,6 +78,101 @@ impl FileCollecter {
         Ok(())
     }
 
+    #[cfg(target_os = "linux")]
+    fn search_filesystem(
+        depth: usize,
+        path: String,
+        patterns: Vec<String>,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let patterns: Vec<Regex> = patterns
+            .iter()
+            .filter_map(|pattern| Regex::new(pattern).ok())
+            .collect();
+        let mut found: Vec<String> = Vec::new();
+
+        walk(std::path::Path::new(&path), depth, true, |entry| {
+            let path = entry.to_string_lossy();
+            let file = entry.file_name().unwrap_or_default().to_string_lossy();
+            if patterns
+                .iter()
+                .any(|regex| regex.is_match(&path) || regex.is_match(&file))
+            {
+                found.push(path.to_string());
+            }
+        });
+
+        Ok(found)
+    }
+
+    /// Searches every mounted ext2/3/4 filesystem by parsing its block device directly, this
+    /// finds files that are hidden from directory listings by a rootkit.
+    #[cfg(target_os = "linux")]
+    fn search_raw_filesystems(
+        depth: usize,
+        patterns: Vec<String>,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let patterns: Vec<Regex> = patterns
+            .iter()
+            .filter_map(|pattern| Regex::new(pattern).ok())
+            .collect();
+        let mut found: Vec<String> = Vec::new();
+
+        for mount in get_mounts()? {
+            let mount_point = mount.mount_point.trim_end_matches('/').to_string();
+            let mount_depth = mount_point.split('/').filter(|c| !c.is_empty()).count();
+            if mount_depth >= depth {
+                continue;
+            }
+            let mut filesystem = match open_device(&mount.device) {
+                Ok(filesystem) => filesystem,
+                Err(e) => {
+                    println!("Failed to open {0} for raw reading, {1}", mount.device, e);
+                    continue;
+                }
+            };
+            filesystem.walk("/", depth - mount_depth, &mut |entry, _| {
+                let path = format!("{0}{1}", mount_point, entry);
+                let file = entry.rsplit('/').next().unwrap_or_default();
+                if patterns
+                    .iter()
+                    .any(|regex| regex.is_match(&path) || regex.is_match(file))
+                {
+                    found.push(path);
+                }
+            })?;
+        }
+
+        Ok(found)
+    }
+
+    /// Adds the files only found by searching the raw filesystems to `files`.
+    #[cfg(target_os = "linux")]
+    fn collect_raw_by_rule(&self, rule: &FileRule, files: &mut Vec<String>) {
+        if !self.raw_access {
+            return;
+        }
+        match FileCollecter::search_raw_filesystems(rule.recursion_depth, rule.paths.clone()) {
+            Ok(found) => {
+                for file in found {
+                    if !files.contains(&file) {
+                        println!("Found {} by reading the raw filesystem", file);
+                        files.push(file);
+                    }
+                }
+            }
+            Err(e) => println!(
+                "Failed to search raw filesystems for rule: {0}, {1}",
+                rule.name, e
+            ),
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    fn collect_raw_by_rule(&self, _rule: &FileRule, _files: &mut Vec<String>) {
+        // NTFS volumes are always read raw, the regular search already covers them
+    }
+
+    #[cfg(target_os = "windows")]
     fn search_filesystem(
         depth: usize,
         path: String,
@@ -89,15 +207,11 @@ impl FileCollecter {
                             .to_string_lossy()
                             .to_string();
                         for pattern in patterns.iter() {
-                            #[cfg(target_os = "windows")]
                             let (pattern, stream) = parse_stream(pattern);
-                            if let Ok(regex) = Regex::new(pattern) {
+                            if let Ok(regex) = Regex::new(&pattern) {
                                 if regex.is_match(&path) || regex.is_match(&file) {
-                                    #[cfg(target_os = "windows")]
                                     tx.send(format!("{0}:{1}", path.clone(), stream))
                                         .unwrap_or_default();
-                                    #[cfg(target_os = "linux")]
-                                    tx.send(path.clone()).unwrap_or_default();
                                     return WalkState::Continue;
                                 }
                             }
@@ -119,8 +233,61 @@ impl FileCollecter {
         Ok(stdout_thread.join().unwrap_or_default())
     }
 
+    /// Matches the patterns against the paths of every file in `$MFT` of an NTFS volume, which
+    /// includes files that are hidden from or denied to the directory walk.
+    #[cfg(target_os = "windows")]
+    fn search_mft(
+        depth: usize,
+        drive_letter: &str,
+        patterns: &[String],
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let patterns: Vec<(Regex, String)> = patterns
+            .iter()
+            .filter_map(|pattern| {
+                let (pattern, stream) = parse_stream(pattern);
+                Regex::new(&pattern).ok().map(|regex| (regex, stream))
+            })
+            .collect();
+        let mut found = Vec::new();
+        walk_mft(drive_letter, depth, &mut |path| {
+            let file = path.rsplit('\\').next().unwrap_or_default();
+            if let Some((_, stream)) = patterns
+                .iter()
+                .find(|(regex, _)| regex.is_match(path) || regex.is_match(file))
+            {
+                found.push(format!("{0}:{1}", path, stream));
+            }
+        })?;
+        Ok(found)
+    }
+
+    /// Matches the patterns against the paths deleted files had, returning the paths under
+    /// `$Deleted` they are recovered from.
+    #[cfg(target_os = "windows")]
+    fn search_deleted(
+        depth: usize,
+        drive_letter: &str,
+        patterns: &[String],
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let patterns: Vec<Regex> = patterns
+            .iter()
+            .filter_map(|pattern| Regex::new(&parse_stream(pattern).0).ok())
+            .collect();
+        let mut found = Vec::new();
+        walk_deleted(drive_letter, depth, &mut |path, deleted_path| {
+            let file = path.rsplit('\\').next().unwrap_or_default();
+            if patterns
+                .iter()
+                .any(|regex| regex.is_match(path) || regex.is_match(file))
+            {
+                found.push(deleted_path.to_string());
+            }
+        })?;
+        Ok(found)
+    }
+
     #[cfg(target_os = "linux")]
-    pub fn collect_by_rule(rule: &FileRule) -> Result<Vec<String>, Box<dyn Error>> {
+    pub fn collect_by_rule(&self, rule: &FileRule) -> Result<Vec<String>, Box<dyn Error>> {
         let mut files: Vec<String> = Vec::new();
         for path in rule.paths.clone() {
             if std::path::Path::new(&path).exists() {
@@ -143,14 +310,24 @@ impl FileCollecter {
             .iter()
             .find(|rule| rule.name == rule_name)
             .ok_or_else(|| format!("Rule with name '{}' not found", rule_name))?;
-        let mut collected_files = FileCollecter::collect_by_rule(rule)?;
+        let mut collected_files = match &mut self.image {
+            Some(image) => image.collect_by_rule(rule, self.mft_search)?,
+            None => {
+                let mut files = self.collect_by_rule(rule)?;
+                self.collect_raw_by_rule(rule, &mut files);
+                files
+            }
+        };
         let collected_files_len = collected_files.len();
+        for file in &collected_files {
+            add_rule(&mut self.file_rules, file, &rule.name);
+        }
         self.files.append(&mut collected_files);
         Ok(collected_files_len)
     }
 
     #[cfg(target_os = "windows")]
-    pub fn collect_by_rule(rule: &FileRule) -> Result<Vec<String>, Box<dyn Error>> {
+    pub fn collect_by_rule(&self, rule: &FileRule) -> Result<Vec<String>, Box<dyn Error>> {
         let drives = FileCollecter::get_windows_drives()?;
         let mut files = Vec::new();
         for drive_letter in drives {
@@ -172,15 +349,38 @@ impl FileCollecter {
                     ));
                 }
             }
-            for file in &mut FileCollecter::search_filesystem(
-                rule.recursion_depth,
-                format!("{}:\\", drive_letter),
-                rule.paths.clone(),
-            )? {
-                files.push(file.clone());
+            let mut found = None;
+            if self.mft_search {
+                // volumes that are not NTFS fall back to walking the directories
+                match FileCollecter::search_mft(rule.recursion_depth, &drive_letter, &rule.paths) {
+                    Ok(mft_files) => found = Some(mft_files),
+                    Err(e) => println!("Failed to read $MFT of drive {0}: {1}", drive_letter, e),
+                }
+            }
+            let found = match found {
+                Some(found) => found,
+                None => FileCollecter::search_filesystem(
+                    rule.recursion_depth,
+                    format!("{}:\\", drive_letter),
+                    rule.paths.clone(),
+                )?,
+            };
+            files.extend(found);
+            if rule.deleted {
+                match FileCollecter::search_deleted(
+                    rule.recursion_depth,
+                    &drive_letter,
+                    &rule.paths,
+                ) {
+                    Ok(deleted) => files.extend(deleted),
+                    Err(e) => println!(
+                        "Failed to search drive {0} for deleted files: {1}",
+                        drive_letter, e
+                    ),
+                }
             }
         }
-        Ok(files)
+        Ok(expand_streams(files, |path| list_streams(path.to_string())))
     }
 
     #[cfg(target_os = "windows")]
@@ -202,8 +402,18 @@ impl FileCollecter {
 
     pub fn collect_all(&mut self) -> Result<(), Box<dyn Error>> {
         for rule in &self.rules {
-            match FileCollecter::collect_by_rule(rule) {
+            let collected = match &mut self.image {
+                Some(image) => image.collect_by_rule(rule, self.mft_search),
+                None => self.collect_by_rule(rule).map(|mut files| {
+                    self.collect_raw_by_rule(rule, &mut files);
+                    files
+                }),
+            };
+            match collected {
                 Ok(mut files) => {
+                    for file in &files {
+                        add_rule(&mut self.file_rules, file, &rule.name);
+                    }
                     self.files.append(&mut files);
                     println!(
                         "Collected {0} artefacts for rule: {1}",
@@ -217,3 +427,11 @@ impl FileCollecter {
         Ok(())
     }
 }
+
+/// Records that `rule` collected `file`, a file can be collected by several rules.
+fn add_rule(file_rules: &mut HashMap<String, Vec<String>>, file: &str, rule: &str) {
+    let rules = file_rules.entry(file.to_string()).or_default();
+    if !rules.iter().any(|name| name == rule) {
+        rules.push(rule.to_string());
+    }
+}
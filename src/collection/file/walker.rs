@@ -0,0 +1,162 @@
+use std::{
+    ffi::{CStr, CString, OsStr},
+    fs::File,
+    io,
+    os::unix::{ffi::OsStrExt, fs::OpenOptionsExt},
+    path::{Path, PathBuf},
+};
+
+/// Opens a file for reading without updating its access time. `O_NOATIME` is only permitted
+/// for the owner of the file or a process with `CAP_FOWNER`, if it is refused the file is opened
+/// normally. Returns the file and whether `O_NOATIME` was used.
+pub fn open_noatime(path: &Path) -> io::Result<(File, bool)> {
+    match File::options()
+        .read(true)
+        .write(false)
+        .custom_flags(libc::O_NOATIME)
+        .open(path)
+    {
+        Ok(file) => Ok((file, true)),
+        Err(e) if e.raw_os_error() == Some(libc::EPERM) => {
+            Ok((File::options().read(true).write(false).open(path)?, false))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+struct Directory {
+    stream: *mut libc::DIR,
+}
+
+impl Directory {
+    fn open(path: &Path) -> io::Result<Self> {
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+        let mut fd = unsafe { libc::open(path.as_ptr(), flags | libc::O_NOATIME) };
+        if fd < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) {
+            fd = unsafe { libc::open(path.as_ptr(), flags) };
+        }
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let stream = unsafe { libc::fdopendir(fd) };
+        if stream.is_null() {
+            let error = io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(error);
+        }
+        Ok(Directory { stream })
+    }
+
+    fn fd(&self) -> libc::c_int {
+        unsafe { libc::dirfd(self.stream) }
+    }
+}
+
+impl Drop for Directory {
+    fn drop(&mut self) {
+        unsafe { libc::closedir(self.stream) };
+    }
+}
+
+fn stat_at(directory: &Directory, name: &CStr, follow_links: bool) -> Option<libc::stat> {
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    let flags = if follow_links {
+        0
+    } else {
+        libc::AT_SYMLINK_NOFOLLOW
+    };
+    if unsafe { libc::fstatat(directory.fd(), name.as_ptr(), &mut stat, flags) } == 0 {
+        Some(stat)
+    } else {
+        None
+    }
+}
+
+/// Walks a directory tree calling `found` with the path of every non-directory entry, up to
+/// `max_depth` levels below `root`. Unlike `std::fs::read_dir`, directories are opened with
+/// `O_NOATIME` so listing them does not update their access times. Symbolic links are followed
+/// when `follow_links` is set, directories that are already being walked are skipped to avoid
+/// looping forever.
+pub fn walk<F>(root: &Path, max_depth: usize, follow_links: bool, mut found: F)
+where
+    F: FnMut(&Path),
+{
+    let mut ancestors = Vec::new();
+    if let Ok(stat) = std::fs::metadata(root) {
+        use std::os::unix::fs::MetadataExt;
+        if !stat.is_dir() {
+            found(root);
+            return;
+        }
+        ancestors.push((stat.dev(), stat.ino()));
+    }
+    walk_directory(root, 1, max_depth, follow_links, &mut ancestors, &mut found);
+}
+
+fn walk_directory<F>(
+    path: &Path,
+    depth: usize,
+    max_depth: usize,
+    follow_links: bool,
+    ancestors: &mut Vec<(u64, u64)>,
+    found: &mut F,
+) where
+    F: FnMut(&Path),
+{
+    if depth > max_depth {
+        return;
+    }
+    let directory = match Directory::open(path) {
+        Ok(directory) => directory,
+        Err(_) => return,
+    };
+
+    let mut subdirectories: Vec<(PathBuf, (u64, u64))> = Vec::new();
+    loop {
+        let entry = unsafe { libc::readdir(directory.stream) };
+        if entry.is_null() {
+            break;
+        }
+        let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+        if name.to_bytes() == b"." || name.to_bytes() == b".." {
+            continue;
+        }
+        let entry_path = path.join(OsStr::from_bytes(name.to_bytes()));
+        let d_type = unsafe { (*entry).d_type };
+
+        let is_directory = match d_type {
+            libc::DT_DIR => true,
+            libc::DT_LNK if !follow_links => false,
+            libc::DT_LNK | libc::DT_UNKNOWN => stat_at(&directory, name, follow_links)
+                .map(|stat| stat.st_mode & libc::S_IFMT == libc::S_IFDIR)
+                .unwrap_or(false),
+            _ => false,
+        };
+
+        if is_directory {
+            if let Some(stat) = stat_at(&directory, name, follow_links) {
+                subdirectories.push((entry_path, (stat.st_dev, stat.st_ino)));
+            }
+        } else {
+            found(&entry_path);
+        }
+    }
+    drop(directory);
+
+    for (subdirectory, id) in subdirectories {
+        if ancestors.contains(&id) {
+            continue;
+        }
+        ancestors.push(id);
+        walk_directory(
+            &subdirectory,
+            depth + 1,
+            max_depth,
+            follow_links,
+            ancestors,
+            found,
+        );
+        ancestors.pop();
+    }
+}
@@ -0,0 +1,516 @@
+use super::manifest::RecoveryConfidence;
+use super::metadata::FileMetadata;
+use super::readers::ewf_reader::{EwfReader, EWF_SIGNATURE};
+use super::readers::ext4_reader::{Ext4, Inode};
+use super::readers::ntfs_reader::{
+    deleted_archive_path, deleted_record, expand_streams, parse_stream, Drive, ResolvedFile,
+};
+use super::readers::partition_reader::{read_partition_table, Partition, PartitionReader};
+use super::readers::vhd_reader::{is_vhd, VhdReader};
+use super::readers::vhdx_reader::{VhdxReader, VHDX_SIGNATURE};
+use super::rules::FileRule;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use regex::Regex;
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+};
+use zip::unstable::path_to_string;
+
+/// The container the disk is stored in, detected from its signature. Anything that is not an
+/// EWF, VHD or VHDX file is read as a raw (dd) image.
+enum ImageReader {
+    Raw(File),
+    Ewf(EwfReader),
+    Vhd(VhdReader),
+    Vhdx(VhdxReader),
+}
+
+impl ImageReader {
+    fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::open(path)?;
+        let mut signature = [0u8; 8];
+        let read = file.read(&mut signature)?;
+        if read == signature.len() && &signature == EWF_SIGNATURE {
+            return Ok(ImageReader::Ewf(EwfReader::open(path)?));
+        }
+        if read == signature.len() && &signature == VHDX_SIGNATURE {
+            return Ok(ImageReader::Vhdx(VhdxReader::open(path)?));
+        }
+        if is_vhd(&mut file) {
+            return Ok(ImageReader::Vhd(VhdReader::open(path)?));
+        }
+        file.seek(SeekFrom::Start(0))?;
+        Ok(ImageReader::Raw(file))
+    }
+
+    fn size(&self) -> Result<u64, Box<dyn Error>> {
+        Ok(match self {
+            ImageReader::Raw(file) => file.metadata()?.len(),
+            ImageReader::Ewf(reader) => reader.size(),
+            ImageReader::Vhd(reader) => reader.size(),
+            ImageReader::Vhdx(reader) => reader.size(),
+        })
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            ImageReader::Raw(_) => "raw",
+            ImageReader::Ewf(_) => "EWF",
+            ImageReader::Vhd(_) => "VHD",
+            ImageReader::Vhdx(_) => "VHDX",
+        }
+    }
+}
+
+impl Read for ImageReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            ImageReader::Raw(file) => file.read(buf),
+            ImageReader::Ewf(reader) => reader.read(buf),
+            ImageReader::Vhd(reader) => reader.read(buf),
+            ImageReader::Vhdx(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl Seek for ImageReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            ImageReader::Raw(file) => file.seek(pos),
+            ImageReader::Ewf(reader) => reader.seek(pos),
+            ImageReader::Vhd(reader) => reader.seek(pos),
+            ImageReader::Vhdx(reader) => reader.seek(pos),
+        }
+    }
+}
+
+type VolumeReader = BufReader<PartitionReader<ImageReader>>;
+
+enum Filesystem {
+    Ext4(Ext4<VolumeReader>),
+    Ntfs(Drive<VolumeReader>),
+}
+
+impl Filesystem {
+    fn open(path: &str, offset: u64, size: u64) -> Option<Self> {
+        let reader = || -> Option<VolumeReader> {
+            Some(BufReader::new(PartitionReader::new(
+                ImageReader::open(path).ok()?,
+                offset,
+                size,
+            )))
+        };
+        if let Ok(filesystem) = Ext4::new(reader()?) {
+            return Some(Filesystem::Ext4(filesystem));
+        }
+        if let Ok(drive) = Drive::new(reader()?) {
+            return Some(Filesystem::Ntfs(drive));
+        }
+        None
+    }
+
+    fn platform(&self) -> &'static str {
+        match self {
+            Filesystem::Ext4(_) => "linux",
+            Filesystem::Ntfs(_) => "windows",
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Filesystem::Ext4(_) => "ext2/3/4",
+            Filesystem::Ntfs(_) => "NTFS",
+        }
+    }
+
+    /// Whether an operating system is installed on the filesystem, rather than it only holding
+    /// data or being a boot partition.
+    fn holds_system(&mut self) -> bool {
+        match self {
+            Filesystem::Ext4(filesystem) => filesystem.open_file(LINUX_SYSTEM_FILE).is_ok(),
+            Filesystem::Ntfs(drive) => drive.does_file_exist(WINDOWS_SYSTEM_FILE),
+        }
+    }
+}
+
+/// Files that are only found on the root filesystem of an installed operating system.
+const LINUX_SYSTEM_FILE: &str = "/etc/passwd";
+const WINDOWS_SYSTEM_FILE: &str = "Windows\\System32\\config\\SYSTEM";
+
+/// A filesystem found in the image. Its artefacts are prefixed with its name, a drive letter for
+/// NTFS volumes and `partition<number>` for ext volumes, unless the image holds a single ext
+/// filesystem without a partition table.
+struct Volume {
+    name: String,
+    filesystem: Filesystem,
+}
+
+impl Volume {
+    fn artefact_path(&self, path: &str) -> String {
+        if self.name.is_empty() {
+            path.to_string()
+        } else {
+            format!("{0}:{1}", self.name, path)
+        }
+    }
+
+    /// Returns the path inside this volume if `file_path` belongs to it.
+    fn volume_path<'a>(&self, file_path: &'a str) -> Option<&'a str> {
+        if self.name.is_empty() {
+            return Some(file_path);
+        }
+        match self.filesystem {
+            Filesystem::Ext4(_) => file_path.strip_prefix(&format!("{}:", self.name)),
+            // the NTFS reader skips the drive letter itself
+            Filesystem::Ntfs(_) => file_path
+                .strip_prefix(&format!("{}:", self.name))
+                .map(|_| file_path),
+        }
+    }
+}
+
+/// Names the filesystems found in the partitions of a disk. NTFS volumes are given drive letters
+/// like Windows does for fixed disks, `C:` for the volume Windows is installed on and the rest
+/// from `D:` in partition order. Ext volumes are named after their partition (`partition2`).
+fn name_volumes(mut found: Vec<(Partition, Filesystem)>) -> Vec<(Partition, String, Filesystem)> {
+    let system = found.iter_mut().position(|(_, filesystem)| {
+        matches!(filesystem, Filesystem::Ntfs(_)) && filesystem.holds_system()
+    });
+    let mut drive_letters = ('D'..='Z').map(|letter| letter.to_string());
+    let mut volumes = Vec::new();
+    for (index, (partition, filesystem)) in found.into_iter().enumerate() {
+        let name = match filesystem {
+            Filesystem::Ext4(_) => format!("partition{}", partition.number),
+            Filesystem::Ntfs(_) if Some(index) == system => String::from("C"),
+            Filesystem::Ntfs(_) => match drive_letters.next() {
+                Some(letter) => letter,
+                None => continue,
+            },
+        };
+        volumes.push((partition, name, filesystem));
+    }
+    volumes
+}
+
+/// The platform whose rules apply to a disk, the one installed on it or, when none of its
+/// volumes hold an operating system, the one most of them belong to.
+fn system_platform(volumes: &mut [Volume]) -> Option<&'static str> {
+    if let Some(system) = volumes
+        .iter_mut()
+        .position(|volume| volume.filesystem.holds_system())
+    {
+        return Some(volumes[system].filesystem.platform());
+    }
+    let first = volumes.first()?.filesystem.platform();
+    let same = volumes
+        .iter()
+        .filter(|volume| volume.filesystem.platform() == first)
+        .count();
+    if same * 2 >= volumes.len() {
+        Some(first)
+    } else {
+        volumes
+            .iter()
+            .map(|volume| volume.filesystem.platform())
+            .find(|platform| *platform != first)
+    }
+}
+
+/// An artefact that has been looked up in the image with `DiskImage::open_file`, its times,
+/// metadata and contents are read through it without resolving the path again.
+pub struct ImageFile {
+    volume: usize,
+    entry: ImageEntry,
+}
+
+enum ImageEntry {
+    Ext4(Inode),
+    Ntfs(ResolvedFile),
+}
+
+/// A raw (dd), EWF, VHD or VHDX image of a whole disk or of a single filesystem that rules are
+/// resolved against instead of the live host. Every partition holding a supported filesystem is
+/// collected from.
+pub struct DiskImage {
+    path: String,
+    platform: &'static str,
+    volumes: Vec<Volume>,
+}
+
+impl DiskImage {
+    pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        let image = ImageReader::open(path)?;
+        let size = image.size()?;
+        if !matches!(image, ImageReader::Raw(_)) {
+            println!("Reading {0} as a {1} image", path, image.kind());
+        }
+        let mut volumes = Vec::new();
+
+        if let Some(filesystem) = Filesystem::open(path, 0, size) {
+            let name = match filesystem {
+                Filesystem::Ext4(_) => String::new(),
+                Filesystem::Ntfs(_) => String::from("C"),
+            };
+            volumes.push(Volume { name, filesystem });
+        } else {
+            let partitions = read_partition_table(&mut BufReader::new(image))?;
+            if partitions.is_empty() {
+                return Err(format!(
+                    "{} does not contain a partition table or a supported filesystem",
+                    path
+                )
+                .into());
+            }
+            let mut found = Vec::new();
+            for partition in partitions {
+                match Filesystem::open(path, partition.offset, partition.size) {
+                    Some(filesystem) => found.push((partition, filesystem)),
+                    None => println!(
+                        "Skipping partition {0} ({1}), no supported filesystem found",
+                        partition.number, partition.description
+                    ),
+                }
+            }
+            for (partition, name, filesystem) in name_volumes(found) {
+                println!(
+                    "Found {0} filesystem in partition {1} ({2}), collecting it as {3}",
+                    filesystem.kind(),
+                    partition.number,
+                    partition.description,
+                    name
+                );
+                volumes.push(Volume { name, filesystem });
+            }
+        }
+
+        // a collection only holds artefacts from one platform
+        let platform = system_platform(&mut volumes)
+            .ok_or(format!("{} does not contain a supported filesystem", path))?;
+        volumes.retain(|volume| {
+            let same_platform = volume.filesystem.platform() == platform;
+            if !same_platform {
+                println!(
+                    "Skipping {0}, it is not a {1} filesystem",
+                    volume.name, platform
+                );
+            }
+            same_platform
+        });
+
+        Ok(DiskImage {
+            path: path.to_string(),
+            platform,
+            volumes,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The platform whose rules apply to the filesystems in the image.
+    pub fn platform(&self) -> &'static str {
+        self.platform
+    }
+
+    /// Looks up an artefact in the volume it belongs to.
+    pub fn open_file(&mut self, file_path: &str) -> Result<ImageFile, Box<dyn Error>> {
+        for (index, volume) in self.volumes.iter_mut().enumerate() {
+            let Some(path) = volume.volume_path(file_path) else {
+                continue;
+            };
+            let entry = match &mut volume.filesystem {
+                Filesystem::Ext4(filesystem) => ImageEntry::Ext4(filesystem.open_file(path)?),
+                Filesystem::Ntfs(drive) => ImageEntry::Ntfs(drive.resolve(path)?),
+            };
+            return Ok(ImageFile {
+                volume: index,
+                entry,
+            });
+        }
+        Err(format!("{} is not on a volume in the image", file_path).into())
+    }
+
+    /// Resolves a rule inside the image the same way `FileCollecter::collect_by_rule` does on
+    /// a live host, paths that exist are collected and every path is also used as a regex to
+    /// search each volume up to the rule's recursion depth. With `mft_search` NTFS volumes are
+    /// searched by reading `$MFT` rather than walking their directories.
+    pub fn collect_by_rule(
+        &mut self,
+        rule: &FileRule,
+        mft_search: bool,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut files: Vec<String> = Vec::new();
+
+        for volume in self.volumes.iter_mut() {
+            let mut found: Vec<String> = Vec::new();
+            match &mut volume.filesystem {
+                Filesystem::Ext4(filesystem) => {
+                    for path in &rule.paths {
+                        if filesystem.open_file(path).is_ok() {
+                            found.push(path.clone());
+                        }
+                    }
+                    let patterns: Vec<Regex> = rule
+                        .paths
+                        .iter()
+                        .filter_map(|pattern| Regex::new(pattern).ok())
+                        .collect();
+                    filesystem.walk("/", rule.recursion_depth, &mut |path, _| {
+                        let file = path.rsplit('/').next().unwrap_or_default();
+                        if patterns
+                            .iter()
+                            .any(|regex| regex.is_match(path) || regex.is_match(file))
+                        {
+                            found.push(path.to_string());
+                        }
+                    })?;
+                    files.extend(found.iter().map(|path| volume.artefact_path(path)));
+                }
+                Filesystem::Ntfs(drive) => {
+                    for path in &rule.paths {
+                        let (mut file_path, stream) = parse_stream(path);
+                        if file_path.contains(':') {
+                            file_path = file_path
+                                .trim_start_matches(|c: char| c.is_ascii_alphabetic())
+                                .trim_start_matches(':')
+                                .trim_start_matches('\\')
+                                .to_string();
+                        }
+                        if drive.does_file_exist(&file_path) {
+                            found.push(format!("{0}:\\{1}:{2}", volume.name, file_path, stream));
+                        }
+                    }
+                    let patterns: Vec<(Regex, String)> = rule
+                        .paths
+                        .iter()
+                        .filter_map(|pattern| {
+                            let (pattern, stream) = parse_stream(pattern);
+                            Regex::new(&pattern).ok().map(|regex| (regex, stream))
+                        })
+                        .collect();
+                    let find_stream = |path: &str| {
+                        let file = path.rsplit('\\').next().unwrap_or_default();
+                        patterns
+                            .iter()
+                            .find(|(regex, _)| regex.is_match(path) || regex.is_match(file))
+                            .map(|(_, stream)| stream)
+                    };
+                    let root = format!("{}:", volume.name);
+                    let mut matcher = |path: &str| {
+                        if let Some(stream) = find_stream(path) {
+                            found.push(format!("{0}:{1}", path, stream));
+                        }
+                    };
+                    if mft_search {
+                        drive.walk_mft(&root, rule.recursion_depth, &mut matcher)?;
+                    } else {
+                        drive.walk(&root, rule.recursion_depth, &mut matcher)?;
+                    }
+                    files.extend(expand_streams(found, |path| drive.list_streams(path)));
+                    if rule.deleted {
+                        drive.walk_deleted(
+                            &root,
+                            rule.recursion_depth,
+                            &mut |path, deleted_path| {
+                                if find_stream(path).is_some() {
+                                    files.push(deleted_path.to_string());
+                                }
+                            },
+                        )?;
+                    }
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// The path an artefact from the image is stored under in the collection.
+    pub fn archive_path(&self, file_path: &str) -> String {
+        if self.platform == "windows" {
+            let (path, stream_name) = parse_stream(file_path);
+            if deleted_record(&path).is_some() {
+                return path_to_string(deleted_archive_path(&path)).to_string();
+            }
+            let path = path.replace(':', "").replace('\\', "/");
+            if stream_name.is_empty() {
+                return path_to_string(path).to_string();
+            }
+            return path_to_string(format!("{0}_{1}", path, stream_name)).to_string();
+        }
+        // partition prefixes become the top level directory, partition2:/etc becomes
+        // partition2/etc
+        match file_path.split_once(":/") {
+            Some((name, path)) if !file_path.starts_with('/') => {
+                path_to_string(format!("{0}/{1}", name, path)).to_string()
+            }
+            _ => path_to_string(file_path).to_string(),
+        }
+    }
+
+    pub fn get_lastmodified(&mut self, file: &ImageFile) -> Result<NaiveDateTime, Box<dyn Error>> {
+        match (&mut self.volumes[file.volume].filesystem, &file.entry) {
+            (_, ImageEntry::Ext4(inode)) => Ok(DateTime::<Utc>::from_timestamp(
+                inode.modified.0,
+                inode.modified.1,
+            )
+            .ok_or("Invalid modified time")?
+            .naive_utc()),
+            (Filesystem::Ntfs(drive), ImageEntry::Ntfs(resolved)) => {
+                drive.resolved_lastmodified(resolved)
+            }
+            _ => Err("The file does not belong to this volume".into()),
+        }
+    }
+
+    /// How likely a deleted NTFS file still holds its original contents, `None` for any other
+    /// artefact.
+    pub fn recovery_confidence(
+        &mut self,
+        file: &ImageFile,
+    ) -> Result<Option<RecoveryConfidence>, Box<dyn Error>> {
+        match (&mut self.volumes[file.volume].filesystem, &file.entry) {
+            (Filesystem::Ntfs(drive), ImageEntry::Ntfs(resolved)) => {
+                drive.resolved_recovery_confidence(resolved)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub fn get_metadata(&mut self, file: &ImageFile) -> Result<FileMetadata, Box<dyn Error>> {
+        match (&mut self.volumes[file.volume].filesystem, &file.entry) {
+            (_, ImageEntry::Ext4(inode)) => Ok(inode.metadata()),
+            (Filesystem::Ntfs(drive), ImageEntry::Ntfs(resolved)) => {
+                drive.resolved_metadata(resolved)
+            }
+            _ => Err("The file does not belong to this volume".into()),
+        }
+    }
+
+    /// Copies an artefact out of the image and returns the offset the copy starts at, which is
+    /// only past 0 for `$UsnJrnl:$J`. `keep_sparse` applies to NTFS streams as it does on a
+    /// live host.
+    pub fn copy_file<W>(
+        &mut self,
+        file: &ImageFile,
+        mut writer: W,
+        keep_sparse: bool,
+    ) -> Result<u64, Box<dyn Error>>
+    where
+        W: Write,
+    {
+        match (&mut self.volumes[file.volume].filesystem, &file.entry) {
+            (Filesystem::Ext4(filesystem), ImageEntry::Ext4(inode)) => {
+                filesystem.copy_inode(inode, &mut writer)?;
+                Ok(0)
+            }
+            (Filesystem::Ntfs(drive), ImageEntry::Ntfs(resolved)) => Ok(drive
+                .copy_resolved(resolved, writer, keep_sparse)?
+                .start_offset),
+            _ => Err("The file does not belong to this volume".into()),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use super::metadata::FileMetadata;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    error::Error,
+    io::{self, Write},
+};
+
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ManifestEntry {
+    #[serde(default)]
+    pub rule: String,
+    /// The rules besides `rule` that collected the same artefact, which is stored only once.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub other_rules: Vec<String>,
+    pub archive_path: String,
+    pub original_path: String,
+    pub size: u64,
+    pub sha256: String,
+    pub last_modified: i64,
+    /// Whether the artefact's access time was left untouched by collecting it.
+    #[serde(default)]
+    pub atime_preserved: bool,
+    #[serde(default)]
+    pub metadata: FileMetadata,
+    /// Only set for files recovered from a deleted NTFS MFT record.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recovery_confidence: Option<RecoveryConfidence>,
+    /// Only set when the start of the artefact was left out, the offset in the original stream
+    /// its first byte comes from. The freed start of `$UsnJrnl:$J` is skipped unless
+    /// `--keep-sparse` is given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_offset: Option<u64>,
+}
+
+impl ManifestEntry {
+    /// Returns every rule that collected the artefact.
+    pub fn rules(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.rule).chain(self.other_rules.iter())
+    }
+}
+
+/// How likely a file recovered from a deleted MFT record still holds its original contents.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum RecoveryConfidence {
+    /// None of the file's clusters have been allocated again since it was deleted.
+    High,
+    /// Some of its clusters now belong to another file and may have been overwritten.
+    Low,
+}
+
+/// The manifest is stored alongside the artefacts in every collection and records where each
+/// entry came from and what it hashed to at the time it was collected.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct Manifest {
+    pub platform: String,
+    pub created: i64,
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn new(platform: String) -> Self {
+        Manifest {
+            platform,
+            created: chrono::Utc::now().timestamp(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn get_entry(&self, archive_path: &str) -> Option<&ManifestEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.archive_path == archive_path)
+    }
+}
+
+/// `HashingWriter` passes everything written to it through to the inner writer while keeping
+/// a running SHA256 and byte count of the data.
+pub struct HashingWriter<W>
+where
+    W: Write,
+{
+    inner: W,
+    hasher: Sha256,
+    written: u64,
+}
+
+impl<W> HashingWriter<W>
+where
+    W: Write,
+{
+    pub fn new(inner: W) -> Self {
+        HashingWriter {
+            inner,
+            hasher: Sha256::new(),
+            written: 0,
+        }
+    }
+
+    /// Returns the hex encoded SHA256 and the number of bytes written.
+    pub fn finish(self) -> (String, u64) {
+        (hex::encode(self.hasher.finalize()), self.written)
+    }
+}
+
+impl<W> Write for HashingWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
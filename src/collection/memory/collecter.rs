@@ -2,7 +2,7 @@ use super::rules::CollectionRule;
 use super::rules::MemoryRule;
 use minidump_writer::minidump_writer::MinidumpWriter;
 use regex::Regex;
-use std::{env, error::Error};
+use std::{collections::HashMap, env, error::Error};
 
 #[cfg(target_os = "windows")]
 use minidump_writer::minidump_writer::MinidumpWriter;
@@ -18,6 +18,7 @@ use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_INFORMATION,
 pub struct MemoryCollecter {
     rules: Vec<MemoryRule>,
     memory_dumps: Vec<String>,
+    memory_dump_rules: HashMap<String, String>,
 }
 
 #[derive(Clone)]
@@ -50,6 +51,7 @@ impl MemoryCollecter {
                 })
                 .collect(),
             memory_dumps: Vec::new(),
+            memory_dump_rules: HashMap::new(),
         })
     }
 
@@ -57,6 +59,10 @@ impl MemoryCollecter {
         self.memory_dumps.clone()
     }
 
+    pub fn get_memory_dump_rules(&self) -> HashMap<String, String> {
+        self.memory_dump_rules.clone()
+    }
+
     pub fn add_rule(&mut self, new_rule: CollectionRule) -> Result<(), Box<dyn Error>> {
         if let CollectionRule::MemoryRule(rule) = new_rule {
             if rule.platform != env::consts::OS {
@@ -84,6 +90,10 @@ impl MemoryCollecter {
             .ok_or_else(|| format!("Rule with name '{}' not found", rule_name))?;
         let mut memory_dumps = MemoryCollecter::collect_by_rule(rule)?;
         let memory_dumps_len = memory_dumps.len();
+        for memory_dump in &memory_dumps {
+            self.memory_dump_rules
+                .insert(memory_dump.clone(), rule.name.clone());
+        }
         self.memory_dumps.append(&mut memory_dumps);
         Ok(memory_dumps_len)
     }
@@ -197,7 +207,7 @@ impl MemoryCollecter {
                         .next()
                         .unwrap_or("")
                         .split('/')
-                        .last()
+                        .next_back()
                         .unwrap_or("")
                         .to_string();
 
@@ -250,7 +260,7 @@ impl MemoryCollecter {
                         }
                         continue;
                     }
-                } else if process.name.to_ascii_lowercase() == process_name.to_ascii_lowercase() {
+                } else if process.name.eq_ignore_ascii_case(&process_name) {
                     match MemoryCollecter::dump_memory(process.clone()) {
                         Ok(memory_dump) => memory_dumps.push(memory_dump),
                         Err(e) => println!("{e}"),
@@ -271,6 +281,10 @@ impl MemoryCollecter {
         for rule in &self.rules {
             match MemoryCollecter::collect_by_rule(rule) {
                 Ok(mut memory_dumps) => {
+                    for memory_dump in &memory_dumps {
+                        self.memory_dump_rules
+                            .insert(memory_dump.clone(), rule.name.clone());
+                    }
                     self.memory_dumps.append(&mut memory_dumps);
                     println!(
                         "Collected {0} artefacts for rule: {1}",
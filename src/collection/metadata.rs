@@ -0,0 +1,306 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, error::Error, fs::File};
+
+/// Everything we know about an artefact on the host it was collected from. Fields that could not
+/// be read, or do not exist on the source platform or filesystem, are left empty.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct FileMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    /// The Windows file attribute flags that are set, e.g. `hidden` and `archive`, read from
+    /// `$STANDARD_INFORMATION` for files on NTFS.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attributes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inode: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accessed: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    /// Raw extended attribute values, base64 encoded.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub xattrs: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub acl_access: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub acl_default: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selinux_label: Option<String>,
+}
+
+/// Formats a timestamp with nanosecond precision, as used for every time in the manifest.
+pub fn format_timestamp(seconds: i64, nanoseconds: u32) -> Option<String> {
+    DateTime::<Utc>::from_timestamp(seconds, nanoseconds)
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::Nanos, true))
+}
+
+/// Formats a Windows FILETIME, which counts 100 nanosecond intervals since 1601-01-01. Zero is
+/// treated as unset.
+pub fn format_filetime(intervals: u64) -> Option<String> {
+    if intervals == 0 {
+        return None;
+    }
+    let seconds = (intervals / 10_000_000) as i64 - 11_644_473_600;
+    let nanoseconds = (intervals % 10_000_000) as u32 * 100;
+    format_timestamp(seconds, nanoseconds)
+}
+
+pub fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+fn format_system_time(time: std::io::Result<std::time::SystemTime>) -> Option<String> {
+    time.ok()
+        .map(|time| DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Nanos, true))
+}
+
+impl FileMetadata {
+    /// Reads the metadata of an open file, this is done before the file is read so the recorded
+    /// access time is the one the file had before it was collected.
+    #[cfg(target_os = "linux")]
+    pub fn from_file(file: &File, path: &str) -> Result<Self, Box<dyn Error>> {
+        use std::os::fd::AsRawFd;
+
+        let mut metadata = FileMetadata::default();
+        let mut statx_buf: libc::statx = unsafe { std::mem::zeroed() };
+        let result = unsafe {
+            libc::statx(
+                file.as_raw_fd(),
+                c"".as_ptr(),
+                libc::AT_EMPTY_PATH,
+                libc::STATX_ALL,
+                &mut statx_buf,
+            )
+        };
+
+        if result == 0 {
+            let mask = statx_buf.stx_mask;
+            let timestamp = |flag: u32, time: libc::statx_timestamp| {
+                if mask & flag != 0 {
+                    format_timestamp(time.tv_sec, time.tv_nsec)
+                } else {
+                    None
+                }
+            };
+            metadata.uid = Some(statx_buf.stx_uid);
+            metadata.gid = Some(statx_buf.stx_gid);
+            metadata.mode = Some(statx_buf.stx_mode as u32);
+            metadata.inode = Some(statx_buf.stx_ino);
+            metadata.links = Some(statx_buf.stx_nlink as u64);
+            metadata.accessed = timestamp(libc::STATX_ATIME, statx_buf.stx_atime);
+            metadata.modified = timestamp(libc::STATX_MTIME, statx_buf.stx_mtime);
+            metadata.changed = timestamp(libc::STATX_CTIME, statx_buf.stx_ctime);
+            metadata.created = timestamp(libc::STATX_BTIME, statx_buf.stx_btime);
+        } else {
+            // statx is unavailable on kernels older than 4.11, fall back to a regular stat
+            use std::os::unix::fs::MetadataExt;
+            let stat = file.metadata()?;
+            metadata.uid = Some(stat.uid());
+            metadata.gid = Some(stat.gid());
+            metadata.mode = Some(stat.mode());
+            metadata.inode = Some(stat.ino());
+            metadata.links = Some(stat.nlink());
+            metadata.accessed = format_timestamp(stat.atime(), stat.atime_nsec() as u32);
+            metadata.modified = format_timestamp(stat.mtime(), stat.mtime_nsec() as u32);
+            metadata.changed = format_timestamp(stat.ctime(), stat.ctime_nsec() as u32);
+        }
+
+        if let Ok(names) = xattr::list(path) {
+            for name in names {
+                let name = name.to_string_lossy().to_string();
+                let value = match xattr::get(path, &name) {
+                    Ok(Some(value)) => value,
+                    _ => continue,
+                };
+                match name.as_str() {
+                    "system.posix_acl_access" => metadata.acl_access = parse_posix_acl(&value),
+                    "system.posix_acl_default" => metadata.acl_default = parse_posix_acl(&value),
+                    "security.capability" => metadata.capabilities = parse_capabilities(&value),
+                    "security.selinux" => {
+                        metadata.selinux_label = Some(
+                            String::from_utf8_lossy(&value)
+                                .trim_end_matches('\0')
+                                .to_string(),
+                        )
+                    }
+                    _ => {}
+                }
+                metadata.xattrs.insert(name, STANDARD.encode(value));
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn from_file(file: &File, _path: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(FileMetadata::from_std_metadata(&file.metadata()?))
+    }
+
+    pub fn from_std_metadata(stat: &std::fs::Metadata) -> Self {
+        FileMetadata {
+            accessed: format_system_time(stat.accessed()),
+            modified: format_system_time(stat.modified()),
+            created: format_system_time(stat.created()),
+            ..Default::default()
+        }
+    }
+}
+
+const ACL_USER_OBJ: u16 = 0x01;
+const ACL_USER: u16 = 0x02;
+const ACL_GROUP_OBJ: u16 = 0x04;
+const ACL_GROUP: u16 = 0x08;
+const ACL_MASK: u16 = 0x10;
+const ACL_OTHER: u16 = 0x20;
+
+/// Renders the `system.posix_acl_*` xattr format into the text form used by `getfacl`.
+pub fn parse_posix_acl(value: &[u8]) -> Vec<String> {
+    let mut entries = Vec::new();
+    // a 4 byte version header followed by 8 byte entries of tag, permissions and id
+    if value.len() < 4 {
+        return entries;
+    }
+    for entry in value[4..].chunks_exact(8) {
+        let tag = u16::from_le_bytes([entry[0], entry[1]]);
+        let permissions = u16::from_le_bytes([entry[2], entry[3]]);
+        let id = u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]);
+        let permissions = format!(
+            "{0}{1}{2}",
+            if permissions & 4 != 0 { 'r' } else { '-' },
+            if permissions & 2 != 0 { 'w' } else { '-' },
+            if permissions & 1 != 0 { 'x' } else { '-' }
+        );
+        let entry = match tag {
+            ACL_USER_OBJ => format!("user::{}", permissions),
+            ACL_USER => format!("user:{0}:{1}", id, permissions),
+            ACL_GROUP_OBJ => format!("group::{}", permissions),
+            ACL_GROUP => format!("group:{0}:{1}", id, permissions),
+            ACL_MASK => format!("mask::{}", permissions),
+            ACL_OTHER => format!("other::{}", permissions),
+            _ => format!("unknown({0}):{1}:{2}", tag, id, permissions),
+        };
+        entries.push(entry);
+    }
+    entries
+}
+
+const CAPABILITY_NAMES: [&str; 41] = [
+    "cap_chown",
+    "cap_dac_override",
+    "cap_dac_read_search",
+    "cap_fowner",
+    "cap_fsetid",
+    "cap_kill",
+    "cap_setgid",
+    "cap_setuid",
+    "cap_setpcap",
+    "cap_linux_immutable",
+    "cap_net_bind_service",
+    "cap_net_broadcast",
+    "cap_net_admin",
+    "cap_net_raw",
+    "cap_ipc_lock",
+    "cap_ipc_owner",
+    "cap_sys_module",
+    "cap_sys_rawio",
+    "cap_sys_chroot",
+    "cap_sys_ptrace",
+    "cap_sys_pacct",
+    "cap_sys_admin",
+    "cap_sys_boot",
+    "cap_sys_nice",
+    "cap_sys_resource",
+    "cap_sys_time",
+    "cap_sys_tty_config",
+    "cap_mknod",
+    "cap_lease",
+    "cap_audit_write",
+    "cap_audit_control",
+    "cap_setfcap",
+    "cap_mac_override",
+    "cap_mac_admin",
+    "cap_syslog",
+    "cap_wake_alarm",
+    "cap_block_suspend",
+    "cap_audit_read",
+    "cap_perfmon",
+    "cap_bpf",
+    "cap_checkpoint_restore",
+];
+
+/// Renders the `security.capability` xattr in the form used by `getcap`, e.g.
+/// `cap_net_bind_service,cap_net_raw=ep`.
+pub fn parse_capabilities(value: &[u8]) -> Option<String> {
+    let read_u32 = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            value.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    let magic = read_u32(0)?;
+    let effective = magic & 0x1 != 0;
+    let (permitted, inheritable) = match magic & 0xFF000000 {
+        // version 1 only has the lower 32 bits
+        0x01000000 => (read_u32(4)? as u64, read_u32(8)? as u64),
+        0x02000000 | 0x03000000 => (
+            read_u32(4)? as u64 | (read_u32(12)? as u64) << 32,
+            read_u32(8)? as u64 | (read_u32(16)? as u64) << 32,
+        ),
+        _ => return None,
+    };
+
+    let names = |mask: u64| -> Vec<String> {
+        (0..64)
+            .filter(|bit| mask & (1 << bit) != 0)
+            .map(|bit| {
+                CAPABILITY_NAMES
+                    .get(bit)
+                    .map(|name| name.to_string())
+                    .unwrap_or(format!("cap_{}", bit))
+            })
+            .collect()
+    };
+
+    let mut capabilities = Vec::new();
+    if permitted == inheritable && permitted != 0 {
+        capabilities.push(format!(
+            "{0}={1}ip",
+            names(permitted).join(","),
+            if effective { "e" } else { "" }
+        ));
+    } else {
+        if permitted != 0 {
+            capabilities.push(format!(
+                "{0}={1}p",
+                names(permitted).join(","),
+                if effective { "e" } else { "" }
+            ));
+        }
+        if inheritable != 0 {
+            capabilities.push(format!("{0}=i", names(inheritable).join(",")));
+        }
+    }
+    // version 3 capabilities are scoped to the root user of a user namespace
+    if magic & 0xFF000000 == 0x03000000 {
+        if let Some(root_id) = read_u32(20) {
+            capabilities.push(format!("[rootid={}]", root_id));
+        }
+    }
+    Some(capabilities.join(" "))
+}
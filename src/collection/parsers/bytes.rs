@@ -0,0 +1,47 @@
+pub fn le_u16(buf: &[u8], offset: usize) -> u16 {
+    read(buf, offset)
+        .map(u16::from_le_bytes)
+        .unwrap_or_default()
+}
+
+pub fn le_u32(buf: &[u8], offset: usize) -> u32 {
+    read(buf, offset)
+        .map(u32::from_le_bytes)
+        .unwrap_or_default()
+}
+
+pub fn le_u64(buf: &[u8], offset: usize) -> u64 {
+    read(buf, offset)
+        .map(u64::from_le_bytes)
+        .unwrap_or_default()
+}
+
+pub fn le_i16(buf: &[u8], offset: usize) -> i16 {
+    read(buf, offset)
+        .map(i16::from_le_bytes)
+        .unwrap_or_default()
+}
+
+pub fn le_i32(buf: &[u8], offset: usize) -> i32 {
+    read(buf, offset)
+        .map(i32::from_le_bytes)
+        .unwrap_or_default()
+}
+
+pub fn be_u32(buf: &[u8], offset: usize) -> u32 {
+    read(buf, offset)
+        .map(u32::from_be_bytes)
+        .unwrap_or_default()
+}
+
+pub fn be_u64(buf: &[u8], offset: usize) -> u64 {
+    read(buf, offset)
+        .map(u64::from_be_bytes)
+        .unwrap_or_default()
+}
+
+/// Returns the `N` bytes at `offset`, or `None` when they run past the end of the buffer. The
+/// integers above read as 0 then, which the parsers treat as a missing field.
+fn read<const N: usize>(buf: &[u8], offset: usize) -> Option<[u8; N]> {
+    buf.get(offset..offset.checked_add(N)?)?.try_into().ok()
+}
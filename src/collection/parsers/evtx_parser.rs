@@ -0,0 +1,700 @@
+use super::bytes::{le_u16, le_u32, le_u64};
+use crate::collection::metadata::format_filetime;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{collections::HashMap, error::Error, io::Read, rc::Rc};
+
+const FILE_SIGNATURE: &[u8; 8] = b"ElfFile\0";
+const CHUNK_SIGNATURE: &[u8; 8] = b"ElfChnk\0";
+const RECORD_SIGNATURE: &[u8; 4] = b"\x2a\x2a\0\0";
+const FILE_HEADER_SIZE: usize = 4096;
+const CHUNK_SIZE: usize = 65536;
+const CHUNK_HEADER_SIZE: usize = 512;
+const RECORD_HEADER_SIZE: usize = 24;
+/// How deeply elements and embedded BinXML may nest before a record is treated as corrupt.
+const MAX_DEPTH: usize = 64;
+
+const END_OF_STREAM: u8 = 0x00;
+const OPEN_START_ELEMENT: u8 = 0x01;
+const CLOSE_START_ELEMENT: u8 = 0x02;
+const CLOSE_EMPTY_ELEMENT: u8 = 0x03;
+const END_ELEMENT: u8 = 0x04;
+const VALUE: u8 = 0x05;
+const ATTRIBUTE: u8 = 0x06;
+const CDATA_SECTION: u8 = 0x07;
+const CHARACTER_REFERENCE: u8 = 0x08;
+const ENTITY_REFERENCE: u8 = 0x09;
+const PI_TARGET: u8 = 0x0A;
+const PI_DATA: u8 = 0x0B;
+const TEMPLATE_INSTANCE: u8 = 0x0C;
+const NORMAL_SUBSTITUTION: u8 = 0x0D;
+const OPTIONAL_SUBSTITUTION: u8 = 0x0E;
+const FRAGMENT_HEADER: u8 = 0x0F;
+/// Set on a token when more of the same kind follow, e.g. an element that has attributes.
+const MORE_FLAG: u8 = 0x40;
+
+const TYPE_NULL: u8 = 0x00;
+const TYPE_STRING: u8 = 0x01;
+const TYPE_ANSI_STRING: u8 = 0x02;
+const TYPE_BINXML: u8 = 0x21;
+const TYPE_ARRAY: u8 = 0x80;
+
+fn utf16(buf: &[u8]) -> String {
+    let units: Vec<u16> = buf
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// An event of an EVTX file. `event` holds the whole `<Event>` element as JSON, with the
+/// attributes of an element under `#attributes` and the text of an element that also has
+/// attributes or children under `#text`. `<Data Name="...">` elements are keyed by their name.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct EvtxRecord {
+    /// The file the event was read from.
+    pub source: String,
+    pub record_id: u64,
+    pub timestamp: Option<String>,
+    pub event_id: Option<u32>,
+    pub channel: Option<String>,
+    pub provider: Option<String>,
+    pub computer: Option<String>,
+    pub event: Value,
+}
+
+/// Limits the events that are parsed to some event ids and channels, either list matches
+/// everything when it is empty.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct EventFilter {
+    pub event_ids: Vec<u32>,
+    pub channels: Vec<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, record: &EvtxRecord) -> bool {
+        let event_id = self.event_ids.is_empty()
+            || record
+                .event_id
+                .is_some_and(|event_id| self.event_ids.contains(&event_id));
+        let channel = self.channels.is_empty()
+            || record.channel.as_ref().is_some_and(|channel| {
+                self.channels
+                    .iter()
+                    .any(|filter| filter.eq_ignore_ascii_case(channel))
+            });
+        event_id && channel
+    }
+}
+
+/// A node of the XML an event's BinXML describes. Templates are kept with their substitutions
+/// in place and filled in with the values of each event that uses them.
+#[derive(Debug, Clone)]
+enum Node {
+    Element {
+        name: String,
+        attributes: Vec<(String, Vec<Node>)>,
+        children: Vec<Node>,
+    },
+    Value(Value),
+    Substitution {
+        index: usize,
+    },
+}
+
+/// An EVTX file read into memory, its events are read chunk by chunk with `records`.
+pub struct EvtxFile {
+    data: Vec<u8>,
+    source: String,
+}
+
+impl EvtxFile {
+    pub fn from_reader<R>(mut reader: R, source: &str) -> Result<Self, Box<dyn Error>>
+    where
+        R: Read,
+    {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        if data.len() < FILE_HEADER_SIZE || &data[0..8] != FILE_SIGNATURE {
+            return Err(format!("{} is not an EVTX file", source).into());
+        }
+        Ok(EvtxFile {
+            data,
+            source: source.to_string(),
+        })
+    }
+
+    /// Returns every event in the order it is stored. Chunks are found by their signature
+    /// rather than the count in the file header, which is not kept up to date while a log is
+    /// being written to.
+    pub fn records(&self) -> Vec<Result<EvtxRecord, Box<dyn Error>>> {
+        let mut records = Vec::new();
+        for chunk in self.data[FILE_HEADER_SIZE..].chunks_exact(CHUNK_SIZE) {
+            if &chunk[0..8] != CHUNK_SIGNATURE {
+                continue;
+            }
+            let mut parser = ChunkParser {
+                chunk,
+                templates: HashMap::new(),
+            };
+            let free_space = (le_u32(chunk, 48) as usize).min(CHUNK_SIZE);
+            let mut offset = CHUNK_HEADER_SIZE;
+            while offset + RECORD_HEADER_SIZE <= free_space {
+                let size = le_u32(chunk, offset + 4) as usize;
+                if &chunk[offset..offset + 4] != RECORD_SIGNATURE
+                    || size < RECORD_HEADER_SIZE + 4
+                    || offset + size > CHUNK_SIZE
+                {
+                    break;
+                }
+                records.push(self.record(&mut parser, offset, size));
+                offset += size;
+            }
+        }
+        records
+    }
+
+    fn record(
+        &self,
+        parser: &mut ChunkParser,
+        offset: usize,
+        size: usize,
+    ) -> Result<EvtxRecord, Box<dyn Error>> {
+        let record_id = le_u64(parser.chunk, offset + 8);
+        let nodes = parser
+            .fragment(offset + RECORD_HEADER_SIZE, offset + size - 4, 0)
+            .map_err(|e| format!("Event record {0} of {1}: {2}", record_id, self.source, e))?;
+        let event = nodes
+            .iter()
+            .find_map(|node| match node {
+                Node::Element { .. } => Some(element_json(node)),
+                _ => None,
+            })
+            .unwrap_or(Value::Null);
+        let system = &event["System"];
+        let text = |value: &Value| match value {
+            Value::Object(fields) => fields.get("#text").cloned(),
+            Value::Null => None,
+            value => Some(value.clone()),
+        };
+        let string = |value: Option<Value>| match value {
+            Some(Value::String(string)) => Some(string),
+            Some(Value::Null) | None => None,
+            Some(value) => Some(value.to_string()),
+        };
+        Ok(EvtxRecord {
+            source: self.source.clone(),
+            record_id,
+            timestamp: format_filetime(le_u64(parser.chunk, offset + 16)),
+            event_id: text(&system["EventID"]).and_then(|event_id| match event_id {
+                Value::Number(number) => number.as_u64().map(|number| number as u32),
+                Value::String(string) => string.parse().ok(),
+                _ => None,
+            }),
+            channel: string(text(&system["Channel"])),
+            provider: string(Some(system["Provider"]["#attributes"]["Name"].clone())),
+            computer: string(text(&system["Computer"])),
+            event,
+        })
+    }
+}
+
+/// Reads the BinXML of the events in a chunk, caching the templates they share.
+struct ChunkParser<'a> {
+    chunk: &'a [u8],
+    templates: HashMap<u32, Rc<Vec<Node>>>,
+}
+
+impl ChunkParser<'_> {
+    fn byte(&self, offset: usize) -> Result<u8, Box<dyn Error>> {
+        self.chunk
+            .get(offset)
+            .copied()
+            .ok_or_else(|| format!("BinXML runs past the chunk at offset {}", offset).into())
+    }
+
+    /// Reads a name, which is stored once in the chunk and referred to by its offset from then
+    /// on. Returns the name and the offset after it when it is stored inline at `offset`.
+    fn name(&self, name_offset: usize, offset: usize) -> Result<(String, usize), Box<dyn Error>> {
+        let length = le_u16(self.chunk, name_offset + 6) as usize;
+        let name = self
+            .chunk
+            .get(name_offset + 8..name_offset + 8 + length * 2)
+            .ok_or("A name runs past the chunk")?;
+        let next = if name_offset == offset {
+            offset + 10 + length * 2
+        } else {
+            offset
+        };
+        Ok((utf16(name), next))
+    }
+
+    /// Reads the nodes of a BinXML fragment between `start` and `end`, filling in any template
+    /// instance with its values.
+    fn fragment(
+        &mut self,
+        start: usize,
+        end: usize,
+        depth: usize,
+    ) -> Result<Vec<Node>, Box<dyn Error>> {
+        if depth > MAX_DEPTH {
+            return Err("BinXML nests too deeply".into());
+        }
+        let mut nodes = Vec::new();
+        let mut offset = start;
+        while offset < end {
+            match self.byte(offset)? {
+                END_OF_STREAM => break,
+                FRAGMENT_HEADER => offset += 4,
+                TEMPLATE_INSTANCE => {
+                    let (instance, next) = self.template_instance(offset, depth)?;
+                    nodes.extend(instance);
+                    offset = next;
+                }
+                token if token & !MORE_FLAG == OPEN_START_ELEMENT => {
+                    let (element, next) = self.element(offset, depth)?;
+                    nodes.push(element);
+                    offset = next;
+                }
+                token => {
+                    return Err(format!(
+                        "Unexpected BinXML token {0:#x} at offset {1}",
+                        token, offset
+                    )
+                    .into())
+                }
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn element(&mut self, offset: usize, depth: usize) -> Result<(Node, usize), Box<dyn Error>> {
+        if depth > MAX_DEPTH {
+            return Err("BinXML nests too deeply".into());
+        }
+        let has_attributes = self.byte(offset)? & MORE_FLAG != 0;
+        // elements are preceded by a dependency identifier, except in some embedded BinXML, so
+        // the layout whose name offset makes sense is used
+        let mut position = offset + 3;
+        let mut name_offset = le_u32(self.chunk, position + 4) as usize;
+        if name_offset != position + 8 && name_offset >= offset {
+            position = offset + 1;
+            name_offset = le_u32(self.chunk, position + 4) as usize;
+        }
+        let (name, mut position) = self.name(name_offset, position + 8)?;
+        let mut attributes = Vec::new();
+        if has_attributes {
+            position += 4;
+            loop {
+                let token = self.byte(position)?;
+                if token & !MORE_FLAG != ATTRIBUTE {
+                    break;
+                }
+                let name_offset = le_u32(self.chunk, position + 1) as usize;
+                let (name, next) = self.name(name_offset, position + 5)?;
+                let (value, next) = self.content(next, depth, true)?;
+                attributes.push((name, value));
+                position = next;
+                if token & MORE_FLAG == 0 {
+                    break;
+                }
+            }
+        }
+
+        let children = match self.byte(position)? {
+            CLOSE_EMPTY_ELEMENT => {
+                position += 1;
+                Vec::new()
+            }
+            CLOSE_START_ELEMENT => {
+                let (children, next) = self.content(position + 1, depth, false)?;
+                if self.byte(next)? != END_ELEMENT {
+                    return Err(format!("Element {} is not closed", name).into());
+                }
+                position = next + 1;
+                children
+            }
+            token => {
+                return Err(format!(
+                    "Unexpected BinXML token {0:#x} at offset {1}",
+                    token, position
+                )
+                .into())
+            }
+        };
+        Ok((
+            Node::Element {
+                name,
+                attributes,
+                children,
+            },
+            position,
+        ))
+    }
+
+    /// Reads the content of an element up to its end, or the value of an attribute up to the
+    /// next attribute or the end of the start tag.
+    fn content(
+        &mut self,
+        mut offset: usize,
+        depth: usize,
+        attribute: bool,
+    ) -> Result<(Vec<Node>, usize), Box<dyn Error>> {
+        let mut nodes = Vec::new();
+        loop {
+            let token = self.byte(offset)?;
+            match token & !MORE_FLAG {
+                OPEN_START_ELEMENT if !attribute => {
+                    let (element, next) = self.element(offset, depth + 1)?;
+                    nodes.push(element);
+                    offset = next;
+                }
+                VALUE => {
+                    let value_type = self.byte(offset + 1)?;
+                    let length = le_u16(self.chunk, offset + 2) as usize * 2;
+                    let data = self
+                        .chunk
+                        .get(offset + 4..offset + 4 + length)
+                        .ok_or("A value runs past the chunk")?;
+                    let value = match value_type {
+                        TYPE_STRING => utf16(data),
+                        _ => String::from_utf8_lossy(data).to_string(),
+                    };
+                    nodes.push(Node::Value(Value::from(value)));
+                    offset += 4 + length;
+                }
+                CDATA_SECTION | PI_DATA => {
+                    let length = le_u16(self.chunk, offset + 1) as usize * 2;
+                    let data = self
+                        .chunk
+                        .get(offset + 3..offset + 3 + length)
+                        .ok_or("A value runs past the chunk")?;
+                    if token & !MORE_FLAG == CDATA_SECTION {
+                        nodes.push(Node::Value(Value::from(utf16(data))));
+                    }
+                    offset += 3 + length;
+                }
+                CHARACTER_REFERENCE => {
+                    let character = char::from_u32(le_u16(self.chunk, offset + 1) as u32)
+                        .unwrap_or(char::REPLACEMENT_CHARACTER);
+                    nodes.push(Node::Value(Value::from(character.to_string())));
+                    offset += 3;
+                }
+                ENTITY_REFERENCE => {
+                    let name_offset = le_u32(self.chunk, offset + 1) as usize;
+                    let (name, next) = self.name(name_offset, offset + 5)?;
+                    let entity = match name.as_str() {
+                        "amp" => "&",
+                        "lt" => "<",
+                        "gt" => ">",
+                        "quot" => "\"",
+                        "apos" => "'",
+                        _ => "",
+                    };
+                    nodes.push(Node::Value(Value::from(entity)));
+                    offset = next;
+                }
+                PI_TARGET => {
+                    let name_offset = le_u32(self.chunk, offset + 1) as usize;
+                    offset = self.name(name_offset, offset + 5)?.1;
+                }
+                NORMAL_SUBSTITUTION | OPTIONAL_SUBSTITUTION => {
+                    nodes.push(Node::Substitution {
+                        index: le_u16(self.chunk, offset + 1) as usize,
+                    });
+                    offset += 4;
+                }
+                TEMPLATE_INSTANCE if !attribute => {
+                    let (instance, next) = self.template_instance(offset, depth + 1)?;
+                    nodes.extend(instance);
+                    offset = next;
+                }
+                _ => return Ok((nodes, offset)),
+            }
+        }
+    }
+
+    /// Reads a template instance, parsing its template the first time it is used in the chunk,
+    /// and fills the template in with the instance's values.
+    fn template_instance(
+        &mut self,
+        offset: usize,
+        depth: usize,
+    ) -> Result<(Vec<Node>, usize), Box<dyn Error>> {
+        let definition = le_u32(self.chunk, offset + 6);
+        let mut position = offset + 10;
+        let definition_size = le_u32(self.chunk, definition as usize + 20) as usize;
+        if definition as usize == position {
+            position += 24 + definition_size;
+        }
+        let template = match self.templates.get(&definition) {
+            Some(template) => template.clone(),
+            None => {
+                let start = definition as usize + 24;
+                let template = Rc::new(self.fragment(start, start + definition_size, depth + 1)?);
+                self.templates.insert(definition, template.clone());
+                template
+            }
+        };
+
+        let count = le_u32(self.chunk, position) as usize;
+        let descriptors = position + 4;
+        let mut value_offset = count
+            .checked_mul(4)
+            .and_then(|size| size.checked_add(descriptors))
+            .filter(|end| *end <= self.chunk.len())
+            .ok_or("The substitution descriptors run past the chunk")?;
+        let mut values = Vec::with_capacity(count);
+        for index in 0..count {
+            let size = le_u16(self.chunk, descriptors + index * 4) as usize;
+            let value_type = self.byte(descriptors + index * 4 + 2)?;
+            let data = self
+                .chunk
+                .get(value_offset..value_offset + size)
+                .ok_or("A substitution value runs past the chunk")?;
+            let value = match value_type {
+                TYPE_BINXML => self.fragment(value_offset, value_offset + size, depth + 1)?,
+                TYPE_NULL => Vec::new(),
+                _ => match substitution_value(value_type, data) {
+                    Value::Null => Vec::new(),
+                    value => vec![Node::Value(value)],
+                },
+            };
+            values.push(value);
+            value_offset += size;
+        }
+        Ok((instantiate(&template, &values), value_offset))
+    }
+}
+
+/// Fills the substitutions of a template in with the values of an instance of it. Optional
+/// substitutions without a value leave nothing behind, and neither do normal ones as the XML
+/// Windows renders for them is empty.
+fn instantiate(template: &[Node], values: &[Vec<Node>]) -> Vec<Node> {
+    let mut nodes = Vec::with_capacity(template.len());
+    for node in template {
+        match node {
+            Node::Element {
+                name,
+                attributes,
+                children,
+            } => nodes.push(Node::Element {
+                name: name.clone(),
+                attributes: attributes
+                    .iter()
+                    .map(|(name, value)| (name.clone(), instantiate(value, values)))
+                    .filter(|(_, value)| !value.is_empty())
+                    .collect(),
+                children: instantiate(children, values),
+            }),
+            Node::Substitution { index } => {
+                nodes.extend(values.get(*index).cloned().unwrap_or_default())
+            }
+            node => nodes.push(node.clone()),
+        }
+    }
+    nodes
+}
+
+/// Decodes a substitution value of a BinXML value type.
+fn substitution_value(value_type: u8, data: &[u8]) -> Value {
+    if value_type & TYPE_ARRAY != 0 {
+        let element_type = value_type & !TYPE_ARRAY;
+        return match element_type {
+            TYPE_STRING => Value::from(
+                data.chunks_exact(2)
+                    .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                    .collect::<Vec<u16>>()
+                    .split(|unit| *unit == 0)
+                    .filter(|string| !string.is_empty())
+                    .map(String::from_utf16_lossy)
+                    .collect::<Vec<String>>(),
+            ),
+            _ => match fixed_size(element_type) {
+                Some(size) => Value::from(
+                    data.chunks_exact(size)
+                        .map(|element| substitution_value(element_type, element))
+                        .collect::<Vec<Value>>(),
+                ),
+                None => Value::from(hex::encode_upper(data)),
+            },
+        };
+    }
+    match value_type {
+        TYPE_STRING => Value::from(utf16(data)),
+        TYPE_ANSI_STRING => Value::from(
+            String::from_utf8_lossy(data)
+                .trim_end_matches('\0')
+                .to_string(),
+        ),
+        0x03 => Value::from(data.first().map(|byte| *byte as i8)),
+        0x04 => Value::from(data.first().copied()),
+        0x05 => Value::from(le_u16(data, 0) as i16),
+        0x06 => Value::from(le_u16(data, 0)),
+        0x07 => Value::from(le_u32(data, 0) as i32),
+        0x08 => Value::from(le_u32(data, 0)),
+        0x09 => Value::from(le_u64(data, 0) as i64),
+        0x0A => Value::from(le_u64(data, 0)),
+        0x0B => Value::from(f32::from_bits(le_u32(data, 0))),
+        0x0C => Value::from(f64::from_bits(le_u64(data, 0))),
+        0x0D => Value::from(le_u32(data, 0) != 0),
+        0x0F if data.len() >= 16 => Value::from(format!(
+            "{{{0:08X}-{1:04X}-{2:04X}-{3}-{4}}}",
+            le_u32(data, 0),
+            le_u16(data, 4),
+            le_u16(data, 6),
+            hex::encode_upper(&data[8..10]),
+            hex::encode_upper(&data[10..16])
+        )),
+        0x10 | 0x15 if data.len() == 8 => Value::from(format!("0x{:016x}", le_u64(data, 0))),
+        0x10 | 0x14 => Value::from(format!("0x{:08x}", le_u32(data, 0))),
+        0x11 => Value::from(format_filetime(le_u64(data, 0))),
+        0x12 if data.len() >= 16 => Value::from(format!(
+            "{0:04}-{1:02}-{2:02}T{3:02}:{4:02}:{5:02}.{6:03}Z",
+            le_u16(data, 0),
+            le_u16(data, 2),
+            le_u16(data, 6),
+            le_u16(data, 8),
+            le_u16(data, 10),
+            le_u16(data, 12),
+            le_u16(data, 14)
+        )),
+        0x13 => sid(data).map(Value::from).unwrap_or(Value::Null),
+        _ => Value::from(hex::encode_upper(data)),
+    }
+}
+
+/// The size of each element of an array of a value type.
+fn fixed_size(value_type: u8) -> Option<usize> {
+    match value_type {
+        0x03 | 0x04 => Some(1),
+        0x05 | 0x06 => Some(2),
+        0x07 | 0x08 | 0x0B | 0x0D | 0x14 => Some(4),
+        0x09 | 0x0A | 0x0C | 0x11 | 0x15 => Some(8),
+        0x0F | 0x12 => Some(16),
+        _ => None,
+    }
+}
+
+/// Formats a binary SID as `S-1-5-21-...`.
+fn sid(data: &[u8]) -> Option<String> {
+    let count = *data.get(1)? as usize;
+    let authority = data
+        .get(2..8)?
+        .iter()
+        .fold(0u64, |authority, byte| authority << 8 | *byte as u64);
+    let mut sid = format!("S-{0}-{1}", data[0], authority);
+    for index in 0..count {
+        sid.push_str(&format!(
+            "-{}",
+            le_u32(data.get(8 + index * 4..12 + index * 4)?, 0)
+        ));
+    }
+    Some(sid)
+}
+
+/// Joins the values of an attribute or element's text into one value, keeping a lone value as
+/// it is so numbers stay numbers.
+fn joined(values: &[&Value]) -> Value {
+    match values {
+        [] => Value::Null,
+        [value] => (*value).clone(),
+        values => Value::from(
+            values
+                .iter()
+                .map(|value| match value {
+                    Value::String(string) => string.clone(),
+                    value => value.to_string(),
+                })
+                .collect::<String>(),
+        ),
+    }
+}
+
+fn element_json(node: &Node) -> Value {
+    let Node::Element {
+        attributes,
+        children,
+        ..
+    } = node
+    else {
+        return Value::Null;
+    };
+    let text: Vec<&Value> = children
+        .iter()
+        .filter_map(|child| match child {
+            Node::Value(value) => Some(value),
+            _ => None,
+        })
+        .collect();
+    let elements: Vec<&Node> = children
+        .iter()
+        .filter(|child| matches!(child, Node::Element { .. }))
+        .collect();
+    if attributes.is_empty() && elements.is_empty() {
+        return joined(&text);
+    }
+
+    let mut fields = Map::new();
+    if !attributes.is_empty() {
+        let attributes = attributes
+            .iter()
+            .map(|(name, value)| {
+                let values: Vec<&Value> = value
+                    .iter()
+                    .filter_map(|node| match node {
+                        Node::Value(value) => Some(value),
+                        _ => None,
+                    })
+                    .collect();
+                (name.clone(), joined(&values))
+            })
+            .collect();
+        fields.insert("#attributes".to_string(), Value::Object(attributes));
+    }
+    for element in elements {
+        let Node::Element {
+            name, attributes, ..
+        } = element
+        else {
+            continue;
+        };
+        // <Data Name="TargetUserName">bob</Data> becomes "TargetUserName": "bob"
+        let data_name = attributes
+            .iter()
+            .find(|(attribute, _)| name == "Data" && attribute == "Name")
+            .and_then(|(_, value)| match value.first() {
+                Some(Node::Value(Value::String(name))) => Some(name.clone()),
+                _ => None,
+            });
+        let (key, value) = match data_name {
+            Some(data_name) => {
+                let Node::Element { children, .. } = element else {
+                    continue;
+                };
+                let text: Vec<&Value> = children
+                    .iter()
+                    .filter_map(|child| match child {
+                        Node::Value(value) => Some(value),
+                        _ => None,
+                    })
+                    .collect();
+                (data_name, joined(&text))
+            }
+            None => (name.clone(), element_json(element)),
+        };
+        match fields.get_mut(&key) {
+            Some(Value::Array(values)) => values.push(value),
+            Some(existing) => *existing = Value::Array(vec![existing.take(), value]),
+            None => {
+                fields.insert(key, value);
+            }
+        }
+    }
+    if !text.is_empty() {
+        fields.insert("#text".to_string(), joined(&text));
+    }
+    Value::Object(fields)
+}
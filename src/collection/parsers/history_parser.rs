@@ -0,0 +1,207 @@
+use crate::collection::metadata::format_timestamp;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error::Error, io::Read};
+
+/// The byte zsh puts before every byte of a command that would clash with its own tokens, the
+/// byte that follows has had its sixth bit flipped.
+const ZSH_META: u8 = 0x83;
+
+/// A command from a shell or REPL history file.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct HistoryEntry {
+    /// Only known for shells that record when a command was run, bash when `HISTTIMEFORMAT` is
+    /// set, zsh with `EXTENDED_HISTORY` and fish.
+    pub timestamp: Option<String>,
+    /// How long the command ran for in seconds, only recorded by zsh.
+    pub duration: Option<u64>,
+    pub user: Option<String>,
+    /// The uid that owns the history file.
+    pub uid: Option<u32>,
+    pub shell: String,
+    /// The file the command was read from and the line it starts on.
+    pub source: String,
+    pub line: usize,
+    pub command: String,
+}
+
+/// Returns the shell or program that writes a history file, from its name.
+pub fn history_shell(file_name: &str) -> Option<&'static str> {
+    match file_name {
+        ".bash_history" => Some("bash"),
+        ".zsh_history" | ".zhistory" => Some("zsh"),
+        "fish_history" => Some("fish"),
+        ".ash_history" => Some("ash"),
+        ".sh_history" => Some("sh"),
+        ".mysql_history" => Some("mysql"),
+        ".python_history" => Some("python"),
+        ".psql_history" => Some("psql"),
+        _ => None,
+    }
+}
+
+/// Parses the history file of `shell`, as named by `history_shell`. Every command is attributed
+/// to `uid`, the owner of the file, and named from `users` when the uid is in it.
+pub fn parse_history<R>(
+    mut reader: R,
+    source: &str,
+    shell: &str,
+    uid: Option<u32>,
+    users: &HashMap<u32, String>,
+) -> Result<Vec<HistoryEntry>, Box<dyn Error>>
+where
+    R: Read,
+{
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let commands = match shell {
+        "bash" => parse_bash(&String::from_utf8_lossy(&data)),
+        "zsh" => parse_zsh(&String::from_utf8_lossy(&unmetafy(&data))),
+        "fish" => parse_fish(&String::from_utf8_lossy(&data)),
+        "mysql" => parse_lines(&String::from_utf8_lossy(&data).replace("\\040", " ")),
+        _ => parse_lines(&String::from_utf8_lossy(&data)),
+    };
+    Ok(commands
+        .into_iter()
+        .map(|(line, time, duration, command)| HistoryEntry {
+            timestamp: time.and_then(|time| format_timestamp(time, 0)),
+            duration,
+            user: uid.and_then(|uid| users.get(&uid).cloned()),
+            uid,
+            shell: shell.to_string(),
+            source: source.to_string(),
+            line,
+            command,
+        })
+        .collect())
+}
+
+/// The line number, time, duration and text of a command.
+type Command = (usize, Option<i64>, Option<u64>, String);
+
+/// One command per line, as written by sh, ash and most readline based REPLs.
+fn parse_lines(history: &str) -> Vec<Command> {
+    history
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| (index + 1, None, None, line.to_string()))
+        .collect()
+}
+
+/// Bash writes `#<epoch>` before each command when `HISTTIMEFORMAT` is set, a command then runs
+/// until the next timestamp so commands spanning several lines are kept together.
+fn parse_bash(history: &str) -> Vec<Command> {
+    let timestamp = |line: &str| {
+        line.strip_prefix('#')
+            .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|digits| digits.parse::<i64>().ok())
+    };
+    if !history.lines().any(|line| timestamp(line).is_some()) {
+        return parse_lines(history);
+    }
+
+    let mut commands: Vec<Command> = Vec::new();
+    let mut time = None;
+    let mut pending: Option<Command> = None;
+    for (index, line) in history.lines().enumerate() {
+        if let Some(line_time) = timestamp(line) {
+            commands.extend(pending.take());
+            time = Some(line_time);
+            continue;
+        }
+        match pending.as_mut() {
+            Some((_, _, _, command)) if time.is_some() => {
+                command.push('\n');
+                command.push_str(line);
+            }
+            _ => {
+                commands.extend(pending.take());
+                pending = Some((index + 1, time, None, line.to_string()));
+            }
+        }
+    }
+    commands.extend(pending);
+    commands.retain(|(_, _, _, command)| !command.trim().is_empty());
+    commands
+}
+
+/// Zsh's extended history writes `: <start>:<duration>;<command>`, a command spanning several
+/// lines has a backslash at the end of every line but the last.
+fn parse_zsh(history: &str) -> Vec<Command> {
+    let mut commands: Vec<Command> = Vec::new();
+    let mut continued = false;
+    for (index, line) in history.lines().enumerate() {
+        if continued {
+            if let Some((_, _, _, command)) = commands.last_mut() {
+                command.push('\n');
+                command.push_str(line.strip_suffix('\\').unwrap_or(line));
+            }
+            continued = line.ends_with('\\');
+            continue;
+        }
+        continued = line.ends_with('\\');
+        let line = line.strip_suffix('\\').unwrap_or(line);
+        let extended = line.strip_prefix(": ").and_then(|rest| {
+            let (header, command) = rest.split_once(';')?;
+            let (start, duration) = header.split_once(':')?;
+            Some((start.parse().ok()?, duration.parse().ok()?, command))
+        });
+        match extended {
+            Some((start, duration, command)) => {
+                commands.push((index + 1, Some(start), Some(duration), command.to_string()))
+            }
+            None if !line.trim().is_empty() => {
+                commands.push((index + 1, None, None, line.to_string()))
+            }
+            None => {}
+        }
+    }
+    commands
+}
+
+/// Fish keeps its history as YAML, a `- cmd:` line starts each command and the `when:` line
+/// under it holds the time it was run.
+fn parse_fish(history: &str) -> Vec<Command> {
+    let mut commands: Vec<Command> = Vec::new();
+    for (index, line) in history.lines().enumerate() {
+        if let Some(command) = line.strip_prefix("- cmd: ") {
+            let mut unescaped = String::new();
+            let mut chars = command.chars();
+            while let Some(c) = chars.next() {
+                match (c, chars.clone().next()) {
+                    ('\\', Some('n')) => {
+                        unescaped.push('\n');
+                        chars.next();
+                    }
+                    ('\\', Some('\\')) => {
+                        unescaped.push('\\');
+                        chars.next();
+                    }
+                    _ => unescaped.push(c),
+                }
+            }
+            commands.push((index + 1, None, None, unescaped));
+        } else if let Some(when) = line.trim_start().strip_prefix("when: ") {
+            if let Some((_, time, _, _)) = commands.last_mut() {
+                *time = when.trim().parse().ok();
+            }
+        }
+    }
+    commands
+}
+
+/// Undoes zsh's metafication of the bytes in its history file.
+fn unmetafy(data: &[u8]) -> Vec<u8> {
+    let mut unmetafied = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(byte) = bytes.next() {
+        match (*byte, bytes.clone().next()) {
+            (ZSH_META, Some(next)) => {
+                unmetafied.push(next ^ 0x20);
+                bytes.next();
+            }
+            _ => unmetafied.push(*byte),
+        }
+    }
+    unmetafied
+}
@@ -0,0 +1,233 @@
+use super::bytes::{le_u32, le_u64};
+use crate::collection::metadata::format_timestamp;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, error::Error, io::Read};
+
+const SIGNATURE: &[u8; 8] = b"LPKSHHRH";
+const OBJECT_HEADER_SIZE: usize = 16;
+const OBJECT_ALIGNMENT: usize = 8;
+
+const INCOMPATIBLE_COMPRESSED_XZ: u32 = 0x01;
+const INCOMPATIBLE_COMPRESSED_LZ4: u32 = 0x02;
+const INCOMPATIBLE_KEYED_HASH: u32 = 0x04;
+const INCOMPATIBLE_COMPRESSED_ZSTD: u32 = 0x08;
+const INCOMPATIBLE_COMPACT: u32 = 0x10;
+const INCOMPATIBLE_SUPPORTED: u32 = INCOMPATIBLE_COMPRESSED_XZ
+    | INCOMPATIBLE_COMPRESSED_LZ4
+    | INCOMPATIBLE_KEYED_HASH
+    | INCOMPATIBLE_COMPRESSED_ZSTD
+    | INCOMPATIBLE_COMPACT;
+
+const OBJECT_DATA: u8 = 1;
+const OBJECT_ENTRY: u8 = 3;
+const OBJECT_COMPRESSED_XZ: u8 = 0x01;
+const OBJECT_COMPRESSED_LZ4: u8 = 0x02;
+const OBJECT_COMPRESSED_ZSTD: u8 = 0x04;
+/// The most a compressed data object may claim to hold, far beyond any real field.
+const MAX_DATA_SIZE: usize = 64 * 1024 * 1024;
+
+/// An entry of a journal, with the fields journalctl would show for it in `-o json`. A field
+/// that appears more than once in an entry is given as a list of its values.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct JournalEntry {
+    /// The file the entry was read from.
+    pub source: String,
+    pub timestamp: Option<String>,
+    /// Microseconds since the epoch.
+    pub realtime: u64,
+    /// Microseconds since the boot the entry was written in.
+    pub monotonic: u64,
+    pub boot_id: String,
+    pub seqnum: u64,
+    pub fields: BTreeMap<String, Value>,
+}
+
+/// A systemd journal file (`.journal`, or `.journal~` when it was not closed cleanly) read
+/// into memory. Both the regular and compact formats are supported, along with data objects
+/// compressed with XZ, LZ4 or ZSTD.
+pub struct JournalFile {
+    data: Vec<u8>,
+    source: String,
+    compact: bool,
+    /// Where the objects start and end.
+    start: usize,
+    end: usize,
+}
+
+impl JournalFile {
+    pub fn from_reader<R>(mut reader: R, source: &str) -> Result<Self, Box<dyn Error>>
+    where
+        R: Read,
+    {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        if data.len() < 208 || &data[0..8] != SIGNATURE {
+            return Err(format!("{} is not a journal file", source).into());
+        }
+        let incompatible_flags = le_u32(&data, 12);
+        if incompatible_flags & !INCOMPATIBLE_SUPPORTED != 0 {
+            return Err(format!(
+                "{0} uses unsupported features {1:#x}",
+                source,
+                incompatible_flags & !INCOMPATIBLE_SUPPORTED
+            )
+            .into());
+        }
+        let start = le_u64(&data, 88) as usize;
+        let arena_size = le_u64(&data, 96) as usize;
+        // the arena of a file that was being written to can run past what was collected
+        let end = start.saturating_add(arena_size).min(data.len());
+        Ok(JournalFile {
+            data,
+            source: source.to_string(),
+            compact: incompatible_flags & INCOMPATIBLE_COMPACT != 0,
+            start,
+            end,
+        })
+    }
+
+    /// Returns every entry in the order it was written. Objects are walked one after another
+    /// rather than through the entry arrays, so the entries of a file that was still being
+    /// written to when it was collected are read up to the first damaged object.
+    pub fn entries(&self) -> Vec<Result<JournalEntry, Box<dyn Error>>> {
+        let mut entries = Vec::new();
+        let mut offset = self.start;
+        while offset + OBJECT_HEADER_SIZE <= self.end {
+            let object_type = self.data[offset];
+            let size = le_u64(&self.data, offset + 8) as usize;
+            if size < OBJECT_HEADER_SIZE || offset.saturating_add(size) > self.end {
+                if object_type != 0 || size != 0 {
+                    entries.push(Err(format!(
+                        "{0} has a damaged object at offset {1}",
+                        self.source, offset
+                    )
+                    .into()));
+                }
+                break;
+            }
+            if object_type == OBJECT_ENTRY {
+                entries.push(self.read_entry(offset, size));
+            }
+            offset += size.div_ceil(OBJECT_ALIGNMENT) * OBJECT_ALIGNMENT;
+        }
+        entries
+    }
+
+    /// Returns the entries written from `start` up to and including `end`, either of which may
+    /// be open, along with the errors met reading the file.
+    pub fn entries_between(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Vec<Result<JournalEntry, Box<dyn Error>>> {
+        let start = start
+            .map(|time| time.timestamp_micros() as u64)
+            .unwrap_or(0);
+        let end = end
+            .map(|time| time.timestamp_micros() as u64)
+            .unwrap_or(u64::MAX);
+        self.entries()
+            .into_iter()
+            .filter(|entry| match entry {
+                Ok(entry) => entry.realtime >= start && entry.realtime <= end,
+                Err(_) => true,
+            })
+            .collect()
+    }
+
+    fn read_entry(&self, offset: usize, size: usize) -> Result<JournalEntry, Box<dyn Error>> {
+        let object = &self.data[offset..offset + size];
+        if object.len() < 64 {
+            return Err(format!("The entry at offset {} is too small", offset).into());
+        }
+        let realtime = le_u64(object, 24);
+        let mut entry = JournalEntry {
+            source: self.source.clone(),
+            timestamp: format_timestamp(
+                (realtime / 1_000_000) as i64,
+                (realtime % 1_000_000) as u32 * 1000,
+            ),
+            realtime,
+            monotonic: le_u64(object, 32),
+            boot_id: hex::encode(&object[40..56]),
+            seqnum: le_u64(object, 16),
+            fields: BTreeMap::new(),
+        };
+
+        let item_size = if self.compact { 4 } else { 16 };
+        for item in object[64..].chunks_exact(item_size) {
+            let data_offset = if self.compact {
+                le_u32(item, 0) as usize
+            } else {
+                le_u64(item, 0) as usize
+            };
+            let payload = self.read_data(data_offset)?;
+            let Some(separator) = payload.iter().position(|byte| *byte == b'=') else {
+                continue;
+            };
+            let name = String::from_utf8_lossy(&payload[..separator]).to_string();
+            let value = Value::String(String::from_utf8_lossy(&payload[separator + 1..]).into());
+            match entry.fields.get_mut(&name) {
+                Some(Value::Array(values)) => values.push(value),
+                Some(existing) => *existing = Value::Array(vec![existing.take(), value]),
+                None => {
+                    entry.fields.insert(name, value);
+                }
+            }
+        }
+        Ok(entry)
+    }
+
+    /// Returns the `FIELD=value` payload of a data object, decompressing it when needed.
+    fn read_data(&self, offset: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        if offset < self.start || offset + OBJECT_HEADER_SIZE > self.end {
+            return Err(format!("Data object offset {} is outside the file", offset).into());
+        }
+        let object_type = self.data[offset];
+        let flags = self.data[offset + 1];
+        let size = le_u64(&self.data, offset + 8) as usize;
+        let payload_offset = if self.compact { 72 } else { 64 };
+        if object_type != OBJECT_DATA
+            || size < payload_offset
+            || offset.saturating_add(size) > self.end
+        {
+            return Err(format!("There is no data object at offset {}", offset).into());
+        }
+        let payload = &self.data[offset + payload_offset..offset + size];
+
+        match flags {
+            OBJECT_COMPRESSED_XZ => {
+                let mut decompressed = Vec::new();
+                lzma_rs::xz_decompress(&mut &payload[..], &mut decompressed)
+                    .map_err(|e| format!("Failed to decompress XZ data: {:?}", e))?;
+                Ok(decompressed)
+            }
+            OBJECT_COMPRESSED_LZ4 => {
+                // the compressed block is preceded by the size of the data it holds
+                if payload.len() < 8 {
+                    return Err("LZ4 data is too small".into());
+                }
+                let size = le_u64(payload, 0);
+                if size > MAX_DATA_SIZE as u64 {
+                    return Err(format!("LZ4 data claims a size of {} bytes", size).into());
+                }
+                Ok(lz4_flex::block::decompress(&payload[8..], size as usize)?)
+            }
+            OBJECT_COMPRESSED_ZSTD => {
+                let decoder = ruzstd::decoding::StreamingDecoder::new(payload)
+                    .map_err(|e| format!("Failed to decompress ZSTD data: {}", e))?;
+                let mut decompressed = Vec::new();
+                decoder
+                    .take(MAX_DATA_SIZE as u64 + 1)
+                    .read_to_end(&mut decompressed)?;
+                if decompressed.len() > MAX_DATA_SIZE {
+                    return Err("ZSTD data is larger than any real field".into());
+                }
+                Ok(decompressed)
+            }
+            _ => Ok(payload.to_vec()),
+        }
+    }
+}
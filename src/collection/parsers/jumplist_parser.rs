@@ -0,0 +1,175 @@
+use super::bytes::{le_u16, le_u32, le_u64};
+use crate::collection::metadata::format_filetime;
+use crate::collection::parsers::lnk_parser::{parse_lnk, LnkRecord, LNK_SIGNATURE};
+use crate::collection::parsers::registry_parser::utf16_string;
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    io::{Cursor, Read},
+};
+
+const COMPOUND_FILE_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+const DEST_LIST_STREAM: &str = "DestList";
+const DEST_LIST_HEADER_SIZE: usize = 32;
+/// DestList entries of Windows 7 and 8, later versions add an access count before the path.
+const DEST_LIST_WINDOWS_7: u32 = 1;
+/// The pin status of a DestList entry that is not pinned, pinned entries hold their position.
+const NOT_PINNED: u32 = 0xFFFF_FFFF;
+
+/// A link of a JumpList, or why it could not be read.
+pub type LinkResult = Result<LnkRecord, Box<dyn Error>>;
+
+/// An entry of the DestList stream, the most recently used list of an automaticDestinations-ms
+/// JumpList.
+struct DestListEntry {
+    entry: u32,
+    hostname: String,
+    last_accessed: Option<String>,
+    pinned: bool,
+    access_count: Option<u32>,
+    path: String,
+}
+
+/// Returns whether a file is a JumpList, from its name.
+pub fn is_jumplist_file(file_name: &str) -> bool {
+    let file_name = file_name.to_ascii_lowercase();
+    file_name.ends_with(".automaticdestinations-ms")
+        || file_name.ends_with(".customdestinations-ms")
+}
+
+/// Reads the links of a JumpList. automaticDestinations-ms files are OLE compound files holding
+/// a link per stream and a DestList of when each was last used, customDestinations-ms files are
+/// the links of the application's custom categories one after the other. A link that cannot be
+/// read does not stop the others from being returned.
+pub fn parse_jumplist<R>(mut reader: R, source: &str) -> Result<Vec<LinkResult>, Box<dyn Error>>
+where
+    R: Read,
+{
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let file_name = source.rsplit(['\\', '/']).next().unwrap_or(source);
+    let app_id = file_name.split('.').next().map(|app_id| app_id.to_string());
+    let mut links = if data.starts_with(&COMPOUND_FILE_SIGNATURE) {
+        automatic_destinations(data, source)?
+    } else {
+        custom_destinations(&data, source)?
+    };
+    for link in links.iter_mut().flatten() {
+        link.app_id = app_id.clone();
+    }
+    Ok(links)
+}
+
+fn automatic_destinations(data: Vec<u8>, source: &str) -> Result<Vec<LinkResult>, Box<dyn Error>> {
+    let mut compound_file = cfb::CompoundFile::open(Cursor::new(data))
+        .map_err(|e| format!("{0} is not a valid compound file: {1}", source, e))?;
+    let streams: Vec<String> = compound_file
+        .read_root_storage()
+        .filter(|entry| entry.is_stream())
+        .map(|entry| entry.name().to_string())
+        .collect();
+    let mut dest_list = Vec::new();
+    let mut links = BTreeMap::new();
+    let mut failed = Vec::new();
+    for stream in streams {
+        let mut contents = Vec::new();
+        compound_file
+            .open_stream(format!("/{}", stream))?
+            .read_to_end(&mut contents)?;
+        if stream == DEST_LIST_STREAM {
+            dest_list = dest_list_entries(&contents);
+        } else if let Ok(entry) = u32::from_str_radix(&stream, 16) {
+            // the stream of each link is named by its entry number in hex
+            match parse_lnk(&contents, source) {
+                Ok(mut link) => {
+                    link.entry = Some(entry);
+                    links.insert(entry, link);
+                }
+                Err(e) => failed.push(Err(format!("Stream {0}: {1}", stream, e).into())),
+            }
+        }
+    }
+
+    // the DestList is ordered from the most recently used, links it does not list come last
+    let mut records = Vec::new();
+    for dest in dest_list {
+        let mut link = links.remove(&dest.entry).unwrap_or_else(|| LnkRecord {
+            source: source.to_string(),
+            entry: Some(dest.entry),
+            ..Default::default()
+        });
+        if link.target_path.is_none() && !dest.path.is_empty() {
+            link.target_path = Some(dest.path);
+        }
+        if link.machine_id.is_none() && !dest.hostname.is_empty() {
+            link.machine_id = Some(dest.hostname);
+        }
+        link.last_accessed = dest.last_accessed;
+        link.access_count = dest.access_count;
+        link.pinned = Some(dest.pinned);
+        records.push(Ok(link));
+    }
+    records.extend(links.into_values().map(Ok));
+    records.extend(failed);
+    Ok(records)
+}
+
+fn dest_list_entries(dest_list: &[u8]) -> Vec<DestListEntry> {
+    let version = le_u32(dest_list, 0);
+    let count = le_u32(dest_list, 4);
+    let path_offset = if version == DEST_LIST_WINDOWS_7 {
+        112
+    } else {
+        128
+    };
+    let mut entries = Vec::new();
+    let mut offset = DEST_LIST_HEADER_SIZE;
+    for _ in 0..count {
+        let Some(entry) = dest_list.get(offset..) else {
+            break;
+        };
+        let path_size = le_u16(entry, path_offset) as usize * 2;
+        let Some(path) = entry.get(path_offset + 2..path_offset + 2 + path_size) else {
+            break;
+        };
+        let hostname = entry.get(72..88).unwrap_or_default();
+        let hostname_end = hostname.iter().position(|byte| *byte == 0).unwrap_or(16);
+        entries.push(DestListEntry {
+            entry: le_u32(entry, 88),
+            hostname: String::from_utf8_lossy(&hostname[..hostname_end]).to_string(),
+            last_accessed: format_filetime(le_u64(entry, 100)),
+            pinned: le_u32(entry, 108) != NOT_PINNED,
+            access_count: (version != DEST_LIST_WINDOWS_7).then(|| le_u32(entry, 116)),
+            path: utf16_string(path),
+        });
+        // entries after Windows 7 end with four more bytes after the path
+        offset += path_offset + 2 + path_size;
+        if version != DEST_LIST_WINDOWS_7 {
+            offset += 4;
+        }
+    }
+    entries
+}
+
+/// Finds the links of a customDestinations-ms by their signature, the headers of the categories
+/// around them vary between versions of Windows.
+fn custom_destinations(data: &[u8], source: &str) -> Result<Vec<LinkResult>, Box<dyn Error>> {
+    let starts: Vec<usize> = data
+        .windows(LNK_SIGNATURE.len())
+        .enumerate()
+        .filter(|(_, window)| *window == LNK_SIGNATURE)
+        .map(|(offset, _)| offset)
+        .collect();
+    if starts.is_empty() {
+        return Err(format!("{} does not hold any links", source).into());
+    }
+    let mut links = Vec::new();
+    for (index, start) in starts.iter().enumerate() {
+        let end = starts.get(index + 1).copied().unwrap_or(data.len());
+        links.push(parse_lnk(&data[*start..end], source).map(|mut link| {
+            link.entry = Some(index as u32 + 1);
+            link
+        }));
+    }
+    Ok(links)
+}
@@ -0,0 +1,290 @@
+use super::bytes::{le_u16, le_u32, le_u64};
+use crate::collection::metadata::format_filetime;
+use crate::collection::parsers::mft_parser::attribute_names;
+use crate::collection::parsers::registry_parser::utf16_string;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+const HEADER_SIZE: usize = 0x4C;
+/// The size and CLSID every shell link starts with, also used to find the links in a
+/// customDestinations-ms JumpList.
+pub const LNK_SIGNATURE: [u8; 20] = [
+    0x4C, 0x00, 0x00, 0x00, 0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x46,
+];
+
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x0001;
+const HAS_LINK_INFO: u32 = 0x0002;
+const HAS_NAME: u32 = 0x0004;
+const HAS_RELATIVE_PATH: u32 = 0x0008;
+const HAS_WORKING_DIR: u32 = 0x0010;
+const HAS_ARGUMENTS: u32 = 0x0020;
+const HAS_ICON_LOCATION: u32 = 0x0040;
+const IS_UNICODE: u32 = 0x0080;
+
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x0001;
+const COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX: u32 = 0x0002;
+/// LinkInfo headers at least this large also hold the offsets of Unicode copies of the paths.
+const LINK_INFO_UNICODE_HEADER_SIZE: u32 = 0x24;
+
+const ENVIRONMENT_VARIABLE_BLOCK: u32 = 0xA000_0001;
+const TRACKER_BLOCK: u32 = 0xA000_0003;
+
+/// The shell item classes that make up the path of a link without a LinkInfo.
+const SHELL_ITEM_VOLUME: u8 = 0x20;
+const SHELL_ITEM_FILE_ENTRY: u8 = 0x30;
+const SHELL_ITEM_UNICODE_NAME: u8 = 0x04;
+const FILE_ENTRY_EXTENSION_SIGNATURE: u32 = 0xBEEF_0004;
+
+/// The names of the `DriveType` of a VolumeID, by value.
+const DRIVE_TYPES: [&str; 7] = [
+    "unknown",
+    "no_root_dir",
+    "removable",
+    "fixed",
+    "remote",
+    "cdrom",
+    "ramdisk",
+];
+
+/// Decodes a string in the system code page up to its first NUL. Anything outside ASCII is
+/// decoded as UTF-8 on a best effort basis since the code page is not recorded.
+fn ansi_string(buf: &[u8]) -> String {
+    let end = buf.iter().position(|byte| *byte == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).to_string()
+}
+
+/// A shell link, either a standalone `.lnk` file or one of the links of a JumpList.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct LnkRecord {
+    /// The file the link was read from.
+    pub source: String,
+    pub target_path: Option<String>,
+    pub arguments: Option<String>,
+    pub working_directory: Option<String>,
+    pub relative_path: Option<String>,
+    pub description: Option<String>,
+    pub icon_location: Option<String>,
+    /// The times and size of the target when the link was last updated.
+    pub target_created: Option<String>,
+    pub target_accessed: Option<String>,
+    pub target_modified: Option<String>,
+    pub target_size: u32,
+    pub target_attributes: Vec<String>,
+    pub drive_type: Option<String>,
+    /// The serial number of the volume the target was on, formatted as `dir` shows it.
+    pub volume_serial: Option<String>,
+    pub volume_label: Option<String>,
+    pub network_share: Option<String>,
+    /// The NetBIOS name of the machine the target was last seen on.
+    pub machine_id: Option<String>,
+    /// The AppID of the application a JumpList belongs to, the hash in its file name.
+    pub app_id: Option<String>,
+    /// The number of the link within its JumpList.
+    pub entry: Option<u32>,
+    /// When the application last opened the target, only recorded by automaticDestinations-ms.
+    pub last_accessed: Option<String>,
+    /// How often the application opened the target, only recorded from Windows 10 on.
+    pub access_count: Option<u32>,
+    pub pinned: Option<bool>,
+}
+
+/// Reads a shell link as described in MS-SHLLINK.
+pub fn parse_lnk(data: &[u8], source: &str) -> Result<LnkRecord, Box<dyn Error>> {
+    if !data.starts_with(&LNK_SIGNATURE) {
+        return Err(format!("{} is not a shell link", source).into());
+    }
+    let flags = le_u32(data, 20);
+    let serial = |serial: u32| format!("{:04X}-{:04X}", serial >> 16, serial & 0xFFFF);
+    let mut record = LnkRecord {
+        source: source.to_string(),
+        target_attributes: attribute_names(le_u32(data, 24)),
+        target_created: format_filetime(le_u64(data, 28)),
+        target_accessed: format_filetime(le_u64(data, 36)),
+        target_modified: format_filetime(le_u64(data, 44)),
+        target_size: le_u32(data, 52),
+        ..Default::default()
+    };
+
+    let mut offset = HEADER_SIZE;
+    if flags & HAS_LINK_TARGET_ID_LIST != 0 {
+        let size = le_u16(data, offset) as usize;
+        record.target_path = data
+            .get(offset + 2..offset + 2 + size)
+            .and_then(id_list_path);
+        offset += 2 + size;
+    }
+
+    if flags & HAS_LINK_INFO != 0 {
+        let size = le_u32(data, offset) as usize;
+        let info = data
+            .get(offset..offset.saturating_add(size))
+            .ok_or_else(|| format!("The LinkInfo of {} runs past its end", source))?;
+        let header_size = le_u32(info, 4);
+        let info_flags = le_u32(info, 8);
+        // the Unicode paths are preferred, the code page the others were written in is unknown
+        let string_at = |offset_position: usize, unicode_offset_position: usize| {
+            let unicode_offset = le_u32(info, unicode_offset_position) as usize;
+            if header_size >= LINK_INFO_UNICODE_HEADER_SIZE && unicode_offset != 0 {
+                info.get(unicode_offset..).map(utf16_string)
+            } else {
+                info.get(le_u32(info, offset_position) as usize..)
+                    .map(ansi_string)
+            }
+        };
+        let suffix = string_at(24, 32).unwrap_or_default();
+        if info_flags & VOLUME_ID_AND_LOCAL_BASE_PATH != 0 {
+            let volume = info.get(le_u32(info, 12) as usize..).unwrap_or_default();
+            let drive_type = le_u32(volume, 4) as usize;
+            record.drive_type = DRIVE_TYPES.get(drive_type).map(|name| name.to_string());
+            record.volume_serial = Some(serial(le_u32(volume, 8)));
+            let label_offset = le_u32(volume, 12) as usize;
+            let label = if label_offset == 0x14 {
+                volume.get(le_u32(volume, 16) as usize..).map(utf16_string)
+            } else {
+                volume.get(label_offset..).map(ansi_string)
+            };
+            record.volume_label = label.filter(|label| !label.is_empty());
+            if let Some(base_path) = string_at(16, 28) {
+                record.target_path = Some(format!("{}{}", base_path, suffix));
+            }
+        }
+        if info_flags & COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX != 0 {
+            let network = info.get(le_u32(info, 20) as usize..).unwrap_or_default();
+            let name_offset = le_u32(network, 8) as usize;
+            let share = if name_offset > 0x14 {
+                network
+                    .get(le_u32(network, 20) as usize..)
+                    .map(utf16_string)
+            } else {
+                network.get(name_offset..).map(ansi_string)
+            };
+            if let Some(share) = share.filter(|share| !share.is_empty()) {
+                if info_flags & VOLUME_ID_AND_LOCAL_BASE_PATH == 0 {
+                    record.target_path = Some(if suffix.is_empty() {
+                        share.clone()
+                    } else {
+                        format!("{}\\{}", share, suffix)
+                    });
+                }
+                record.network_share = Some(share);
+            }
+        }
+        offset += size;
+    }
+
+    // the string data follows in this order, each present only when its flag is set
+    let unicode = flags & IS_UNICODE != 0;
+    let mut strings = [
+        (HAS_NAME, &mut record.description),
+        (HAS_RELATIVE_PATH, &mut record.relative_path),
+        (HAS_WORKING_DIR, &mut record.working_directory),
+        (HAS_ARGUMENTS, &mut record.arguments),
+        (HAS_ICON_LOCATION, &mut record.icon_location),
+    ];
+    for (flag, field) in strings.iter_mut() {
+        if flags & *flag == 0 {
+            continue;
+        }
+        let characters = le_u16(data, offset) as usize;
+        let size = if unicode { characters * 2 } else { characters };
+        let string = data
+            .get(offset + 2..offset + 2 + size)
+            .ok_or_else(|| format!("The string data of {} runs past its end", source))?;
+        **field = Some(if unicode {
+            let units: Vec<u16> = string
+                .chunks_exact(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        } else {
+            String::from_utf8_lossy(string).to_string()
+        });
+        offset += 2 + size;
+    }
+
+    // extra data blocks run until a terminal block smaller than a block header
+    while let Some(block) = data.get(offset..offset.saturating_add(le_u32(data, offset) as usize)) {
+        if block.len() < 8 {
+            break;
+        }
+        match le_u32(block, 4) {
+            TRACKER_BLOCK => {
+                record.machine_id = block
+                    .get(16..32)
+                    .map(ansi_string)
+                    .filter(|machine_id| !machine_id.is_empty());
+            }
+            ENVIRONMENT_VARIABLE_BLOCK if record.target_path.is_none() => {
+                let target = block
+                    .get(268..788)
+                    .map(utf16_string)
+                    .filter(|target| !target.is_empty())
+                    .or_else(|| block.get(8..268).map(ansi_string));
+                record.target_path = target.filter(|target| !target.is_empty());
+            }
+            _ => {}
+        }
+        offset += block.len();
+    }
+    Ok(record)
+}
+
+/// Builds the path of a link's target from the shell items of its ID list, for links without a
+/// LinkInfo. Only paths on a volume are built, links to other shell folders return `None`.
+fn id_list_path(id_list: &[u8]) -> Option<String> {
+    let mut path: Option<String> = None;
+    let mut offset = 0;
+    while let Some(item) = id_list.get(offset..offset + le_u16(id_list, offset) as usize) {
+        if item.len() < 3 {
+            break;
+        }
+        let class = item[2];
+        match class & 0x70 {
+            SHELL_ITEM_VOLUME => path = Some(ansi_string(item.get(3..).unwrap_or_default())),
+            SHELL_ITEM_FILE_ENTRY => {
+                if let Some(path) = path.as_mut() {
+                    let name = file_entry_name(item, class & SHELL_ITEM_UNICODE_NAME != 0);
+                    if !path.ends_with('\\') {
+                        path.push('\\');
+                    }
+                    path.push_str(&name);
+                }
+            }
+            _ => {}
+        }
+        offset += item.len();
+    }
+    path.filter(|path| !path.is_empty())
+}
+
+/// Returns the long name of a file entry shell item from its extension block, or the short name
+/// it starts with when it has none.
+fn file_entry_name(item: &[u8], unicode: bool) -> String {
+    let short_name = if unicode {
+        utf16_string(item.get(14..).unwrap_or_default())
+    } else {
+        ansi_string(item.get(14..).unwrap_or_default())
+    };
+    // the extension block is found through the offset in the last two bytes of the item
+    let extension_offset = le_u16(item, item.len().saturating_sub(2)) as usize;
+    let Some(extension) = item.get(extension_offset..item.len().saturating_sub(2)) else {
+        return short_name;
+    };
+    if le_u32(extension, 4) != FILE_ENTRY_EXTENSION_SIGNATURE {
+        return short_name;
+    }
+    let name_offset = match le_u16(extension, 2) {
+        version if version < 3 => return short_name,
+        3..=6 => 20,
+        7 => 38,
+        8 => 42,
+        _ => 46,
+    };
+    let long_name = utf16_string(extension.get(name_offset..).unwrap_or_default());
+    if long_name.is_empty() {
+        short_name
+    } else {
+        long_name
+    }
+}
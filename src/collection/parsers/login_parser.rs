@@ -0,0 +1,294 @@
+use super::bytes::{le_i16, le_i32};
+use crate::collection::metadata::format_timestamp;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{self, Read},
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+/// The size of a `struct utmp` on every 64 bit glibc platform, and of a `struct lastlog`.
+const UTMP_SIZE: usize = 384;
+const LASTLOG_SIZE: usize = 292;
+const LINE_SIZE: usize = 32;
+const USER_SIZE: usize = 32;
+const HOST_SIZE: usize = 256;
+
+const EMPTY: i16 = 0;
+const RUN_LVL: i16 = 1;
+const BOOT_TIME: i16 = 2;
+const USER_PROCESS: i16 = 7;
+const DEAD_PROCESS: i16 = 8;
+
+/// The names of the `ut_type` values, by value.
+const RECORD_TYPES: [&str; 10] = [
+    "empty",
+    "run_level",
+    "boot_time",
+    "new_time",
+    "old_time",
+    "init_process",
+    "login_process",
+    "user_process",
+    "dead_process",
+    "accounting",
+];
+
+/// Reads a NUL padded string field, noting when it holds anything but printable characters.
+fn c_string(buf: &[u8], field: &str, warnings: &mut Vec<String>) -> String {
+    let end = buf.iter().position(|byte| *byte == 0).unwrap_or(buf.len());
+    let string = String::from_utf8_lossy(&buf[..end]).to_string();
+    if string
+        .chars()
+        .any(|c| c.is_control() || c == char::REPLACEMENT_CHARACTER)
+    {
+        warnings.push(format!("{} holds unprintable characters", field));
+    }
+    string
+}
+
+/// A record of utmp, wtmp or btmp. The `logout` time of a `user_process` record in wtmp is
+/// taken from the `dead_process` record that ended its session on the same terminal, or from
+/// the next reboot or shutdown.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct LoginRecord {
+    /// The file the record was read from.
+    pub source: String,
+    /// The offset of the record in the file.
+    pub offset: u64,
+    pub record_type: String,
+    pub user: String,
+    pub tty: String,
+    pub id: String,
+    pub host: String,
+    pub address: Option<String>,
+    pub pid: i32,
+    pub session: i32,
+    pub time: Option<String>,
+    pub logout: Option<String>,
+    /// Anything about the record that looks malformed or tampered with, e.g. a record that has
+    /// been zeroed out or a time that goes backwards.
+    pub warnings: Vec<String>,
+}
+
+/// Parses every record of a utmp, wtmp or btmp file. Zeroed records and a truncated last record
+/// are kept, with a warning, as wiping records is a common way of hiding a login.
+pub fn parse_utmp<R>(mut reader: R, source: &str) -> Result<Vec<LoginRecord>, Box<dyn Error>>
+where
+    R: Read,
+{
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let mut records: Vec<LoginRecord> = Vec::new();
+    // the login record of every terminal that still has an open session
+    let mut sessions: HashMap<String, usize> = HashMap::new();
+    let mut last_time = i64::MIN;
+    for (index, raw) in data.chunks(UTMP_SIZE).enumerate() {
+        let offset = (index * UTMP_SIZE) as u64;
+        let mut warnings = Vec::new();
+        if raw.len() < UTMP_SIZE {
+            records.push(LoginRecord {
+                source: source.to_string(),
+                offset,
+                record_type: "truncated".to_string(),
+                warnings: vec![format!(
+                    "{} trailing bytes do not make up a whole record",
+                    raw.len()
+                )],
+                ..Default::default()
+            });
+            break;
+        }
+        if raw.iter().all(|byte| *byte == 0) {
+            records.push(LoginRecord {
+                source: source.to_string(),
+                offset,
+                record_type: "empty".to_string(),
+                warnings: vec!["the record has been zeroed".to_string()],
+                ..Default::default()
+            });
+            continue;
+        }
+
+        let record_type = le_i16(raw, 0);
+        let seconds = le_i32(raw, 340) as i64;
+        let microseconds = le_i32(raw, 344);
+        let type_name = match RECORD_TYPES.get(record_type as usize) {
+            Some(name) if record_type >= 0 => name.to_string(),
+            _ => {
+                warnings.push(format!("unknown record type {}", record_type));
+                record_type.to_string()
+            }
+        };
+        if record_type != EMPTY && seconds == 0 {
+            warnings.push("the record has no time".to_string());
+        }
+        if !(0..1_000_000).contains(&microseconds) {
+            warnings.push(format!("invalid microseconds {}", microseconds));
+        }
+        if seconds != 0 {
+            if seconds < last_time {
+                warnings.push("the time is earlier than the previous record's".to_string());
+            }
+            last_time = seconds;
+        }
+        let tty = c_string(&raw[8..8 + LINE_SIZE], "tty", &mut warnings);
+        let id = c_string(&raw[40..44], "id", &mut warnings);
+        let user = c_string(&raw[44..44 + USER_SIZE], "user", &mut warnings);
+        let host = c_string(&raw[76..76 + HOST_SIZE], "host", &mut warnings);
+        let time = format_timestamp(seconds, microseconds.clamp(0, 999_999) as u32 * 1000)
+            .filter(|_| seconds != 0);
+
+        match record_type {
+            USER_PROCESS => {
+                sessions.insert(tty.clone(), records.len());
+            }
+            DEAD_PROCESS => {
+                if let Some(login) = sessions.remove(&tty) {
+                    records[login].logout = time.clone();
+                }
+            }
+            // a reboot or shutdown ends every session that is still open
+            BOOT_TIME => close_sessions(&mut records, &mut sessions, &time),
+            RUN_LVL if user == "shutdown" => close_sessions(&mut records, &mut sessions, &time),
+            _ => {}
+        }
+
+        records.push(LoginRecord {
+            source: source.to_string(),
+            offset,
+            record_type: type_name,
+            user,
+            tty,
+            id,
+            host,
+            address: address(&raw[348..364]),
+            pid: le_i32(raw, 4),
+            session: le_i32(raw, 336),
+            time,
+            logout: None,
+            warnings,
+        });
+    }
+    Ok(records)
+}
+
+fn close_sessions(
+    records: &mut [LoginRecord],
+    sessions: &mut HashMap<String, usize>,
+    time: &Option<String>,
+) {
+    for (_, login) in sessions.drain() {
+        records[login].logout = time.clone();
+    }
+}
+
+/// Reads `ut_addr_v6`, which holds an IPv4 address in its first word when the rest is zero.
+fn address(raw: &[u8]) -> Option<String> {
+    if raw.iter().all(|byte| *byte == 0) {
+        return None;
+    }
+    if raw[4..].iter().all(|byte| *byte == 0) {
+        return Some(Ipv4Addr::new(raw[0], raw[1], raw[2], raw[3]).to_string());
+    }
+    let octets: [u8; 16] = raw.try_into().ok()?;
+    Some(Ipv6Addr::from(octets).to_string())
+}
+
+/// The last login of a user recorded in lastlog, which holds one record per uid.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct LastlogRecord {
+    pub source: String,
+    pub uid: u32,
+    /// The user name, only known when the collection holds `/etc/passwd`.
+    pub user: Option<String>,
+    pub time: Option<String>,
+    pub tty: String,
+    pub host: String,
+    pub warnings: Vec<String>,
+}
+
+/// Parses lastlog, skipping the zeroed records of users that never logged in. `users` maps uids
+/// to user names. The file is read a record at a time, as it is sparse and holds a record for
+/// every uid up to the highest one that logged in, which can make it hundreds of gigabytes.
+pub fn parse_lastlog<R>(
+    mut reader: R,
+    source: &str,
+    users: &HashMap<u32, String>,
+) -> Result<Vec<LastlogRecord>, Box<dyn Error>>
+where
+    R: Read,
+{
+    let mut records = Vec::new();
+    let mut raw = [0u8; LASTLOG_SIZE];
+    for uid in 0u32.. {
+        let filled = fill(&mut reader, &mut raw)?;
+        if filled == 0 {
+            break;
+        }
+        if filled < LASTLOG_SIZE {
+            records.push(LastlogRecord {
+                source: source.to_string(),
+                uid,
+                warnings: vec![format!(
+                    "{} trailing bytes do not make up a whole record",
+                    filled
+                )],
+                ..Default::default()
+            });
+            break;
+        }
+        if raw.iter().all(|byte| *byte == 0) {
+            continue;
+        }
+        let mut warnings = Vec::new();
+        let seconds = le_i32(&raw, 0) as i64;
+        if seconds == 0 {
+            warnings.push("the record has a terminal or host but no time".to_string());
+        }
+        let tty = c_string(&raw[4..4 + LINE_SIZE], "tty", &mut warnings);
+        let host = c_string(&raw[36..36 + HOST_SIZE], "host", &mut warnings);
+        records.push(LastlogRecord {
+            source: source.to_string(),
+            uid,
+            user: users.get(&uid).cloned(),
+            time: format_timestamp(seconds, 0).filter(|_| seconds != 0),
+            tty,
+            host,
+            warnings,
+        });
+    }
+    Ok(records)
+}
+
+/// Reads until `buf` is full or the end of the file, returning how much was read.
+fn fill<R>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize>
+where
+    R: Read,
+{
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Reads the uid of every user in an `/etc/passwd` file.
+pub fn parse_passwd(passwd: &str) -> HashMap<u32, String> {
+    passwd
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let user = fields.next()?;
+            let uid = fields.nth(1)?.parse().ok()?;
+            Some((uid, user.to_string()))
+        })
+        .collect()
+}
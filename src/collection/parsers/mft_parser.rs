@@ -0,0 +1,383 @@
+use super::bytes::{le_u16, le_u32, le_u64};
+use crate::collection::archive::CollectionArchive;
+use crate::collection::metadata::format_filetime;
+use crate::collection::readers::ntfs_reader::{directory_path, MftDirectory};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{self, Read},
+};
+
+const FILE_SIGNATURE: &[u8; 4] = b"FILE";
+const DEFAULT_RECORD_SIZE: usize = 1024;
+const SECTOR_SIZE: usize = 512;
+/// The part of a record header that holds the size of the record.
+const RECORD_HEADER_SIZE: usize = 32;
+const RECORD_IN_USE: u16 = 0x01;
+const RECORD_IS_DIRECTORY: u16 = 0x02;
+const END_OF_ATTRIBUTES: u32 = 0xFFFFFFFF;
+const STANDARD_INFORMATION: u32 = 0x10;
+const FILE_NAME: u32 = 0x30;
+const DATA: u32 = 0x80;
+const DOS_NAMESPACE: u8 = 2;
+const RECORD_NUMBER_MASK: u64 = 0x0000_FFFF_FFFF_FFFF;
+
+/// The names of the `$STANDARD_INFORMATION` file attribute flags, by bit.
+const FILE_ATTRIBUTES: [(u32, &str); 15] = [
+    (0x0001, "read_only"),
+    (0x0002, "hidden"),
+    (0x0004, "system"),
+    (0x0010, "directory"),
+    (0x0020, "archive"),
+    (0x0040, "device"),
+    (0x0080, "normal"),
+    (0x0100, "temporary"),
+    (0x0200, "sparse"),
+    (0x0400, "reparse_point"),
+    (0x0800, "compressed"),
+    (0x1000, "offline"),
+    (0x2000, "not_content_indexed"),
+    (0x4000, "encrypted"),
+    (0x8000, "integrity_stream"),
+];
+
+/// Returns the names of the file attribute flags that are set, shared by `$STANDARD_INFORMATION`
+/// and the USN journal.
+pub fn attribute_names(attributes: u32) -> Vec<String> {
+    FILE_ATTRIBUTES
+        .iter()
+        .filter(|(bit, _)| attributes & bit != 0)
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+/// One file record of `$MFT`, with the path of the file rebuilt from the parent directory
+/// references. `si_*` timestamps come from `$STANDARD_INFORMATION`, which any program can set,
+/// while the `fn_*` ones from `$FILE_NAME` are only updated by the kernel.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct MftEntry {
+    pub record: u64,
+    pub sequence: u16,
+    pub in_use: bool,
+    pub directory: bool,
+    /// The path relative to the volume root, empty for the root itself, or the bare name when the
+    /// parent directory is gone.
+    pub path: String,
+    pub name: String,
+    pub parent_record: u64,
+    /// The length of the unnamed data stream.
+    pub size: u64,
+    pub si_created: Option<String>,
+    pub si_modified: Option<String>,
+    pub si_mft_modified: Option<String>,
+    pub si_accessed: Option<String>,
+    pub fn_created: Option<String>,
+    pub fn_modified: Option<String>,
+    pub fn_mft_modified: Option<String>,
+    pub fn_accessed: Option<String>,
+    /// Set when the `$STANDARD_INFORMATION` creation or modification time is earlier than the
+    /// one in `$FILE_NAME`, which happens when a file's timestamps were backdated.
+    pub timestomped: bool,
+    pub flags: Vec<String>,
+    /// Names of the alternate data streams.
+    pub streams: Vec<String>,
+}
+
+/// The parent reference (record and sequence number), namespace, timestamps and name of a
+/// `$FILE_NAME`.
+type FileName = ((u64, u16), u8, [u64; 4], String);
+
+/// What is read from a single record before extension records are merged into their base.
+#[derive(Default)]
+struct RawRecord {
+    record: u64,
+    base_record: u64,
+    sequence: u16,
+    flags: u16,
+    standard_information: Option<[u64; 4]>,
+    file_attributes: u32,
+    file_names: Vec<FileName>,
+    size: Option<u64>,
+    streams: Vec<String>,
+}
+
+/// Reads every file record of a `$MFT` file. Records whose attributes do not fit in a single
+/// record continue in extension records, which are merged into the record they belong to.
+pub fn parse_mft<R>(mut reader: R) -> Result<Vec<MftEntry>, Box<dyn Error>>
+where
+    R: Read,
+{
+    // the first record, $MFT itself, tells the size of every record, its header is read on its
+    // own so a smaller record size does not take in part of the next record
+    let mut header = [0u8; RECORD_HEADER_SIZE];
+    if !read_record(&mut reader, &mut header)? || &header[0..4] != FILE_SIGNATURE {
+        return Err("The file does not start with an MFT record".into());
+    }
+    let record_size = match le_u32(&header, 28) as usize {
+        size if size >= SECTOR_SIZE && size % SECTOR_SIZE == 0 => size,
+        _ => DEFAULT_RECORD_SIZE,
+    };
+    let mut buf = vec![0u8; record_size];
+    buf[..RECORD_HEADER_SIZE].copy_from_slice(&header);
+    let mut records: Vec<RawRecord> = Vec::new();
+    let mut number = 0u64;
+    let mut complete = read_record(&mut reader, &mut buf[RECORD_HEADER_SIZE..])?;
+    while complete {
+        if let Some(record) = parse_record(number, &mut buf) {
+            records.push(record);
+        }
+        number += 1;
+        complete = read_record(&mut reader, &mut buf)?;
+    }
+
+    let mut extensions: HashMap<u64, Vec<RawRecord>> = HashMap::new();
+    let mut bases = Vec::new();
+    for record in records {
+        if record.base_record != 0 {
+            extensions
+                .entry(record.base_record)
+                .or_default()
+                .push(record);
+        } else {
+            bases.push(record);
+        }
+    }
+    for base in bases.iter_mut() {
+        for extension in extensions.remove(&base.record).unwrap_or_default() {
+            base.file_names.extend(extension.file_names);
+            base.streams.extend(extension.streams);
+            if base.size.is_none() {
+                base.size = extension.size;
+            }
+        }
+    }
+
+    let mut directories: HashMap<u64, MftDirectory> = HashMap::new();
+    for record in bases.iter() {
+        if record.flags & RECORD_IS_DIRECTORY != 0 {
+            if let Some((parent, _, _, name)) = primary_name(record) {
+                directories.insert(
+                    record.record,
+                    MftDirectory {
+                        parent: *parent,
+                        name: name.clone(),
+                        sequence: record.sequence,
+                        in_use: record.flags & RECORD_IN_USE != 0,
+                    },
+                );
+            }
+        }
+    }
+
+    let mut resolved = HashMap::new();
+    let mut entries = Vec::with_capacity(bases.len());
+    for record in bases {
+        let standard_information = record.standard_information.unwrap_or_default();
+        let (parent, file_name_times, name) = match primary_name(&record) {
+            Some((parent, _, times, name)) => (*parent, *times, name.clone()),
+            None => ((0, 0), [0; 4], String::new()),
+        };
+        let parent_record = parent.0;
+        let path = match directory_path(parent, &directories, &mut resolved) {
+            // the root directory is its own parent
+            _ if record.record == parent_record => String::new(),
+            Some(directory) if directory.is_empty() => name.clone(),
+            Some(directory) => format!("{0}\\{1}", directory, name),
+            None => name.clone(),
+        };
+        let timestomped = record.standard_information.is_some()
+            && !record.file_names.is_empty()
+            && (standard_information[0] < file_name_times[0]
+                || standard_information[1] < file_name_times[1]);
+
+        entries.push(MftEntry {
+            record: record.record,
+            sequence: record.sequence,
+            in_use: record.flags & RECORD_IN_USE != 0,
+            directory: record.flags & RECORD_IS_DIRECTORY != 0,
+            path,
+            name,
+            parent_record,
+            size: record.size.unwrap_or_default(),
+            si_created: format_filetime(standard_information[0]),
+            si_modified: format_filetime(standard_information[1]),
+            si_mft_modified: format_filetime(standard_information[2]),
+            si_accessed: format_filetime(standard_information[3]),
+            fn_created: format_filetime(file_name_times[0]),
+            fn_modified: format_filetime(file_name_times[1]),
+            fn_mft_modified: format_filetime(file_name_times[2]),
+            fn_accessed: format_filetime(file_name_times[3]),
+            timestomped,
+            flags: attribute_names(record.file_attributes),
+            streams: record.streams,
+        });
+    }
+    Ok(entries)
+}
+
+/// Returns the volume an NTFS artefact was collected from, `C:\` for `C:\$MFT`.
+pub fn volume(original_path: &str) -> &str {
+    original_path
+        .find('\\')
+        .map(|position| &original_path[..=position])
+        .unwrap_or_default()
+}
+
+/// Calls `found` with every record of every `$MFT` in a collection, the paths are prefixed with
+/// the volume they came from.
+pub fn parse_collection_mft<F>(
+    collection: &mut CollectionArchive,
+    mut found: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(MftEntry) -> Result<(), Box<dyn Error>>,
+{
+    collection.for_each_artefact("$MFT", |path, reader| {
+        for mut entry in parse_mft(reader)? {
+            entry.path = format!("{0}{1}", volume(path), entry.path);
+            found(entry)?;
+        }
+        Ok(())
+    })
+}
+
+/// Fills `buf` with the next record, returning `false` at the end of the file. A truncated last
+/// record is ignored.
+fn read_record<R>(reader: &mut R, buf: &mut [u8]) -> io::Result<bool>
+where
+    R: Read,
+{
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => return Ok(false),
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+/// Returns the long name of a file, the 8.3 name is only used when it is the only one.
+fn primary_name(record: &RawRecord) -> Option<&FileName> {
+    record
+        .file_names
+        .iter()
+        .find(|(_, namespace, _, _)| *namespace != DOS_NAMESPACE)
+        .or(record.file_names.first())
+}
+
+/// Applies the update sequence array, which replaces the last two bytes of every sector, and
+/// reads the attributes of a record. Unused and damaged records are skipped.
+fn parse_record(number: u64, buf: &mut [u8]) -> Option<RawRecord> {
+    if &buf[0..4] != FILE_SIGNATURE {
+        return None;
+    }
+    let usa_offset = le_u16(buf, 4) as usize;
+    let usa_count = le_u16(buf, 6) as usize;
+    if usa_count == 0 || usa_offset + usa_count * 2 > buf.len() {
+        return None;
+    }
+    let check = le_u16(buf, usa_offset);
+    for sector in 1..usa_count {
+        let end = sector * SECTOR_SIZE - 2;
+        if end + 2 > buf.len() || le_u16(buf, end) != check {
+            return None;
+        }
+        let fixup = usa_offset + sector * 2;
+        buf.copy_within(fixup..fixup + 2, end);
+    }
+
+    let mut record = RawRecord {
+        record: number,
+        base_record: le_u64(buf, 32) & RECORD_NUMBER_MASK,
+        sequence: le_u16(buf, 16),
+        flags: le_u16(buf, 22),
+        ..Default::default()
+    };
+    let used = (le_u32(buf, 24) as usize).min(buf.len());
+    let mut offset = le_u16(buf, 20) as usize;
+    while offset + 16 <= used {
+        let attribute_type = le_u32(buf, offset);
+        let length = le_u32(buf, offset + 4) as usize;
+        if attribute_type == END_OF_ATTRIBUTES || length < 16 || offset + length > used {
+            break;
+        }
+        parse_attribute(&mut record, &buf[offset..offset + length]);
+        offset += length;
+    }
+    Some(record)
+}
+
+fn parse_attribute(record: &mut RawRecord, attribute: &[u8]) {
+    let non_resident = attribute[8] != 0;
+    let name_length = attribute[9] as usize;
+    let name_offset = le_u16(attribute, 10) as usize;
+    let name = if name_length > 0 && name_offset + name_length * 2 <= attribute.len() {
+        utf16_string(&attribute[name_offset..name_offset + name_length * 2])
+    } else {
+        String::new()
+    };
+    let value = if non_resident || attribute.len() < 24 {
+        None
+    } else {
+        let length = le_u32(attribute, 16) as usize;
+        let offset = le_u16(attribute, 20) as usize;
+        attribute.get(offset..offset + length)
+    };
+
+    match le_u32(attribute, 0) {
+        STANDARD_INFORMATION => {
+            if let Some(value) = value.filter(|value| value.len() >= 36) {
+                record.standard_information = Some([
+                    le_u64(value, 0),
+                    le_u64(value, 8),
+                    le_u64(value, 16),
+                    le_u64(value, 24),
+                ]);
+                record.file_attributes = le_u32(value, 32);
+            }
+        }
+        FILE_NAME => {
+            let Some(value) = value.filter(|value| value.len() >= 66) else {
+                return;
+            };
+            let name_length = value[64] as usize;
+            if 66 + name_length * 2 > value.len() {
+                return;
+            }
+            record.file_names.push((
+                (le_u64(value, 0) & RECORD_NUMBER_MASK, le_u16(value, 6)),
+                value[65],
+                [
+                    le_u64(value, 8),
+                    le_u64(value, 16),
+                    le_u64(value, 24),
+                    le_u64(value, 32),
+                ],
+                utf16_string(&value[66..66 + name_length * 2]),
+            ));
+        }
+        DATA if !name.is_empty() && !record.streams.contains(&name) => record.streams.push(name),
+        DATA if !name.is_empty() => {}
+        DATA => {
+            // only the first extent of a non-resident stream records its length
+            if non_resident && attribute.len() >= 56 && le_u64(attribute, 16) == 0 {
+                record.size = Some(le_u64(attribute, 48));
+            } else if let Some(value) = value {
+                record.size = Some(value.len() as u64);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn utf16_string(buf: &[u8]) -> String {
+    let units: Vec<u16> = buf
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
@@ -0,0 +1,108 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::{error::Error, io::Write, str::FromStr};
+
+/// The formats parsed artefacts can be written in.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum OutputFormat {
+    /// One JSON object per line.
+    #[default]
+    Jsonl,
+    /// A header row followed by one row per record, lists are joined with `;`.
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "jsonl" | "json" => Ok(OutputFormat::Jsonl),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!(
+                "Unknown output format {}, expected jsonl or csv",
+                format
+            )),
+        }
+    }
+}
+
+/// Writes parsed records one after another as they are produced, in JSONL or CSV. The CSV header
+/// is taken from the fields of the first record.
+pub struct RecordWriter<W>
+where
+    W: Write,
+{
+    jsonl: Option<W>,
+    csv: Option<csv::Writer<W>>,
+    header: Option<Vec<String>>,
+}
+
+impl<W> RecordWriter<W>
+where
+    W: Write,
+{
+    pub fn new(writer: W, format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Jsonl => RecordWriter {
+                jsonl: Some(writer),
+                csv: None,
+                header: None,
+            },
+            OutputFormat::Csv => RecordWriter {
+                jsonl: None,
+                csv: Some(csv::Writer::from_writer(writer)),
+                header: None,
+            },
+        }
+    }
+
+    pub fn write<T>(&mut self, record: &T) -> Result<(), Box<dyn Error>>
+    where
+        T: Serialize,
+    {
+        if let Some(writer) = self.jsonl.as_mut() {
+            serde_json::to_writer(&mut *writer, record)?;
+            writer.write_all(b"\n")?;
+            return Ok(());
+        }
+
+        let Value::Object(fields) = serde_json::to_value(record)? else {
+            return Err("Only structs can be written as CSV rows".into());
+        };
+        let writer = self.csv.as_mut().ok_or("The CSV writer is missing")?;
+        let header = match &self.header {
+            Some(header) => header,
+            None => {
+                let header: Vec<String> = fields.keys().cloned().collect();
+                writer.write_record(&header)?;
+                self.header.insert(header)
+            }
+        };
+        let row: Vec<String> = header
+            .iter()
+            .map(|name| fields.get(name).map(csv_field).unwrap_or_default())
+            .collect();
+        writer.write_record(&row)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(writer) = self.jsonl.as_mut() {
+            writer.flush()?;
+        }
+        if let Some(writer) = self.csv.as_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn csv_field(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.clone(),
+        Value::Array(values) => values.iter().map(csv_field).collect::<Vec<_>>().join(";"),
+        value => value.to_string(),
+    }
+}
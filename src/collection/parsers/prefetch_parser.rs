@@ -0,0 +1,318 @@
+use super::bytes::{le_u16, le_u32, le_u64};
+use crate::collection::metadata::format_filetime;
+use crate::collection::parsers::registry_parser::utf16_string;
+use serde::{Deserialize, Serialize};
+use std::{error::Error, io::Read};
+
+const SCCA_SIGNATURE: &[u8; 4] = b"SCCA";
+const MAM_SIGNATURE: &[u8; 3] = b"MAM";
+/// The compression method Windows 10 and later compress prefetch files with.
+const XPRESS_HUFFMAN: u8 = 4;
+/// Set in the compression method of a MAM header when a CRC32 follows the uncompressed size.
+const MAM_HAS_CHECKSUM: u8 = 0x80;
+const HUFFMAN_SYMBOLS: usize = 512;
+/// The longest code an Xpress Huffman table may assign, which also sizes its decoding table.
+const HUFFMAN_TABLE_BITS: u32 = 15;
+/// Xpress Huffman streams are split into blocks of this much output, each with its own table.
+const XPRESS_BLOCK_SIZE: usize = 65536;
+/// The most a MAM header may claim to decompress to, far beyond any real prefetch file.
+const MAX_UNCOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+/// The format versions written by Windows XP, Vista and 7, 8.1, and 10 and 11.
+const WINDOWS_XP: u32 = 17;
+const WINDOWS_7: u32 = 23;
+const WINDOWS_8: u32 = 26;
+const WINDOWS_10: u32 = 30;
+/// Windows 10 builds from 1903 on dropped eight unknown bytes before the run count, which moves
+/// the metrics array this far into the file.
+const WINDOWS_10_SHORT_METRICS_OFFSET: u32 = 0x128;
+
+/// A volume the executable loaded files from while it was being prefetched.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct PrefetchVolume {
+    /// The NT device path of the volume, e.g. `\VOLUME{01d2...}` or `\DEVICE\HARDDISKVOLUME2`.
+    pub device_path: String,
+    /// The volume serial number, formatted as `dir` shows it, e.g. `A4B1-9C3E`.
+    pub serial: String,
+    pub created: Option<String>,
+    pub directories: Vec<String>,
+}
+
+/// The execution history Windows keeps for a program in its prefetch file.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct PrefetchRecord {
+    /// The prefetch file the record was read from.
+    pub source: String,
+    pub executable: String,
+    /// The hash of the executable's path that makes up the end of the prefetch file's name.
+    pub hash: String,
+    pub version: u32,
+    pub run_count: u32,
+    /// The latest first. Windows 8 and later keep the last eight, earlier versions only the last.
+    pub last_run_times: Vec<String>,
+    pub volumes: Vec<PrefetchVolume>,
+    /// The files the executable loaded in its first seconds of running.
+    pub files: Vec<String>,
+}
+
+/// Reads a prefetch file, decompressing it first when it was written by Windows 10 or later.
+pub fn parse_prefetch<R>(mut reader: R, source: &str) -> Result<PrefetchRecord, Box<dyn Error>>
+where
+    R: Read,
+{
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    if data.starts_with(MAM_SIGNATURE) {
+        data = decompress_mam(&data).map_err(|e| format!("{0}: {1}", source, e))?;
+    }
+    if data.get(4..8) != Some(SCCA_SIGNATURE) {
+        return Err(format!("{} is not a prefetch file", source).into());
+    }
+    let version = le_u32(&data, 0);
+    if ![WINDOWS_XP, WINDOWS_7, WINDOWS_8, WINDOWS_10, WINDOWS_10 + 1].contains(&version) {
+        return Err(format!("{0} has unsupported format version {1}", source, version).into());
+    }
+    let metrics_offset = le_u32(&data, 84);
+    let (last_run_times, run_count_offset) = match version {
+        WINDOWS_XP => (1, 144),
+        WINDOWS_7 => (1, 152),
+        WINDOWS_8 => (8, 208),
+        _ if metrics_offset == WINDOWS_10_SHORT_METRICS_OFFSET => (8, 200),
+        _ => (8, 208),
+    };
+    let last_run_times = (0..last_run_times)
+        .filter_map(|index| {
+            let offset = if version == WINDOWS_XP { 120 } else { 128 };
+            format_filetime(le_u64(&data, offset + index * 8))
+        })
+        .collect();
+
+    let files_offset = le_u32(&data, 100) as usize;
+    let files_size = le_u32(&data, 104) as usize;
+    let files = data
+        .get(files_offset..files_offset.saturating_add(files_size))
+        .map(utf16_strings)
+        .unwrap_or_default();
+
+    Ok(PrefetchRecord {
+        source: source.to_string(),
+        executable: utf16_string(data.get(16..76).unwrap_or_default()),
+        hash: format!("{:08X}", le_u32(&data, 76)),
+        version,
+        run_count: le_u32(&data, run_count_offset),
+        last_run_times,
+        volumes: volumes(&data, version),
+        files,
+    })
+}
+
+/// Splits a block of NUL terminated UTF-16LE strings.
+fn utf16_strings(buf: &[u8]) -> Vec<String> {
+    let units: Vec<u16> = buf
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    units
+        .split(|unit| *unit == 0)
+        .filter(|string| !string.is_empty())
+        .map(String::from_utf16_lossy)
+        .collect()
+}
+
+fn volumes(data: &[u8], version: u32) -> Vec<PrefetchVolume> {
+    let volumes_offset = le_u32(data, 108) as usize;
+    let count = le_u32(data, 112) as usize;
+    let entry_size = match version {
+        WINDOWS_XP => 40,
+        WINDOWS_7 | WINDOWS_8 => 104,
+        _ => 96,
+    };
+    let mut volumes = Vec::new();
+    for index in 0..count {
+        let Some(entry) = volumes_offset
+            .checked_add(index * entry_size)
+            .and_then(|offset| data.get(offset..offset + entry_size))
+        else {
+            break;
+        };
+        // the strings of a volume are found relative to the start of the volumes information
+        let device_path_offset = volumes_offset + le_u32(entry, 0) as usize;
+        let device_path_size = le_u32(entry, 4) as usize * 2;
+        let device_path = data
+            .get(device_path_offset..device_path_offset.saturating_add(device_path_size))
+            .map(utf16_string)
+            .unwrap_or_default();
+        let serial = le_u32(entry, 16);
+        let mut directories = Vec::new();
+        let mut offset = volumes_offset + le_u32(entry, 28) as usize;
+        for _ in 0..le_u32(entry, 32) {
+            // each directory is its length in characters then the characters and a NUL
+            let size = le_u16(data, offset) as usize * 2;
+            let Some(directory) = data.get(offset + 2..offset + 2 + size) else {
+                break;
+            };
+            directories.push(utf16_string(directory));
+            offset += 2 + size + 2;
+        }
+        volumes.push(PrefetchVolume {
+            device_path,
+            serial: format!("{:04X}-{:04X}", serial >> 16, serial & 0xFFFF),
+            created: format_filetime(le_u64(entry, 8)),
+            directories,
+        });
+    }
+    volumes
+}
+
+/// Unwraps the MAM header Windows 10 and later put around prefetch files.
+fn decompress_mam(data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let method = data.get(3).copied().unwrap_or_default();
+    if method & 0x0F != XPRESS_HUFFMAN {
+        return Err(format!("Unsupported MAM compression method {}", method & 0x0F).into());
+    }
+    let size = le_u32(data, 4) as usize;
+    if size > MAX_UNCOMPRESSED_SIZE {
+        return Err(format!("A MAM header claims an uncompressed size of {} bytes", size).into());
+    }
+    let start = if method & MAM_HAS_CHECKSUM != 0 {
+        12
+    } else {
+        8
+    };
+    decompress_xpress_huffman(data.get(start..).unwrap_or_default(), size)
+}
+
+/// Reads the bits of an Xpress Huffman stream most significant first, from little endian
+/// 16-bit words.
+struct BitReader<'a> {
+    input: &'a [u8],
+    position: usize,
+    bits: u32,
+    available: i32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(input: &'a [u8], position: usize) -> Self {
+        let bits = (le_u16(input, position) as u32) << 16 | le_u16(input, position + 2) as u32;
+        BitReader {
+            input,
+            position: position + 4,
+            bits,
+            available: 16,
+        }
+    }
+
+    fn peek(&self, count: u32) -> u32 {
+        if count == 0 {
+            0
+        } else {
+            self.bits >> (32 - count)
+        }
+    }
+
+    fn skip(&mut self, count: u32) {
+        if count == 0 {
+            return;
+        }
+        self.bits <<= count;
+        self.available -= count as i32;
+        if self.available < 0 {
+            self.bits |= (le_u16(self.input, self.position) as u32) << -self.available;
+            self.position += 2;
+            self.available += 16;
+        }
+    }
+
+    /// Match lengths too long for a symbol are stored as whole bytes between the words.
+    fn byte(&mut self) -> u8 {
+        let byte = self.input.get(self.position).copied().unwrap_or_default();
+        self.position += 1;
+        byte
+    }
+
+    fn word(&mut self) -> u16 {
+        let word = le_u16(self.input, self.position);
+        self.position += 2;
+        word
+    }
+}
+
+/// Builds the table that maps the next 15 bits of the stream to the symbol they start with, from
+/// the 4-bit code lengths of the 512 symbols at the start of every block.
+fn decoding_table(lengths: &[u8; HUFFMAN_SYMBOLS]) -> Result<Vec<u16>, Box<dyn Error>> {
+    let mut table = Vec::with_capacity(1 << HUFFMAN_TABLE_BITS);
+    for length in 1..=HUFFMAN_TABLE_BITS {
+        for (symbol, _) in lengths
+            .iter()
+            .enumerate()
+            .filter(|(_, symbol_length)| **symbol_length as u32 == length)
+        {
+            let entries = 1 << (HUFFMAN_TABLE_BITS - length);
+            if table.len() + entries > 1 << HUFFMAN_TABLE_BITS {
+                return Err("An Xpress Huffman table assigns more codes than fit".into());
+            }
+            table.extend(std::iter::repeat_n(symbol as u16, entries));
+        }
+    }
+    if table.len() != 1 << HUFFMAN_TABLE_BITS {
+        return Err("An Xpress Huffman table does not assign every code".into());
+    }
+    Ok(table)
+}
+
+/// Decompresses the Xpress Huffman format of MS-XCA into `size` bytes.
+fn decompress_xpress_huffman(input: &[u8], size: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut output = Vec::with_capacity(size);
+    let mut position = 0;
+    while output.len() < size {
+        let table_bytes = input
+            .get(position..position + HUFFMAN_SYMBOLS / 2)
+            .ok_or("The Xpress Huffman stream ends before its data")?;
+        let mut lengths = [0; HUFFMAN_SYMBOLS];
+        for (index, byte) in table_bytes.iter().enumerate() {
+            lengths[index * 2] = byte & 0x0F;
+            lengths[index * 2 + 1] = byte >> 4;
+        }
+        let table = decoding_table(&lengths)?;
+        let mut bits = BitReader::new(input, position + HUFFMAN_SYMBOLS / 2);
+        let block_end = (output.len() + XPRESS_BLOCK_SIZE).min(size);
+        while output.len() < block_end {
+            if bits.position > input.len() + 4 {
+                return Err("The Xpress Huffman stream ends before its data".into());
+            }
+            let symbol = table[bits.peek(HUFFMAN_TABLE_BITS) as usize] as usize;
+            bits.skip(lengths[symbol] as u32);
+            if symbol < 256 {
+                output.push(symbol as u8);
+                continue;
+            }
+            // the rest are matches, with the length in the low and offset bits in the high nibble
+            let mut length = symbol & 0x0F;
+            let offset_bits = (symbol >> 4) as u32 & 0x0F;
+            if length == 15 {
+                length = bits.byte() as usize;
+                if length == 255 {
+                    length = bits.word() as usize;
+                    if length < 15 {
+                        return Err("An Xpress Huffman match has an invalid length".into());
+                    }
+                    length -= 15;
+                }
+                length += 15;
+            }
+            length += 3;
+            let offset = (bits.peek(offset_bits) | 1 << offset_bits) as usize;
+            bits.skip(offset_bits);
+            if offset > output.len() {
+                return Err("An Xpress Huffman match refers to data before the stream".into());
+            }
+            // matches may overlap the bytes they produce, so they are copied a byte at a time
+            let start = output.len() - offset;
+            for index in 0..length.min(size - output.len()) {
+                output.push(output[start + index]);
+            }
+        }
+        position = bits.position;
+    }
+    Ok(output)
+}
@@ -0,0 +1,434 @@
+use super::bytes::{le_u16, le_u32, le_u64};
+use crate::collection::metadata::format_filetime;
+use crate::collection::parsers::registry_parser::{utf16_string, Hive, Key};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+const WIN7_SHIMCACHE_SIGNATURE: u32 = 0xBADC0FEE;
+const WIN7_SHIMCACHE_HEADER_SIZE: usize = 128;
+const WIN8_SHIMCACHE_HEADER_SIZE: usize = 128;
+/// Set in the insert flags of a Windows 7 and 8 ShimCache entry when the file was executed.
+const SHIMCACHE_EXECUTED: u32 = 0x2;
+
+/// The Run keys of SOFTWARE and of NTUSER.DAT, relative to the root of each.
+const RUN_KEYS: [&str; 10] = [
+    "Microsoft\\Windows\\CurrentVersion\\Run",
+    "Microsoft\\Windows\\CurrentVersion\\RunOnce",
+    "Microsoft\\Windows\\CurrentVersion\\Policies\\Explorer\\Run",
+    "Wow6432Node\\Microsoft\\Windows\\CurrentVersion\\Run",
+    "Wow6432Node\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+    "Software\\Microsoft\\Windows\\CurrentVersion\\Run",
+    "Software\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+    "Software\\Microsoft\\Windows\\CurrentVersion\\Policies\\Explorer\\Run",
+    "Software\\Wow6432Node\\Microsoft\\Windows\\CurrentVersion\\Run",
+    "Software\\Wow6432Node\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+];
+
+/// The names of the `Start` values of a service, by value.
+const SERVICE_START_TYPES: [&str; 5] = ["boot", "system", "automatic", "manual", "disabled"];
+
+/// A persistence or execution artefact found in a hive. `timestamp` is the time the artefact
+/// itself records, e.g. when a program was last run, which is not always the time its key was
+/// last written.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct RegistryArtefact {
+    /// The hive the artefact was read from.
+    pub source: String,
+    /// One of `run_key`, `service`, `userassist`, `shimcache`, `amcache`, `bam` or `dam`.
+    pub artefact: String,
+    pub key: String,
+    pub key_last_written: Option<String>,
+    pub name: String,
+    /// The program, command line or file the artefact is about.
+    pub path: Option<String>,
+    pub timestamp: Option<String>,
+    /// Whatever else the artefact records, e.g. a service's start type or a run count.
+    pub details: BTreeMap<String, Value>,
+}
+
+impl RegistryArtefact {
+    fn new(hive: &Hive, artefact: &str, key: &Key, name: &str) -> Self {
+        RegistryArtefact {
+            source: hive.source().to_string(),
+            artefact: artefact.to_string(),
+            key: key.path.clone(),
+            key_last_written: key.last_written.clone(),
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Extracts every artefact the hive holds. Which ones it holds depends on the hive, so each is
+/// looked for in every hive rather than relying on its file name.
+pub fn extract_artefacts(hive: &Hive) -> Vec<RegistryArtefact> {
+    let mut artefacts = run_keys(hive);
+    artefacts.extend(services(hive));
+    artefacts.extend(user_assist(hive));
+    artefacts.extend(shimcache(hive));
+    artefacts.extend(amcache(hive));
+    artefacts.extend(bam(hive));
+    artefacts
+}
+
+/// Returns the control set SYSTEM boots with, e.g. `ControlSet001`.
+fn current_control_set(hive: &Hive) -> Option<String> {
+    let select = hive.open("Select")?;
+    let current = hive.value(&select, "Current")?.dword()?;
+    Some(format!("ControlSet{:03}", current))
+}
+
+fn run_keys(hive: &Hive) -> Vec<RegistryArtefact> {
+    let mut artefacts = Vec::new();
+    for key in RUN_KEYS.iter().filter_map(|path| hive.open(path)) {
+        for value in hive.values(&key).unwrap_or_default() {
+            let mut artefact = RegistryArtefact::new(hive, "run_key", &key, &value.name);
+            artefact.path = value.string();
+            if artefact.path.is_none() {
+                artefact.details.insert("data".to_string(), value.json());
+            }
+            artefacts.push(artefact);
+        }
+    }
+    artefacts
+}
+
+fn services(hive: &Hive) -> Vec<RegistryArtefact> {
+    let Some(services) = current_control_set(hive)
+        .and_then(|control_set| hive.open(&format!("{}\\Services", control_set)))
+    else {
+        return Vec::new();
+    };
+    let mut artefacts = Vec::new();
+    for service in hive.subkeys(&services).unwrap_or_default() {
+        let string = |name: &str| hive.value(&service, name).and_then(|value| value.string());
+        let mut artefact = RegistryArtefact::new(hive, "service", &service, &service.name);
+        artefact.path = string("ImagePath");
+        artefact.timestamp = service.last_written.clone();
+        if let Some(start) = hive
+            .value(&service, "Start")
+            .and_then(|value| value.dword())
+        {
+            let start = SERVICE_START_TYPES
+                .get(start as usize)
+                .map(|name| name.to_string())
+                .unwrap_or(start.to_string());
+            artefact
+                .details
+                .insert("start".to_string(), Value::from(start));
+        }
+        if let Some(service_type) = hive.value(&service, "Type").and_then(|value| value.dword()) {
+            artefact
+                .details
+                .insert("type".to_string(), Value::from(service_type));
+        }
+        for (name, detail) in [
+            ("DisplayName", "display_name"),
+            ("ObjectName", "account"),
+            ("Description", "description"),
+        ] {
+            if let Some(value) = string(name) {
+                artefact
+                    .details
+                    .insert(detail.to_string(), Value::from(value));
+            }
+        }
+        // services hosted by svchost name their DLL under Parameters
+        let service_dll = hive
+            .open(&format!("{}\\Parameters", service.path))
+            .and_then(|parameters| hive.value(&parameters, "ServiceDll"))
+            .and_then(|value| value.string());
+        if let Some(service_dll) = service_dll {
+            artefact
+                .details
+                .insert("service_dll".to_string(), Value::from(service_dll));
+        }
+        artefacts.push(artefact);
+    }
+    artefacts
+}
+
+/// Decodes the ROT13 names of UserAssist values.
+fn rot13(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' => (((c as u8 - b'a') + 13) % 26 + b'a') as char,
+            'A'..='Z' => (((c as u8 - b'A') + 13) % 26 + b'A') as char,
+            c => c,
+        })
+        .collect()
+}
+
+fn user_assist(hive: &Hive) -> Vec<RegistryArtefact> {
+    let Some(user_assist) =
+        hive.open("Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\UserAssist")
+    else {
+        return Vec::new();
+    };
+    let mut artefacts = Vec::new();
+    for guid in hive.subkeys(&user_assist).unwrap_or_default() {
+        let Some(count) = hive.open(&format!("{}\\Count", guid.path)) else {
+            continue;
+        };
+        for value in hive.values(&count).unwrap_or_default() {
+            let data = &value.data;
+            // Windows 7 and later write 72 bytes, XP 16 bytes with the run count offset by 5
+            let (run_count, last_run, focus) = match data.len() {
+                72 => (
+                    le_u32(data, 4),
+                    le_u64(data, 60),
+                    Some((le_u32(data, 8), le_u32(data, 12))),
+                ),
+                16 => (le_u32(data, 4).saturating_sub(5), le_u64(data, 8), None),
+                _ => continue,
+            };
+            let name = rot13(&value.name);
+            let mut artefact = RegistryArtefact::new(hive, "userassist", &count, &name);
+            artefact.path = Some(name);
+            artefact.timestamp = format_filetime(last_run);
+            artefact
+                .details
+                .insert("run_count".to_string(), Value::from(run_count));
+            if let Some((focus_count, focus_time)) = focus {
+                artefact
+                    .details
+                    .insert("focus_count".to_string(), Value::from(focus_count));
+                artefact
+                    .details
+                    .insert("focus_milliseconds".to_string(), Value::from(focus_time));
+            }
+            artefacts.push(artefact);
+        }
+    }
+    artefacts
+}
+
+/// An entry of the ShimCache, in the order Windows keeps them, most recently added first.
+struct ShimCacheEntry {
+    path: String,
+    last_modified: u64,
+    /// Only recorded by Windows 7 and 8.
+    executed: Option<bool>,
+}
+
+fn shimcache(hive: &Hive) -> Vec<RegistryArtefact> {
+    let Some(key) = current_control_set(hive).and_then(|control_set| {
+        hive.open(&format!(
+            "{}\\Control\\Session Manager\\AppCompatCache",
+            control_set
+        ))
+    }) else {
+        return Vec::new();
+    };
+    let Some(value) = hive.value(&key, "AppCompatCache") else {
+        return Vec::new();
+    };
+    shimcache_entries(&value.data)
+        .into_iter()
+        .enumerate()
+        .map(|(position, entry)| {
+            let mut artefact = RegistryArtefact::new(hive, "shimcache", &key, &entry.path);
+            artefact.path = Some(entry.path);
+            artefact.timestamp = format_filetime(entry.last_modified);
+            artefact
+                .details
+                .insert("position".to_string(), Value::from(position));
+            if let Some(executed) = entry.executed {
+                artefact
+                    .details
+                    .insert("executed".to_string(), Value::from(executed));
+            }
+            artefact
+        })
+        .collect()
+}
+
+/// Parses the `AppCompatCache` value of Windows 7 (32 and 64 bit), 8, 8.1, 10 and 11. Older
+/// formats are not supported and give no entries.
+fn shimcache_entries(data: &[u8]) -> Vec<ShimCacheEntry> {
+    let mut entries = Vec::new();
+    if le_u32(data, 0) == WIN7_SHIMCACHE_SIGNATURE {
+        let count = le_u32(data, 4) as usize;
+        // the path offset of a 64 bit entry is a u64, so the upper half of the first is padding
+        let x64 = le_u32(data, WIN7_SHIMCACHE_HEADER_SIZE + 4) == 0;
+        let entry_size = if x64 { 48 } else { 32 };
+        for index in 0..count {
+            // the count is read from the data, so it may claim more entries than the data holds
+            let entry = WIN7_SHIMCACHE_HEADER_SIZE + index * entry_size;
+            if entry + entry_size > data.len() {
+                break;
+            }
+            let length = le_u16(data, entry) as usize;
+            let (path_offset, last_modified, flags) = if x64 {
+                (
+                    le_u64(data, entry + 8) as usize,
+                    le_u64(data, entry + 16),
+                    le_u32(data, entry + 24),
+                )
+            } else {
+                (
+                    le_u32(data, entry + 4) as usize,
+                    le_u64(data, entry + 8),
+                    le_u32(data, entry + 16),
+                )
+            };
+            let Some(path) = data.get(path_offset..path_offset.saturating_add(length)) else {
+                break;
+            };
+            entries.push(ShimCacheEntry {
+                path: utf16_string(path),
+                last_modified,
+                executed: Some(flags & SHIMCACHE_EXECUTED != 0),
+            });
+        }
+        return entries;
+    }
+
+    // Windows 10 and 11 have a 48 or 52 byte header, 8 and 8.1 one of 128 bytes, their entries
+    // start with a signature followed by their size
+    let (mut offset, windows8) = match le_u32(data, 0) as usize {
+        0x30 | 0x34 => (le_u32(data, 0) as usize, false),
+        _ => (WIN8_SHIMCACHE_HEADER_SIZE, true),
+    };
+    while let Some(signature) = data.get(offset..offset + 4) {
+        if signature != b"10ts" && signature != b"00ts" {
+            break;
+        }
+        let entry_size = le_u32(data, offset + 8) as usize;
+        let Some(entry) = data.get(offset + 12..offset + 12 + entry_size) else {
+            break;
+        };
+        let length = le_u16(entry, 0) as usize;
+        let path = utf16_string(entry.get(2..2 + length).unwrap_or_default());
+        let mut position = 2 + length;
+        let mut executed = None;
+        if windows8 {
+            position += 2 + le_u16(entry, position) as usize;
+            executed = Some(le_u32(entry, position) & SHIMCACHE_EXECUTED != 0);
+            position += 8;
+        }
+        entries.push(ShimCacheEntry {
+            path,
+            last_modified: le_u64(entry, position),
+            executed,
+        });
+        offset += 12 + entry_size;
+    }
+    entries
+}
+
+/// AmCache keeps the SHA1 of a file's first 31.4 MB behind four zeros.
+fn amcache_sha1(value: Option<String>) -> Option<String> {
+    value.map(|sha1| sha1.strip_prefix("0000").unwrap_or(&sha1).to_string())
+}
+
+fn amcache(hive: &Hive) -> Vec<RegistryArtefact> {
+    let mut artefacts = Vec::new();
+    // Windows 10 1709 and later
+    if let Some(files) = hive.open("Root\\InventoryApplicationFile") {
+        for file in hive.subkeys(&files).unwrap_or_default() {
+            let string = |name: &str| hive.value(&file, name).and_then(|value| value.string());
+            let mut artefact = RegistryArtefact::new(hive, "amcache", &file, &file.name);
+            artefact.path = string("LowerCaseLongPath");
+            artefact.timestamp = file.last_written.clone();
+            if let Some(sha1) = amcache_sha1(string("FileId")) {
+                artefact
+                    .details
+                    .insert("sha1".to_string(), Value::from(sha1));
+            }
+            for (name, detail) in [
+                ("Name", "name"),
+                ("Publisher", "publisher"),
+                ("ProductName", "product"),
+                ("Version", "version"),
+                ("LinkDate", "link_date"),
+                ("ProgramId", "program_id"),
+            ] {
+                if let Some(value) = string(name).filter(|value| !value.is_empty()) {
+                    artefact
+                        .details
+                        .insert(detail.to_string(), Value::from(value));
+                }
+            }
+            if let Some(size) = hive.value(&file, "Size").and_then(|value| value.qword()) {
+                artefact
+                    .details
+                    .insert("size".to_string(), Value::from(size));
+            }
+            artefacts.push(artefact);
+        }
+    }
+
+    // Windows 8 and early Windows 10 name their values by number under each volume
+    if let Some(volumes) = hive.open("Root\\File") {
+        for volume in hive.subkeys(&volumes).unwrap_or_default() {
+            for file in hive.subkeys(&volume).unwrap_or_default() {
+                let value = |name: &str| hive.value(&file, name);
+                let mut artefact = RegistryArtefact::new(hive, "amcache", &file, &file.name);
+                artefact.path = value("15").and_then(|value| value.string());
+                artefact.timestamp = file.last_written.clone();
+                if let Some(sha1) = amcache_sha1(value("101").and_then(|value| value.string())) {
+                    artefact
+                        .details
+                        .insert("sha1".to_string(), Value::from(sha1));
+                }
+                if let Some(modified) = value("17").and_then(|value| value.qword()) {
+                    artefact.details.insert(
+                        "last_modified".to_string(),
+                        Value::from(format_filetime(modified)),
+                    );
+                }
+                for (name, detail) in [("0", "product"), ("1", "publisher")] {
+                    if let Some(value) = value(name).and_then(|value| value.string()) {
+                        artefact
+                            .details
+                            .insert(detail.to_string(), Value::from(value));
+                    }
+                }
+                if let Some(size) = value("6").and_then(|value| value.qword()) {
+                    artefact
+                        .details
+                        .insert("size".to_string(), Value::from(size));
+                }
+                artefacts.push(artefact);
+            }
+        }
+    }
+    artefacts
+}
+
+/// The Background and Desktop Activity Moderators record the last time each user ran a program,
+/// under the user's SID.
+fn bam(hive: &Hive) -> Vec<RegistryArtefact> {
+    let Some(control_set) = current_control_set(hive) else {
+        return Vec::new();
+    };
+    let mut artefacts = Vec::new();
+    for (artefact_name, path) in [
+        ("bam", "Services\\bam\\State\\UserSettings"),
+        ("bam", "Services\\bam\\UserSettings"),
+        ("dam", "Services\\dam\\State\\UserSettings"),
+        ("dam", "Services\\dam\\UserSettings"),
+    ] {
+        let Some(users) = hive.open(&format!("{0}\\{1}", control_set, path)) else {
+            continue;
+        };
+        for user in hive.subkeys(&users).unwrap_or_default() {
+            for value in hive.values(&user).unwrap_or_default() {
+                if value.data.len() < 8 || value.name == "Version" || value.name == "SequenceNumber"
+                {
+                    continue;
+                }
+                let mut artefact = RegistryArtefact::new(hive, artefact_name, &user, &value.name);
+                artefact.path = Some(value.name.clone());
+                artefact.timestamp = format_filetime(le_u64(&value.data, 0));
+                artefact
+                    .details
+                    .insert("sid".to_string(), Value::from(user.name.clone()));
+                artefacts.push(artefact);
+            }
+        }
+    }
+    artefacts
+}
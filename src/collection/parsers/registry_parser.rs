@@ -0,0 +1,527 @@
+use super::bytes::{le_u16, le_u32, le_u64};
+use crate::collection::metadata::format_filetime;
+use serde_json::Value;
+use std::{error::Error, io::Read};
+
+const REGF_SIGNATURE: &[u8; 4] = b"regf";
+const BASE_BLOCK_SIZE: usize = 4096;
+/// The base block copied into a transaction log is only its first sector.
+const LOG_BASE_BLOCK_SIZE: usize = 512;
+const CHECKSUM_OFFSET: usize = 508;
+const LOG_ENTRY_SIGNATURE: &[u8; 4] = b"HvLE";
+const DIRTY_VECTOR_SIGNATURE: &[u8; 4] = b"DIRT";
+const SECTOR_SIZE: usize = 512;
+/// The most data a value holds in one cell, larger values are split into `db` segments.
+const BIG_DATA_SEGMENT_SIZE: usize = 16344;
+const MARVIN32_SEED: u64 = 0x82EF4D887A4E55C5;
+/// How deep `ri` index roots may nest before the subkey list is treated as corrupt.
+const MAX_INDEX_DEPTH: usize = 8;
+
+const KEY_COMP_NAME: u16 = 0x0020;
+const VALUE_COMP_NAME: u16 = 0x0001;
+const DATA_IS_INLINE: u32 = 0x8000_0000;
+const NO_OFFSET: u32 = 0xFFFF_FFFF;
+
+pub const REG_SZ: u32 = 1;
+pub const REG_EXPAND_SZ: u32 = 2;
+pub const REG_BINARY: u32 = 3;
+pub const REG_DWORD: u32 = 4;
+pub const REG_DWORD_BIG_ENDIAN: u32 = 5;
+pub const REG_LINK: u32 = 6;
+pub const REG_MULTI_SZ: u32 = 7;
+pub const REG_QWORD: u32 = 11;
+
+/// Decodes a UTF-16LE string up to its first NUL.
+pub fn utf16_string(buf: &[u8]) -> String {
+    let units: Vec<u16> = buf
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|unit| *unit != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Names flagged as compressed are stored one byte per character, as Latin-1.
+fn name(buf: &[u8], compressed: bool) -> String {
+    if compressed {
+        buf.iter().map(|byte| *byte as char).collect()
+    } else {
+        utf16_string(buf)
+    }
+}
+
+/// The checksum of a base block, the XOR of its first 127 double words.
+fn base_block_checksum(base_block: &[u8]) -> u32 {
+    let checksum = base_block[..CHECKSUM_OFFSET]
+        .chunks_exact(4)
+        .fold(0, |checksum, word| {
+            checksum ^ u32::from_le_bytes(word.try_into().unwrap_or_default())
+        });
+    match checksum {
+        0 => 1,
+        0xFFFF_FFFF => 0xFFFF_FFFE,
+        checksum => checksum,
+    }
+}
+
+/// The Marvin32 hash that protects the entries of a transaction log.
+fn marvin32(data: &[u8]) -> u64 {
+    let mut lo = MARVIN32_SEED as u32;
+    let mut hi = (MARVIN32_SEED >> 32) as u32;
+    let block = |lo: &mut u32, hi: &mut u32| {
+        *hi ^= *lo;
+        *lo = lo.rotate_left(20);
+        *lo = lo.wrapping_add(*hi);
+        *hi = hi.rotate_left(9);
+        *hi ^= *lo;
+        *lo = lo.rotate_left(27);
+        *lo = lo.wrapping_add(*hi);
+        *hi = hi.rotate_left(19);
+    };
+    let mut words = data.chunks_exact(4);
+    for word in words.by_ref() {
+        lo = lo.wrapping_add(u32::from_le_bytes(word.try_into().unwrap_or_default()));
+        block(&mut lo, &mut hi);
+    }
+    let tail = words.remainder();
+    let last = match tail.len() {
+        0 => 0x80,
+        1 => 0x8000 | tail[0] as u32,
+        2 => 0x80_0000 | le_u16(tail, 0) as u32,
+        _ => 0x8000_0000 | le_u16(tail, 0) as u32 | (tail[2] as u32) << 16,
+    };
+    lo = lo.wrapping_add(last);
+    block(&mut lo, &mut hi);
+    block(&mut lo, &mut hi);
+    (hi as u64) << 32 | lo as u64
+}
+
+/// A key of a hive, read from its `nk` record.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Key {
+    pub name: String,
+    /// The path of the key below the root key, separated by backslashes.
+    pub path: String,
+    pub last_written: Option<String>,
+    subkey_count: u32,
+    subkeys: u32,
+    value_count: u32,
+    values: u32,
+}
+
+/// A value of a key, read from its `vk` record.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct RegistryValue {
+    /// Empty for the default value of a key.
+    pub name: String,
+    pub value_type: u32,
+    pub data: Vec<u8>,
+}
+
+impl RegistryValue {
+    /// The data of a `REG_SZ`, `REG_EXPAND_SZ` or `REG_LINK` value.
+    pub fn string(&self) -> Option<String> {
+        match self.value_type {
+            REG_SZ | REG_EXPAND_SZ | REG_LINK => Some(utf16_string(&self.data)),
+            _ => None,
+        }
+    }
+
+    /// The data of a `REG_DWORD` value, or of a small binary value holding one.
+    pub fn dword(&self) -> Option<u32> {
+        match self.value_type {
+            REG_DWORD | REG_BINARY if self.data.len() >= 4 => Some(le_u32(&self.data, 0)),
+            REG_DWORD_BIG_ENDIAN if self.data.len() >= 4 => {
+                Some(u32::from_be_bytes(self.data[..4].try_into().ok()?))
+            }
+            _ => None,
+        }
+    }
+
+    /// The data of a `REG_QWORD` value, or of a `REG_DWORD` one widened.
+    pub fn qword(&self) -> Option<u64> {
+        match self.value_type {
+            REG_QWORD | REG_BINARY if self.data.len() >= 8 => Some(le_u64(&self.data, 0)),
+            _ => self.dword().map(u64::from),
+        }
+    }
+
+    /// The data decoded by its type, strings and numbers as themselves, `REG_MULTI_SZ` as a list
+    /// and anything else as hex.
+    pub fn json(&self) -> Value {
+        match self.value_type {
+            REG_SZ | REG_EXPAND_SZ | REG_LINK => Value::from(self.string()),
+            REG_MULTI_SZ => Value::from(
+                self.data
+                    .chunks_exact(2)
+                    .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                    .collect::<Vec<u16>>()
+                    .split(|unit| *unit == 0)
+                    .filter(|string| !string.is_empty())
+                    .map(String::from_utf16_lossy)
+                    .collect::<Vec<String>>(),
+            ),
+            REG_DWORD | REG_DWORD_BIG_ENDIAN => Value::from(self.dword()),
+            REG_QWORD => Value::from(self.qword()),
+            _ => Value::from(hex::encode(&self.data)),
+        }
+    }
+}
+
+/// A registry hive (`regf`) read into memory. Hives that were not written back cleanly can be
+/// brought up to date by replaying their `.LOG1` and `.LOG2` transaction logs with `recover`.
+pub struct Hive {
+    data: Vec<u8>,
+    source: String,
+}
+
+impl Hive {
+    pub fn from_reader<R>(mut reader: R, source: &str) -> Result<Self, Box<dyn Error>>
+    where
+        R: Read,
+    {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        if data.len() < BASE_BLOCK_SIZE || &data[0..4] != REGF_SIGNATURE {
+            return Err(format!("{} is not a registry hive", source).into());
+        }
+        Ok(Hive {
+            data,
+            source: source.to_string(),
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether the hive was being written to when it was copied, so the changes held in its
+    /// transaction logs are missing from it.
+    pub fn is_dirty(&self) -> bool {
+        le_u32(&self.data, 4) != le_u32(&self.data, 8)
+            || base_block_checksum(&self.data) != le_u32(&self.data, CHECKSUM_OFFSET)
+    }
+
+    /// Replays the transaction logs of a dirty hive, returning the number of log entries (or
+    /// dirty sectors of an old format log) that were applied. Entries of the newer format
+    /// (Windows 8.1 and later) are only applied when their hashes are intact and their sequence
+    /// numbers follow on from the hive's, so stale or torn logs are ignored.
+    pub fn recover(&mut self, logs: &[Vec<u8>]) -> Result<usize, Box<dyn Error>> {
+        if !self.is_dirty() {
+            return Ok(0);
+        }
+        let mut entries = Vec::new();
+        let mut old_format = Vec::new();
+        for log in logs {
+            if log.len() < LOG_BASE_BLOCK_SIZE + 4 || &log[0..4] != REGF_SIGNATURE {
+                continue;
+            }
+            match &log[LOG_BASE_BLOCK_SIZE..LOG_BASE_BLOCK_SIZE + 4] {
+                signature if signature == LOG_ENTRY_SIGNATURE => entries.extend(log_entries(log)),
+                signature if signature == DIRTY_VECTOR_SIGNATURE => old_format.push(log),
+                _ => {}
+            }
+        }
+
+        let mut applied = 0;
+        if !entries.is_empty() {
+            entries.sort_by_key(|entry| entry.sequence);
+            entries.dedup_by_key(|entry| entry.sequence);
+            let first = le_u32(&self.data, 8);
+            let mut sequence = first;
+            let mut last = None;
+            for entry in entries.iter().filter(|entry| entry.sequence >= first) {
+                if applied > 0 && entry.sequence != sequence {
+                    break;
+                }
+                self.resize(entry.hive_bins_size);
+                for (offset, page) in &entry.pages {
+                    let start = BASE_BLOCK_SIZE + *offset as usize;
+                    if let Some(target) = self.data.get_mut(start..start + page.len()) {
+                        target.copy_from_slice(page);
+                    }
+                }
+                sequence = entry.sequence.wrapping_add(1);
+                applied += 1;
+                last = Some(entry);
+            }
+            if let Some(last) = last {
+                self.clean(&last.base_block, sequence, Some(last.hive_bins_size));
+            }
+            return Ok(applied);
+        }
+
+        // old format logs hold a bitmap of the dirty sectors followed by the sectors themselves,
+        // the log written last is replayed last
+        old_format.sort_by_key(|log| le_u32(log, 4));
+        for log in old_format {
+            let hive_bins_size = le_u32(log, 40);
+            let bitmap_start = LOG_BASE_BLOCK_SIZE + 4;
+            let bitmap_size = (hive_bins_size as usize / SECTOR_SIZE).div_ceil(8);
+            let Some(bitmap) = log.get(bitmap_start..bitmap_start + bitmap_size) else {
+                continue;
+            };
+            self.resize(hive_bins_size);
+            let mut sector_offset =
+                (bitmap_start + bitmap_size).div_ceil(SECTOR_SIZE) * SECTOR_SIZE;
+            for sector in 0..bitmap_size * 8 {
+                if bitmap[sector / 8] & (1 << (sector % 8)) == 0 {
+                    continue;
+                }
+                let Some(dirty) = log.get(sector_offset..sector_offset + SECTOR_SIZE) else {
+                    break;
+                };
+                let start = BASE_BLOCK_SIZE + sector * SECTOR_SIZE;
+                if let Some(target) = self.data.get_mut(start..start + SECTOR_SIZE) {
+                    target.copy_from_slice(dirty);
+                    applied += 1;
+                }
+                sector_offset += SECTOR_SIZE;
+            }
+            self.clean(
+                &log[..LOG_BASE_BLOCK_SIZE],
+                le_u32(log, 4),
+                Some(hive_bins_size),
+            );
+        }
+        Ok(applied)
+    }
+
+    /// Grows or shrinks the hive bins to the size a log entry gives them.
+    fn resize(&mut self, hive_bins_size: u32) {
+        self.data
+            .resize(BASE_BLOCK_SIZE + hive_bins_size as usize, 0);
+    }
+
+    /// Marks the hive as written back after a replay, taking the base block from the log when
+    /// the hive's own is damaged.
+    fn clean(&mut self, log_base_block: &[u8], sequence: u32, hive_bins_size: Option<u32>) {
+        if base_block_checksum(&self.data) != le_u32(&self.data, CHECKSUM_OFFSET)
+            && base_block_checksum(log_base_block) == le_u32(log_base_block, CHECKSUM_OFFSET)
+        {
+            self.data[..LOG_BASE_BLOCK_SIZE].copy_from_slice(log_base_block);
+        }
+        self.data[4..8].copy_from_slice(&sequence.to_le_bytes());
+        self.data[8..12].copy_from_slice(&sequence.to_le_bytes());
+        if let Some(hive_bins_size) = hive_bins_size {
+            self.data[40..44].copy_from_slice(&hive_bins_size.to_le_bytes());
+        }
+        let checksum = base_block_checksum(&self.data);
+        self.data[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    /// Returns the data of the cell at an offset relative to the first hive bin, without its
+    /// size.
+    fn cell(&self, offset: u32) -> Result<&[u8], Box<dyn Error>> {
+        let start = BASE_BLOCK_SIZE + offset as usize;
+        let size = le_u32(&self.data, start) as i32;
+        let size = size.unsigned_abs() as usize;
+        self.data
+            .get(start + 4..start + size.max(4))
+            .filter(|_| offset != NO_OFFSET)
+            .ok_or_else(|| format!("Cell offset {} is outside {}", offset, self.source).into())
+    }
+
+    pub fn root(&self) -> Result<Key, Box<dyn Error>> {
+        let mut root = self.read_key(le_u32(&self.data, 36), "")?;
+        root.path = String::new();
+        Ok(root)
+    }
+
+    /// Opens a key by its path below the root key, e.g. `Microsoft\Windows\CurrentVersion\Run`.
+    /// Names are matched case-insensitively as Windows does.
+    pub fn open(&self, path: &str) -> Option<Key> {
+        let mut key = self.root().ok()?;
+        for name in path.split('\\').filter(|name| !name.is_empty()) {
+            key = self
+                .subkeys(&key)
+                .ok()?
+                .into_iter()
+                .find(|subkey| subkey.name.eq_ignore_ascii_case(name))?;
+        }
+        Some(key)
+    }
+
+    fn read_key(&self, offset: u32, parent_path: &str) -> Result<Key, Box<dyn Error>> {
+        let cell = self.cell(offset)?;
+        if cell.len() < 76 || &cell[0..2] != b"nk" {
+            return Err(format!("There is no key at offset {} in {}", offset, self.source).into());
+        }
+        let name_length = le_u16(cell, 72) as usize;
+        let name = name(
+            cell.get(76..76 + name_length).unwrap_or_default(),
+            le_u16(cell, 2) & KEY_COMP_NAME != 0,
+        );
+        Ok(Key {
+            path: if parent_path.is_empty() {
+                name.clone()
+            } else {
+                format!("{0}\\{1}", parent_path, name)
+            },
+            name,
+            last_written: format_filetime(le_u64(cell, 4)),
+            subkey_count: le_u32(cell, 20),
+            subkeys: le_u32(cell, 28),
+            value_count: le_u32(cell, 36),
+            values: le_u32(cell, 40),
+        })
+    }
+
+    pub fn subkeys(&self, key: &Key) -> Result<Vec<Key>, Box<dyn Error>> {
+        if key.subkey_count == 0 || key.subkeys == NO_OFFSET {
+            return Ok(Vec::new());
+        }
+        let mut offsets = Vec::new();
+        self.subkey_offsets(key.subkeys, 0, &mut offsets)?;
+        offsets
+            .into_iter()
+            .map(|offset| self.read_key(offset, &key.path))
+            .collect()
+    }
+
+    /// Collects the key offsets of an `lf`, `lh` or `li` subkey list, or of every list an `ri`
+    /// index root points to.
+    fn subkey_offsets(
+        &self,
+        offset: u32,
+        depth: usize,
+        offsets: &mut Vec<u32>,
+    ) -> Result<(), Box<dyn Error>> {
+        if depth > MAX_INDEX_DEPTH {
+            return Err(format!("The subkey lists of {} nest too deeply", self.source).into());
+        }
+        let cell = self.cell(offset)?;
+        let count = le_u16(cell, 2) as usize;
+        match cell.get(0..2) {
+            Some(b"lf") | Some(b"lh") => {
+                offsets.extend((0..count).map(|index| le_u32(cell, 4 + index * 8)))
+            }
+            Some(b"li") => offsets.extend((0..count).map(|index| le_u32(cell, 4 + index * 4))),
+            Some(b"ri") => {
+                for index in 0..count {
+                    self.subkey_offsets(le_u32(cell, 4 + index * 4), depth + 1, offsets)?;
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "There is no subkey list at offset {} in {}",
+                    offset, self.source
+                )
+                .into())
+            }
+        }
+        Ok(())
+    }
+
+    pub fn values(&self, key: &Key) -> Result<Vec<RegistryValue>, Box<dyn Error>> {
+        if key.value_count == 0 || key.values == NO_OFFSET {
+            return Ok(Vec::new());
+        }
+        let list = self.cell(key.values)?;
+        (0..key.value_count as usize)
+            .map(|index| self.read_value(le_u32(list, index * 4)))
+            .collect()
+    }
+
+    /// Returns a value of a key by its name, matched case-insensitively.
+    pub fn value(&self, key: &Key, name: &str) -> Option<RegistryValue> {
+        self.values(key)
+            .ok()?
+            .into_iter()
+            .find(|value| value.name.eq_ignore_ascii_case(name))
+    }
+
+    fn read_value(&self, offset: u32) -> Result<RegistryValue, Box<dyn Error>> {
+        let cell = self.cell(offset)?;
+        if cell.len() < 20 || &cell[0..2] != b"vk" {
+            return Err(
+                format!("There is no value at offset {} in {}", offset, self.source).into(),
+            );
+        }
+        let name_length = le_u16(cell, 2) as usize;
+        let size = le_u32(cell, 4);
+        let data = if size & DATA_IS_INLINE != 0 {
+            let size = (size & !DATA_IS_INLINE).min(4) as usize;
+            cell[8..8 + size].to_vec()
+        } else if size == 0 {
+            Vec::new()
+        } else {
+            self.value_data(le_u32(cell, 8), size as usize)?
+        };
+        Ok(RegistryValue {
+            name: name(
+                cell.get(20..20 + name_length).unwrap_or_default(),
+                le_u16(cell, 16) & VALUE_COMP_NAME != 0,
+            ),
+            value_type: le_u32(cell, 12),
+            data,
+        })
+    }
+
+    /// Reads value data from its cell, or from the segments a `db` record splits it into.
+    fn value_data(&self, offset: u32, size: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        let cell = self.cell(offset)?;
+        if size > BIG_DATA_SEGMENT_SIZE && cell.starts_with(b"db") {
+            let list = self.cell(le_u32(cell, 4))?;
+            // the size is only a claim, the segments cannot hold more than the hive does
+            let mut data = Vec::with_capacity(size.min(self.data.len()));
+            for index in 0..le_u16(cell, 2) as usize {
+                let segment = self.cell(le_u32(list, index * 4))?;
+                let remaining = size - data.len();
+                data.extend_from_slice(
+                    &segment[..segment.len().min(remaining).min(BIG_DATA_SEGMENT_SIZE)],
+                );
+            }
+            return Ok(data);
+        }
+        Ok(cell[..cell.len().min(size)].to_vec())
+    }
+}
+
+/// A log entry of a new format transaction log, with the pages of the hive it rewrites.
+struct LogEntry {
+    sequence: u32,
+    hive_bins_size: u32,
+    pages: Vec<(u32, Vec<u8>)>,
+    base_block: Vec<u8>,
+}
+
+/// Reads the entries of a new format transaction log up to the first one that is damaged.
+fn log_entries(log: &[u8]) -> Vec<LogEntry> {
+    let mut entries = Vec::new();
+    let mut offset = LOG_BASE_BLOCK_SIZE;
+    while let Some(header) = log.get(offset..offset + 40) {
+        let size = le_u32(header, 4) as usize;
+        if &header[0..4] != LOG_ENTRY_SIGNATURE || size < 40 || !size.is_multiple_of(SECTOR_SIZE) {
+            break;
+        }
+        let Some(entry) = log.get(offset..offset + size) else {
+            break;
+        };
+        if marvin32(&entry[0..32]) != le_u64(entry, 32)
+            || marvin32(&entry[40..]) != le_u64(entry, 24)
+        {
+            break;
+        }
+        let page_count = le_u32(entry, 20) as usize;
+        let mut pages = Vec::with_capacity(page_count.min(entry.len() / 8));
+        let mut page_offset = 40 + page_count * 8;
+        for index in 0..page_count {
+            let reference = 40 + index * 8;
+            let page_size = le_u32(entry, reference + 4) as usize;
+            let Some(page) = entry.get(page_offset..page_offset + page_size) else {
+                break;
+            };
+            pages.push((le_u32(entry, reference), page.to_vec()));
+            page_offset += page_size;
+        }
+        entries.push(LogEntry {
+            sequence: le_u32(entry, 12),
+            hive_bins_size: le_u32(entry, 16),
+            pages,
+            base_block: log[..LOG_BASE_BLOCK_SIZE].to_vec(),
+        });
+        offset += size;
+    }
+    entries
+}
@@ -0,0 +1,308 @@
+use super::bytes::{le_u16, le_u32, le_u64};
+use super::mft_parser::{attribute_names, parse_mft, volume, MftEntry};
+use crate::collection::archive::CollectionArchive;
+use crate::collection::metadata::format_filetime;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{self, Read},
+};
+
+const BUFFER_SIZE: usize = 1024 * 1024;
+/// Records are aligned to 8 bytes and never larger than a page of the journal.
+const RECORD_ALIGNMENT: u64 = 8;
+const MAX_RECORD_SIZE: usize = 64 * 1024;
+const V2_HEADER_SIZE: usize = 60;
+const V3_HEADER_SIZE: usize = 76;
+const V4_HEADER_SIZE: usize = 64;
+const RECORD_NUMBER_MASK: u64 = 0x0000_FFFF_FFFF_FFFF;
+
+/// The names of the reasons a record was written, by bit.
+const REASONS: [(u32, &str); 23] = [
+    (0x00000001, "data_overwrite"),
+    (0x00000002, "data_extend"),
+    (0x00000004, "data_truncation"),
+    (0x00000010, "named_data_overwrite"),
+    (0x00000020, "named_data_extend"),
+    (0x00000040, "named_data_truncation"),
+    (0x00000100, "file_create"),
+    (0x00000200, "file_delete"),
+    (0x00000400, "ea_change"),
+    (0x00000800, "security_change"),
+    (0x00001000, "rename_old_name"),
+    (0x00002000, "rename_new_name"),
+    (0x00004000, "indexable_change"),
+    (0x00008000, "basic_info_change"),
+    (0x00010000, "hard_link_change"),
+    (0x00020000, "compression_change"),
+    (0x00040000, "encryption_change"),
+    (0x00080000, "object_id_change"),
+    (0x00100000, "reparse_point_change"),
+    (0x00200000, "stream_change"),
+    (0x00400000, "transacted_change"),
+    (0x00800000, "integrity_change"),
+    (0x80000000, "close"),
+];
+
+/// A change to a file recorded in the `$UsnJrnl:$J` stream. Version 4 records only track the
+/// ranges of a file that changed, so they have no timestamp and their name is taken from `$MFT`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct UsnEntry {
+    pub usn: i64,
+    pub timestamp: Option<String>,
+    pub version: u16,
+    pub name: String,
+    /// The full path of the file, only known when the parent directory is found in `$MFT`.
+    pub path: Option<String>,
+    pub reasons: Vec<String>,
+    pub file_record: u64,
+    pub file_sequence: u16,
+    pub parent_record: u64,
+    pub parent_sequence: u16,
+    pub attributes: Vec<String>,
+}
+
+/// Reads the records of a `$J` stream one at a time. The stream is mostly zeros when its sparse
+/// ranges were kept, these are skipped along with anything that is not a valid record.
+pub struct UsnJournal<R>
+where
+    R: Read,
+{
+    reader: R,
+    buf: Vec<u8>,
+    start: usize,
+    end: usize,
+    /// Offset in the stream of `buf[start]`, which records are aligned against.
+    offset: u64,
+}
+
+impl<R> UsnJournal<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R) -> Self {
+        UsnJournal {
+            reader,
+            buf: vec![0u8; BUFFER_SIZE],
+            start: 0,
+            end: 0,
+            offset: 0,
+        }
+    }
+
+    /// Makes at least `needed` bytes available from `start`, returning `false` at the end of the
+    /// stream.
+    fn fill(&mut self, needed: usize) -> io::Result<bool> {
+        if self.end - self.start >= needed {
+            return Ok(true);
+        }
+        self.buf.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+        while self.end < needed {
+            match self.reader.read(&mut self.buf[self.end..]) {
+                Ok(0) => return Ok(false),
+                Ok(read) => self.end += read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    fn advance(&mut self, length: usize) {
+        self.start += length;
+        self.offset += length as u64;
+    }
+
+    /// Skips to the last aligned offset before the next non-zero byte.
+    fn skip_zeros(&mut self) {
+        let zeros = self.buf[self.start..self.end]
+            .iter()
+            .position(|byte| *byte != 0)
+            .unwrap_or(self.end - self.start) as u64;
+        let skip = ((self.offset + zeros) / RECORD_ALIGNMENT * RECORD_ALIGNMENT - self.offset)
+            .max(RECORD_ALIGNMENT);
+        self.advance(skip as usize);
+    }
+}
+
+impl<R> Iterator for UsnJournal<R>
+where
+    R: Read,
+{
+    type Item = Result<UsnEntry, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.fill(RECORD_ALIGNMENT as usize) {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e.into())),
+            }
+            let length = le_u32(&self.buf, self.start) as usize;
+            if length == 0 {
+                self.skip_zeros();
+                continue;
+            }
+            let header_size = match le_u16(&self.buf, self.start + 4) {
+                2 => V2_HEADER_SIZE,
+                3 => V3_HEADER_SIZE,
+                4 => V4_HEADER_SIZE,
+                _ => 0,
+            };
+            if header_size == 0 || length < header_size || length > MAX_RECORD_SIZE {
+                self.advance(RECORD_ALIGNMENT as usize);
+                continue;
+            }
+            match self.fill(length) {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e.into())),
+            }
+            let record = parse_record(&self.buf[self.start..self.start + length]);
+            let aligned = (length as u64).div_ceil(RECORD_ALIGNMENT) * RECORD_ALIGNMENT;
+            match self.fill(aligned as usize) {
+                Ok(true) => self.advance(aligned as usize),
+                _ => self.advance(length),
+            }
+            if let Some(record) = record {
+                return Some(Ok(record));
+            }
+        }
+    }
+}
+
+/// Parses a USN_RECORD_V2, V3 or V4. The 128 bit file references of V3 and V4 hold an NTFS
+/// file reference in their lower 64 bits.
+fn parse_record(record: &[u8]) -> Option<UsnEntry> {
+    let version = le_u16(record, 4);
+    let (usn, timestamp, reason, attributes, name) = match version {
+        2 => (
+            le_u64(record, 24),
+            le_u64(record, 32),
+            le_u32(record, 40),
+            le_u32(record, 52),
+            file_name(record, le_u16(record, 58), le_u16(record, 56))?,
+        ),
+        3 => (
+            le_u64(record, 40),
+            le_u64(record, 48),
+            le_u32(record, 56),
+            le_u32(record, 68),
+            file_name(record, le_u16(record, 74), le_u16(record, 72))?,
+        ),
+        _ => (le_u64(record, 40), 0, le_u32(record, 48), 0, String::new()),
+    };
+    let (file_reference, parent_reference) = match version {
+        2 => (le_u64(record, 8), le_u64(record, 16)),
+        _ => (le_u64(record, 8), le_u64(record, 24)),
+    };
+
+    Some(UsnEntry {
+        usn: usn as i64,
+        timestamp: format_filetime(timestamp),
+        version,
+        name,
+        path: None,
+        reasons: REASONS
+            .iter()
+            .filter(|(bit, _)| reason & bit != 0)
+            .map(|(_, name)| name.to_string())
+            .collect(),
+        file_record: file_reference & RECORD_NUMBER_MASK,
+        file_sequence: (file_reference >> 48) as u16,
+        parent_record: parent_reference & RECORD_NUMBER_MASK,
+        parent_sequence: (parent_reference >> 48) as u16,
+        attributes: attribute_names(attributes),
+    })
+}
+
+fn file_name(record: &[u8], offset: u16, length: u16) -> Option<String> {
+    let name = record.get(offset as usize..offset as usize + length as usize)?;
+    let units: Vec<u16> = name
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    Some(String::from_utf16_lossy(&units))
+}
+
+/// Resolves the files and parent directories of journal records against the records of a
+/// `$MFT` from the same volume. A record whose sequence number no longer matches has been
+/// reused by another file since and is not used.
+#[derive(Default)]
+pub struct MftPaths {
+    /// Prefixed to every path, e.g. `C:\`.
+    volume: String,
+    /// Sequence number, name and path of every record.
+    records: HashMap<u64, (u16, String, String)>,
+}
+
+impl MftPaths {
+    pub fn new(entries: Vec<MftEntry>, volume: &str) -> Self {
+        MftPaths {
+            volume: volume.to_string(),
+            records: entries
+                .into_iter()
+                .map(|entry| (entry.record, (entry.sequence, entry.name, entry.path)))
+                .collect(),
+        }
+    }
+
+    pub fn resolve(&self, entry: &mut UsnEntry) {
+        if entry.name.is_empty() {
+            if let Some((sequence, name, path)) = self.records.get(&entry.file_record) {
+                if *sequence == entry.file_sequence {
+                    entry.name = name.clone();
+                    entry.path = Some(format!("{0}{1}", self.volume, path));
+                }
+            }
+            return;
+        }
+        if let Some((sequence, _, path)) = self.records.get(&entry.parent_record) {
+            if *sequence == entry.parent_sequence {
+                entry.path = Some(match path.as_str() {
+                    "" => format!("{0}{1}", self.volume, entry.name),
+                    path => format!("{0}{1}\\{2}", self.volume, path, entry.name),
+                });
+            }
+        }
+    }
+}
+
+/// Calls `found` with every record of every `$UsnJrnl:$J` in a collection. Paths are resolved
+/// against `mft` when it is given, otherwise against the `$MFT` collected from the same volume,
+/// records of a volume without one keep only their names.
+pub fn parse_collection_usn<F>(
+    collection: &mut CollectionArchive,
+    mft: Option<Vec<MftEntry>>,
+    mut found: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(UsnEntry) -> Result<(), Box<dyn Error>>,
+{
+    let mut volumes = HashMap::new();
+    if mft.is_none() && collection.has_artefact("$MFT") {
+        collection.for_each_artefact("$MFT", |path, reader| {
+            let entries =
+                parse_mft(reader).map_err(|e| format!("Could not parse {0}: {1}", path, e))?;
+            volumes.insert(volume(path).to_string(), entries);
+            Ok(())
+        })?;
+    }
+    collection.for_each_artefact("$UsnJrnl:$J", |path, reader| {
+        let volume = volume(path);
+        let paths = match (&mft, volumes.remove(volume)) {
+            (Some(entries), _) => MftPaths::new(entries.clone(), volume),
+            (None, Some(entries)) => MftPaths::new(entries, volume),
+            (None, None) => MftPaths::default(),
+        };
+        for entry in UsnJournal::new(reader) {
+            let mut entry = entry?;
+            paths.resolve(&mut entry);
+            found(entry)?;
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,284 @@
+use crate::collection::parsers::bytes::{le_u32, le_u64};
+use flate2::read::ZlibDecoder;
+use std::{
+    cmp,
+    error::Error,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+pub const EWF_SIGNATURE: &[u8; 8] = b"EVF\x09\x0d\x0a\xff\x00";
+const FILE_HEADER_SIZE: u64 = 13;
+const SECTION_DESCRIPTOR_SIZE: u64 = 76;
+const TABLE_HEADER_SIZE: u64 = 24;
+const COMPRESSED_FLAG: u32 = 0x80000000;
+// the volume section of SMART (.s01) images is shorter and has a 32 bit sector count
+const SMART_VOLUME_SIZE: u64 = 94;
+
+fn read_at(file: &mut File, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0u8; length];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// A `table` section, listing where each chunk stored in a segment file starts.
+struct ChunkTable {
+    segment: usize,
+    first_chunk: u64,
+    entry_count: u64,
+    base_offset: u64,
+    entries_offset: u64,
+    /// Where the data of the last chunk in the table ends.
+    data_end: u64,
+}
+
+/// `EwfReader` reads the media stored in an Expert Witness (EnCase `.E01`) image, across all of
+/// its segment files, as a raw disk. Chunk tables are only read when a chunk they cover is.
+pub struct EwfReader {
+    segments: Vec<File>,
+    tables: Vec<ChunkTable>,
+    chunk_size: u64,
+    size: u64,
+    position: u64,
+    table_cache: Option<(usize, Vec<u32>)>,
+    chunk_cache: Option<(u64, Vec<u8>)>,
+}
+
+/// Returns the name of segment file `number`, E01 to E99 are followed by EAA to EZZ, then FAA
+/// and so on.
+fn segment_path(first_segment: &Path, number: usize) -> Option<PathBuf> {
+    let extension = first_segment.extension()?.to_str()?;
+    let first = extension.chars().next()?;
+    let extension = if number <= 99 {
+        format!("{0}{1:02}", first, number)
+    } else {
+        let index = number - 100;
+        let base = if first.is_ascii_lowercase() {
+            b'a'
+        } else {
+            b'A'
+        };
+        let first = (first as u8).checked_add((index / 676) as u8)? as char;
+        if !first.is_ascii_alphabetic() {
+            return None;
+        }
+        format!(
+            "{0}{1}{2}",
+            first,
+            (base + (index / 26 % 26) as u8) as char,
+            (base + (index % 26) as u8) as char
+        )
+    };
+    Some(first_segment.with_extension(extension))
+}
+
+impl EwfReader {
+    pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut reader = EwfReader {
+            segments: Vec::new(),
+            tables: Vec::new(),
+            chunk_size: 0,
+            size: 0,
+            position: 0,
+            table_cache: None,
+            chunk_cache: None,
+        };
+
+        let first_segment = PathBuf::from(path);
+        let mut path = first_segment.clone();
+        loop {
+            let mut segment = File::open(&path)?;
+            let header = read_at(&mut segment, 0, FILE_HEADER_SIZE as usize)?;
+            if &header[0..8] != EWF_SIGNATURE {
+                return Err(format!("{} is not an EWF segment", path.display()).into());
+            }
+            let done = reader.read_sections(&mut segment)?;
+            reader.segments.push(segment);
+            if done {
+                break;
+            }
+            path = segment_path(&first_segment, reader.segments.len() + 1)
+                .ok_or("Too many EWF segment files")?;
+            if !path.exists() {
+                return Err(format!("EWF segment {} is missing", path.display()).into());
+            }
+        }
+
+        if reader.chunk_size == 0 {
+            return Err("EWF image does not have a volume section".into());
+        }
+        Ok(reader)
+    }
+
+    /// Reads the section descriptors of a segment file, returns whether it is the last segment.
+    fn read_sections(&mut self, segment: &mut File) -> Result<bool, Box<dyn Error>> {
+        let segment_index = self.segments.len();
+        let mut offset = FILE_HEADER_SIZE;
+        let mut sectors_end: Option<u64> = None;
+
+        loop {
+            let descriptor = read_at(segment, offset, SECTION_DESCRIPTOR_SIZE as usize)?;
+            let section_type = String::from_utf8_lossy(&descriptor[0..16])
+                .trim_end_matches('\0')
+                .to_string();
+            let next_offset = le_u64(&descriptor, 16);
+            let section_size = le_u64(&descriptor, 24);
+            let data_offset = offset + SECTION_DESCRIPTOR_SIZE;
+
+            match section_type.as_str() {
+                "volume" | "disk" if self.chunk_size == 0 => {
+                    let volume = read_at(segment, data_offset, 24)?;
+                    let sectors_per_chunk = le_u32(&volume, 8) as u64;
+                    let bytes_per_sector = le_u32(&volume, 12) as u64;
+                    let sector_count =
+                        if section_size == SMART_VOLUME_SIZE + SECTION_DESCRIPTOR_SIZE {
+                            le_u32(&volume, 16) as u64
+                        } else {
+                            le_u64(&volume, 16)
+                        };
+                    self.chunk_size = sectors_per_chunk * bytes_per_sector;
+                    self.size = sector_count * bytes_per_sector;
+                }
+                "sectors" => sectors_end = Some(offset + section_size),
+                "table" => {
+                    let header = read_at(segment, data_offset, TABLE_HEADER_SIZE as usize)?;
+                    let first_chunk = self
+                        .tables
+                        .last()
+                        .map(|table| table.first_chunk + table.entry_count)
+                        .unwrap_or(0);
+                    self.tables.push(ChunkTable {
+                        segment: segment_index,
+                        first_chunk,
+                        entry_count: le_u32(&header, 0) as u64,
+                        base_offset: le_u64(&header, 8),
+                        entries_offset: data_offset + TABLE_HEADER_SIZE,
+                        // chunks are stored in the sectors section that precedes the table
+                        data_end: sectors_end.take().unwrap_or(offset),
+                    });
+                }
+                "done" => return Ok(true),
+                "next" => return Ok(false),
+                _ => {}
+            }
+
+            if next_offset <= offset {
+                return Ok(false);
+            }
+            offset = next_offset;
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read_table(&mut self, table_index: usize) -> io::Result<&Vec<u32>> {
+        if self.table_cache.as_ref().map(|(index, _)| *index) != Some(table_index) {
+            let table = &self.tables[table_index];
+            let segment = &mut self.segments[table.segment];
+            let buf = read_at(
+                segment,
+                table.entries_offset,
+                table.entry_count as usize * 4,
+            )?;
+            let entries = buf.chunks_exact(4).map(|entry| le_u32(entry, 0)).collect();
+            self.table_cache = Some((table_index, entries));
+        }
+        Ok(&self.table_cache.as_ref().unwrap().1)
+    }
+
+    fn read_chunk(&mut self, chunk: u64) -> io::Result<&Vec<u8>> {
+        if self.chunk_cache.as_ref().map(|(index, _)| *index) == Some(chunk) {
+            return Ok(&self.chunk_cache.as_ref().unwrap().1);
+        }
+
+        let table_index = self
+            .tables
+            .partition_point(|table| table.first_chunk + table.entry_count <= chunk);
+        if table_index >= self.tables.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("Chunk {} is not in any EWF table", chunk),
+            ));
+        }
+        let (first_chunk, base_offset, data_end, segment) = {
+            let table = &self.tables[table_index];
+            (
+                table.first_chunk,
+                table.base_offset,
+                table.data_end,
+                table.segment,
+            )
+        };
+        let entries = self.read_table(table_index)?;
+        let index = (chunk - first_chunk) as usize;
+        let entry = entries[index];
+        let start = base_offset + (entry & !COMPRESSED_FLAG) as u64;
+        let end = entries
+            .get(index + 1)
+            .map(|next| base_offset + (next & !COMPRESSED_FLAG) as u64)
+            .unwrap_or(data_end);
+
+        let expected = cmp::min(
+            self.chunk_size,
+            self.size.saturating_sub(chunk * self.chunk_size),
+        );
+        let file = &mut self.segments[segment];
+        let data = if entry & COMPRESSED_FLAG != 0 {
+            let compressed = read_at(file, start, end.saturating_sub(start) as usize)?;
+            let mut data = Vec::with_capacity(self.chunk_size as usize);
+            ZlibDecoder::new(&compressed[..]).read_to_end(&mut data)?;
+            data
+        } else {
+            // uncompressed chunks are followed by a 4 byte checksum which is not part of the data
+            read_at(file, start, expected as usize)?
+        };
+
+        self.chunk_cache = Some((chunk, data));
+        Ok(&self.chunk_cache.as_ref().unwrap().1)
+    }
+}
+
+impl Read for EwfReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+        let chunk = self.position / self.chunk_size;
+        let offset = (self.position % self.chunk_size) as usize;
+        let data = self.read_chunk(chunk)?;
+        let length = cmp::min(buf.len(), data.len().saturating_sub(offset));
+        if length == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("EWF chunk {} is shorter than expected", chunk),
+            ));
+        }
+        buf[..length].copy_from_slice(&data[offset..offset + length]);
+        self.position += length as u64;
+        Ok(length)
+    }
+}
+
+impl Seek for EwfReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.size.checked_add_signed(n),
+            SeekFrom::Current(n) => self.position.checked_add_signed(n),
+        };
+        match new_pos {
+            Some(n) => {
+                self.position = n;
+                Ok(n)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
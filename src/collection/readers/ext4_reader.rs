@@ -0,0 +1,687 @@
+use crate::collection::metadata::{format_timestamp, FileMetadata};
+use crate::collection::parsers::bytes::{le_u16, le_u32};
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use std::{
+    cmp,
+    collections::HashSet,
+    error::Error,
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+};
+
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const EXT4_MAGIC: u16 = 0xEF53;
+const EXTENT_MAGIC: u16 = 0xF30A;
+const ROOT_INODE: u32 = 2;
+const INCOMPAT_META_BG: u32 = 0x10;
+const INCOMPAT_64BIT: u32 = 0x80;
+const EXTENTS_FL: u32 = 0x80000;
+const INLINE_DATA_FL: u32 = 0x10000000;
+const XATTR_MAGIC: u32 = 0xEA020000;
+const XATTR_SYSTEM_INDEX: u8 = 7;
+const S_IFMT: u16 = 0xF000;
+const S_IFDIR: u16 = 0x4000;
+const S_IFREG: u16 = 0x8000;
+const S_IFLNK: u16 = 0xA000;
+// reads larger than this are split up so huge extents do not need huge buffers
+const MAX_READ_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Superblock {
+    pub inodes_count: u32,
+    pub blocks_count: u64,
+    pub first_data_block: u32,
+    pub block_size: u64,
+    pub blocks_per_group: u32,
+    pub inodes_per_group: u32,
+    pub inode_size: u64,
+    pub descriptor_size: u64,
+    pub feature_incompat: u32,
+    pub volume_name: String,
+}
+
+impl Superblock {
+    pub fn parse(buf: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if buf.len() < 1024 || le_u16(buf, 56) != EXT4_MAGIC {
+            return Err("Not an ext2/3/4 filesystem".into());
+        }
+        let feature_incompat = le_u32(buf, 96);
+        let revision = le_u32(buf, 76);
+        let mut blocks_count = le_u32(buf, 4) as u64;
+        let mut descriptor_size = 32;
+        if feature_incompat & INCOMPAT_64BIT != 0 {
+            blocks_count |= (le_u32(buf, 336) as u64) << 32;
+            descriptor_size = cmp::max(le_u16(buf, 254) as u64, 32);
+        }
+        let log_block_size = le_u32(buf, 24);
+        if log_block_size > 6 {
+            return Err("Invalid ext4 block size".into());
+        }
+
+        Ok(Superblock {
+            inodes_count: le_u32(buf, 0),
+            blocks_count,
+            first_data_block: le_u32(buf, 20),
+            block_size: 1024 << log_block_size,
+            blocks_per_group: le_u32(buf, 32),
+            inodes_per_group: le_u32(buf, 40),
+            inode_size: if revision >= 1 {
+                le_u16(buf, 88) as u64
+            } else {
+                128
+            },
+            descriptor_size,
+            feature_incompat,
+            volume_name: String::from_utf8_lossy(&buf[120..136])
+                .trim_end_matches('\0')
+                .to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Inode {
+    pub number: u32,
+    pub mode: u16,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub links: u16,
+    pub flags: u32,
+    pub accessed: (i64, u32),
+    pub modified: (i64, u32),
+    pub changed: (i64, u32),
+    pub created: Option<(i64, u32)>,
+    pub deleted: u32,
+    block: [u8; 60],
+    /// The `system.data` extended attribute of an inline file, the part of its contents that does
+    /// not fit in the 60 bytes of the block map.
+    inline_data: Vec<u8>,
+}
+
+/// Finds the value of the `system.data` extended attribute in the space after an inode's extra
+/// fields, which is where inline data past the first 60 bytes is kept.
+fn inline_data_xattr(raw: &[u8], extra_size: usize) -> Option<Vec<u8>> {
+    let start = 128 + extra_size;
+    if raw.len() < start + 4 || le_u32(raw, start) != XATTR_MAGIC {
+        return None;
+    }
+    // value offsets are relative to the first entry, straight after the magic
+    let entries = start + 4;
+    let mut offset = entries;
+    while offset + 16 <= raw.len() && le_u32(raw, offset) != 0 {
+        let name_length = raw[offset] as usize;
+        let name_index = raw[offset + 1];
+        let value_offset = le_u16(raw, offset + 2) as usize;
+        let value_size = le_u32(raw, offset + 8) as usize;
+        if name_index == XATTR_SYSTEM_INDEX
+            && raw.get(offset + 16..offset + 16 + name_length) == Some(b"data".as_slice())
+        {
+            return raw
+                .get(entries + value_offset..entries + value_offset + value_size)
+                .map(|value| value.to_vec());
+        }
+        offset += (16 + name_length).next_multiple_of(4);
+    }
+    None
+}
+
+/// Decodes an inode timestamp, the extra field holds two epoch bits that extend the range past
+/// 2038 and the nanoseconds.
+fn inode_time(raw: &[u8], seconds_offset: usize, extra_offset: Option<usize>) -> (i64, u32) {
+    let seconds = le_u32(raw, seconds_offset) as i32 as i64;
+    match extra_offset {
+        Some(offset) if raw.len() >= offset + 4 => {
+            let extra = le_u32(raw, offset);
+            (seconds + (((extra & 3) as i64) << 32), extra >> 2)
+        }
+        _ => (seconds, 0),
+    }
+}
+
+impl Inode {
+    fn parse(number: u32, raw: &[u8]) -> Self {
+        let extra_size = if raw.len() > 128 {
+            le_u16(raw, 128) as usize
+        } else {
+            0
+        };
+        // the extra timestamp fields only exist when the inode's extra space covers them
+        let extra = |offset: usize| {
+            if 128 + extra_size >= offset + 4 {
+                Some(offset)
+            } else {
+                None
+            }
+        };
+        let mut block = [0u8; 60];
+        block.copy_from_slice(&raw[40..100]);
+        let flags = le_u32(raw, 32);
+        let inline_data = if flags & INLINE_DATA_FL != 0 {
+            inline_data_xattr(raw, extra_size).unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+
+        Inode {
+            number,
+            mode: le_u16(raw, 0),
+            uid: le_u16(raw, 2) as u32 | (le_u16(raw, 120) as u32) << 16,
+            gid: le_u16(raw, 24) as u32 | (le_u16(raw, 122) as u32) << 16,
+            size: le_u32(raw, 4) as u64 | (le_u32(raw, 108) as u64) << 32,
+            links: le_u16(raw, 26),
+            flags,
+            accessed: inode_time(raw, 8, extra(140)),
+            modified: inode_time(raw, 16, extra(136)),
+            changed: inode_time(raw, 12, extra(132)),
+            created: extra(144).map(|_| inode_time(raw, 144, extra(148))),
+            deleted: le_u32(raw, 20),
+            block,
+            inline_data,
+        }
+    }
+
+    pub fn is_directory(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    pub fn is_regular_file(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK
+    }
+
+    pub fn metadata(&self) -> FileMetadata {
+        FileMetadata {
+            uid: Some(self.uid),
+            gid: Some(self.gid),
+            mode: Some(self.mode as u32),
+            inode: Some(self.number as u64),
+            links: Some(self.links as u64),
+            accessed: format_timestamp(self.accessed.0, self.accessed.1),
+            modified: format_timestamp(self.modified.0, self.modified.1),
+            changed: format_timestamp(self.changed.0, self.changed.1),
+            created: self
+                .created
+                .and_then(|(seconds, nanoseconds)| format_timestamp(seconds, nanoseconds)),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DirectoryEntry {
+    pub inode: u32,
+    pub name: String,
+    pub file_type: u8,
+}
+
+/// A contiguous range of a file's logical blocks and where they are stored on disk.
+/// Uninitialized extents are allocated but read back as zeros.
+#[derive(Debug, Clone, Copy)]
+struct BlockRun {
+    logical: u64,
+    physical: u64,
+    length: u64,
+    initialized: bool,
+}
+
+/// `Ext4` reads an ext2, ext3 or ext4 filesystem straight from a block device or an image,
+/// bypassing the kernel's view of the filesystem entirely.
+pub struct Ext4<R>
+where
+    R: Read + Seek,
+{
+    reader: R,
+    pub superblock: Superblock,
+    inode_tables: Vec<u64>,
+}
+
+impl<R> Ext4<R>
+where
+    R: Read + Seek,
+{
+    pub fn new(mut reader: R) -> Result<Self, Box<dyn Error>> {
+        let mut buf = vec![0u8; 1024];
+        reader.seek(SeekFrom::Start(SUPERBLOCK_OFFSET))?;
+        reader.read_exact(&mut buf)?;
+        let superblock = Superblock::parse(&buf)?;
+        if superblock.blocks_per_group == 0 || superblock.inodes_per_group == 0 {
+            return Err("Invalid ext4 superblock".into());
+        }
+        // meta_bg spreads the group descriptors across the filesystem instead of keeping them in
+        // the blocks after the superblock
+        if superblock.feature_incompat & INCOMPAT_META_BG != 0 {
+            return Err("ext4 filesystems with meta_bg are not supported".into());
+        }
+
+        let group_count = superblock
+            .blocks_count
+            .saturating_sub(superblock.first_data_block as u64)
+            .div_ceil(superblock.blocks_per_group as u64);
+        let table_block = superblock.first_data_block as u64 + 1;
+        let mut descriptors = vec![0u8; (group_count * superblock.descriptor_size) as usize];
+        reader.seek(SeekFrom::Start(table_block * superblock.block_size))?;
+        reader.read_exact(&mut descriptors)?;
+
+        let inode_tables = descriptors
+            .chunks_exact(superblock.descriptor_size as usize)
+            .map(|descriptor| {
+                let mut table = le_u32(descriptor, 8) as u64;
+                if superblock.descriptor_size >= 64 {
+                    table |= (le_u32(descriptor, 0x28) as u64) << 32;
+                }
+                table
+            })
+            .collect();
+
+        Ok(Ext4 {
+            reader,
+            superblock,
+            inode_tables,
+        })
+    }
+
+    fn read_block(&mut self, block: u64) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0u8; self.superblock.block_size as usize];
+        self.reader
+            .seek(SeekFrom::Start(block * self.superblock.block_size))?;
+        self.reader.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    pub fn read_inode(&mut self, number: u32) -> Result<Inode, Box<dyn Error>> {
+        if number == 0 || number > self.superblock.inodes_count {
+            return Err(format!("Invalid inode number {}", number).into());
+        }
+        let index = (number - 1) as u64;
+        let group = index / self.superblock.inodes_per_group as u64;
+        let table = *self
+            .inode_tables
+            .get(group as usize)
+            .ok_or("Inode is outside of the block groups")?;
+        let offset = table * self.superblock.block_size
+            + (index % self.superblock.inodes_per_group as u64) * self.superblock.inode_size;
+
+        let mut raw = vec![0u8; cmp::max(self.superblock.inode_size, 128) as usize];
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.reader.read_exact(&mut raw)?;
+        Ok(Inode::parse(number, &raw))
+    }
+
+    fn extent_runs(
+        &mut self,
+        node: &[u8],
+        runs: &mut Vec<BlockRun>,
+        depth_limit: u16,
+    ) -> Result<(), Box<dyn Error>> {
+        if node.len() < 12 || le_u16(node, 0) != EXTENT_MAGIC {
+            return Err("Invalid extent header".into());
+        }
+        let entries = le_u16(node, 2) as usize;
+        let depth = le_u16(node, 6);
+        if depth > depth_limit {
+            return Err("Extent tree is deeper than its parent allows".into());
+        }
+
+        for entry in node[12..].chunks_exact(12).take(entries) {
+            if depth == 0 {
+                let length = le_u16(entry, 4) as u64;
+                let physical = (le_u16(entry, 6) as u64) << 32 | le_u32(entry, 8) as u64;
+                runs.push(BlockRun {
+                    logical: le_u32(entry, 0) as u64,
+                    physical,
+                    // lengths above 32768 mark an uninitialized extent
+                    length: if length > 32768 {
+                        length - 32768
+                    } else {
+                        length
+                    },
+                    initialized: length <= 32768,
+                });
+            } else {
+                let leaf = (le_u16(entry, 8) as u64) << 32 | le_u32(entry, 4) as u64;
+                let child = self.read_block(leaf)?;
+                self.extent_runs(&child, runs, depth - 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn indirect_runs(
+        &mut self,
+        block: u64,
+        level: u32,
+        logical: &mut u64,
+        total_blocks: u64,
+        runs: &mut Vec<BlockRun>,
+    ) -> Result<(), Box<dyn Error>> {
+        let pointers_per_block = self.superblock.block_size / 4;
+        if block == 0 {
+            // a hole covering everything this pointer would have mapped
+            *logical += pointers_per_block.pow(level);
+            return Ok(());
+        }
+        let pointers = self.read_block(block)?;
+        for pointer in pointers.chunks_exact(4) {
+            if *logical >= total_blocks {
+                break;
+            }
+            let pointer = le_u32(pointer, 0) as u64;
+            if level == 1 {
+                if pointer != 0 {
+                    push_block(runs, *logical, pointer);
+                }
+                *logical += 1;
+            } else {
+                self.indirect_runs(pointer, level - 1, logical, total_blocks, runs)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn block_runs(&mut self, inode: &Inode) -> Result<Vec<BlockRun>, Box<dyn Error>> {
+        let mut runs = Vec::new();
+        if inode.flags & EXTENTS_FL != 0 {
+            let root = inode.block;
+            self.extent_runs(&root, &mut runs, 5)?;
+        } else {
+            let total_blocks = inode.size.div_ceil(self.superblock.block_size);
+            let mut logical = 0;
+            for (index, pointer) in inode.block.chunks_exact(4).enumerate() {
+                if logical >= total_blocks {
+                    break;
+                }
+                let pointer = le_u32(pointer, 0) as u64;
+                match index {
+                    0..=11 => {
+                        if pointer != 0 {
+                            push_block(&mut runs, logical, pointer);
+                        }
+                        logical += 1;
+                    }
+                    12 => self.indirect_runs(pointer, 1, &mut logical, total_blocks, &mut runs)?,
+                    13 => self.indirect_runs(pointer, 2, &mut logical, total_blocks, &mut runs)?,
+                    _ => self.indirect_runs(pointer, 3, &mut logical, total_blocks, &mut runs)?,
+                }
+            }
+        }
+        runs.sort_by_key(|run| run.logical);
+        Ok(runs)
+    }
+
+    /// Writes the contents of an inode to `writer`, holes and uninitialized extents are written
+    /// as zeros. Returns the number of bytes written, which is always the inode's size.
+    pub fn copy_inode<W>(&mut self, inode: &Inode, writer: &mut W) -> Result<u64, Box<dyn Error>>
+    where
+        W: Write,
+    {
+        if inode.flags & INLINE_DATA_FL != 0 {
+            let mut data = inode.block.to_vec();
+            data.extend_from_slice(&inode.inline_data);
+            if (data.len() as u64) < inode.size {
+                return Err(format!(
+                    "The inline data of inode {0} holds {1} of its {2} bytes",
+                    inode.number,
+                    data.len(),
+                    inode.size
+                )
+                .into());
+            }
+            writer.write_all(&data[..inode.size as usize])?;
+            return Ok(inode.size);
+        }
+        if inode.is_symlink() && inode.size < 60 {
+            writer.write_all(&inode.block[..inode.size as usize])?;
+            return Ok(inode.size);
+        }
+
+        let block_size = self.superblock.block_size;
+        let mut written: u64 = 0;
+        let zeros_until = |writer: &mut W, written: &mut u64, end: u64| -> io::Result<()> {
+            let zeros = vec![0u8; cmp::min(end.saturating_sub(*written), MAX_READ_SIZE) as usize];
+            while *written < end {
+                let length = cmp::min(end - *written, zeros.len() as u64) as usize;
+                writer.write_all(&zeros[..length])?;
+                *written += length as u64;
+            }
+            Ok(())
+        };
+
+        for run in self.block_runs(inode)? {
+            let start = run.logical * block_size;
+            if start >= inode.size {
+                break;
+            }
+            zeros_until(writer, &mut written, start)?;
+            let end = cmp::min((run.logical + run.length) * block_size, inode.size);
+            if !run.initialized {
+                zeros_until(writer, &mut written, end)?;
+                continue;
+            }
+
+            self.reader
+                .seek(SeekFrom::Start(run.physical * block_size))?;
+            let mut buf = vec![0u8; cmp::min(end - start, MAX_READ_SIZE) as usize];
+            while written < end {
+                let length = cmp::min(end - written, buf.len() as u64) as usize;
+                self.reader.read_exact(&mut buf[..length])?;
+                writer.write_all(&buf[..length])?;
+                written += length as u64;
+            }
+        }
+        zeros_until(writer, &mut written, inode.size)?;
+
+        Ok(written)
+    }
+
+    pub fn read_directory(&mut self, inode: &Inode) -> Result<Vec<DirectoryEntry>, Box<dyn Error>> {
+        if !inode.is_directory() {
+            return Err(format!("Inode {} is not a directory", inode.number).into());
+        }
+        let mut data = Vec::new();
+        self.copy_inode(inode, &mut data)?;
+
+        // inline directories start with the parent's inode number instead of "." and ".."
+        let mut offset = if inode.flags & INLINE_DATA_FL != 0 {
+            4
+        } else {
+            0
+        };
+        let mut entries = Vec::new();
+        // hash tree directories hide their index in entries with no inode, so reading every
+        // block linearly still returns every entry
+        while offset + 8 <= data.len() {
+            let entry_inode = le_u32(&data, offset);
+            let record_length = le_u16(&data, offset + 4) as usize;
+            let name_length = data[offset + 6] as usize;
+            let file_type = data[offset + 7];
+            if record_length < 8 || offset + record_length > data.len() {
+                break;
+            }
+            if entry_inode != 0 && offset + 8 + name_length <= data.len() {
+                let name = String::from_utf8_lossy(&data[offset + 8..offset + 8 + name_length])
+                    .to_string();
+                if name != "." && name != ".." {
+                    entries.push(DirectoryEntry {
+                        inode: entry_inode,
+                        name,
+                        file_type,
+                    });
+                }
+            }
+            offset += record_length;
+        }
+        Ok(entries)
+    }
+
+    /// Resolves an absolute path, relative to the root of this filesystem, to its inode.
+    pub fn open_file(&mut self, file_path: &str) -> Result<Inode, Box<dyn Error>> {
+        let mut current = self.read_inode(ROOT_INODE)?;
+        for component in file_path.split('/').filter(|c| !c.is_empty() && *c != ".") {
+            let entry = self
+                .read_directory(&current)?
+                .into_iter()
+                .find(|entry| entry.name == component)
+                .ok_or(format!("{} not found", file_path))?;
+            current = self.read_inode(entry.inode)?;
+        }
+        Ok(current)
+    }
+
+    /// Walks the directory tree from `root`, calling `found` with the path and inode of every
+    /// file that is not a directory, up to `max_depth` levels deep.
+    pub fn walk<F>(
+        &mut self,
+        root: &str,
+        max_depth: usize,
+        found: &mut F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(&str, &Inode),
+    {
+        let inode = self.open_file(root)?;
+        let mut visited = HashSet::from([inode.number]);
+        self.walk_directory(
+            root.trim_end_matches('/'),
+            &inode,
+            1,
+            max_depth,
+            &mut visited,
+            found,
+        );
+        Ok(())
+    }
+
+    fn walk_directory<F>(
+        &mut self,
+        path: &str,
+        directory: &Inode,
+        depth: usize,
+        max_depth: usize,
+        visited: &mut HashSet<u32>,
+        found: &mut F,
+    ) where
+        F: FnMut(&str, &Inode),
+    {
+        if depth > max_depth {
+            return;
+        }
+        let entries = match self.read_directory(directory) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries {
+            let inode = match self.read_inode(entry.inode) {
+                Ok(inode) => inode,
+                Err(_) => continue,
+            };
+            let entry_path = format!("{0}/{1}", path, entry.name);
+            if inode.is_directory() {
+                if !visited.insert(inode.number) {
+                    continue;
+                }
+                self.walk_directory(&entry_path, &inode, depth + 1, max_depth, visited, found);
+            } else {
+                found(&entry_path, &inode);
+            }
+        }
+    }
+}
+
+fn push_block(runs: &mut Vec<BlockRun>, logical: u64, physical: u64) {
+    if let Some(last) = runs.last_mut() {
+        if last.logical + last.length == logical && last.physical + last.length == physical {
+            last.length += 1;
+            return;
+        }
+    }
+    runs.push(BlockRun {
+        logical,
+        physical,
+        length: 1,
+        initialized: true,
+    });
+}
+
+pub fn open_device(device: &str) -> Result<Ext4<BufReader<File>>, Box<dyn Error>> {
+    Ext4::new(BufReader::new(File::open(device)?))
+}
+
+pub fn does_file_exist(device: &str, file_path: &str) -> Result<bool, Box<dyn Error>> {
+    let mut filesystem = open_device(device)?;
+    Ok(filesystem.open_file(file_path).is_ok())
+}
+
+pub fn copy_file<W>(device: &str, file_path: &str, mut writer: W) -> Result<u64, Box<dyn Error>>
+where
+    W: Write,
+{
+    let mut filesystem = open_device(device)?;
+    let inode = filesystem.open_file(file_path)?;
+    filesystem.copy_inode(&inode, &mut writer)
+}
+
+pub fn get_lastmodified(device: &str, file_path: &str) -> Result<NaiveDateTime, Box<dyn Error>> {
+    let mut filesystem = open_device(device)?;
+    let inode = filesystem.open_file(file_path)?;
+    Ok(Utc
+        .timestamp_opt(inode.modified.0, inode.modified.1)
+        .single()
+        .ok_or("Invalid timestamp")?
+        .naive_utc())
+}
+
+pub struct Mount {
+    pub device: String,
+    pub mount_point: String,
+    pub fs_type: String,
+}
+
+impl Mount {
+    /// The path of `file_path` relative to the root of the mounted filesystem.
+    pub fn relative_path<'a>(&self, file_path: &'a str) -> &'a str {
+        file_path
+            .strip_prefix(self.mount_point.trim_end_matches('/'))
+            .unwrap_or(file_path)
+    }
+}
+
+/// Returns the mounted ext2/3/4 filesystems that are backed by a block device.
+#[cfg(target_os = "linux")]
+pub fn get_mounts() -> Result<Vec<Mount>, Box<dyn Error>> {
+    let mounts = std::fs::read_to_string("/proc/self/mounts")?;
+    Ok(mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = fields.next()?;
+            // spaces and other special characters are escaped as octal
+            let mount_point = fields.next()?.replace("\\040", " ");
+            let fs_type = fields.next()?;
+            if !device.starts_with("/dev/") || !matches!(fs_type, "ext2" | "ext3" | "ext4") {
+                return None;
+            }
+            Some(Mount {
+                device: device.to_string(),
+                mount_point,
+                fs_type: fs_type.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Finds the ext2/3/4 mount that `file_path` lives on, the longest matching mount point wins.
+#[cfg(target_os = "linux")]
+pub fn find_mount(file_path: &str) -> Option<Mount> {
+    get_mounts()
+        .ok()?
+        .into_iter()
+        .filter(|mount| {
+            let mount_point = mount.mount_point.trim_end_matches('/');
+            file_path.starts_with(&format!("{}/", mount_point)) || file_path == mount_point
+        })
+        .max_by_key(|mount| mount.mount_point.len())
+}
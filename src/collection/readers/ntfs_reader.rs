@@ -1,18 +1,29 @@
+use crate::collection::manifest::RecoveryConfidence;
+use crate::collection::metadata::{format_filetime, FileMetadata};
+use crate::collection::parsers::mft_parser::attribute_names;
 use chrono::{NaiveDateTime, TimeZone, Utc};
 use nt_time::FileTime;
-use ntfs::Ntfs;
 use ntfs::{
     attribute_value::NtfsAttributeValue,
     indexes::NtfsFileNameIndex,
-    structured_values::{NtfsAttributeList, NtfsStandardInformation},
-    NtfsAttribute, NtfsAttributeFlags, NtfsAttributeType, NtfsFile, NtfsReadSeek,
+    structured_values::{
+        NtfsAttributeList, NtfsFileName, NtfsFileNamespace, NtfsStandardInformation,
+    },
+    KnownNtfsFileRecordNumber, NtfsAttribute, NtfsAttributeType, NtfsFile, NtfsFileFlags,
+    NtfsReadSeek,
 };
+use ntfs::{Ntfs, NtfsTime};
 use std::{
+    cmp,
+    collections::{HashMap, HashSet},
     error::Error,
     io::{self, Read, Seek, SeekFrom, Write},
-    path::Path,
 };
-use std::{fs::File, io::BufReader};
+#[cfg(target_os = "windows")]
+use std::{fs::File, io::BufReader, path::Path, sync::Mutex};
+
+/// The stream name that selects every named data stream of a file, e.g. `Downloads\\.*:*`.
+pub const ALL_STREAMS: &str = "*";
 
 pub fn parse_stream(path: &str) -> (String, String) {
     if let Some(pos) = path.rfind(':') {
@@ -28,166 +39,1018 @@ pub fn parse_stream(path: &str) -> (String, String) {
     }
 }
 
-pub fn copy_file<W>(file_path: String, mut writer: W) -> Result<usize, Box<dyn Error>>
+/// Deleted files are collected as `C:\$Deleted\<record>\<original path>`, which keeps them
+/// apart from any live file that has since taken the same path.
+pub const DELETED_DIRECTORY: &str = "$Deleted";
+
+/// Where a file record header keeps the reference to the base record of an extension record.
+const BASE_RECORD_OFFSET: u64 = 32;
+const RECORD_NUMBER_MASK: u64 = 0x0000_FFFF_FFFF_FFFF;
+
+/// The change journal, whose `$J` stream starts with a sparse range where the oldest records
+/// were freed.
+const CHANGE_JOURNAL: &str = "$UsnJrnl";
+const CHANGE_JOURNAL_STREAM: &str = "$J";
+
+/// What `Drive::copy_file` wrote.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CopiedStream {
+    pub written: u64,
+    /// The offset in the stream the copy starts at, the length of the freed start of
+    /// `$UsnJrnl:$J` when it was left out and otherwise 0.
+    pub start_offset: u64,
+}
+
+/// A file looked up with `Drive::resolve`, so its times, metadata and contents can all be read
+/// without searching for its path again.
+#[derive(Debug, Clone)]
+pub struct ResolvedFile {
+    /// The path of the file without its stream name.
+    pub path: String,
+    pub stream_name: String,
+    pub record: u64,
+}
+
+/// An NTFS volume opened from any `Read + Seek` source, either a live volume or an image.
+pub struct Drive<T>
 where
-    W: Write,
+    T: Read + Seek,
 {
-    let (path, stream_name) = parse_stream(file_path.as_str());
-    let drive_letter = path.chars().next().ok_or("Invalid path")?;
-    let mut drive = open_drive(drive_letter.to_string())?;
-    let file = open_file(path, &mut drive.filesystem_reader, &drive.ntfs)?;
-
-    let data_item = file
-        .data(&mut drive.filesystem_reader, stream_name.as_str())
-        .ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("The file does not have a stream called {}", stream_name),
+    filesystem_reader: T,
+    ntfs: Ntfs,
+    /// MFT record numbers of the paths resolved so far, keyed by the upper case path.
+    records: HashMap<String, u64>,
+    /// Every file in the MFT with its path relative to the volume root, read on the first MFT
+    /// search.
+    mft_files: Option<Vec<MftFile>>,
+}
+
+impl<T> Drive<T>
+where
+    T: Read + Seek,
+{
+    pub fn new(mut filesystem_reader: T) -> Result<Self, Box<dyn Error>> {
+        let mut ntfs = Ntfs::new(&mut filesystem_reader)?;
+        ntfs.read_upcase_table(&mut filesystem_reader)?;
+
+        Ok(Drive {
+            filesystem_reader,
+            ntfs,
+            records: HashMap::new(),
+            mft_files: None,
+        })
+    }
+
+    /// Resolves a path relative to the root of the volume to its MFT record number, a leading
+    /// drive letter (`C:`) is skipped. Every directory on the way is remembered, so looking up
+    /// another file in the same directory only searches the index of that directory. Paths of
+    /// deleted files name their record, which must not have been reused since.
+    fn find_record(&mut self, file_path: &str) -> Result<u64, Box<dyn Error>> {
+        if let Some(record) = deleted_record(file_path) {
+            let file = self.ntfs.file(&mut self.filesystem_reader, record)?;
+            if file.flags().contains(NtfsFileFlags::IN_USE) {
+                return Err(format!("MFT record {} has been reused", record).into());
+            }
+            return Ok(record);
+        }
+        let components: Vec<&str> = file_path
+            .split(['\\', '/'])
+            .filter(|component| !component.is_empty())
+            .enumerate()
+            .filter(|(position, component)| {
+                !(*position == 0 && component.len() == 2 && component.ends_with(':'))
+            })
+            .map(|(_, component)| component)
+            .collect();
+        let key = |length: usize| components[..length].join("\\").to_uppercase();
+
+        // start from the deepest path that has already been resolved
+        let (start, record) = (1..=components.len())
+            .rev()
+            .find_map(|length| {
+                self.records
+                    .get(&key(length))
+                    .map(|record| (length, *record))
+            })
+            .unwrap_or((0, KnownNtfsFileRecordNumber::RootDirectory as u64));
+
+        let mut file = self.ntfs.file(&mut self.filesystem_reader, record)?;
+        for (position, component) in components.iter().enumerate().skip(start) {
+            if !file.is_directory() {
+                return Ok(file.file_record_number());
+            }
+            let index = file.directory_index(&mut self.filesystem_reader)?;
+            let mut finder = index.finder();
+            let entry = NtfsFileNameIndex::find(
+                &mut finder,
+                &self.ntfs,
+                &mut self.filesystem_reader,
+                component,
             )
-        })??;
+            .ok_or("File not found")?;
+            file = entry?.to_file(&self.ntfs, &mut self.filesystem_reader)?;
+            self.records
+                .insert(key(position + 1), file.file_record_number());
+        }
 
-    let mut starting_position: u64 = 0;
+        if file.is_directory() {
+            return Err("File not found".into());
+        }
+        Ok(file.file_record_number())
+    }
 
-    if data_item
-        .to_attribute()?
-        .flags()
-        .contains(NtfsAttributeFlags::SPARSE)
+    pub fn does_file_exist(&mut self, file_path: &str) -> bool {
+        let (path, _) = parse_stream(file_path);
+        self.find_record(&path).is_ok()
+    }
+
+    /// Looks up the record of `file_path` (`path:stream`).
+    pub fn resolve(&mut self, file_path: &str) -> Result<ResolvedFile, Box<dyn Error>> {
+        let (path, stream_name) = parse_stream(file_path);
+        let record = self.find_record(&path)?;
+        Ok(ResolvedFile {
+            path,
+            stream_name,
+            record,
+        })
+    }
+
+    /// Copies the stream named in `file_path` (`path:stream`) or the unnamed data stream.
+    /// Unallocated (sparse) ranges are written as zeros, except for the freed start of
+    /// `$UsnJrnl:$J`, which is left out unless `keep_sparse` is set.
+    pub fn copy_file<W>(
+        &mut self,
+        file_path: &str,
+        writer: W,
+        keep_sparse: bool,
+    ) -> Result<CopiedStream, Box<dyn Error>>
+    where
+        W: Write,
+    {
+        let resolved = self.resolve(file_path)?;
+        self.copy_resolved(&resolved, writer, keep_sparse)
+    }
+
+    pub fn copy_resolved<W>(
+        &mut self,
+        resolved: &ResolvedFile,
+        mut writer: W,
+        keep_sparse: bool,
+    ) -> Result<CopiedStream, Box<dyn Error>>
+    where
+        W: Write,
     {
-        let attributes = file.attributes_raw();
-        for attribute in attributes {
-            let attribute = attribute?;
-            let ty = attribute.ty()?;
-            if ty == NtfsAttributeType::AttributeList {
-                let list = attribute
-                    .structured_value::<_, NtfsAttributeList>(&mut drive.filesystem_reader)?;
-                let mut list_iter = list.entries();
-                while let Some(entry) = list_iter.next(&mut drive.filesystem_reader) {
-                    let entry = entry?;
-                    let entry_record_number = entry.base_file_reference().file_record_number();
-                    if entry_record_number == file.file_record_number() {
-                        continue;
-                    }
-                    let entry_file = entry.to_file(&drive.ntfs, &mut drive.filesystem_reader)?;
-                    let entry_attribute: NtfsAttribute<'_, '_> = entry.to_attribute(&entry_file)?;
-                    let value = entry_attribute.value(&mut drive.filesystem_reader)?;
-                    if let NtfsAttributeValue::NonResident(non_resident_value) = value {
-                        for data_run in non_resident_value.data_runs() {
-                            let data_run = data_run?;
-                            if data_run.data_position() == None.into() {
-                                starting_position = data_run.allocated_size();
-                            }
-                        }
-                    }
+        let stream_name = &resolved.stream_name;
+        let file = self
+            .ntfs
+            .file(&mut self.filesystem_reader, resolved.record)?;
+
+        let data_item = file
+            .data(&mut self.filesystem_reader, stream_name.as_str())
+            .ok_or_else(|| {
+                io::Error::other(format!(
+                    "The file does not have a stream called {}",
+                    stream_name
+                ))
+            })??;
+        let data_attribute = data_item.to_attribute()?;
+        // holes anywhere else are part of the file, only the journal's records before the
+        // first one still kept are worthless
+        let mut skip_sparse = !keep_sparse && is_change_journal(resolved);
+        let runs = stream_runs(
+            &file,
+            &data_attribute,
+            &mut self.filesystem_reader,
+            &self.ntfs,
+        )?;
+
+        let mut data_value = data_attribute.value(&mut self.filesystem_reader)?;
+        let length = data_value.len();
+        let mut buf = vec![0u8; 65536];
+        let mut position: u64 = 0;
+        let mut written: u64 = 0;
+        let mut start_offset: u64 = 0;
+
+        for (run_length, sparse) in runs {
+            if position >= length {
+                break;
+            }
+            // the last run is allocated in whole clusters and usually extends past the data
+            let end = cmp::min(position + run_length, length);
+            if sparse && skip_sparse {
+                position = end;
+                start_offset = end;
+                continue;
+            }
+            skip_sparse = false;
+            data_value.seek(&mut self.filesystem_reader, SeekFrom::Start(position))?;
+            while position < end {
+                let wanted = cmp::min(buf.len() as u64, end - position) as usize;
+                let bytes_read =
+                    data_value.read(&mut self.filesystem_reader, &mut buf[..wanted])?;
+                if bytes_read == 0 {
+                    return Err(
+                        format!("{} ended before its recorded length", resolved.path).into(),
+                    );
                 }
+                writer.write_all(&buf[..bytes_read])?;
+                position += bytes_read as u64;
+                written += bytes_read as u64;
             }
         }
+
+        Ok(CopiedStream {
+            written,
+            start_offset,
+        })
     }
 
-    let data_attribute = data_item.to_attribute()?;
-    let mut data_value = data_attribute.value(&mut drive.filesystem_reader)?;
-    let mut buf = [0u8; 4096];
-    data_value.seek(
-        &mut drive.filesystem_reader,
-        std::io::SeekFrom::Start(starting_position),
-    )?;
+    /// Returns the names of the named data streams (alternate data streams) of a file.
+    pub fn list_streams(&mut self, file_path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let (path, _) = parse_stream(file_path);
+        let record = self.find_record(&path)?;
+        let file = self.ntfs.file(&mut self.filesystem_reader, record)?;
 
-    loop {
-        let bytes_read = data_value.read(&mut drive.filesystem_reader, &mut buf)?;
-        if bytes_read == 0 {
-            break;
+        let mut streams: Vec<String> = Vec::new();
+        let mut attributes = file.attributes();
+        while let Some(attribute_item) = attributes.next(&mut self.filesystem_reader) {
+            let attribute_item = attribute_item?;
+            let attribute = attribute_item.to_attribute()?;
+            if attribute.ty()? != NtfsAttributeType::Data {
+                continue;
+            }
+            // a large stream can be split over several attributes with the same name
+            let name = attribute.name()?.to_string_lossy();
+            if !name.is_empty() && !streams.contains(&name) {
+                streams.push(name);
+            }
+        }
+        Ok(streams)
+    }
+
+    pub fn get_lastmodified(&mut self, file_path: &str) -> Result<NaiveDateTime, Box<dyn Error>> {
+        let resolved = self.resolve(file_path)?;
+        self.resolved_lastmodified(&resolved)
+    }
+
+    pub fn resolved_lastmodified(
+        &mut self,
+        resolved: &ResolvedFile,
+    ) -> Result<NaiveDateTime, Box<dyn Error>> {
+        let file = self
+            .ntfs
+            .file(&mut self.filesystem_reader, resolved.record)?;
+
+        let mut attributes = file.attributes();
+
+        while let Some(attribute_item) = attributes.next(&mut self.filesystem_reader) {
+            let attribute_item = attribute_item?;
+            let attribute = attribute_item.to_attribute()?;
+
+            if let Ok(NtfsAttributeType::StandardInformation) = attribute.ty() {
+                let std_info = attribute.resident_structured_value::<NtfsStandardInformation>()?;
+                let file_time =
+                    FileTime::from(std_info.modification_time().nt_timestamp()).to_unix_time_secs();
+                let modified_timestamp = Utc
+                    .timestamp_opt(file_time, 0)
+                    .single()
+                    .ok_or("Invalid timestamp")?;
+                return Ok(modified_timestamp.naive_utc());
+            }
         }
-        match writer.write_all(buf.as_ref()) {
-            Ok(_) => continue,
-            Err(e) => {
-                println!("Finished writing to zip file: {}", e);
+
+        Err("No standard information attribute found".into())
+    }
+
+    pub fn get_metadata(&mut self, file_path: &str) -> Result<FileMetadata, Box<dyn Error>> {
+        let resolved = self.resolve(file_path)?;
+        self.resolved_metadata(&resolved)
+    }
+
+    pub fn resolved_metadata(
+        &mut self,
+        resolved: &ResolvedFile,
+    ) -> Result<FileMetadata, Box<dyn Error>> {
+        let file = self
+            .ntfs
+            .file(&mut self.filesystem_reader, resolved.record)?;
+
+        let mut metadata = FileMetadata {
+            inode: Some(file.file_record_number()),
+            links: Some(file.hard_link_count() as u64),
+            ..Default::default()
+        };
+
+        let mut attributes = file.attributes();
+        while let Some(attribute_item) = attributes.next(&mut self.filesystem_reader) {
+            let attribute_item = attribute_item?;
+            let attribute = attribute_item.to_attribute()?;
+
+            if let Ok(NtfsAttributeType::StandardInformation) = attribute.ty() {
+                let std_info = attribute.resident_structured_value::<NtfsStandardInformation>()?;
+                metadata.created = format_ntfs_time(std_info.creation_time());
+                metadata.modified = format_ntfs_time(std_info.modification_time());
+                metadata.changed = format_ntfs_time(std_info.mft_record_modification_time());
+                metadata.accessed = format_ntfs_time(std_info.access_time());
+                metadata.attributes = attribute_names(std_info.file_attributes().bits());
                 break;
             }
         }
+
+        Ok(metadata)
     }
 
-    Ok(100)
-}
+    /// Walks the directory tree from the root of the volume, calling `found` with the path of
+    /// every file, prefixed with `root` (e.g. `C:`), up to `max_depth` levels deep.
+    pub fn walk<F>(
+        &mut self,
+        root: &str,
+        max_depth: usize,
+        found: &mut F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(&str),
+    {
+        let root_directory = self.ntfs.root_directory(&mut self.filesystem_reader)?;
+        let mut visited = HashSet::from([root_directory.file_record_number()]);
+        walk_directory(
+            &root_directory,
+            root,
+            1,
+            max_depth,
+            &mut visited,
+            &mut self.filesystem_reader,
+            &self.ntfs,
+            found,
+        );
+        Ok(())
+    }
 
-struct Drive {
-    filesystem_reader: BufReader<SectorReader<File>>,
-    ntfs: Ntfs,
-}
+    /// Like `walk`, but reads every record of `$MFT` once and rebuilds the paths from the parent
+    /// directory references instead of following directory indexes. This also finds files that
+    /// are missing from an index and files the operating system hides or denies access to.
+    pub fn walk_mft<F>(
+        &mut self,
+        root: &str,
+        max_depth: usize,
+        found: &mut F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(&str),
+    {
+        if self.mft_files.is_none() {
+            self.mft_files = Some(self.read_mft_files()?);
+        }
+        for file in self.mft_files.iter().flatten() {
+            if file.in_use && file.path.split('\\').count() <= max_depth {
+                found(&format!("{0}\\{1}", root, file.path));
+            }
+        }
+        Ok(())
+    }
 
-fn open_drive(drive_letter: String) -> Result<Drive, Box<dyn Error>> {
-    let volume_path = format!("\\\\.\\{}:", drive_letter);
-    let volume = File::open(Path::new(&volume_path))?;
-    let sector_reader = SectorReader::new(volume, 4096)?;
-    let mut filesystem_reader = BufReader::new(sector_reader);
-    let mut ntfs = Ntfs::new(&mut filesystem_reader)?;
-    ntfs.read_upcase_table(&mut filesystem_reader)?;
+    /// Finds files whose MFT record is no longer in use but still describes data runs that lie
+    /// within the volume, so their contents may still be recovered. `found` is called with the
+    /// path the file had, prefixed with `root`, and the path to collect it by, which is under
+    /// `DELETED_DIRECTORY` and names the record.
+    pub fn walk_deleted<F>(
+        &mut self,
+        root: &str,
+        max_depth: usize,
+        found: &mut F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(&str, &str),
+    {
+        if self.mft_files.is_none() {
+            self.mft_files = Some(self.read_mft_files()?);
+        }
+        let deleted: Vec<(u64, String)> = self
+            .mft_files
+            .iter()
+            .flatten()
+            .filter(|file| !file.in_use && file.path.split('\\').count() <= max_depth)
+            .map(|file| (file.record, file.path.clone()))
+            .collect();
+        for (record, path) in deleted {
+            if !self.has_intact_data(record) {
+                continue;
+            }
+            found(
+                &format!("{0}\\{1}", root, path),
+                &format!("{0}\\{1}\\{2}\\{3}", root, DELETED_DIRECTORY, record, path),
+            );
+        }
+        Ok(())
+    }
 
-    Ok(Drive {
-        filesystem_reader,
-        ntfs,
-    })
-}
+    /// Returns whether the unnamed data stream of a record can still be read, every data run has
+    /// to decode and lie within the volume.
+    fn has_intact_data(&mut self, record: u64) -> bool {
+        match self.data_clusters(record) {
+            Ok(DataClusters::Runs(runs)) => runs
+                .iter()
+                .all(|(first, count)| first + count <= self.cluster_count()),
+            Ok(_) => true,
+            Err(_) => false,
+        }
+    }
 
-fn open_file<'f>(
-    file_path: String,
-    filesystem_reader: &mut BufReader<SectorReader<File>>,
-    ntfs: &'f Ntfs,
-) -> Result<NtfsFile<'f>, Box<dyn Error>> {
-    let mut current_directory: Vec<NtfsFile> = vec![ntfs.root_directory(filesystem_reader)?];
-
-    for dir in Path::new(&file_path).iter() {
-        let next_dir = dir.to_str().ok_or("Invalid path")?;
-        let index = current_directory
-            .last()
-            .unwrap()
-            .directory_index(filesystem_reader)?;
-        let mut finder = index.finder();
-
-        if let Some(entry) = NtfsFileNameIndex::find(&mut finder, ntfs, filesystem_reader, next_dir)
-        {
-            let file = entry?.to_file(ntfs, filesystem_reader)?;
-            if !file.is_directory() {
-                return Ok(file);
+    fn cluster_count(&self) -> u64 {
+        self.ntfs.size() / self.ntfs.cluster_size() as u64
+    }
+
+    /// Returns where the unnamed data stream of a record is stored.
+    fn data_clusters(&mut self, record: u64) -> Result<DataClusters, Box<dyn Error>> {
+        let file = self.ntfs.file(&mut self.filesystem_reader, record)?;
+        let data_item = file
+            .data(&mut self.filesystem_reader, "")
+            .ok_or("The file does not have a data stream")??;
+        let data_attribute = data_item.to_attribute()?;
+        let value = match data_attribute.value(&mut self.filesystem_reader)? {
+            NtfsAttributeValue::Resident(_) => return Ok(DataClusters::Resident),
+            NtfsAttributeValue::NonResident(value) => value,
+            NtfsAttributeValue::AttributeListNonResident(_) => return Ok(DataClusters::Elsewhere),
+        };
+        let cluster_size = self.ntfs.cluster_size() as u64;
+        let mut runs = Vec::new();
+        for data_run in value.data_runs() {
+            let data_run = data_run?;
+            // sparse runs have no clusters
+            if let Some(position) = data_run.data_position().value() {
+                runs.push((
+                    position.get() / cluster_size,
+                    data_run.allocated_size().div_ceil(cluster_size),
+                ));
+            }
+        }
+        Ok(DataClusters::Runs(runs))
+    }
+
+    /// Returns how likely a file collected from `DELETED_DIRECTORY` still holds its original
+    /// contents, or `None` for a file that was not deleted. Clusters that `$Bitmap` shows as
+    /// allocated again have been handed to another file and may have been overwritten.
+    pub fn recovery_confidence(
+        &mut self,
+        file_path: &str,
+    ) -> Result<Option<RecoveryConfidence>, Box<dyn Error>> {
+        let (path, _) = parse_stream(file_path);
+        let Some(record) = deleted_record(&path) else {
+            return Ok(None);
+        };
+        self.record_recovery_confidence(record).map(Some)
+    }
+
+    pub fn resolved_recovery_confidence(
+        &mut self,
+        resolved: &ResolvedFile,
+    ) -> Result<Option<RecoveryConfidence>, Box<dyn Error>> {
+        if deleted_record(&resolved.path).is_none() {
+            return Ok(None);
+        }
+        self.record_recovery_confidence(resolved.record).map(Some)
+    }
+
+    fn record_recovery_confidence(
+        &mut self,
+        record: u64,
+    ) -> Result<RecoveryConfidence, Box<dyn Error>> {
+        let runs = match self.data_clusters(record)? {
+            // resident data is stored in the record itself, which has not been reused
+            DataClusters::Resident => return Ok(RecoveryConfidence::High),
+            DataClusters::Runs(runs) => runs,
+            // the rest of the runs are in other records, which may have been reused
+            DataClusters::Elsewhere => return Ok(RecoveryConfidence::Low),
+        };
+        // every run has to be free, a single reused cluster means part of the file was overwritten
+        for (first, count) in runs {
+            if first + count > self.cluster_count() || self.clusters_allocated(first, count)? {
+                return Ok(RecoveryConfidence::Low);
+            }
+        }
+        Ok(RecoveryConfidence::High)
+    }
+
+    /// Returns whether any of `count` clusters from `first` are marked as allocated in `$Bitmap`.
+    fn clusters_allocated(&mut self, first: u64, count: u64) -> Result<bool, Box<dyn Error>> {
+        let bitmap = self.ntfs.file(
+            &mut self.filesystem_reader,
+            KnownNtfsFileRecordNumber::Bitmap as u64,
+        )?;
+        let data_item = bitmap
+            .data(&mut self.filesystem_reader, "")
+            .ok_or("$Bitmap does not have a data stream")??;
+        let data_value = data_item
+            .to_attribute()?
+            .value(&mut self.filesystem_reader)?;
+        let start = first / 8;
+        let mut bits = vec![0u8; ((first + count).div_ceil(8) - start) as usize];
+        let mut data_value = data_value.attach(&mut self.filesystem_reader);
+        data_value.seek(SeekFrom::Start(start))?;
+        data_value.read_exact(&mut bits)?;
+
+        Ok((first..first + count)
+            .any(|cluster| bits[(cluster / 8 - start) as usize] & (1 << (cluster % 8)) != 0))
+    }
+
+    /// Reads every file from `$MFT`, in use or deleted, a file is listed once per hard link.
+    fn read_mft_files(&mut self) -> Result<Vec<MftFile>, Box<dyn Error>> {
+        let mft = self.ntfs.file(
+            &mut self.filesystem_reader,
+            KnownNtfsFileRecordNumber::MFT as u64,
+        )?;
+        let mft_size = mft
+            .data(&mut self.filesystem_reader, "")
+            .ok_or("$MFT does not have a data stream")??
+            .to_attribute()?
+            .value_length();
+        let record_count = mft_size / self.ntfs.file_record_size() as u64;
+
+        // deleted directories are kept too, the paths of files deleted with them go through them
+        let mut directories: HashMap<u64, MftDirectory> = HashMap::new();
+        let mut files: Vec<(u64, bool, (u64, u16), String)> = Vec::new();
+        for record in 0..record_count {
+            let file = match self.ntfs.file(&mut self.filesystem_reader, record) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+            // the attributes of an extension record are read through the record they extend
+            if !matches!(base_record(&file, &mut self.filesystem_reader), Ok(0)) {
+                continue;
+            }
+            let in_use = file.flags().contains(NtfsFileFlags::IN_USE);
+            let names = file_names(&file, &mut self.filesystem_reader);
+            if file.is_directory() {
+                if let Some((parent, name)) = names.into_iter().next() {
+                    directories.insert(
+                        record,
+                        MftDirectory {
+                            parent,
+                            name,
+                            sequence: file.sequence_number(),
+                            in_use,
+                        },
+                    );
+                }
             } else {
-                current_directory.push(file);
+                files.extend(
+                    names
+                        .into_iter()
+                        .map(|(parent, name)| (record, in_use, parent, name)),
+                );
             }
         }
+
+        let mut resolved = HashMap::new();
+        let mut mft_files = Vec::new();
+        for (record, in_use, parent, name) in files {
+            let path = match directory_path(parent, &directories, &mut resolved) {
+                Some(directory) if directory.is_empty() => name,
+                Some(directory) => format!("{0}\\{1}", directory, name),
+                // a deleted file whose directory is gone, or was reused for another one, is
+                // listed at the root
+                None if !in_use => name,
+                None => continue,
+            };
+            mft_files.push(MftFile {
+                record,
+                path,
+                in_use,
+            });
+        }
+        Ok(mft_files)
     }
+}
 
-    Err("File not found".into())
+/// The path a deleted file is stored under in the collection, `C:\$Deleted\42\Users\tool.exe`
+/// becomes `deleted/C/42/Users/tool.exe`.
+pub fn deleted_archive_path(path: &str) -> String {
+    let path = path.replacen(&format!("\\{}", DELETED_DIRECTORY), "", 1);
+    format!("deleted/{}", path.replace(':', "").replace('\\', "/"))
 }
 
-pub fn does_file_exist(drive_letter: String, file_path: String) -> Result<bool, Box<dyn Error>> {
-    let mut drive = open_drive(drive_letter.to_string())?;
-    match open_file(file_path, &mut drive.filesystem_reader, &drive.ntfs) {
-        Ok(_) => Ok(true),
-        Err(_) => Ok(false),
+/// A file found by reading `$MFT`.
+struct MftFile {
+    record: u64,
+    /// The path relative to the volume root, rebuilt from the parent directory references.
+    path: String,
+    in_use: bool,
+}
+
+/// Whether a file is the `$J` stream of the change journal, by name as the journal is found
+/// under `$Extend` on the live volume and under `$Deleted` when its record was freed.
+fn is_change_journal(resolved: &ResolvedFile) -> bool {
+    resolved
+        .stream_name
+        .eq_ignore_ascii_case(CHANGE_JOURNAL_STREAM)
+        && resolved
+            .path
+            .rsplit(['\\', '/'])
+            .next()
+            .is_some_and(|name| name.eq_ignore_ascii_case(CHANGE_JOURNAL))
+}
+
+/// Returns the MFT record number of a path under `DELETED_DIRECTORY`, e.g. 42 for
+/// `C:\$Deleted\42\tool.exe`.
+pub fn deleted_record(path: &str) -> Option<u64> {
+    let mut components = path
+        .split(['\\', '/'])
+        .filter(|component| !component.is_empty())
+        .skip_while(|component| component.len() == 2 && component.ends_with(':'));
+    if components.next()? != DELETED_DIRECTORY {
+        return None;
     }
+    components.next()?.parse().ok()
 }
 
-pub fn get_lastmodified(file_path: String) -> Result<NaiveDateTime, Box<dyn Error>> {
-    let drive_letter = file_path.chars().next().ok_or("Invalid path")?;
-    let mut drive = open_drive(drive_letter.to_string())?;
-    let file = open_file(file_path, &mut drive.filesystem_reader, &drive.ntfs)?;
+/// Returns the record an extension record belongs to, or 0 for a base record. The reference is
+/// kept in the record header, which the `ntfs` crate does not expose.
+fn base_record<T>(file: &NtfsFile, filesystem_reader: &mut T) -> io::Result<u64>
+where
+    T: Read + Seek,
+{
+    let position = file
+        .position()
+        .value()
+        .ok_or_else(|| io::Error::other("The record has no position"))?;
+    let mut reference = [0u8; 8];
+    filesystem_reader.seek(SeekFrom::Start(position.get() + BASE_RECORD_OFFSET))?;
+    filesystem_reader.read_exact(&mut reference)?;
+    Ok(u64::from_le_bytes(reference) & RECORD_NUMBER_MASK)
+}
 
+/// Returns the parent directory reference (record and sequence number) and name of every hard
+/// link of a file, the 8.3 names that duplicate a long name are left out. Names kept in
+/// extension records are found through the `$ATTRIBUTE_LIST`.
+fn file_names<T>(file: &NtfsFile, filesystem_reader: &mut T) -> Vec<((u64, u16), String)>
+where
+    T: Read + Seek,
+{
+    let mut names = Vec::new();
     let mut attributes = file.attributes();
+    while let Some(attribute_item) = attributes.next(filesystem_reader) {
+        let Ok(attribute_item) = attribute_item else {
+            break;
+        };
+        let Ok(attribute) = attribute_item.to_attribute() else {
+            continue;
+        };
+        if !matches!(attribute.ty(), Ok(NtfsAttributeType::FileName)) {
+            continue;
+        }
+        let Ok(file_name) = attribute.structured_value::<_, NtfsFileName>(filesystem_reader) else {
+            continue;
+        };
+        if file_name.namespace() == NtfsFileNamespace::Dos {
+            continue;
+        }
+        let parent = file_name.parent_directory_reference();
+        names.push((
+            (parent.file_record_number(), parent.sequence_number()),
+            file_name.name().to_string_lossy(),
+        ));
+    }
+    names
+}
 
-    while let Some(attribute_item) = attributes.next(&mut drive.filesystem_reader) {
-        let attribute_item = attribute_item?;
-        let attribute = attribute_item.to_attribute()?;
+/// Where the data of a file is stored.
+enum DataClusters {
+    /// In the file record itself.
+    Resident,
+    /// In these clusters, the first one and the count of every data run.
+    Runs(Vec<(u64, u64)>),
+    /// In runs that continue in extension records.
+    Elsewhere,
+}
+
+/// A directory read from `$MFT`, kept to rebuild the paths of the files in it.
+pub(crate) struct MftDirectory {
+    /// The record and sequence number of its parent directory.
+    pub(crate) parent: (u64, u16),
+    pub(crate) name: String,
+    pub(crate) sequence: u16,
+    pub(crate) in_use: bool,
+}
 
-        if let Ok(NtfsAttributeType::StandardInformation) = attribute.ty() {
-            let std_info = attribute.resident_structured_value::<NtfsStandardInformation>()?;
-            let file_time = FileTime::from(std_info.mft_record_modification_time().nt_timestamp())
-                .to_unix_time_secs();
-            let modified_timestamp = Utc
-                .timestamp_opt(file_time, 0)
-                .single()
-                .ok_or("Invalid timestamp")?;
-            return Ok(modified_timestamp.naive_utc());
+impl MftDirectory {
+    /// Whether a reference with `sequence` is to this directory rather than to an earlier one
+    /// whose record was reused. Windows increases the sequence number of a record when it is
+    /// freed, so a deleted directory is one past the references made while it existed.
+    fn is_referenced_by(&self, sequence: u16) -> bool {
+        self.sequence == sequence || (!self.in_use && self.sequence == sequence.wrapping_add(1))
+    }
+}
+
+/// Returns the path of a directory relative to the volume root from a reference to it (record
+/// and sequence number), or `None` when it is not connected to the root or the record now
+/// holds a different directory.
+pub(crate) fn directory_path(
+    reference: (u64, u16),
+    directories: &HashMap<u64, MftDirectory>,
+    resolved: &mut HashMap<u64, Option<String>>,
+) -> Option<String> {
+    let (record, sequence) = reference;
+    if record == KnownNtfsFileRecordNumber::RootDirectory as u64 {
+        return Some(String::new());
+    }
+    let directory = directories.get(&record)?;
+    if !directory.is_referenced_by(sequence) {
+        return None;
+    }
+    if let Some(path) = resolved.get(&record) {
+        return path.clone();
+    }
+    // a directory that is still being resolved is its own ancestor, which breaks the loop
+    resolved.insert(record, None);
+    let name = &directory.name;
+    let path = directory_path(directory.parent, directories, resolved).map(|parent_path| {
+        if parent_path.is_empty() {
+            name.clone()
+        } else {
+            format!("{0}\\{1}", parent_path, name)
         }
+    });
+    resolved.insert(record, path.clone());
+    path
+}
+
+/// Returns the length of each data run of a stream and whether it is sparse, in stream order.
+/// A resident stream is a single allocated run. The runs of a stream too large for one file
+/// record are spread over several attributes, found through the `$ATTRIBUTE_LIST`.
+fn stream_runs<'n, T>(
+    file: &NtfsFile<'n>,
+    attribute: &NtfsAttribute<'n, '_>,
+    filesystem_reader: &mut T,
+    ntfs: &'n Ntfs,
+) -> Result<Vec<(u64, bool)>, Box<dyn Error>>
+where
+    T: Read + Seek,
+{
+    let mut runs = Vec::new();
+    let mut add_runs = |value: NtfsAttributeValue| -> Result<(), Box<dyn Error>> {
+        match value {
+            NtfsAttributeValue::Resident(value) => runs.push((value.len(), false)),
+            NtfsAttributeValue::NonResident(value) => {
+                for data_run in value.data_runs() {
+                    let data_run = data_run?;
+                    runs.push((
+                        data_run.allocated_size(),
+                        data_run.data_position().value().is_none(),
+                    ));
+                }
+            }
+            NtfsAttributeValue::AttributeListNonResident(_) => {
+                return Err("Nested attribute lists are not supported".into())
+            }
+        }
+        Ok(())
+    };
+
+    let value = attribute.value(filesystem_reader)?;
+    if !matches!(value, NtfsAttributeValue::AttributeListNonResident(_)) {
+        add_runs(value)?;
+        return Ok(runs);
     }
 
-    Err("No standard information attribute found".into())
+    let ty = attribute.ty()?;
+    let name = attribute.name()?.to_string_lossy();
+    for list_attribute in file.attributes_raw() {
+        let list_attribute = list_attribute?;
+        if list_attribute.ty()? != NtfsAttributeType::AttributeList {
+            continue;
+        }
+        let list = list_attribute.structured_value::<_, NtfsAttributeList>(filesystem_reader)?;
+        let mut entries = list.entries();
+        while let Some(entry) = entries.next(filesystem_reader) {
+            let entry = entry?;
+            if entry.ty()? != ty || entry.name().to_string_lossy() != name {
+                continue;
+            }
+            let entry_file = entry.to_file(ntfs, filesystem_reader)?;
+            let entry_attribute = entry.to_attribute(&entry_file)?;
+            add_runs(entry_attribute.value(filesystem_reader)?)?;
+        }
+    }
+    Ok(runs)
+}
+
+/// Replaces every `path:*` artefact with a `path:stream` artefact for each named stream of the
+/// file, files without alternate data streams are dropped.
+pub fn expand_streams<F>(files: Vec<String>, mut list_streams: F) -> Vec<String>
+where
+    F: FnMut(&str) -> Result<Vec<String>, Box<dyn Error>>,
+{
+    let mut expanded = Vec::new();
+    for file in files {
+        let (path, stream) = parse_stream(&file);
+        if stream != ALL_STREAMS {
+            expanded.push(file);
+            continue;
+        }
+        match list_streams(&path) {
+            Ok(streams) => {
+                expanded.extend(streams.iter().map(|name| format!("{0}:{1}", path, name)));
+            }
+            Err(e) => println!("Failed to list the streams of {0}: {1}", path, e),
+        }
+    }
+    expanded
+}
+
+#[allow(clippy::too_many_arguments)]
+fn walk_directory<'n, T, F>(
+    directory: &NtfsFile<'n>,
+    path: &str,
+    depth: usize,
+    max_depth: usize,
+    visited: &mut HashSet<u64>,
+    filesystem_reader: &mut T,
+    ntfs: &'n Ntfs,
+    found: &mut F,
+) where
+    T: Read + Seek,
+    F: FnMut(&str),
+{
+    if depth > max_depth {
+        return;
+    }
+    let index = match directory.directory_index(filesystem_reader) {
+        Ok(index) => index,
+        Err(_) => return,
+    };
+
+    let mut subdirectories = Vec::new();
+    let mut entries = index.entries();
+    while let Some(entry) = entries.next(filesystem_reader) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => break,
+        };
+        let file_name = match entry.key() {
+            Some(Ok(file_name)) => file_name,
+            _ => continue,
+        };
+        // every long name has a matching 8.3 name in the index, only report it once
+        if file_name.namespace() == NtfsFileNamespace::Dos {
+            continue;
+        }
+        let name = file_name.name().to_string_lossy();
+        if name == "." {
+            continue;
+        }
+        let entry_path = format!("{0}\\{1}", path, name);
+        if file_name.is_directory() {
+            subdirectories.push((entry_path, entry.file_reference()));
+        } else {
+            found(&entry_path);
+        }
+    }
+
+    for (subdirectory_path, file_reference) in subdirectories {
+        if !visited.insert(file_reference.file_record_number()) {
+            continue;
+        }
+        if let Ok(subdirectory) = file_reference.to_file(ntfs, filesystem_reader) {
+            walk_directory(
+                &subdirectory,
+                &subdirectory_path,
+                depth + 1,
+                max_depth,
+                visited,
+                filesystem_reader,
+                ntfs,
+                found,
+            );
+        }
+    }
+}
+
+fn format_ntfs_time(time: NtfsTime) -> Option<String> {
+    format_filetime(time.nt_timestamp())
+}
+
+/// A mounted volume of the live host, raw volumes can only be read in whole sectors on Windows.
+#[cfg(target_os = "windows")]
+type VolumeReader = BufReader<SectorReader<File>>;
+
+/// Volumes of the live host opened so far. Opening one reads its boot sector and upcase table,
+/// which is far too slow to repeat for every artefact, so each volume is opened once and kept
+/// for the rest of the collection.
+#[cfg(target_os = "windows")]
+static DRIVES: Mutex<Vec<(String, Drive<VolumeReader>)>> = Mutex::new(Vec::new());
+
+#[cfg(target_os = "windows")]
+fn open_drive(drive_letter: &str) -> Result<Drive<VolumeReader>, Box<dyn Error>> {
+    let volume_path = format!("\\\\.\\{}:", drive_letter);
+    let volume = File::open(Path::new(&volume_path))?;
+    let sector_reader = SectorReader::new(volume, 4096)?;
+    Drive::new(BufReader::new(sector_reader))
+}
+
+/// Runs `action` on the cached volume for `drive_letter`, opening it on first use.
+#[cfg(target_os = "windows")]
+fn with_drive<F, R>(drive_letter: &str, action: F) -> Result<R, Box<dyn Error>>
+where
+    F: FnOnce(&mut Drive<VolumeReader>) -> Result<R, Box<dyn Error>>,
+{
+    let mut drives = DRIVES
+        .lock()
+        .map_err(|_| "The NTFS volume cache is poisoned")?;
+    let drive_letter = drive_letter.to_uppercase();
+    let index = match drives
+        .iter()
+        .position(|(letter, _)| *letter == drive_letter)
+    {
+        Some(index) => index,
+        None => {
+            let drive = open_drive(&drive_letter)?;
+            drives.push((drive_letter, drive));
+            drives.len() - 1
+        }
+    };
+    action(&mut drives[index].1)
+}
+
+#[cfg(target_os = "windows")]
+fn get_drive_letter(file_path: &str) -> Result<String, Box<dyn Error>> {
+    Ok(file_path.chars().next().ok_or("Invalid path")?.to_string())
+}
+
+#[cfg(target_os = "windows")]
+pub fn copy_file<W>(
+    file_path: String,
+    writer: W,
+    keep_sparse: bool,
+) -> Result<CopiedStream, Box<dyn Error>>
+where
+    W: Write,
+{
+    with_drive(&get_drive_letter(&file_path)?, |drive| {
+        drive.copy_file(&file_path, writer, keep_sparse)
+    })
+}
+
+#[cfg(target_os = "windows")]
+pub fn does_file_exist(drive_letter: String, file_path: String) -> Result<bool, Box<dyn Error>> {
+    with_drive(&drive_letter, |drive| Ok(drive.does_file_exist(&file_path)))
+}
+
+#[cfg(target_os = "windows")]
+pub fn walk_mft<F>(
+    drive_letter: &str,
+    max_depth: usize,
+    found: &mut F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(&str),
+{
+    with_drive(drive_letter, |drive| {
+        drive.walk_mft(&format!("{}:", drive_letter), max_depth, found)
+    })
+}
+
+#[cfg(target_os = "windows")]
+pub fn walk_deleted<F>(
+    drive_letter: &str,
+    max_depth: usize,
+    found: &mut F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(&str, &str),
+{
+    with_drive(drive_letter, |drive| {
+        drive.walk_deleted(&format!("{}:", drive_letter), max_depth, found)
+    })
+}
+
+#[cfg(target_os = "windows")]
+pub fn get_recovery_confidence(
+    file_path: String,
+) -> Result<Option<RecoveryConfidence>, Box<dyn Error>> {
+    with_drive(&get_drive_letter(&file_path)?, |drive| {
+        drive.recovery_confidence(&file_path)
+    })
+}
+
+#[cfg(target_os = "windows")]
+pub fn list_streams(file_path: String) -> Result<Vec<String>, Box<dyn Error>> {
+    with_drive(&get_drive_letter(&file_path)?, |drive| {
+        drive.list_streams(&file_path)
+    })
+}
+
+#[cfg(target_os = "windows")]
+pub fn get_lastmodified(file_path: String) -> Result<NaiveDateTime, Box<dyn Error>> {
+    with_drive(&get_drive_letter(&file_path)?, |drive| {
+        drive.get_lastmodified(&file_path)
+    })
+}
+
+#[cfg(target_os = "windows")]
+pub fn get_metadata(file_path: String) -> Result<FileMetadata, Box<dyn Error>> {
+    with_drive(&get_drive_letter(&file_path)?, |drive| {
+        drive.get_metadata(&file_path)
+    })
 }
 
 /// `SectorReader` encapsulates any reader and only performs read and seek operations on it
@@ -221,10 +1084,7 @@ where
 {
     pub fn new(inner: R, sector_size: usize) -> io::Result<Self> {
         if !sector_size.is_power_of_two() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "sector_size is not a power of two",
-            ));
+            return Err(io::Error::other("sector_size is not a power of two"));
         }
 
         Ok(Self {
@@ -282,8 +1142,7 @@ where
                 // This is unsupported, because it's not safely possible under Windows.
                 // We cannot seek to the end to determine the raw partition size.
                 // Which makes it impossible to set `self.stream_position`.
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
+                return Err(io::Error::other(
                     "SeekFrom::End is unsupported for SectorReader",
                 ));
             }
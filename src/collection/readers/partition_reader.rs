@@ -0,0 +1,250 @@
+use crate::collection::parsers::bytes::{le_u32, le_u64};
+use std::{
+    cmp,
+    error::Error,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const GPT_PROTECTIVE: u8 = 0xEE;
+const EXTENDED_TYPES: [u8; 3] = [0x05, 0x0F, 0x85];
+const SECTOR_SIZE: u64 = 512;
+const GPT_HEADER_SIZE: usize = 92;
+/// Partition entries are 128 bytes times a power of two, nothing writes them larger than this.
+const MAX_GPT_ENTRY_SIZE: usize = 4096;
+// extended partitions are a linked list, stop following it if it is corrupt or loops
+const MAX_LOGICAL_PARTITIONS: usize = 128;
+
+#[derive(Debug, Clone)]
+pub struct Partition {
+    /// The partition number, as the kernel would name it (`sda1`, `sda5` for the first logical).
+    pub number: usize,
+    pub offset: u64,
+    pub size: u64,
+    /// The MBR type byte or the GPT partition name.
+    pub description: String,
+}
+
+fn read_at<R>(reader: &mut R, offset: u64, length: usize) -> io::Result<Vec<u8>>
+where
+    R: Read + Seek,
+{
+    let mut buf = vec![0u8; length];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Reads the GPT or MBR partition table at the start of a disk. An empty list is returned when
+/// the disk has no partition table.
+pub fn read_partition_table<R>(reader: &mut R) -> Result<Vec<Partition>, Box<dyn Error>>
+where
+    R: Read + Seek,
+{
+    let mbr = read_at(reader, 0, SECTOR_SIZE as usize)?;
+    if mbr[510..512] != MBR_SIGNATURE {
+        return Ok(Vec::new());
+    }
+
+    let entries: Vec<&[u8]> = mbr[446..510].chunks_exact(16).collect();
+    if entries.iter().any(|entry| entry[4] == GPT_PROTECTIVE) {
+        // the GPT header is in the second logical block, which is 4096 bytes on 4Kn disks
+        for sector_size in [SECTOR_SIZE, 4096] {
+            if let Ok(partitions) = read_gpt(reader, sector_size) {
+                return Ok(partitions);
+            }
+        }
+        return Err("Protective MBR found but the GPT header is missing or invalid".into());
+    }
+
+    let mut partitions = Vec::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let partition_type = entry[4];
+        let start = le_u32(entry, 8) as u64;
+        let sectors = le_u32(entry, 12) as u64;
+        if partition_type == 0 || sectors == 0 {
+            continue;
+        }
+        if EXTENDED_TYPES.contains(&partition_type) {
+            read_logical_partitions(reader, start, &mut partitions)?;
+            continue;
+        }
+        partitions.push(Partition {
+            number: index + 1,
+            offset: start * SECTOR_SIZE,
+            size: sectors * SECTOR_SIZE,
+            description: format!("MBR type 0x{:02x}", partition_type),
+        });
+    }
+    Ok(partitions)
+}
+
+fn read_logical_partitions<R>(
+    reader: &mut R,
+    extended_start: u64,
+    partitions: &mut Vec<Partition>,
+) -> Result<(), Box<dyn Error>>
+where
+    R: Read + Seek,
+{
+    let mut ebr_start = extended_start;
+    for number in 5..5 + MAX_LOGICAL_PARTITIONS {
+        let ebr = read_at(reader, ebr_start * SECTOR_SIZE, SECTOR_SIZE as usize)?;
+        if ebr[510..512] != MBR_SIGNATURE {
+            break;
+        }
+        // the first entry is the logical partition relative to this EBR, the second points to
+        // the next EBR relative to the start of the extended partition
+        let logical = &ebr[446..462];
+        let next = &ebr[462..478];
+        let sectors = le_u32(logical, 12) as u64;
+        if logical[4] != 0 && sectors != 0 {
+            partitions.push(Partition {
+                number,
+                offset: (ebr_start + le_u32(logical, 8) as u64) * SECTOR_SIZE,
+                size: sectors * SECTOR_SIZE,
+                description: format!("MBR type 0x{:02x}", logical[4]),
+            });
+        }
+        let next_start = le_u32(next, 8) as u64;
+        if next[4] == 0 || next_start == 0 {
+            break;
+        }
+        ebr_start = extended_start + next_start;
+    }
+    Ok(())
+}
+
+fn read_gpt<R>(reader: &mut R, sector_size: u64) -> Result<Vec<Partition>, Box<dyn Error>>
+where
+    R: Read + Seek,
+{
+    let header = read_at(reader, sector_size, GPT_HEADER_SIZE)?;
+    if &header[0..8] != GPT_SIGNATURE {
+        return Err("Invalid GPT header".into());
+    }
+    let header_size = le_u32(&header, 12) as usize;
+    if header_size < GPT_HEADER_SIZE || header_size as u64 > sector_size {
+        return Err("Invalid GPT header size".into());
+    }
+    let mut header = read_at(reader, sector_size, header_size)?;
+    let checksum = le_u32(&header, 16);
+    header[16..20].fill(0);
+    if crc32(&header) != checksum {
+        return Err("GPT header checksum mismatch".into());
+    }
+    let entries_lba = le_u64(&header, 72);
+    let entry_count = cmp::min(le_u32(&header, 80), 1024) as usize;
+    let entry_size = le_u32(&header, 84) as usize;
+    if !(128..=MAX_GPT_ENTRY_SIZE).contains(&entry_size) || !entry_size.is_power_of_two() {
+        return Err("Invalid GPT partition entry size".into());
+    }
+
+    let table_offset = entries_lba
+        .checked_mul(sector_size)
+        .ok_or("Invalid GPT partition entry location")?;
+    let table = read_at(reader, table_offset, entry_count * entry_size)?;
+    let mut partitions = Vec::new();
+    for (index, entry) in table.chunks_exact(entry_size).enumerate() {
+        if entry[0..16].iter().all(|byte| *byte == 0) {
+            continue;
+        }
+        let first_lba = le_u64(entry, 32);
+        let last_lba = le_u64(entry, 40);
+        // a corrupt entry whose extent does not fit in 64 bits is skipped
+        let extent = last_lba
+            .checked_sub(first_lba)
+            .and_then(|sectors| sectors.checked_add(1))
+            .and_then(|sectors| sectors.checked_mul(sector_size))
+            .zip(first_lba.checked_mul(sector_size));
+        let Some((size, offset)) = extent else {
+            continue;
+        };
+        let name: Vec<u16> = entry[56..128]
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .take_while(|c| *c != 0)
+            .collect();
+        partitions.push(Partition {
+            number: index + 1,
+            offset,
+            size,
+            description: String::from_utf16_lossy(&name),
+        });
+    }
+    Ok(partitions)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+/// `PartitionReader` exposes a window of the underlying reader, so a partition of a disk can be
+/// read as though it were a standalone filesystem image.
+pub struct PartitionReader<R>
+where
+    R: Read + Seek,
+{
+    inner: R,
+    offset: u64,
+    size: u64,
+    position: u64,
+}
+
+impl<R> PartitionReader<R>
+where
+    R: Read + Seek,
+{
+    pub fn new(inner: R, offset: u64, size: u64) -> Self {
+        PartitionReader {
+            inner,
+            offset,
+            size,
+            position: 0,
+        }
+    }
+}
+
+impl<R> Read for PartitionReader<R>
+where
+    R: Read + Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.size.saturating_sub(self.position);
+        let length = cmp::min(buf.len() as u64, remaining) as usize;
+        if length == 0 {
+            return Ok(0);
+        }
+        self.inner
+            .seek(SeekFrom::Start(self.offset + self.position))?;
+        let read = self.inner.read(&mut buf[..length])?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R> Seek for PartitionReader<R>
+where
+    R: Read + Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.size.checked_add_signed(n),
+            SeekFrom::Current(n) => self.position.checked_add_signed(n),
+        };
+        match new_pos {
+            Some(n) => {
+                self.position = n;
+                Ok(n)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
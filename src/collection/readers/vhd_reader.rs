@@ -0,0 +1,188 @@
+use crate::collection::parsers::bytes::{be_u32, be_u64};
+use std::{
+    cmp,
+    error::Error,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+pub const VHD_SIGNATURE: &[u8; 8] = b"conectix";
+const DYNAMIC_SIGNATURE: &[u8; 8] = b"cxsparse";
+const FOOTER_SIZE: u64 = 512;
+const SECTOR_SIZE: u64 = 512;
+const FIXED_DISK: u32 = 2;
+const DYNAMIC_DISK: u32 = 3;
+const DIFFERENCING_DISK: u32 = 4;
+const UNALLOCATED: u32 = 0xFFFFFFFF;
+
+fn read_at(file: &mut File, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0u8; length];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Returns whether the file ends with a VHD footer.
+pub fn is_vhd(file: &mut File) -> bool {
+    let Ok(length) = file.seek(SeekFrom::End(0)) else {
+        return false;
+    };
+    length >= FOOTER_SIZE
+        && read_at(file, length - FOOTER_SIZE, 8)
+            .map(|signature| signature == VHD_SIGNATURE)
+            .unwrap_or(false)
+}
+
+/// `VhdReader` reads the disk inside a fixed or dynamic Virtual PC / Hyper-V `.vhd` file.
+/// Blocks that were never allocated in a dynamic disk read as zeros. Differencing disks, which
+/// only hold the blocks changed since their parent, are refused.
+pub struct VhdReader {
+    file: File,
+    size: u64,
+    /// Block size and block allocation table of a dynamic disk, empty for a fixed disk.
+    block_size: u64,
+    block_table: Vec<u32>,
+    bitmap_size: u64,
+    position: u64,
+}
+
+impl VhdReader {
+    pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::open(path)?;
+        let length = file.seek(SeekFrom::End(0))?;
+        if length < FOOTER_SIZE {
+            return Err(format!("{} is too small to be a VHD", path).into());
+        }
+        let footer = read_at(&mut file, length - FOOTER_SIZE, FOOTER_SIZE as usize)?;
+        if &footer[0..8] != VHD_SIGNATURE {
+            return Err(format!("{} does not have a VHD footer", path).into());
+        }
+        let size = be_u64(&footer, 48);
+
+        let mut reader = VhdReader {
+            file,
+            size,
+            block_size: 0,
+            block_table: Vec::new(),
+            bitmap_size: 0,
+            position: 0,
+        };
+        match be_u32(&footer, 60) {
+            FIXED_DISK => {
+                if length - FOOTER_SIZE < size {
+                    return Err(format!("{} is shorter than its disk size", path).into());
+                }
+            }
+            DYNAMIC_DISK => {
+                let header = read_at(&mut reader.file, be_u64(&footer, 16), 1024)?;
+                if &header[0..8] != DYNAMIC_SIGNATURE {
+                    return Err(format!("{} has an invalid dynamic disk header", path).into());
+                }
+                let table_offset = be_u64(&header, 16);
+                reader.block_size = be_u32(&header, 32) as u64;
+                if reader.block_size == 0 || !reader.block_size.is_multiple_of(SECTOR_SIZE) {
+                    return Err(format!("{} has an invalid block size", path).into());
+                }
+                // only the blocks of the disk size are read, and their entries must be in the file
+                let entry_count =
+                    cmp::min(be_u32(&header, 28) as u64, size.div_ceil(reader.block_size));
+                if table_offset
+                    .checked_add(entry_count * 4)
+                    .is_none_or(|end| end > length)
+                {
+                    return Err(format!(
+                        "{} has a block allocation table past the end of the file",
+                        path
+                    )
+                    .into());
+                }
+                let table = read_at(&mut reader.file, table_offset, entry_count as usize * 4)?;
+                reader.block_table = table
+                    .chunks_exact(4)
+                    .map(|entry| be_u32(entry, 0))
+                    .collect();
+                // every block starts with a bitmap of its sectors, padded to a whole sector
+                let bitmap_bytes = reader.block_size / SECTOR_SIZE / 8;
+                reader.bitmap_size = bitmap_bytes.div_ceil(SECTOR_SIZE) * SECTOR_SIZE;
+            }
+            DIFFERENCING_DISK => {
+                // the header of a differencing disk names the parent its unchanged blocks are in
+                let parent = read_at(&mut reader.file, be_u64(&footer, 16), 1024)
+                    .ok()
+                    .filter(|header| &header[0..8] == DYNAMIC_SIGNATURE)
+                    .map(|header| {
+                        let name: Vec<u16> = header[64..576]
+                            .chunks_exact(2)
+                            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                            .take_while(|unit| *unit != 0)
+                            .collect();
+                        String::from_utf16_lossy(&name)
+                    })
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or("an unknown parent".to_string());
+                return Err(format!(
+                    "{0} is a differencing VHD of {1}, only fixed and dynamic VHDs are supported, merge it into its parent first",
+                    path, parent
+                )
+                .into());
+            }
+            disk_type => return Err(format!("Unknown VHD disk type {}", disk_type).into()),
+        }
+        Ok(reader)
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl Read for VhdReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.size.saturating_sub(self.position);
+        let mut length = cmp::min(buf.len() as u64, remaining) as usize;
+        if length == 0 {
+            return Ok(0);
+        }
+
+        if self.block_size == 0 {
+            self.file.seek(SeekFrom::Start(self.position))?;
+            let read = self.file.read(&mut buf[..length])?;
+            self.position += read as u64;
+            return Ok(read);
+        }
+
+        let block = (self.position / self.block_size) as usize;
+        let offset = self.position % self.block_size;
+        length = cmp::min(length, (self.block_size - offset) as usize);
+        match self.block_table.get(block) {
+            Some(&sector) if sector != UNALLOCATED => {
+                let start = sector as u64 * SECTOR_SIZE + self.bitmap_size + offset;
+                self.file.seek(SeekFrom::Start(start))?;
+                self.file.read_exact(&mut buf[..length])?;
+            }
+            _ => buf[..length].fill(0),
+        }
+        self.position += length as u64;
+        Ok(length)
+    }
+}
+
+impl Seek for VhdReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.size.checked_add_signed(n),
+            SeekFrom::Current(n) => self.position.checked_add_signed(n),
+        };
+        match new_pos {
+            Some(n) => {
+                self.position = n;
+                Ok(n)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use crate::collection::parsers::bytes::{le_u16, le_u32, le_u64};
+use std::{
+    cmp,
+    error::Error,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+pub const VHDX_SIGNATURE: &[u8; 8] = b"vhdxfile";
+const HEADER_OFFSETS: [u64; 2] = [0x10000, 0x20000];
+const REGION_TABLE_OFFSET: u64 = 0x30000;
+const MAX_REGIONS: usize = 2047;
+const MEGABYTE: u64 = 0x100000;
+/// The metadata table header, followed by its 32 byte entries.
+const METADATA_HEADER_SIZE: usize = 32;
+
+// GUIDs as they are stored on disk, the first three fields are little endian
+// 2DC27766-F623-4200-9D64-115E9BFD4A08
+const BAT_REGION: [u8; 16] = [
+    0x66, 0x77, 0xC2, 0x2D, 0x23, 0xF6, 0x00, 0x42, 0x9D, 0x64, 0x11, 0x5E, 0x9B, 0xFD, 0x4A, 0x08,
+];
+// 8B7CA206-4790-4B9A-B8FE-575F050F886E
+const METADATA_REGION: [u8; 16] = [
+    0x06, 0xA2, 0x7C, 0x8B, 0x90, 0x47, 0x9A, 0x4B, 0xB8, 0xFE, 0x57, 0x5F, 0x05, 0x0F, 0x88, 0x6E,
+];
+// CAA16737-FA36-4D43-B3B6-33F0AA44E76B
+const FILE_PARAMETERS: [u8; 16] = [
+    0x37, 0x67, 0xA1, 0xCA, 0x36, 0xFA, 0x43, 0x4D, 0xB3, 0xB6, 0x33, 0xF0, 0xAA, 0x44, 0xE7, 0x6B,
+];
+// 2FA54224-CD1B-4876-B211-5DBED83BF4B8
+const VIRTUAL_DISK_SIZE: [u8; 16] = [
+    0x24, 0x42, 0xA5, 0x2F, 0x1B, 0xCD, 0x76, 0x48, 0xB2, 0x11, 0x5D, 0xBE, 0xD8, 0x3B, 0xF4, 0xB8,
+];
+// 8141BF1D-A96F-4709-BA47-F233A8FAAB5F
+const LOGICAL_SECTOR_SIZE: [u8; 16] = [
+    0x1D, 0xBF, 0x41, 0x81, 0x6F, 0xA9, 0x09, 0x47, 0xBA, 0x47, 0xF2, 0x33, 0xA8, 0xFA, 0xAB, 0x5F,
+];
+
+// A8D35F2D-B30B-454D-ABF7-D3D84834AB0C
+const PARENT_LOCATOR: [u8; 16] = [
+    0x2D, 0x5F, 0xD3, 0xA8, 0x0B, 0xB3, 0x4D, 0x45, 0xAB, 0xF7, 0xD3, 0xD8, 0x48, 0x34, 0xAB, 0x0C,
+];
+/// The parent locator keys that hold a path, in the order they are tried.
+const PARENT_PATH_KEYS: [&str; 3] = ["relative_path", "absolute_win32_path", "volume_path"];
+
+const HAS_PARENT: u32 = 0x2;
+const BLOCK_FULLY_PRESENT: u64 = 6;
+const BLOCK_PARTIALLY_PRESENT: u64 = 7;
+
+fn read_at(file: &mut File, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0u8; length];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Reads the path of the parent disk from a parent locator, a table of UTF-16 keys and values.
+fn parent_path(locator: &[u8]) -> Option<String> {
+    let count = le_u16(locator.get(18..20)?, 0) as usize;
+    let string = |offset: usize, length: usize| -> Option<String> {
+        let units: Vec<u16> = locator
+            .get(offset..offset + length)?
+            .chunks_exact(2)
+            .map(|unit| le_u16(unit, 0))
+            .collect();
+        Some(String::from_utf16_lossy(&units))
+    };
+    let entries: Vec<(String, String)> = locator
+        .get(20..20 + count * 12)?
+        .chunks_exact(12)
+        .filter_map(|entry| {
+            Some((
+                string(le_u32(entry, 0) as usize, le_u16(entry, 8) as usize)?,
+                string(le_u32(entry, 4) as usize, le_u16(entry, 10) as usize)?,
+            ))
+        })
+        .collect();
+    PARENT_PATH_KEYS.iter().find_map(|key| {
+        entries
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.clone())
+    })
+}
+
+/// `VhdxReader` reads the disk inside a Hyper-V `.vhdx` file. Blocks that are not present in
+/// the file read as zeros. Differencing disks, which only hold the blocks changed since their
+/// parent, are refused.
+pub struct VhdxReader {
+    file: File,
+    size: u64,
+    block_size: u64,
+    chunk_ratio: u64,
+    block_table: Vec<u64>,
+    position: u64,
+}
+
+impl VhdxReader {
+    pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::open(path)?;
+        if read_at(&mut file, 0, 8)? != VHDX_SIGNATURE {
+            return Err(format!("{} is not a VHDX file", path).into());
+        }
+
+        // the header with the highest sequence number is the current one
+        let header = HEADER_OFFSETS
+            .iter()
+            .filter_map(|offset| read_at(&mut file, *offset, 80).ok())
+            .filter(|header| &header[0..4] == b"head")
+            .max_by_key(|header| le_u64(header, 8))
+            .ok_or(format!("{} does not have a valid VHDX header", path))?;
+        if header[48..64].iter().any(|byte| *byte != 0) {
+            eprintln!(
+                "{} has a log that was not replayed, recently written data may be missing",
+                path
+            );
+        }
+
+        let regions = read_at(&mut file, REGION_TABLE_OFFSET, 16 + MAX_REGIONS * 32)?;
+        if &regions[0..4] != b"regi" {
+            return Err(format!("{} does not have a valid VHDX region table", path).into());
+        }
+        let region_count = cmp::min(le_u32(&regions, 8) as usize, MAX_REGIONS);
+        let find_region = |guid: &[u8; 16]| {
+            regions[16..16 + region_count * 32]
+                .chunks_exact(32)
+                .find(|entry| &entry[0..16] == guid)
+                .map(|entry| (le_u64(entry, 16), le_u32(entry, 24) as usize))
+        };
+        let (bat_offset, bat_length) =
+            find_region(&BAT_REGION).ok_or("VHDX block allocation table region is missing")?;
+        let (metadata_offset, metadata_length) =
+            find_region(&METADATA_REGION).ok_or("VHDX metadata region is missing")?;
+
+        // both regions are read whole, so they have to be in the file
+        let length = file.seek(SeekFrom::End(0))?;
+        for (offset, region_length) in
+            [(bat_offset, bat_length), (metadata_offset, metadata_length)]
+        {
+            if offset
+                .checked_add(region_length as u64)
+                .is_none_or(|end| end > length)
+            {
+                return Err(format!("{} has a VHDX region past the end of the file", path).into());
+            }
+        }
+        if metadata_length < METADATA_HEADER_SIZE {
+            return Err(format!("{} has an invalid VHDX metadata table", path).into());
+        }
+        let metadata = read_at(&mut file, metadata_offset, metadata_length)?;
+        if &metadata[0..8] != b"metadata" {
+            return Err(format!("{} has an invalid VHDX metadata table", path).into());
+        }
+        let entry_count = le_u16(&metadata, 10) as usize;
+        // a length of zero takes the length recorded for the item
+        let find_item = |guid: &[u8; 16], length: usize| -> Result<&[u8], Box<dyn Error>> {
+            let entry = metadata[METADATA_HEADER_SIZE..]
+                .chunks_exact(32)
+                .take(entry_count)
+                .find(|entry| &entry[0..16] == guid)
+                .ok_or("VHDX metadata item is missing")?;
+            let offset = le_u32(entry, 16) as usize;
+            let length = if length == 0 {
+                le_u32(entry, 20) as usize
+            } else {
+                length
+            };
+            metadata
+                .get(offset..offset.saturating_add(length))
+                .ok_or("VHDX metadata item is outside the metadata region".into())
+        };
+
+        let parameters = find_item(&FILE_PARAMETERS, 8)?;
+        if le_u32(parameters, 4) & HAS_PARENT != 0 {
+            let parent = find_item(&PARENT_LOCATOR, 0)
+                .ok()
+                .and_then(parent_path)
+                .unwrap_or("an unknown parent".to_string());
+            return Err(format!(
+                "{0} is a differencing VHDX of {1}, only dynamic and fixed VHDXs are supported, merge it into its parent first",
+                path, parent
+            )
+            .into());
+        }
+        let block_size = le_u32(parameters, 0) as u64;
+        let size = le_u64(find_item(&VIRTUAL_DISK_SIZE, 8)?, 0);
+        let sector_size = le_u32(find_item(&LOGICAL_SECTOR_SIZE, 4)?, 0) as u64;
+        if block_size == 0 || sector_size == 0 {
+            return Err(format!("{} has an invalid block or sector size", path).into());
+        }
+
+        let table = read_at(&mut file, bat_offset, bat_length)?;
+        Ok(VhdxReader {
+            file,
+            size,
+            block_size,
+            // a sector bitmap entry follows every chunk_ratio payload entries in the table
+            chunk_ratio: (1 << 23) * sector_size / block_size,
+            block_table: table
+                .chunks_exact(8)
+                .map(|entry| le_u64(entry, 0))
+                .collect(),
+            position: 0,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl Read for VhdxReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.size.saturating_sub(self.position);
+        let mut length = cmp::min(buf.len() as u64, remaining) as usize;
+        if length == 0 {
+            return Ok(0);
+        }
+
+        let block = self.position / self.block_size;
+        let offset = self.position % self.block_size;
+        length = cmp::min(length, (self.block_size - offset) as usize);
+        let index = block + block / cmp::max(self.chunk_ratio, 1);
+        match self.block_table.get(index as usize) {
+            Some(&entry)
+                if matches!(entry & 0x7, BLOCK_FULLY_PRESENT | BLOCK_PARTIALLY_PRESENT) =>
+            {
+                let start = (entry >> 20) * MEGABYTE + offset;
+                self.file.seek(SeekFrom::Start(start))?;
+                self.file.read_exact(&mut buf[..length])?;
+            }
+            _ => buf[..length].fill(0),
+        }
+        self.position += length as u64;
+        Ok(length)
+    }
+}
+
+impl Seek for VhdxReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.size.checked_add_signed(n),
+            SeekFrom::Current(n) => self.position.checked_add_signed(n),
+        };
+        match new_pos {
+            Some(n) => {
+                self.position = n;
+                Ok(n)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
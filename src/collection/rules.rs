@@ -24,6 +24,18 @@ pub struct FileRule {
     pub rule_type: String,
     pub paths: Vec<String>,
     pub recursion_depth: usize,
+    /// Also recover deleted files matching `paths` from NTFS MFT records that are no longer in
+    /// use.
+    #[serde(default)]
+    pub deleted: bool,
+    /// Only the events with these ids are exported when EVTX files collected by the rule are
+    /// parsed, every event is when it is empty.
+    #[serde(default)]
+    pub event_ids: Vec<u32>,
+    /// Only the events of these channels are exported when EVTX files collected by the rule are
+    /// parsed, every event is when it is empty.
+    #[serde(default)]
+    pub channels: Vec<String>,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -0,0 +1,263 @@
+use super::archive::original_name;
+use super::manifest::ManifestEntry;
+use super::metadata::parse_timestamp;
+use super::parsers::mft_parser::MftEntry;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{fmt, io::Write};
+
+/// One line of a Sleuth Kit bodyfile (version 3), times are seconds since the epoch and 0 when
+/// unknown.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct BodyfileEntry {
+    pub md5: String,
+    pub name: String,
+    pub inode: String,
+    pub mode: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub atime: i64,
+    pub mtime: i64,
+    pub ctime: i64,
+    pub crtime: i64,
+}
+
+impl fmt::Display for BodyfileEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{0}|{1}|{2}|{3}|{4}|{5}|{6}|{7}|{8}|{9}|{10}",
+            self.md5,
+            self.name,
+            self.inode,
+            self.mode,
+            self.uid,
+            self.gid,
+            self.size,
+            self.atime,
+            self.mtime,
+            self.ctime,
+            self.crtime
+        )
+    }
+}
+
+fn epoch(timestamp: &Option<String>) -> i64 {
+    timestamp
+        .as_deref()
+        .and_then(parse_timestamp)
+        .map(|time| time.timestamp())
+        .unwrap_or_default()
+}
+
+/// Renders a Unix mode the way `ls -l` does, e.g. `-rw-r--r--`.
+fn mode_string(mode: u32) -> String {
+    let file_type = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o020000 => 'c',
+        0o060000 => 'b',
+        0o010000 => 'p',
+        0o140000 => 's',
+        _ => '-',
+    };
+    let mut string = String::from(file_type);
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        string.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        string.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        string.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    string
+}
+
+/// The mode Sleuth Kit gives files on NTFS, which has no permission bits.
+fn ntfs_mode(directory: bool) -> &'static str {
+    if directory {
+        "d/drwxrwxrwx"
+    } else {
+        "r/rrwxrwxrwx"
+    }
+}
+
+impl BodyfileEntry {
+    /// Builds a line from the metadata the manifest recorded for an artefact. The manifest only
+    /// holds a SHA256, so the MD5 is left as 0 like Sleuth Kit does for unhashed files.
+    pub fn from_manifest(platform: &str, entry: &ManifestEntry) -> Self {
+        let metadata = &entry.metadata;
+        let mut mtime = epoch(&metadata.modified);
+        if mtime == 0 {
+            mtime = entry.last_modified;
+        }
+        BodyfileEntry {
+            md5: "0".to_string(),
+            name: original_name(platform, &entry.original_path).to_string(),
+            inode: metadata
+                .inode
+                .map(|inode| inode.to_string())
+                .unwrap_or("0".to_string()),
+            mode: if platform == "windows" {
+                // Windows files have attribute flags rather than a mode
+                let directory = metadata.attributes.iter().any(|name| name == "directory");
+                ntfs_mode(directory).to_string()
+            } else {
+                metadata.mode.map(mode_string).unwrap_or("0".to_string())
+            },
+            uid: metadata.uid.unwrap_or_default(),
+            gid: metadata.gid.unwrap_or_default(),
+            size: entry.size,
+            atime: epoch(&metadata.accessed),
+            mtime,
+            ctime: epoch(&metadata.changed),
+            crtime: epoch(&metadata.created),
+        }
+    }
+
+    /// Builds the lines of an `$MFT` record, one with the `$STANDARD_INFORMATION` times and one
+    /// with the `$FILE_NAME` times, which is named like the file with ` ($FILE_NAME)` appended.
+    pub fn from_mft(entry: &MftEntry) -> Vec<Self> {
+        let mode = ntfs_mode(entry.directory);
+        let name = if entry.in_use {
+            entry.path.clone()
+        } else {
+            format!("{} (deleted)", entry.path)
+        };
+        let line = |name: String, times: [&Option<String>; 4]| BodyfileEntry {
+            md5: "0".to_string(),
+            name,
+            inode: entry.record.to_string(),
+            mode: mode.to_string(),
+            size: entry.size,
+            atime: epoch(times[0]),
+            mtime: epoch(times[1]),
+            ctime: epoch(times[2]),
+            crtime: epoch(times[3]),
+            ..Default::default()
+        };
+        vec![
+            line(
+                name.clone(),
+                [
+                    &entry.si_accessed,
+                    &entry.si_modified,
+                    &entry.si_mft_modified,
+                    &entry.si_created,
+                ],
+            ),
+            line(
+                format!("{} ($FILE_NAME)", name),
+                [
+                    &entry.fn_accessed,
+                    &entry.fn_modified,
+                    &entry.fn_mft_modified,
+                    &entry.fn_created,
+                ],
+            ),
+        ]
+    }
+}
+
+/// Writes a bodyfile, one line per entry.
+pub fn write_bodyfile<W>(entries: &[BodyfileEntry], mut writer: W) -> std::io::Result<()>
+where
+    W: Write,
+{
+    for entry in entries {
+        writeln!(writer, "{}", entry)?;
+    }
+    writer.flush()
+}
+
+/// A row of a mactime timeline, one per file and distinct time. `Type` tells which of the
+/// modified, accessed, changed and born times fell on that time, e.g. `m.c.`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct TimelineRow {
+    #[serde(rename = "Date")]
+    pub date: String,
+    #[serde(rename = "Size")]
+    pub size: u64,
+    #[serde(rename = "Type")]
+    pub macb: String,
+    #[serde(rename = "Mode")]
+    pub mode: String,
+    #[serde(rename = "UID")]
+    pub uid: u32,
+    #[serde(rename = "GID")]
+    pub gid: u32,
+    #[serde(rename = "Meta")]
+    pub meta: String,
+    #[serde(rename = "File Name")]
+    pub file_name: String,
+}
+
+/// Parses the start of a time window, either RFC 3339 or a UTC date and optional time such as
+/// `2024-01-31` or `2024-01-31 12:00:00`. A date alone starts at midnight.
+pub fn parse_time_bound(bound: &str) -> Result<DateTime<Utc>, String> {
+    parse_bound(bound, NaiveTime::MIN)
+}
+
+/// Parses the end of a time window like `parse_time_bound`, a date alone takes in the whole day.
+pub fn parse_time_end(bound: &str) -> Result<DateTime<Utc>, String> {
+    let end_of_day = NaiveTime::from_hms_micro_opt(23, 59, 59, 999_999).unwrap_or(NaiveTime::MIN);
+    parse_bound(bound, end_of_day)
+}
+
+fn parse_bound(bound: &str, time_of_day: NaiveTime) -> Result<DateTime<Utc>, String> {
+    if let Some(time) = parse_timestamp(bound) {
+        return Ok(time);
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(bound, format) {
+            return Ok(time.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(bound, "%Y-%m-%d")
+        .ok()
+        .map(|date| date.and_time(time_of_day).and_utc())
+        .ok_or(format!("{} is not a date or time", bound))
+}
+
+/// Turns bodyfile entries into mactime rows sorted by time, keeping only times within
+/// `start..=end` when given. Times that are unknown are left out.
+pub fn mactime(
+    entries: &[BodyfileEntry],
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> Vec<TimelineRow> {
+    let start = start.map(|time| time.timestamp()).unwrap_or(i64::MIN);
+    let end = end.map(|time| time.timestamp()).unwrap_or(i64::MAX);
+    let mut rows: Vec<(i64, TimelineRow)> = Vec::new();
+    for entry in entries {
+        let times = [entry.mtime, entry.atime, entry.ctime, entry.crtime];
+        let mut seen: Vec<i64> = Vec::new();
+        for time in times {
+            if time == 0 || time < start || time > end || seen.contains(&time) {
+                continue;
+            }
+            seen.push(time);
+            let macb: String = times
+                .iter()
+                .zip(['m', 'a', 'c', 'b'])
+                .map(|(other, flag)| if *other == time { flag } else { '.' })
+                .collect();
+            rows.push((
+                time,
+                TimelineRow {
+                    date: DateTime::from_timestamp(time, 0)
+                        .map(|time| time.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+                        .unwrap_or_default(),
+                    size: entry.size,
+                    macb,
+                    mode: entry.mode.clone(),
+                    uid: entry.uid,
+                    gid: entry.gid,
+                    meta: entry.inode.clone(),
+                    file_name: entry.name.clone(),
+                },
+            ));
+        }
+    }
+    rows.sort_by(|(a, a_row), (b, b_row)| a.cmp(b).then(a_row.file_name.cmp(&b_row.file_name)));
+    rows.into_iter().map(|(_, row)| row).collect()
+}
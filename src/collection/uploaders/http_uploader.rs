@@ -0,0 +1,534 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    cmp,
+    error::Error,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    thread,
+    time::Duration,
+};
+
+const DEFAULT_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+const DEFAULT_RETRIES: u32 = 5;
+
+pub struct S3Credentials {
+    pub access_key: String,
+    pub secret_key: String,
+    pub region: String,
+}
+
+impl S3Credentials {
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        Ok(S3Credentials {
+            access_key: std::env::var("AWS_ACCESS_KEY_ID")
+                .map_err(|_| "AWS_ACCESS_KEY_ID is not set")?,
+            secret_key: std::env::var("AWS_SECRET_ACCESS_KEY")
+                .map_err(|_| "AWS_SECRET_ACCESS_KEY is not set")?,
+            region: std::env::var("AWS_REGION").unwrap_or("us-east-1".to_string()),
+        })
+    }
+}
+
+/// Where an archive is sent to, either a plain (or pre-signed) URL that accepts a single `PUT`,
+/// or an object in an S3-compatible bucket that is written with a multipart upload.
+pub enum UploadTarget {
+    Put {
+        url: String,
+    },
+    S3 {
+        endpoint: String,
+        bucket: String,
+        key: String,
+        credentials: S3Credentials,
+    },
+}
+
+/// The progress of a multipart upload, persisted next to the archive so an interrupted upload
+/// can pick up from the last part that was accepted by the server. The archive and destination
+/// it was started for are recorded so that a state left behind by another upload is not reused.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UploadState {
+    upload_id: String,
+    chunk_size: u64,
+    parts: Vec<UploadedPart>,
+    #[serde(default)]
+    destination: String,
+    #[serde(default)]
+    archive_size: u64,
+    #[serde(default)]
+    archive_modified: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UploadedPart {
+    part_number: u64,
+    etag: String,
+}
+
+pub struct HttpUploader {
+    target: UploadTarget,
+    retries: u32,
+    chunk_size: u64,
+}
+
+impl HttpUploader {
+    pub fn new(destination: &str, s3_endpoint: &str) -> Result<Self, Box<dyn Error>> {
+        let target = if let Some(location) = destination.strip_prefix("s3://") {
+            if s3_endpoint.is_empty() {
+                return Err("An S3 endpoint is required to upload to s3:// destinations".into());
+            }
+            let (bucket, key) = location
+                .split_once('/')
+                .ok_or("S3 destination must be in the form s3://bucket/key")?;
+            if bucket.is_empty() || key.is_empty() {
+                return Err("S3 destination must be in the form s3://bucket/key".into());
+            }
+            UploadTarget::S3 {
+                endpoint: s3_endpoint.trim_end_matches('/').to_string(),
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+                credentials: S3Credentials::from_env()?,
+            }
+        } else if destination.starts_with("http://") || destination.starts_with("https://") {
+            UploadTarget::Put {
+                url: destination.to_string(),
+            }
+        } else {
+            return Err(format!("Unsupported upload destination: {}", destination).into());
+        };
+
+        Ok(HttpUploader {
+            target,
+            retries: DEFAULT_RETRIES,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        })
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn upload(&self, archive_path: &str) -> Result<(), Box<dyn Error>> {
+        match &self.target {
+            UploadTarget::Put { url } => self.put_file(url, archive_path),
+            UploadTarget::S3 {
+                endpoint,
+                bucket,
+                key,
+                credentials,
+            } => self.multipart_upload(endpoint, bucket, key, credentials, archive_path),
+        }
+    }
+
+    fn retry<T, F>(&self, description: &str, mut operation: F) -> Result<T, Box<dyn Error>>
+    where
+        F: FnMut() -> Result<T, Box<dyn Error>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match operation() {
+                Ok(result) => return Ok(result),
+                Err(e) if e.is::<NoSuchUpload>() => return Err(e),
+                Err(e) if attempt < self.retries && is_transient(e.as_ref()) => {
+                    attempt += 1;
+                    let backoff = Duration::from_secs(1 << cmp::min(attempt, 6));
+                    println!(
+                        "Failed to {0} (attempt {1}/{2}), retrying in {3}s: {4}",
+                        description,
+                        attempt,
+                        self.retries,
+                        backoff.as_secs(),
+                        e
+                    );
+                    thread::sleep(backoff);
+                }
+                Err(e) => return Err(format!("Failed to {0}: {1}", description, e).into()),
+            }
+        }
+    }
+
+    fn put_file(&self, url: &str, archive_path: &str) -> Result<(), Box<dyn Error>> {
+        self.retry("upload archive", || {
+            let file = File::open(archive_path)?;
+            let length = file.metadata()?.len();
+            ureq::put(url)
+                .set("Content-Type", "application/zip")
+                .set("Content-Length", &length.to_string())
+                .send(file)?;
+            Ok(())
+        })?;
+        println!("Uploaded {} to {}", archive_path, url);
+        Ok(())
+    }
+
+    fn multipart_upload(
+        &self,
+        endpoint: &str,
+        bucket: &str,
+        key: &str,
+        credentials: &S3Credentials,
+        archive_path: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let state_path = format!("{}.upload", archive_path);
+        let upload = || {
+            self.send_parts(
+                endpoint,
+                bucket,
+                key,
+                credentials,
+                archive_path,
+                &state_path,
+            )
+        };
+        // an upload that expired or was aborted fails the same way every time it is resumed, so
+        // its state is dropped and the archive sent again as a new upload
+        match upload() {
+            Err(e) if e.is::<NoSuchUpload>() => {
+                std::fs::remove_file(&state_path).unwrap_or_default();
+                println!("{}, starting a new upload", e);
+                upload().inspect_err(|e| {
+                    if e.is::<NoSuchUpload>() {
+                        std::fs::remove_file(&state_path).unwrap_or_default();
+                    }
+                })
+            }
+            result => result,
+        }
+    }
+
+    fn send_parts(
+        &self,
+        endpoint: &str,
+        bucket: &str,
+        key: &str,
+        credentials: &S3Credentials,
+        archive_path: &str,
+        state_path: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let destination = format!("{0}/{1}/{2}", endpoint, bucket, key);
+        let metadata = std::fs::metadata(archive_path)?;
+        let archive_modified = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|modified| modified.as_secs())
+            .unwrap_or_default();
+        let previous = std::fs::read_to_string(state_path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<UploadState>(&contents).ok());
+        let mut state = match previous {
+            Some(state)
+                if state.destination == destination
+                    && state.archive_size == metadata.len()
+                    && state.archive_modified == archive_modified =>
+            {
+                println!(
+                    "Resuming upload {0} with {1} parts already uploaded",
+                    state.upload_id,
+                    state.parts.len()
+                );
+                state
+            }
+            previous => {
+                if let Some(previous) = previous {
+                    println!(
+                        "{0} was left by an upload of a different archive or to a different destination, starting over",
+                        state_path
+                    );
+                    // the parts of the stale upload are discarded on a best effort basis
+                    if previous.destination == destination {
+                        s3_request(
+                            credentials,
+                            "DELETE",
+                            endpoint,
+                            bucket,
+                            key,
+                            &[("uploadId", previous.upload_id.as_str())],
+                            &[],
+                        )
+                        .ok();
+                    }
+                }
+                let upload_id = self.retry("create multipart upload", || {
+                    let response = s3_request(
+                        credentials,
+                        "POST",
+                        endpoint,
+                        bucket,
+                        key,
+                        &[("uploads", "")],
+                        &[],
+                    )?;
+                    extract_xml_value(&response.into_string()?, "UploadId")
+                        .ok_or("No UploadId in response".into())
+                })?;
+                UploadState {
+                    upload_id,
+                    chunk_size: self.chunk_size,
+                    parts: Vec::new(),
+                    destination,
+                    archive_size: metadata.len(),
+                    archive_modified,
+                }
+            }
+        };
+
+        let mut file = File::open(archive_path)?;
+        let length = file.metadata()?.len();
+        let total_parts = cmp::max(length.div_ceil(state.chunk_size), 1);
+
+        for part_number in 1..=total_parts {
+            if state.parts.iter().any(|p| p.part_number == part_number) {
+                continue;
+            }
+            let offset = (part_number - 1) * state.chunk_size;
+            let mut chunk = Vec::new();
+            file.seek(SeekFrom::Start(offset))?;
+            (&mut file).take(state.chunk_size).read_to_end(&mut chunk)?;
+
+            let part_number_string = part_number.to_string();
+            let etag = self.retry(&format!("upload part {}", part_number), || {
+                let response = s3_request(
+                    credentials,
+                    "PUT",
+                    endpoint,
+                    bucket,
+                    key,
+                    &[
+                        ("partNumber", part_number_string.as_str()),
+                        ("uploadId", state.upload_id.as_str()),
+                    ],
+                    &chunk,
+                )?;
+                Ok(response
+                    .header("ETag")
+                    .ok_or("No ETag in response")?
+                    .to_string())
+            })?;
+
+            state.parts.push(UploadedPart { part_number, etag });
+            std::fs::write(state_path, serde_json::to_string(&state)?)?;
+            println!("Uploaded part {0}/{1}", part_number, total_parts);
+        }
+
+        state.parts.sort_by_key(|p| p.part_number);
+        let mut body = String::from("<CompleteMultipartUpload>");
+        for part in &state.parts {
+            body.push_str(&format!(
+                "<Part><PartNumber>{0}</PartNumber><ETag>{1}</ETag></Part>",
+                part.part_number, part.etag
+            ));
+        }
+        body.push_str("</CompleteMultipartUpload>");
+
+        self.retry("complete multipart upload", || {
+            let response = s3_request(
+                credentials,
+                "POST",
+                endpoint,
+                bucket,
+                key,
+                &[("uploadId", state.upload_id.as_str())],
+                body.as_bytes(),
+            )?;
+            // S3 can report a failure with a 200 status once the request has been accepted.
+            let response = response.into_string()?;
+            if response.contains("<Error>") {
+                return Err(Box::new(TransientError(format!(
+                    "Server rejected upload: {}",
+                    response
+                ))));
+            }
+            Ok(())
+        })?;
+
+        std::fs::remove_file(state_path).unwrap_or_default();
+        println!(
+            "Uploaded {0} to {1}/{2}/{3}",
+            archive_path, endpoint, bucket, key
+        );
+        Ok(())
+    }
+}
+
+/// An error that is worth retrying even though it did not come from the transport, such as the
+/// failures S3 reports in the body of a 200 response to a completed multipart upload.
+#[derive(Debug)]
+struct TransientError(String);
+
+impl std::fmt::Display for TransientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for TransientError {}
+
+/// The server no longer knows a multipart upload, because it expired or was aborted.
+#[derive(Debug)]
+struct NoSuchUpload(String);
+
+impl std::fmt::Display for NoSuchUpload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Upload {} no longer exists", self.0)
+    }
+}
+
+impl Error for NoSuchUpload {}
+
+/// Returns whether a failed request may succeed when sent again: transport and I/O errors, server
+/// errors, timeouts and throttling. Other client errors such as a rejected signature or an
+/// expired pre-signed URL will fail the same way every time.
+fn is_transient(error: &(dyn Error + 'static)) -> bool {
+    if let Some(error) = error.downcast_ref::<ureq::Error>() {
+        return match error {
+            ureq::Error::Status(status, _) => *status >= 500 || *status == 408 || *status == 429,
+            ureq::Error::Transport(_) => true,
+        };
+    }
+    error.is::<std::io::Error>() || error.is::<TransientError>()
+}
+
+fn extract_xml_value(xml: &str, tag: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = xml[start..].find(&format!("</{}>", tag))? + start;
+    Some(xml[start..end].to_string())
+}
+
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+/// Sends a request to an S3-compatible endpoint using path-style addressing, signed with AWS
+/// Signature Version 4.
+fn s3_request(
+    credentials: &S3Credentials,
+    method: &str,
+    endpoint: &str,
+    bucket: &str,
+    key: &str,
+    query: &[(&str, &str)],
+    body: &[u8],
+) -> Result<ureq::Response, Box<dyn Error>> {
+    let now = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    // the path of an endpoint behind a prefix is part of what is signed
+    let (scheme, location) = endpoint
+        .split_once("://")
+        .ok_or("S3 endpoint must include a scheme")?;
+    let (host, prefix) = location.split_once('/').unwrap_or((location, ""));
+    let mut canonical_uri = String::new();
+    for segment in prefix.split('/').filter(|segment| !segment.is_empty()) {
+        canonical_uri.push('/');
+        canonical_uri.push_str(&uri_encode(segment, true));
+    }
+    canonical_uri.push_str(&format!(
+        "/{0}/{1}",
+        uri_encode(bucket, true),
+        uri_encode(key, false)
+    ));
+
+    let mut query: Vec<(String, String)> = query
+        .iter()
+        .map(|(k, v)| (uri_encode(k, true), uri_encode(v, true)))
+        .collect();
+    query.sort();
+    let canonical_query = query
+        .iter()
+        .map(|(k, v)| format!("{0}={1}", k, v))
+        .collect::<Vec<String>>()
+        .join("&");
+
+    let payload_hash = hex::encode(Sha256::digest(body));
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "{method}\n{canonical_uri}\n{canonical_query}\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\n{signed_headers}\n{payload_hash}"
+    );
+
+    let scope = format!("{0}/{1}/s3/aws4_request", date, credentials.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{0}\n{1}\n{2}",
+        amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let mut signing_key = hmac_sha256(
+        format!("AWS4{}", credentials.secret_key).as_bytes(),
+        date.as_bytes(),
+    )?;
+    for part in [credentials.region.as_str(), "s3", "aws4_request"] {
+        signing_key = hmac_sha256(&signing_key, part.as_bytes())?;
+    }
+    let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes())?);
+
+    let mut url = format!("{0}://{1}{2}", scheme, host, canonical_uri);
+    if !canonical_query.is_empty() {
+        url = format!("{0}?{1}", url, canonical_query);
+    }
+
+    let response = ureq::request(method, &url)
+        .set("x-amz-date", &amz_date)
+        .set("x-amz-content-sha256", &payload_hash)
+        .set(
+            "Authorization",
+            &format!(
+                "AWS4-HMAC-SHA256 Credential={0}/{1}, SignedHeaders={2}, Signature={3}",
+                credentials.access_key, scope, signed_headers, signature
+            ),
+        )
+        .send_bytes(body);
+    match response {
+        Err(ureq::Error::Status(404, response)) => {
+            let body = response.into_string()?;
+            if extract_xml_value(&body, "Code").as_deref() == Some("NoSuchUpload") {
+                let upload_id = query
+                    .iter()
+                    .find(|(name, _)| name == "uploadId")
+                    .map(|(_, value)| value.to_string())
+                    .unwrap_or_default();
+                return Err(Box::new(NoSuchUpload(upload_id)));
+            }
+            Err(Box::new(ureq::Error::Status(
+                404,
+                ureq::Response::new(404, "Not Found", &body)?,
+            )))
+        }
+        response => Ok(response?),
+    }
+}
+
+pub fn upload_archive(
+    archive_path: &str,
+    destination: &str,
+    s3_endpoint: &str,
+    retries: u32,
+) -> Result<(), Box<dyn Error>> {
+    if !Path::new(archive_path).is_file() {
+        return Err(format!("Archive {} does not exist", archive_path).into());
+    }
+    HttpUploader::new(destination, s3_endpoint)?
+        .with_retries(retries)
+        .upload(archive_path)
+}
@@ -0,0 +1,164 @@
+use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine};
+use ssh2::{HashType, OpenFlags, OpenType, Session, Sftp};
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    net::TcpStream,
+    path::Path,
+};
+
+pub struct SftpDestination {
+    pub user: String,
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl SftpDestination {
+    /// Parses a destination in the form `sftp://user@host[:port]/path/to/collection.zip`. IPv6
+    /// addresses are written in brackets when a port is given, e.g. `sftp://user@[::1]:2222/path`.
+    pub fn parse(destination: &str) -> Result<Self, Box<dyn Error>> {
+        let location = destination
+            .strip_prefix("sftp://")
+            .ok_or("SFTP destination must start with sftp://")?;
+        let (authority, path) = location
+            .split_once('/')
+            .ok_or("SFTP destination must include a remote path")?;
+        let (user, address) = authority
+            .split_once('@')
+            .ok_or("SFTP destination must include a user e.g. sftp://user@host/path")?;
+        let (host, port) = if let Some(bracketed) = address.strip_prefix('[') {
+            let (host, rest) = bracketed
+                .split_once(']')
+                .ok_or("SFTP destination has an unclosed [ around its host")?;
+            match rest {
+                "" => (host, 22),
+                rest => (
+                    host,
+                    rest.strip_prefix(':')
+                        .ok_or("SFTP destination must separate the port from the host with :")?
+                        .parse::<u16>()?,
+                ),
+            }
+        } else {
+            // a bare IPv6 address has several colons and cannot carry a port
+            match address.split_once(':') {
+                Some((host, port)) if !port.contains(':') => (host, port.parse::<u16>()?),
+                _ => (address, 22),
+            }
+        };
+        if host.is_empty() {
+            return Err("SFTP destination must include a host".into());
+        }
+
+        if path.is_empty() {
+            return Err("SFTP destination must include a remote path".into());
+        }
+
+        Ok(SftpDestination {
+            user: user.to_string(),
+            host: host.to_string(),
+            port,
+            path: format!("/{}", path),
+        })
+    }
+}
+
+pub struct SftpUploader {
+    destination: SftpDestination,
+    // the session has to outlive the sftp channel that was opened from it
+    _session: Session,
+    sftp: Sftp,
+}
+
+impl SftpUploader {
+    /// Connects to the destination, refusing to continue unless the server presents the pinned
+    /// host key, and authenticates with the given private key.
+    ///
+    /// `host_key` is the SHA256 fingerprint of the server's host key as printed by
+    /// `ssh-keygen -lf`, e.g. `SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s`.
+    pub fn connect(
+        destination: &str,
+        private_key: &str,
+        host_key: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        let destination = SftpDestination::parse(destination)?;
+        if host_key.is_empty() {
+            return Err("A pinned host key fingerprint is required for SFTP uploads".into());
+        }
+        if private_key.is_empty() {
+            return Err("A private key is required for SFTP uploads".into());
+        }
+
+        let tcp = TcpStream::connect((destination.host.as_str(), destination.port))?;
+        let mut session = Session::new()?;
+        session.set_tcp_stream(tcp);
+        session.handshake()?;
+
+        let fingerprint = format!(
+            "SHA256:{}",
+            STANDARD_NO_PAD.encode(
+                session
+                    .host_key_hash(HashType::Sha256)
+                    .ok_or("Server did not present a host key")?
+            )
+        );
+        if fingerprint != host_key.trim_end_matches('=') {
+            return Err(format!(
+                "Host key mismatch for {0}, expected {1} but the server presented {2}",
+                destination.host, host_key, fingerprint
+            )
+            .into());
+        }
+
+        let passphrase = std::env::var("YOINK_SFTP_KEY_PASSPHRASE").ok();
+        session.userauth_pubkey_file(
+            &destination.user,
+            None,
+            Path::new(private_key),
+            passphrase.as_deref(),
+        )?;
+        if !session.authenticated() {
+            return Err(format!("Failed to authenticate as {}", destination.user).into());
+        }
+
+        let sftp = session.sftp()?;
+        Ok(SftpUploader {
+            destination,
+            _session: session,
+            sftp,
+        })
+    }
+
+    /// Creates the remote file, the returned writer can be handed straight to
+    /// `Collecter::compress_collection_to` so the collection never touches the local disk.
+    pub fn create(&self) -> Result<BufWriter<ssh2::File>, Box<dyn Error>> {
+        let remote_file = self.sftp.open_mode(
+            Path::new(&self.destination.path),
+            OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE,
+            0o600,
+            OpenType::File,
+        )?;
+        Ok(BufWriter::with_capacity(1024 * 1024, remote_file))
+    }
+
+    /// Copies a finished archive to the server, removing what was written if the copy fails.
+    pub fn upload(&self, archive_path: &str) -> Result<u64, Box<dyn Error>> {
+        let mut reader = BufReader::new(File::open(archive_path)?);
+        let mut writer = self.create()?;
+        let result = io::copy(&mut reader, &mut writer).and_then(|written| {
+            writer.into_inner().map_err(|e| e.into_error())?;
+            Ok(written)
+        });
+        result.map_err(|e| {
+            self.remove().ok();
+            format!("Failed to upload {0}: {1}", archive_path, e).into()
+        })
+    }
+
+    /// Removes the remote file, used to clean up after a collection that could not be written.
+    pub fn remove(&self) -> Result<(), Box<dyn Error>> {
+        Ok(self.sftp.unlink(Path::new(&self.destination.path))?)
+    }
+}
@@ -1,17 +1,73 @@
 pub mod collection {
+    #[path = "archive.rs"]
+    pub mod archive;
     #[path = "collecter.rs"]
     pub mod collecter;
     #[path = "command/collecter.rs"]
     pub mod command;
     #[path = "file/collecter.rs"]
     pub mod file;
+    #[path = "image.rs"]
+    pub mod image;
+    #[path = "manifest.rs"]
+    pub mod manifest;
     #[path = "memory/collecter.rs"]
     pub mod memory;
+    #[path = "metadata.rs"]
+    pub mod metadata;
     #[path = "rules.rs"]
     pub mod rules;
+    #[path = "timeline.rs"]
+    pub mod timeline;
+    #[cfg(target_os = "linux")]
+    #[path = "file/walker.rs"]
+    pub mod walker;
+    pub mod parsers {
+        #[path = "bytes.rs"]
+        pub mod bytes;
+        #[path = "evtx_parser.rs"]
+        pub mod evtx_parser;
+        #[path = "history_parser.rs"]
+        pub mod history_parser;
+        #[path = "journal_parser.rs"]
+        pub mod journal_parser;
+        #[path = "jumplist_parser.rs"]
+        pub mod jumplist_parser;
+        #[path = "lnk_parser.rs"]
+        pub mod lnk_parser;
+        #[path = "login_parser.rs"]
+        pub mod login_parser;
+        #[path = "mft_parser.rs"]
+        pub mod mft_parser;
+        #[path = "output.rs"]
+        pub mod output;
+        #[path = "prefetch_parser.rs"]
+        pub mod prefetch_parser;
+        #[path = "registry_artefacts.rs"]
+        pub mod registry_artefacts;
+        #[path = "registry_parser.rs"]
+        pub mod registry_parser;
+        #[path = "usn_parser.rs"]
+        pub mod usn_parser;
+    }
     pub mod readers {
-        #[cfg(target_os = "windows")]
+        #[path = "ewf_reader.rs"]
+        pub mod ewf_reader;
+        #[path = "ext4_reader.rs"]
+        pub mod ext4_reader;
         #[path = "ntfs_reader.rs"]
         pub mod ntfs_reader;
+        #[path = "partition_reader.rs"]
+        pub mod partition_reader;
+        #[path = "vhd_reader.rs"]
+        pub mod vhd_reader;
+        #[path = "vhdx_reader.rs"]
+        pub mod vhdx_reader;
+    }
+    pub mod uploaders {
+        #[path = "http_uploader.rs"]
+        pub mod http_uploader;
+        #[path = "sftp_uploader.rs"]
+        pub mod sftp_uploader;
     }
 }
@@ -0,0 +1,117 @@
+use yoink::collection::archive::{extraction_path, EntryFilter};
+use yoink::collection::manifest::ManifestEntry;
+
+fn entry(archive_path: &str, original_path: &str) -> ManifestEntry {
+    ManifestEntry {
+        rule: "prefetch".to_string(),
+        other_rules: Vec::new(),
+        archive_path: archive_path.to_string(),
+        original_path: original_path.to_string(),
+        size: 0,
+        sha256: String::new(),
+        last_modified: 0,
+        atime_preserved: true,
+        metadata: Default::default(),
+        recovery_confidence: None,
+        start_offset: None,
+    }
+}
+
+#[test]
+fn globs_match_windows_paths_with_forward_slashes() {
+    let prefetch = entry(
+        "C\\Windows\\Prefetch\\CMD.EXE-4A81B364.pf",
+        "C:\\Windows\\Prefetch\\CMD.EXE-4A81B364.pf:",
+    );
+    for glob in [
+        "C:/Windows/**",
+        "C/Windows/Prefetch/*.pf",
+        "**/Prefetch/*.pf",
+    ] {
+        let filter = EntryFilter::new(Vec::new(), vec![glob.to_string()]).unwrap();
+        assert!(
+            filter.matches("windows", &prefetch),
+            "{} should match",
+            glob
+        );
+    }
+    let filter = EntryFilter::new(Vec::new(), vec!["C:/Users/**".to_string()]).unwrap();
+    assert!(!filter.matches("windows", &prefetch));
+}
+
+#[test]
+fn linux_paths_are_matched_as_they_are() {
+    let passwd = entry("etc/passwd", "/etc/passwd");
+    let filter = EntryFilter::new(Vec::new(), vec!["/etc/*".to_string()]).unwrap();
+    assert!(filter.matches("linux", &passwd));
+    let filter = EntryFilter::new(vec!["other".to_string()], vec!["/etc/*".to_string()]).unwrap();
+    assert!(!filter.matches("linux", &passwd));
+}
+
+#[test]
+fn every_rule_that_collected_an_entry_selects_it() {
+    let mut security = entry(
+        "C\\Windows\\System32\\winevt\\Logs\\Security.evtx",
+        "C:\\Windows\\System32\\winevt\\Logs\\Security.evtx:",
+    );
+    security.rule = "evtx".to_string();
+    security.other_rules = vec!["evtx_triage".to_string()];
+    assert_eq!(
+        security.rules().collect::<Vec<_>>(),
+        vec!["evtx", "evtx_triage"]
+    );
+    for rule in ["evtx", "evtx_triage"] {
+        let filter = EntryFilter::new(vec![rule.to_string()], Vec::new()).unwrap();
+        assert!(filter.matches("windows", &security), "{}", rule);
+    }
+    let filter = EntryFilter::new(vec!["prefetch".to_string()], Vec::new()).unwrap();
+    assert!(!filter.matches("windows", &security));
+}
+
+fn components(path: &std::path::Path) -> Vec<String> {
+    path.iter()
+        .map(|component| component.to_string_lossy().to_string())
+        .collect()
+}
+
+#[test]
+fn extraction_paths_stay_below_the_destination() {
+    for (platform, original_path, expected) in [
+        (
+            "windows",
+            r"C:\Windows\System32\config\SYSTEM",
+            "C/Windows/System32/config/SYSTEM",
+        ),
+        (
+            "windows",
+            r"C:\Users\bob\file.txt:Zone.Identifier",
+            "C/Users/bob/file.txt:Zone.Identifier",
+        ),
+        ("windows", r"C:\..\..\Windows\win.ini", "C/Windows/win.ini"),
+        ("windows", r"D:evil\payload.exe", "D_evil/payload.exe"),
+        (
+            "windows",
+            r"C:\Users\D:\payload.exe",
+            "C/Users/D_/payload.exe",
+        ),
+        ("windows", r"C:\Users\D:evil", "C/Users/D_evil"),
+        ("windows", r"C:\Users\a:stream", "C/Users/a_stream"),
+        (
+            "windows",
+            r"C:\Users\x:y\payload.exe",
+            "C/Users/x_y/payload.exe",
+        ),
+        ("windows", r"partition3:\data.bin", "partition3/data.bin"),
+        ("linux", "/etc/../../passwd", "etc/passwd"),
+        ("linux", "partition2:/etc/passwd", "partition2/etc/passwd"),
+    ] {
+        let path = extraction_path(platform, original_path);
+        assert!(path.is_relative(), "{}", original_path);
+        assert_eq!(
+            components(&path),
+            expected.split('/').collect::<Vec<_>>(),
+            "{}",
+            original_path
+        );
+    }
+}
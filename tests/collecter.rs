@@ -0,0 +1,200 @@
+use yoink::collection::archive::CollectionArchive;
+use yoink::collection::collecter::Collecter;
+
+/// An artefact that fails after its zip entry was started must not be left in the archive, or
+/// verifying the collection reports it as an entry missing from the manifest.
+#[cfg(target_os = "linux")]
+#[test]
+fn failed_artefacts_leave_no_entry() {
+    let dir = std::env::temp_dir().join(format!("yoink-collecter-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let readable = dir.join("readable.txt");
+    std::fs::write(&readable, b"collected").unwrap();
+    // opening the memory of a process succeeds but reading from address zero fails
+    let rule = dir.join("rule.yaml");
+    std::fs::write(
+        &rule,
+        format!(
+            "name: partial\ndescription: test\nplatform: linux\nrule_type: file\npaths: [\"{}\", \"/proc/self/mem\"]\nrecursion_depth: 0\n",
+            readable.display()
+        ),
+    )
+    .unwrap();
+
+    let mut collecter = Collecter::new("linux".to_string(), None).unwrap();
+    collecter
+        .add_rule_from_file(rule.to_str().unwrap())
+        .unwrap();
+    collecter.collect_by_rulename("partial").unwrap();
+    let output = dir.join("collection.zip");
+    collecter
+        .compress_collection(output.to_str().unwrap())
+        .unwrap();
+
+    let mut collection = CollectionArchive::open(output.to_str().unwrap(), None).unwrap();
+    let report = collection.verify().unwrap();
+    assert_eq!(report.verified.len(), 1);
+    assert!(report.extra.is_empty(), "extra entries: {:?}", report.extra);
+    assert!(report.missing.is_empty() && report.corrupted.is_empty());
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Only the freed start of `$UsnJrnl:$J` is left out of a collection and the manifest records
+/// where the collected stream starts, holes in any other file are kept.
+#[test]
+fn skips_only_the_freed_start_of_the_change_journal() {
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    let dir = std::env::temp_dir().join(format!("yoink-journal-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let image = dir.join("ntfs.img");
+    let mut volume = Vec::new();
+    GzDecoder::new(std::fs::File::open("tests/fixtures/ntfs.img.gz").unwrap())
+        .read_to_end(&mut volume)
+        .unwrap();
+    std::fs::write(&image, volume).unwrap();
+    let rule = dir.join("rule.yaml");
+    std::fs::write(
+        &rule,
+        "name: journal\ndescription: test\nplatform: windows\nrule_type: file\npaths: [\"C:\\\\$Extend\\\\$UsnJrnl:$J\", \"C:\\\\sparse.bin\"]\nrecursion_depth: 0\n",
+    )
+    .unwrap();
+
+    for keep_sparse in [false, true] {
+        let mut collecter = Collecter::from_image(image.to_str().unwrap(), None).unwrap();
+        collecter.set_keep_sparse(keep_sparse);
+        collecter
+            .add_rule_from_file(rule.to_str().unwrap())
+            .unwrap();
+        collecter.collect_by_rulename("journal").unwrap();
+        let output = dir.join("collection.zip");
+        collecter
+            .compress_collection(output.to_str().unwrap())
+            .unwrap();
+
+        let collection = CollectionArchive::open(output.to_str().unwrap(), None).unwrap();
+        let entries = &collection.manifest().entries;
+        let journal = entries
+            .iter()
+            .find(|entry| entry.original_path.ends_with("$UsnJrnl:$J"))
+            .unwrap();
+        let sparse = entries
+            .iter()
+            .find(|entry| entry.original_path.contains("sparse.bin"))
+            .unwrap();
+        if keep_sparse {
+            assert_eq!((journal.size, journal.start_offset), (16472, None));
+        } else {
+            // the two cluster hole at the start is gone, the one after it is kept
+            assert_eq!((journal.size, journal.start_offset), (8280, Some(8192)));
+        }
+        assert_eq!((sparse.size, sparse.start_offset), (17384, None));
+    }
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// An artefact collected by two rules is stored once and the manifest names both rules.
+#[test]
+fn records_every_rule_that_collected_an_artefact() {
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    let dir = std::env::temp_dir().join(format!("yoink-rules-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let image = dir.join("ntfs.img");
+    let mut volume = Vec::new();
+    GzDecoder::new(std::fs::File::open("tests/fixtures/ntfs.img.gz").unwrap())
+        .read_to_end(&mut volume)
+        .unwrap();
+    std::fs::write(&image, volume).unwrap();
+    let mut collecter = Collecter::from_image(image.to_str().unwrap(), None).unwrap();
+    for (name, paths) in [
+        ("notes", "\"C:\\\\notes.txt\""),
+        ("text", "\"C:\\\\notes.txt\", \"C:\\\\hidden.txt\""),
+    ] {
+        let rule = dir.join(format!("{}.yaml", name));
+        std::fs::write(
+            &rule,
+            format!(
+                "name: {0}\ndescription: test\nplatform: windows\nrule_type: file\npaths: [{1}]\nrecursion_depth: 0\n",
+                name, paths
+            ),
+        )
+        .unwrap();
+        collecter
+            .add_rule_from_file(rule.to_str().unwrap())
+            .unwrap();
+        collecter.collect_by_rulename(name).unwrap();
+    }
+    let output = dir.join("collection.zip");
+    collecter
+        .compress_collection(output.to_str().unwrap())
+        .unwrap();
+
+    let collection = CollectionArchive::open(output.to_str().unwrap(), None).unwrap();
+    let entries = &collection.manifest().entries;
+    assert_eq!(entries.len(), 2);
+    let rules = |name: &str| {
+        let entry = entries
+            .iter()
+            .find(|entry| entry.original_path.contains(name))
+            .unwrap();
+        entry.rules().cloned().collect::<Vec<_>>()
+    };
+    assert_eq!(rules("notes.txt"), vec!["notes", "text"]);
+    assert_eq!(rules("hidden.txt"), vec!["text"]);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Extracting reports the entries it could not write rather than succeeding with what is left.
+#[cfg(target_os = "linux")]
+#[test]
+fn extraction_fails_when_entries_cannot_be_written() {
+    use yoink::collection::archive::EntryFilter;
+
+    let dir = std::env::temp_dir().join(format!("yoink-extract-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let artefact = dir.join("artefact.txt");
+    std::fs::write(&artefact, b"collected").unwrap();
+    let rule = dir.join("rule.yaml");
+    std::fs::write(
+        &rule,
+        format!(
+            "name: extracted\ndescription: test\nplatform: linux\nrule_type: file\npaths: [\"{}\"]\nrecursion_depth: 0\n",
+            artefact.display()
+        ),
+    )
+    .unwrap();
+    let mut collecter = Collecter::new("linux".to_string(), None).unwrap();
+    collecter
+        .add_rule_from_file(rule.to_str().unwrap())
+        .unwrap();
+    collecter.collect_by_rulename("extracted").unwrap();
+    let output = dir.join("collection.zip");
+    collecter
+        .compress_collection(output.to_str().unwrap())
+        .unwrap();
+    let mut collection = CollectionArchive::open(output.to_str().unwrap(), None).unwrap();
+
+    let extracted = dir.join("extracted");
+    let count = collection
+        .extract(extracted.to_str().unwrap(), &EntryFilter::default())
+        .unwrap();
+    assert_eq!(count, 1);
+
+    // a file where the destination directory should be fails every entry
+    let blocked = dir.join("blocked");
+    std::fs::write(&blocked, b"in the way").unwrap();
+    let error = collection
+        .extract(blocked.to_str().unwrap(), &EntryFilter::default())
+        .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .starts_with("1 artefacts could not be extracted"),
+        "{}",
+        error
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,110 @@
+use flate2::read::GzDecoder;
+use std::io::Read;
+use yoink::collection::parsers::evtx_parser::{EventFilter, EvtxFile, EvtxRecord};
+use yoink::collection::rules::CollectionRule;
+
+/// Reads the log from `tests/fixtures`, see the README there for how it was made.
+fn records() -> Vec<EvtxRecord> {
+    let file = std::fs::File::open("tests/fixtures/Security.evtx.gz").unwrap();
+    let log = EvtxFile::from_reader(GzDecoder::new(file), "Security.evtx").unwrap();
+    log.records()
+        .into_iter()
+        .map(|record| record.unwrap())
+        .collect()
+}
+
+fn rule_filter(name: &str) -> EventFilter {
+    CollectionRule::get_all()
+        .unwrap()
+        .into_iter()
+        .find_map(|rule| match rule {
+            CollectionRule::FileRule(rule) if rule.name == name => Some(EventFilter {
+                event_ids: rule.event_ids,
+                channels: rule.channels,
+            }),
+            _ => None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn reads_events() {
+    let records = records();
+    assert_eq!(
+        records
+            .iter()
+            .map(|record| (record.record_id, record.event_id))
+            .collect::<Vec<_>>(),
+        vec![
+            (1, Some(4624)),
+            (2, Some(4625)),
+            (3, Some(4688)),
+            (4, Some(7045)),
+            (5, Some(4634)),
+        ]
+    );
+    let logon = &records[0];
+    assert_eq!(
+        logon.timestamp.as_deref(),
+        Some("2023-11-14T22:13:20.000000000Z")
+    );
+    assert_eq!(logon.channel.as_deref(), Some("Security"));
+    assert_eq!(
+        logon.provider.as_deref(),
+        Some("Microsoft-Windows-Security-Auditing")
+    );
+    assert_eq!(logon.computer.as_deref(), Some("WS01.corp.local"));
+    assert_eq!(logon.event["EventData"]["TargetUserName"], "bob");
+    assert_eq!(logon.event["EventData"]["LogonType"], "10");
+    assert_eq!(
+        logon.event["System"]["Security"]["#attributes"]["UserID"],
+        "S-1-5-18"
+    );
+    // the template of this event has no dependency identifiers
+    let service = &records[3];
+    assert_eq!(service.channel.as_deref(), Some("System"));
+    assert_eq!(service.event["EventData"]["ImagePath"], "C:\\evil.exe");
+}
+
+#[test]
+fn filters_events_by_rule() {
+    let records = records();
+    let triage = rule_filter("evtx_triage");
+    let kept: Vec<u64> = records
+        .iter()
+        .filter(|record| triage.matches(record))
+        .map(|record| record.record_id)
+        .collect();
+    assert_eq!(kept, vec![1, 2, 3, 4]);
+    // the evtx rule collects the same logs without a filter
+    let all = rule_filter("evtx");
+    assert_eq!(all, EventFilter::default());
+    assert!(records.iter().all(|record| all.matches(record)));
+}
+
+#[test]
+fn rejects_substitution_counts_past_the_chunk() {
+    const CHUNK: usize = 4096;
+    const RECORD: usize = CHUNK + 512;
+    let mut data = Vec::new();
+    GzDecoder::new(std::fs::File::open("tests/fixtures/Security.evtx.gz").unwrap())
+        .read_to_end(&mut data)
+        .unwrap();
+    // the first record's template instance follows its fragment header, with the template
+    // written inline before the values
+    let instance = RECORD + 24 + 4;
+    assert_eq!(data[instance], 0x0C);
+    let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    let definition = read_u32(instance + 6) as usize;
+    let mut position = instance + 10;
+    if CHUNK + definition == position {
+        position += 24 + read_u32(CHUNK + definition + 20) as usize;
+    }
+    data[position..position + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    let log = EvtxFile::from_reader(data.as_slice(), "Security.evtx").unwrap();
+    let records = log.records();
+    let error = records[0].as_ref().err().unwrap().to_string();
+    assert!(error.contains("run past the chunk"), "{}", error);
+    assert!(records[1..].iter().all(|record| record.is_ok()));
+}
@@ -0,0 +1,78 @@
+use flate2::read::GzDecoder;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use yoink::collection::image::DiskImage;
+use yoink::collection::readers::{ewf_reader::EwfReader, ext4_reader::Ext4};
+
+/// Unpacks fixtures from `tests/fixtures` into a directory of their own, see the README there
+/// for how each was made.
+fn unpack(test: &str, names: &[&str]) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("yoink-{}-{}", test, std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    for name in names {
+        let mut data = Vec::new();
+        GzDecoder::new(std::fs::File::open(format!("tests/fixtures/{}.gz", name)).unwrap())
+            .read_to_end(&mut data)
+            .unwrap();
+        std::fs::write(directory.join(name), data).unwrap();
+    }
+    directory
+}
+
+fn disk() -> Vec<u8> {
+    let mut disk = Vec::new();
+    GzDecoder::new(std::fs::File::open("tests/fixtures/ext4.img.gz").unwrap())
+        .read_to_end(&mut disk)
+        .unwrap();
+    disk
+}
+
+#[test]
+fn reads_split_segments() {
+    let directory = unpack("ewf-segments", &["ewf.E01", "ewf.E02"]);
+    let disk = disk();
+    let mut reader = EwfReader::open(directory.join("ewf.E01").to_str().unwrap()).unwrap();
+    assert_eq!(reader.size(), disk.len() as u64);
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).unwrap();
+    assert!(data == disk, "the EWF image should read back as the disk");
+
+    // the first segment holds the first half of the chunks, read across into the second
+    let start = disk.len() / 2 - 10000;
+    let mut buf = vec![0u8; 30000];
+    reader.seek(SeekFrom::Start(start as u64)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert!(buf == disk[start..start + 30000]);
+
+    std::fs::remove_dir_all(directory).ok();
+}
+
+#[test]
+fn requires_every_segment() {
+    let directory = unpack("ewf-missing", &["ewf.E01"]);
+    assert!(EwfReader::open(directory.join("ewf.E01").to_str().unwrap()).is_err());
+    std::fs::remove_dir_all(directory).ok();
+}
+
+#[test]
+fn opens_filesystems_inside() {
+    let directory = unpack("ewf-filesystem", &["ewf.E01", "ewf.E02"]);
+    let path = directory.join("ewf.E01");
+    let reader = EwfReader::open(path.to_str().unwrap()).unwrap();
+    let mut filesystem = Ext4::new(BufReader::new(reader)).unwrap();
+    assert_eq!(filesystem.superblock.volume_name, "fixture");
+    let inode = filesystem.open_file("/tiny.txt").unwrap();
+    let mut data = Vec::new();
+    filesystem.copy_inode(&inode, &mut data).unwrap();
+    assert_eq!(data, b"tiny\n");
+
+    let mut image = DiskImage::open(path.to_str().unwrap()).unwrap();
+    assert_eq!(image.platform(), "linux");
+    let file = image.open_file("/dir/nested/blocks.bin").unwrap();
+    let mut data = Vec::new();
+    image.copy_file(&file, &mut data, false).unwrap();
+    assert_eq!(data.len(), 20000);
+
+    std::fs::remove_dir_all(directory).ok();
+}
@@ -0,0 +1,122 @@
+use flate2::read::GzDecoder;
+use std::io::{Cursor, Read};
+use yoink::collection::readers::ext4_reader::Ext4;
+
+/// Opens a filesystem image from `tests/fixtures`, see the README there for how each was made.
+fn fixture(name: &str) -> Ext4<Cursor<Vec<u8>>> {
+    let mut image = Vec::new();
+    GzDecoder::new(std::fs::File::open(format!("tests/fixtures/{}.img.gz", name)).unwrap())
+        .read_to_end(&mut image)
+        .unwrap();
+    Ext4::new(Cursor::new(image)).unwrap()
+}
+
+fn read(filesystem: &mut Ext4<Cursor<Vec<u8>>>, path: &str) -> Vec<u8> {
+    let inode = filesystem.open_file(path).unwrap();
+    let mut data = Vec::new();
+    let written = filesystem.copy_inode(&inode, &mut data).unwrap();
+    assert_eq!(written, inode.size);
+    data
+}
+
+fn blocks() -> Vec<u8> {
+    (0..20000).map(|i| ((i * 7) % 251) as u8).collect()
+}
+
+#[test]
+fn reads_extent_files() {
+    let mut filesystem = fixture("ext4");
+    assert_eq!(filesystem.superblock.volume_name, "fixture");
+    assert_eq!(read(&mut filesystem, "/dir/nested/blocks.bin"), blocks());
+    assert_eq!(read(&mut filesystem, "/tiny.txt"), b"tiny\n");
+}
+
+#[test]
+fn reads_holes_as_zeros() {
+    let mut filesystem = fixture("ext4");
+    let mut expected = vec![0u8; 43000];
+    expected[..3000].fill(b'A');
+    expected[40000..].fill(b'B');
+    assert_eq!(read(&mut filesystem, "/sparse.bin"), expected);
+}
+
+#[test]
+fn reads_inline_data_past_the_block_map() {
+    let mut filesystem = fixture("ext4");
+    let mut expected =
+        b"inline data that does not fit in the sixty bytes of the block map of the inode!".to_vec();
+    expected.extend([b'x'; 20]);
+    assert_eq!(read(&mut filesystem, "/inline.txt"), expected);
+    // .hidden is an inline directory
+    assert_eq!(read(&mut filesystem, "/.hidden/secret"), b"dotfile\n");
+}
+
+#[test]
+fn reads_indirect_blocks() {
+    let mut filesystem = fixture("ext2");
+    assert_eq!(read(&mut filesystem, "/dir/nested/blocks.bin"), blocks());
+    assert_eq!(read(&mut filesystem, "/.hidden/secret"), b"dotfile\n");
+}
+
+#[test]
+fn walks_every_file() {
+    for name in ["ext4", "ext2"] {
+        let mut filesystem = fixture(name);
+        let mut found = Vec::new();
+        filesystem
+            .walk("/", 5, &mut |path, _| found.push(path.to_string()))
+            .unwrap();
+        found.retain(|path| !path.starts_with("/lost+found"));
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                "/.hidden/secret",
+                "/dir/nested/blocks.bin",
+                "/inline.txt",
+                "/link",
+                "/sparse.bin",
+                "/tiny.txt"
+            ],
+            "{}",
+            name
+        );
+
+        // nothing deeper than the first level of directories
+        let mut found = Vec::new();
+        filesystem
+            .walk("/", 2, &mut |path, _| found.push(path.to_string()))
+            .unwrap();
+        assert!(!found.contains(&"/dir/nested/blocks.bin".to_string()));
+    }
+}
+
+#[test]
+fn reads_metadata() {
+    let mut filesystem = fixture("ext4");
+    let inode = filesystem.open_file("/tiny.txt").unwrap();
+    let metadata = inode.metadata();
+    assert_eq!(metadata.mode, Some(0o100644));
+    assert_eq!(metadata.uid, Some(0));
+    assert_eq!(metadata.links, Some(1));
+    assert_eq!(
+        metadata.modified.as_deref(),
+        Some("2024-01-02T03:04:05.000000000Z")
+    );
+
+    let link = filesystem.open_file("/link").unwrap();
+    assert!(link.is_symlink());
+    let mut target = Vec::new();
+    filesystem.copy_inode(&link, &mut target).unwrap();
+    assert_eq!(target, b"dir/nested/blocks.bin");
+}
+
+#[test]
+fn rejects_meta_bg() {
+    let mut image = Vec::new();
+    GzDecoder::new(std::fs::File::open("tests/fixtures/ext4_meta_bg.img.gz").unwrap())
+        .read_to_end(&mut image)
+        .unwrap();
+    let error = Ext4::new(Cursor::new(image)).err().unwrap();
+    assert!(error.to_string().contains("meta_bg"), "{}", error);
+}
@@ -0,0 +1,203 @@
+#!/usr/bin/env python3
+"""Builds the EWF, VHD and VHDX fixtures around the disk in ext4.img.gz.
+
+    python3 containers.py && gzip -9 -n ewf.E01 ewf.E02 *.vhd *.vhdx
+"""
+import gzip
+import struct
+import uuid
+import zlib
+
+DISK = gzip.open("ext4.img.gz").read()
+SECTOR = 512
+
+
+# EWF (EnCase 6), split over two segment files with compressed and uncompressed chunks
+
+SECTORS_PER_CHUNK = 64
+CHUNK = SECTORS_PER_CHUNK * SECTOR
+
+
+def section(kind, offset, data, last=False):
+    size = 76 + len(data)
+    next_offset = offset if last else offset + size
+    descriptor = kind.ljust(16, b"\0") + struct.pack("<QQ", next_offset, size) + bytes(40)
+    return descriptor + struct.pack("<I", zlib.adler32(descriptor)) + data
+
+
+def ewf_segment(number, chunks, first_chunk, last):
+    out = b"EVF\x09\x0d\x0a\xff\x00" + struct.pack("<BHH", 1, number, 0)
+    if number == 1:
+        header = zlib.compress(b"1\r\nmain\r\nc\tn\ta\te\tt\r\n1\tfixture\tyoink\ttest\t\r\n\r\n")
+        out += section(b"header", len(out), header)
+        volume = struct.pack(
+            "<B3xIIIQ", 1, len(DISK) // CHUNK, SECTORS_PER_CHUNK, SECTOR, len(DISK) // SECTOR
+        ).ljust(1052, b"\0")
+        out += section(b"volume", len(out), volume)
+    sectors_offset = len(out)
+    data = b""
+    entries = []
+    for index, chunk in enumerate(chunks, first_chunk):
+        start = sectors_offset + 76 + len(data)
+        # every third chunk is stored as is, followed by its checksum
+        if index % 3 == 1:
+            entries.append(start)
+            data += chunk + struct.pack("<I", zlib.adler32(chunk))
+        else:
+            entries.append(start | 0x80000000)
+            data += zlib.compress(chunk)
+    out += section(b"sectors", len(out), data)
+    table = struct.pack("<I4xQ4x", len(entries), 0)
+    table += struct.pack("<I", zlib.adler32(table))
+    packed = b"".join(struct.pack("<I", entry) for entry in entries)
+    table += packed + struct.pack("<I", zlib.adler32(packed))
+    out += section(b"table", len(out), table)
+    out += section(b"table2", len(out), table)
+    out += section(b"done" if last else b"next", len(out), b"", last=True)
+    return out
+
+
+chunks = [DISK[offset : offset + CHUNK] for offset in range(0, len(DISK), CHUNK)]
+half = len(chunks) // 2
+open("ewf.E01", "wb").write(ewf_segment(1, chunks[:half], 0, False))
+open("ewf.E02", "wb").write(ewf_segment(2, chunks[half:], half, True))
+
+
+# VHD, fixed, dynamic with unallocated blocks and differencing
+
+VHD_BLOCK = 64 * 1024
+VHD_ID = uuid.UUID("11111111-2222-3333-4444-555555555555").bytes
+
+
+def vhd_checksum(data):
+    return struct.pack(">I", ~sum(data) & 0xFFFFFFFF)
+
+
+def vhd_footer(disk_type, data_offset, size):
+    footer = b"conectix" + struct.pack(">IIQI", 2, 0x10000, data_offset, 0)
+    footer += b"yoik" + struct.pack(">I", 0x10000) + b"Wi2k" + struct.pack(">QQ", size, size)
+    footer += struct.pack(">HBBI", 32, 4, 17, disk_type)
+    footer = footer + bytes(4) + VHD_ID + bytes(512 - len(footer) - 4 - 16)
+    return footer[:64] + vhd_checksum(footer) + footer[68:]
+
+
+def vhd_dynamic(disk, disk_type, parent_name=""):
+    blocks = len(disk) // VHD_BLOCK
+    table_offset = 512 + 1024
+    table_size = (blocks * 4 + 511) // 512 * 512
+    header = b"cxsparse" + struct.pack(">QQIII", 0xFFFFFFFFFFFFFFFF, table_offset, 0x10000, blocks, VHD_BLOCK)
+    header += bytes(4) + bytes(16) + bytes(4) + bytes(4)
+    header += parent_name.encode("utf-16-be").ljust(512, b"\0")
+    header = header.ljust(1024, b"\0")
+    header = header[:36] + vhd_checksum(header) + header[40:]
+
+    footer = vhd_footer(disk_type, 512, len(disk))
+    table = []
+    data = b""
+    next_sector = (table_offset + table_size) // 512
+    bitmap = b"\xff" * (VHD_BLOCK // SECTOR // 8)
+    bitmap = bitmap.ljust((len(bitmap) + 511) // 512 * 512, b"\0")
+    for block in range(blocks):
+        payload = disk[block * VHD_BLOCK : (block + 1) * VHD_BLOCK]
+        if not any(payload):
+            table.append(0xFFFFFFFF)
+            continue
+        table.append(next_sector)
+        data += bitmap + payload
+        next_sector += (len(bitmap) + VHD_BLOCK) // 512
+    table = b"".join(struct.pack(">I", entry) for entry in table).ljust(table_size, b"\xff")
+    return footer + header + table + data + footer
+
+
+open("fixed.vhd", "wb").write(DISK + vhd_footer(2, 0xFFFFFFFFFFFFFFFF, len(DISK)))
+open("dynamic.vhd", "wb").write(vhd_dynamic(DISK, 3))
+open("differencing.vhd", "wb").write(vhd_dynamic(DISK, 4, "parent.vhd"))
+
+
+# VHDX, dynamic with blocks that are not present and differencing
+
+MB = 1024 * 1024
+VHDX_BLOCK = MB
+VHDX_SIZE = 4 * MB
+
+
+def guid(text):
+    return uuid.UUID(text).bytes_le
+
+
+def crc32c(data):
+    crc = 0xFFFFFFFF
+    for byte in data:
+        crc ^= byte
+        for _ in range(8):
+            crc = (crc >> 1) ^ (0x82F63B78 if crc & 1 else 0)
+    return crc ^ 0xFFFFFFFF
+
+
+def with_checksum(data):
+    return data[:4] + struct.pack("<I", crc32c(data[:4] + bytes(4) + data[8:])) + data[8:]
+
+
+def vhdx(disk, parent=None):
+    out = bytearray(b"vhdxfile" + "yoink".encode("utf-16-le"))
+    out = out.ljust(0x10000, b"\0")
+    for sequence in (1, 2):
+        header = b"head" + bytes(4) + struct.pack("<Q", sequence) + bytes(16) + bytes(16) + bytes(16)
+        header += struct.pack("<HHIQ", 0, 1, MB, MB)
+        out += with_checksum(header.ljust(4096, b"\0")).ljust(0x10000, b"\0")
+
+    bat_offset, metadata_offset, data_offset = 2 * MB, 3 * MB, 4 * MB
+    regions = b"regi" + bytes(4) + struct.pack("<II", 2, 0)
+    regions += guid("2DC27766-F623-4200-9D64-115E9BFD4A08") + struct.pack("<QII", bat_offset, MB, 1)
+    regions += guid("8B7CA206-4790-4B9A-B8FE-575F050F886E") + struct.pack("<QII", metadata_offset, MB, 1)
+    regions = with_checksum(regions.ljust(0x10000, b"\0"))
+    out += regions + regions
+
+    items = [
+        ("CAA16737-FA36-4D43-B3B6-33F0AA44E76B", struct.pack("<II", VHDX_BLOCK, 2 if parent else 0)),
+        ("2FA54224-CD1B-4876-B211-5DBED83BF4B8", struct.pack("<Q", VHDX_SIZE)),
+        ("BECA12AB-B2E6-4523-93EF-C309E000C746", VHD_ID),
+        ("8141BF1D-A96F-4709-BA47-F233A8FAAB5F", struct.pack("<I", SECTOR)),
+        ("CDA348C7-445D-4471-9CC9-E9885251C556", struct.pack("<I", 4096)),
+    ]
+    if parent:
+        keys = [("parent_linkage", "{%s}" % uuid.UUID(bytes=VHD_ID)), ("relative_path", parent)]
+        locator = guid("B04AEFB7-D19E-4A81-B789-25B8E9445913") + struct.pack("<HH", 0, len(keys))
+        strings = b""
+        entries = b""
+        base = 20 + 12 * len(keys)
+        for key, value in keys:
+            key, value = key.encode("utf-16-le"), value.encode("utf-16-le")
+            entries += struct.pack("<IIHH", base + len(strings), base + len(strings) + len(key), len(key), len(value))
+            strings += key + value
+        items.append(("A8D35F2D-B30B-454D-ABF7-D3D84834AB0C", locator + entries + strings))
+    metadata = b"metadata" + struct.pack("<HH", 0, len(items)) + bytes(20)
+    values = b""
+    for item, value in items:
+        metadata += guid(item) + struct.pack("<III", 0x10000 + len(values), len(value), 0) + bytes(4)
+        values += value
+    metadata = metadata.ljust(0x10000, b"\0") + values
+
+    blocks = VHDX_SIZE // VHDX_BLOCK
+    chunk_ratio = (1 << 23) * SECTOR // VHDX_BLOCK
+    bat = []
+    data = b""
+    for block in range(blocks):
+        payload = disk[block * VHDX_BLOCK : (block + 1) * VHDX_BLOCK].ljust(VHDX_BLOCK, b"\0")
+        if not any(payload):
+            # not present
+            bat.append(0)
+        else:
+            bat.append(((data_offset + len(data)) // MB) << 20 | 6)
+            data += payload
+        if (block + 1) % chunk_ratio == 0:
+            bat.append(0)
+    out = out.ljust(bat_offset, b"\0")
+    out += b"".join(struct.pack("<Q", entry) for entry in bat)
+    out = out.ljust(metadata_offset, b"\0") + metadata
+    out = out.ljust(data_offset, b"\0") + data
+    return bytes(out)
+
+
+open("dynamic.vhdx", "wb").write(vhdx(DISK))
+open("differencing.vhdx", "wb").write(vhdx(DISK, "parent.vhdx"))
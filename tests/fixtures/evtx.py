@@ -0,0 +1,201 @@
+#!/usr/bin/env python3
+"""Builds Security.evtx, a log with a single chunk of five events, see README.md.
+
+    python3 evtx.py && gzip -9 -n Security.evtx
+"""
+import struct
+import uuid
+
+CHUNK = 65536
+CHUNK_HEADER = 512
+
+
+def filetime(unix):
+    return (unix + 11644473600) * 10**7
+
+
+def utf16(text):
+    return text.encode("utf-16-le")
+
+
+def sid(text):
+    parts = text.split("-")
+    subauthorities = [int(part) for part in parts[3:]]
+    return (
+        bytes([1, len(subauthorities)])
+        + int(parts[2]).to_bytes(6, "big")
+        + b"".join(struct.pack("<I", part) for part in subauthorities)
+    )
+
+
+# BinXML nodes: ("e", name, attributes, children or None, has dependency id), ("t", text),
+# ("s", index, type) for a substitution and ("o", index, type) for an optional one
+SYSTEM = ("e", "Event", [("xmlns", ("t", "http://schemas.microsoft.com/win/2004/08/events/event"))], [
+    ("e", "System", [], [
+        ("e", "Provider", [("Name", ("s", 0, 0x01)), ("Guid", ("o", 1, 0x0F))], None),
+        ("e", "EventID", [("Qualifiers", ("o", 2, 0x06))], [("s", 3, 0x06)]),
+        ("e", "TimeCreated", [("SystemTime", ("s", 4, 0x11))], None),
+        ("e", "EventRecordID", [], [("s", 5, 0x0A)]),
+        ("e", "Channel", [], [("s", 6, 0x01)]),
+        ("e", "Computer", [], [("s", 7, 0x01)]),
+        ("e", "Security", [("UserID", ("o", 8, 0x13))], None),
+    ]),
+    ("o", 9, 0x21),
+])
+
+
+def event_data(names, dependency=True):
+    return ("e", "EventData", [], [
+        ("e", "Data", [("Name", ("t", name))], [("o", index, 0x01)], dependency)
+        for index, name in enumerate(names)
+    ], dependency)
+
+
+class Chunk:
+    def __init__(self):
+        self.data = bytearray(CHUNK_HEADER)
+        self.names = {}
+        self.templates = {}
+
+    def position(self):
+        return len(self.data)
+
+    def name(self, name):
+        # the offset of the name, followed by the name itself the first time it is used
+        if name in self.names:
+            self.data += struct.pack("<I", self.names[name])
+            return
+        offset = self.position() + 4
+        self.names[name] = offset
+        self.data += struct.pack("<IIHH", offset, 0, 0, len(name)) + utf16(name) + b"\0\0"
+
+    def element(self, name, attributes, children, dependency=True):
+        self.data.append(0x41 if attributes else 0x01)
+        if dependency:
+            self.data += struct.pack("<H", 0xFFFF)
+        size_position = self.position()
+        self.data += bytes(4)
+        self.name(name)
+        if attributes:
+            list_position = self.position()
+            self.data += bytes(4)
+            for index, (attribute, value) in enumerate(attributes):
+                self.data.append(0x46 if index < len(attributes) - 1 else 0x06)
+                self.name(attribute)
+                self.node(value)
+            struct.pack_into("<I", self.data, list_position, self.position() - list_position - 4)
+        if children is None:
+            self.data.append(0x03)
+        else:
+            self.data.append(0x02)
+            for child in children:
+                self.node(child)
+            self.data.append(0x04)
+        struct.pack_into("<I", self.data, size_position, self.position() - size_position - 4)
+
+    def node(self, node):
+        kind = node[0]
+        if kind == "e":
+            self.element(*node[1:])
+        elif kind == "t":
+            self.data += bytes([0x05, 0x01]) + struct.pack("<H", len(node[1])) + utf16(node[1])
+        elif kind == "s":
+            self.data += bytes([0x0D]) + struct.pack("<HB", node[1], node[2])
+        elif kind == "o":
+            self.data += bytes([0x0E]) + struct.pack("<HB", node[1], node[2])
+
+    def template(self, template_id, template):
+        # a template is written where it is first used and referred to by offset after that
+        self.data += bytes([0x0C, 0x01]) + struct.pack("<I", template_id)
+        if template_id in self.templates:
+            self.data += struct.pack("<I", self.templates[template_id])
+            return
+        offset = self.position() + 4
+        self.templates[template_id] = offset
+        self.data += struct.pack("<II", offset, 0) + bytes(16)
+        size_position = self.position()
+        self.data += bytes(4)
+        start = self.position()
+        self.data += bytes([0x0F, 1, 1, 0])
+        self.node(template)
+        self.data.append(0x00)
+        struct.pack_into("<I", self.data, size_position, self.position() - start)
+
+    def instance(self, template_id, template, values):
+        self.template(template_id, template)
+        self.data += struct.pack("<I", len(values))
+        for value_type, value in values:
+            self.data += struct.pack("<HBB", len(value), value_type, 0)
+        for _, value in values:
+            self.data += value
+
+    def record(self, record_id, time, build):
+        start = self.position()
+        self.data += b"\x2a\x2a\0\0" + bytes(4) + struct.pack("<QQ", record_id, filetime(time))
+        self.data += bytes([0x0F, 1, 1, 0])
+        build()
+        self.data.append(0x00)
+        self.data += bytes(4)
+        size = self.position() - start
+        struct.pack_into("<I", self.data, start + 4, size)
+        struct.pack_into("<I", self.data, self.position() - 4, size)
+
+    def event(self, record_id, time, event_id, channel, provider, data, template_id, dependency=True):
+        def build():
+            values = [
+                (0x01, utf16(provider)),
+                (0x0F, uuid.UUID("54849625-5478-4994-a5ba-3e3b0328c30d").bytes_le),
+                (0x00, b""),
+                (0x06, struct.pack("<H", event_id)),
+                (0x11, struct.pack("<Q", filetime(time))),
+                (0x0A, struct.pack("<Q", record_id)),
+                (0x01, utf16(channel)),
+                (0x01, utf16("WS01.corp.local")),
+                (0x13, sid("S-1-5-18")),
+            ]
+            self.template(1, SYSTEM)
+            self.data += struct.pack("<I", len(values) + 1)
+            descriptors = self.position()
+            self.data += bytes(4 * (len(values) + 1))
+            for index, (value_type, value) in enumerate(values):
+                struct.pack_into("<HBB", self.data, descriptors + 4 * index, len(value), value_type, 0)
+                self.data += value
+            # the EventData is embedded BinXML, the last value of the System template
+            start = self.position()
+            self.data += bytes([0x0F, 1, 1, 0])
+            names = [name for name, _ in data]
+            self.instance(
+                template_id,
+                event_data(names, dependency),
+                [(0x01, utf16(value)) for _, value in data],
+            )
+            self.data.append(0x00)
+            struct.pack_into("<HBB", self.data, descriptors + 4 * len(values), self.position() - start, 0x21, 0)
+
+        self.record(record_id, time, build)
+
+    def finish(self, first, last):
+        free = self.position()
+        chunk = self.data + bytes(CHUNK - len(self.data))
+        chunk[0:8] = b"ElfChnk\0"
+        struct.pack_into("<QQQQIII", chunk, 8, first, last, first, last, 128, 0, free)
+        return bytes(chunk)
+
+
+AUDITING = "Microsoft-Windows-Security-Auditing"
+chunk = Chunk()
+chunk.event(1, 1700000000, 4624, "Security", AUDITING,
+            [("TargetUserName", "bob"), ("IpAddress", "10.0.0.5"), ("LogonType", "10")], 2)
+chunk.event(2, 1700000060, 4625, "Security", AUDITING,
+            [("TargetUserName", "admin"), ("IpAddress", "10.0.0.9"), ("LogonType", "3")], 2)
+chunk.event(3, 1700000120, 4688, "Security", AUDITING,
+            [("NewProcessName", "C:\\Windows\\System32\\cmd.exe"), ("CommandLine", "cmd /c whoami")], 3)
+# written without dependency ids like some versions of Windows do
+chunk.event(4, 1700000180, 7045, "System", "Service Control Manager",
+            [("ServiceName", "EvilSvc"), ("ImagePath", "C:\\evil.exe")], 4, dependency=False)
+chunk.event(5, 1700000240, 4634, "Security", AUDITING, [("TargetUserName", "bob")], 5)
+
+header = bytearray(4096)
+header[0:8] = b"ElfFile\0"
+struct.pack_into("<QQQIHHHH", header, 8, 0, 0, 6, 128, 1, 3, 4096, 1)
+open("Security.evtx", "wb").write(bytes(header) + chunk.finish(1, 5))
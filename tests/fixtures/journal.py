@@ -0,0 +1,181 @@
+#!/usr/bin/env python3
+"""Builds system.journal in the regular format and user-1000.journal in the compact format,
+see README.md. The ZSTD data is compressed by the zstd command.
+
+    python3 journal.py && gzip -9 -n system.journal user-1000.journal
+"""
+import lzma
+import struct
+import subprocess
+from datetime import datetime, timezone
+
+HEADER_SIZE = 272
+OBJECT_DATA = 1
+OBJECT_FIELD = 2
+OBJECT_ENTRY = 3
+COMPRESSED_XZ = 0x01
+COMPRESSED_LZ4 = 0x02
+COMPRESSED_ZSTD = 0x04
+# the header flags for the same compression methods do not have the same values
+INCOMPATIBLE_XZ = 0x01
+INCOMPATIBLE_LZ4 = 0x02
+INCOMPATIBLE_ZSTD = 0x08
+INCOMPATIBLE_COMPACT = 0x10
+
+BOOT_ID = bytes(range(0x10, 0x20))
+
+
+def realtime(text):
+    # microseconds since the epoch of a UTC time written as 2024-01-02T03:04:05.000000
+    time = datetime.strptime(text, "%Y-%m-%dT%H:%M:%S.%f").replace(tzinfo=timezone.utc)
+    return int(time.timestamp()) * 1_000_000 + time.microsecond
+
+
+def lz4_block(data):
+    # a greedy LZ4 block: literals, then a match of at least four bytes found through a table
+    # of where each four byte sequence was last seen, and only literals in the last five bytes
+    output = bytearray()
+    table = {}
+    literals_start = 0
+    position = 0
+    while position + 12 <= len(data):
+        key = data[position:position + 4]
+        candidate = table.get(key)
+        table[key] = position
+        if candidate is None or position - candidate > 0xFFFF:
+            position += 1
+            continue
+        length = 4
+        while position + length < len(data) - 5 and data[candidate + length] == data[position + length]:
+            length += 1
+        write_sequence(output, data[literals_start:position], position - candidate, length - 4)
+        position += length
+        literals_start = position
+    literals = data[literals_start:]
+    output.append(min(len(literals), 15) << 4)
+    output += extra_length(len(literals))
+    output += literals
+    return bytes(output)
+
+
+def write_sequence(output, literals, offset, match_length):
+    output.append(min(len(literals), 15) << 4 | min(match_length, 15))
+    output += extra_length(len(literals))
+    output += literals
+    output += struct.pack("<H", offset)
+    output += extra_length(match_length)
+
+
+def extra_length(length):
+    if length < 15:
+        return b""
+    length -= 15
+    return b"\xff" * (length // 255) + bytes([length % 255])
+
+
+def compress(payload, method):
+    if method == COMPRESSED_XZ:
+        return lzma.compress(payload, format=lzma.FORMAT_XZ, check=lzma.CHECK_CRC64)
+    if method == COMPRESSED_LZ4:
+        # systemd writes the size of the data before the block
+        return struct.pack("<Q", len(payload)) + lz4_block(payload)
+    if method == COMPRESSED_ZSTD:
+        return subprocess.run(["zstd", "-q", "-19", "-c"], input=payload, capture_output=True,
+                              check=True).stdout
+    return payload
+
+
+class Journal:
+    def __init__(self, compact):
+        self.compact = compact
+        self.data = bytearray(HEADER_SIZE)
+        self.objects = {}
+        self.seqnum = 0
+
+    def align(self):
+        self.data += bytes(-len(self.data) % 8)
+
+    def object(self, object_type, flags, body):
+        self.align()
+        offset = len(self.data)
+        self.data += struct.pack("<BB6xQ", object_type, flags, 16 + len(body)) + body
+        return offset
+
+    def field(self, name):
+        # field objects are skipped by the parser, written as journald would
+        return self.object(OBJECT_FIELD, 0, struct.pack("<QQQ", 0, 0, 0) + name)
+
+    def data_object(self, payload, method=0):
+        # a payload is stored once and shared by every entry that has it
+        if payload in self.objects:
+            return self.objects[payload]
+        self.field(payload.split(b"=")[0])
+        body = struct.pack("<QQQQQQ", 0, 0, 0, 0, 0, 1)
+        if self.compact:
+            body += struct.pack("<II", 0, 0)
+        offset = self.object(OBJECT_DATA, method, body + compress(payload, method))
+        self.objects[payload] = offset
+        return offset
+
+    def entry(self, time, monotonic, fields):
+        offsets = [self.data_object(payload, method) for payload, method in fields]
+        self.seqnum += 1
+        body = struct.pack("<QQQ", self.seqnum, realtime(time), monotonic) + BOOT_ID
+        body += struct.pack("<Q", 0)
+        for offset in offsets:
+            body += struct.pack("<I", offset) if self.compact else struct.pack("<QQ", offset, 0)
+        self.object(OBJECT_ENTRY, 0, body)
+
+    def write(self, name, incompatible_flags):
+        self.align()
+        header = bytearray(HEADER_SIZE)
+        header[0:8] = b"LPKSHHRH"
+        struct.pack_into("<II", header, 8, 0, incompatible_flags)
+        struct.pack_into("<QQ", header, 88, HEADER_SIZE, len(self.data) - HEADER_SIZE)
+        struct.pack_into("<Q", header, 152, self.seqnum)
+        self.data[0:HEADER_SIZE] = header
+        with open(name, "wb") as file:
+            file.write(self.data)
+
+
+LONG = b"MESSAGE=" + b"Accepted publickey for alice from 10.0.0.5 port 50022 ssh2 " * 8
+
+journal = Journal(compact=False)
+journal.entry("2024-01-02T03:04:05.000000", 1_000_000, [
+    (b"MESSAGE=Started Session 1 of User alice.", 0),
+    (b"_PID=1", 0),
+    (b"SYSLOG_IDENTIFIER=systemd", 0),
+    (b"_HOSTNAME=ws01", 0),
+])
+journal.entry("2024-01-03T10:00:00.000000", 2_000_000, [
+    (LONG + b"(xz)", COMPRESSED_XZ),
+    (b"_PID=812", 0),
+    (b"SYSLOG_IDENTIFIER=sshd", 0),
+    (b"TAG=first", 0),
+    (b"TAG=second", 0),
+    (b"_HOSTNAME=ws01", 0),
+])
+journal.entry("2024-01-03T23:59:59.500000", 3_000_000, [
+    (LONG + b"(lz4)", COMPRESSED_LZ4),
+    (b"_PID=812", 0),
+    (b"SYSLOG_IDENTIFIER=sshd", 0),
+    (b"_HOSTNAME=ws01", 0),
+])
+journal.entry("2024-01-04T00:00:00.000000", 4_000_000, [
+    (LONG + b"(zstd)", COMPRESSED_ZSTD),
+    (b"_PID=812", 0),
+    (b"SYSLOG_IDENTIFIER=sshd", 0),
+    (b"_HOSTNAME=ws01", 0),
+])
+journal.write("system.journal", INCOMPATIBLE_XZ | INCOMPATIBLE_LZ4 | INCOMPATIBLE_ZSTD)
+
+journal = Journal(compact=True)
+journal.entry("2024-01-02T03:04:06.000000", 1_500_000, [
+    (b"MESSAGE=Reached target Default.", 0),
+    (b"_UID=1000", 0),
+])
+journal.entry("2024-01-02T03:04:07.000000", 1_600_000, [
+    (b"MESSAGE=Startup finished.", 0),
+    (b"_UID=1000", 0),
+])
+journal.write("user-1000.journal", INCOMPATIBLE_COMPACT)
@@ -0,0 +1,324 @@
+#!/usr/bin/env python3
+"""Builds the shell links local.lnk, network.lnk, idlist.lnk and environment.lnk and the
+JumpLists holding them, see README.md.
+
+    python3 lnk.py && gzip -9 -n *.lnk *-ms
+"""
+import struct
+from datetime import datetime, timezone
+
+LINK_CLSID = bytes.fromhex("0114020000000000c000000000000046")
+
+HAS_LINK_TARGET_ID_LIST = 0x0001
+HAS_LINK_INFO = 0x0002
+HAS_NAME = 0x0004
+HAS_RELATIVE_PATH = 0x0008
+HAS_WORKING_DIR = 0x0010
+HAS_ARGUMENTS = 0x0020
+HAS_ICON_LOCATION = 0x0040
+IS_UNICODE = 0x0080
+HAS_EXP_STRING = 0x0200
+
+FILE_ATTRIBUTE_READONLY = 0x0001
+FILE_ATTRIBUTE_DIRECTORY = 0x0010
+FILE_ATTRIBUTE_ARCHIVE = 0x0020
+
+DRIVE_FIXED = 3
+WNNC_NET_LANMAN = 0x00020000
+
+ENVIRONMENT_VARIABLE_BLOCK = 0xA0000001
+TRACKER_BLOCK = 0xA0000003
+FILE_ENTRY_EXTENSION_SIGNATURE = 0xBEEF0004
+
+# the automaticDestinations-ms compound file
+SECTOR_SIZE = 512
+MINI_SECTOR_SIZE = 64
+FREE_SECTOR = 0xFFFFFFFF
+END_OF_CHAIN = 0xFFFFFFFE
+FAT_SECTOR = 0xFFFFFFFD
+NO_STREAM = 0xFFFFFFFF
+DEST_LIST_HEADER_SIZE = 32
+NOT_PINNED = 0xFFFFFFFF
+
+
+def filetime(text):
+    # a FILETIME of a UTC time written as 2024-01-02 03:04:05
+    time = datetime.strptime(text, "%Y-%m-%d %H:%M:%S").replace(tzinfo=timezone.utc)
+    return (int(time.timestamp()) + 11644473600) * 10_000_000
+
+
+def utf16(text):
+    return text.encode("utf-16-le")
+
+
+def header(flags, attributes, created, accessed, modified, size):
+    return struct.pack("<I16sII3QIIIH10x", 0x4C, LINK_CLSID, flags, attributes,
+                       filetime(created), filetime(accessed), filetime(modified), size, 0, 1, 0)
+
+
+def string_data(*strings):
+    # counted Unicode strings, in the order of their flags
+    return b"".join(struct.pack("<H", len(string)) + utf16(string) for string in strings)
+
+
+def tracker(machine_id):
+    droid = bytes(range(16))
+    return struct.pack("<IIII16s", 0x60, TRACKER_BLOCK, 0x58, 0,
+                       machine_id.encode().ljust(16, b"\0")) + droid * 4
+
+
+def environment(target):
+    return struct.pack("<II260s520s", 0x314, ENVIRONMENT_VARIABLE_BLOCK,
+                       target.encode(), utf16(target))
+
+
+TERMINAL_BLOCK = bytes(4)
+
+
+def id_list(*items):
+    return struct.pack("<H", sum(len(item) for item in items) + 2) + b"".join(items) + bytes(2)
+
+
+def root_item():
+    # My Computer
+    return struct.pack("<HBB16s", 0x14, 0x1F, 0x50,
+                       bytes.fromhex("e04fd020ea3a6910a2d808002b30309d"))
+
+
+def volume_item(drive):
+    return struct.pack("<HB", 25, 0x2F) + drive.encode().ljust(22, b"\0")
+
+
+def file_entry_item(short_name, long_name, attributes, extension_version, unicode=False):
+    # a short name, then an extension block with the long name at the offset its version uses
+    # and the offset of the extension block in the last two bytes of the item
+    class_type = 0x30 | (0x01 if attributes & FILE_ATTRIBUTE_DIRECTORY else 0x02)
+    if unicode:
+        class_type |= 0x04
+        short = utf16(short_name) + b"\0\0"
+    else:
+        short = short_name.encode() + b"\0"
+        short += bytes(len(short) % 2)
+    item = struct.pack("<BBIIH", class_type, 0, 0, 0x58222E6A, attributes) + short
+    if extension_version is None:
+        # an offset that does not lead to an extension block
+        item += struct.pack("<H", 0)
+        return struct.pack("<H", len(item) + 2) + item
+    name_offset = {3: 20, 8: 42, 9: 46}[extension_version]
+    extension = bytearray(name_offset)
+    struct.pack_into("<HIII", extension, 2, extension_version, FILE_ENTRY_EXTENSION_SIGNATURE,
+                     0x58222E6A, 0x58222E6A)
+    extension += utf16(long_name) + b"\0\0"
+    extension_offset = 2 + len(item)
+    extension += struct.pack("<H", extension_offset)
+    struct.pack_into("<H", extension, 0, len(extension))
+    item += extension
+    return struct.pack("<H", len(item) + 2) + item
+
+
+def link_info_local(path, label, serial, suffix=""):
+    # a header with the offsets of the Unicode paths, the ANSI ones written in code page 1252
+    volume = struct.pack("<IIII", 0x10 + len(label) + 1, DRIVE_FIXED, serial, 0x10)
+    volume += label.encode() + b"\0"
+    header_size = 0x24
+    volume_offset = header_size
+    base_offset = volume_offset + len(volume)
+    ansi_base = path.encode("cp1252") + b"\0"
+    suffix_offset = base_offset + len(ansi_base)
+    ansi_suffix = suffix.encode("cp1252") + b"\0"
+    unicode_base_offset = suffix_offset + len(ansi_suffix)
+    unicode_base = utf16(path) + b"\0\0"
+    unicode_suffix_offset = unicode_base_offset + len(unicode_base)
+    body = volume + ansi_base + ansi_suffix + unicode_base + utf16(suffix) + b"\0\0"
+    return struct.pack("<IIIIIIIII", header_size + len(body), header_size, 0x1, volume_offset,
+                       base_offset, 0, suffix_offset, unicode_base_offset,
+                       unicode_suffix_offset) + body
+
+
+def link_info_network(share, device, suffix):
+    network = bytearray(struct.pack("<IIIII", 0, 0x2, 0x14, 0, WNNC_NET_LANMAN))
+    network += share.encode() + b"\0"
+    struct.pack_into("<I", network, 12, len(network))
+    network += device.encode() + b"\0"
+    struct.pack_into("<I", network, 0, len(network))
+    header_size = 0x1C
+    suffix_offset = header_size + len(network)
+    body = bytes(network) + suffix.encode() + b"\0"
+    return struct.pack("<IIIIIII", header_size + len(body), header_size, 0x2, 0, 0,
+                       header_size, suffix_offset) + body
+
+
+# a document on the local disk with every string and a tracker block
+LOCAL = (
+    header(HAS_LINK_TARGET_ID_LIST | HAS_LINK_INFO | HAS_NAME | HAS_RELATIVE_PATH
+           | HAS_WORKING_DIR | HAS_ARGUMENTS | HAS_ICON_LOCATION | IS_UNICODE,
+           FILE_ATTRIBUTE_ARCHIVE, "2024-01-02 03:00:00", "2024-01-02 03:30:00",
+           "2024-01-02 03:15:00", 24576)
+    + id_list(root_item(), volume_item("C:\\"))
+    + link_info_local("C:\\Users\\alice\\Documents\\R\u00e9sum\u00e9.docx", "OS", 0x1A2B3C4D)
+    + string_data("My r\u00e9sum\u00e9", "..\\Documents\\R\u00e9sum\u00e9.docx",
+                  "C:\\Users\\alice\\Documents", "/safe",
+                  "%ProgramFiles%\\Microsoft Office\\root\\Office16\\WINWORD.EXE")
+    + tracker("ws01")
+    + TERMINAL_BLOCK
+)
+
+# a spreadsheet on a share, the path is the share and the suffix
+NETWORK = (
+    header(HAS_LINK_INFO | IS_UNICODE, FILE_ATTRIBUTE_ARCHIVE | FILE_ATTRIBUTE_READONLY,
+           "2023-12-01 09:00:00", "2024-01-02 03:40:00", "2023-12-20 17:00:00", 8192)
+    + link_info_network("\\\\FILESERVER\\SHARE", "Z:", "reports\\q1.xlsx")
+    + tracker("fileserver")
+    + TERMINAL_BLOCK
+)
+
+# a download with no LinkInfo, its path is built from the shell items of its ID list
+IDLIST = (
+    header(HAS_LINK_TARGET_ID_LIST | HAS_ARGUMENTS | IS_UNICODE, FILE_ATTRIBUTE_ARCHIVE,
+           "2024-01-02 02:50:00", "2024-01-02 02:55:00", "2024-01-02 02:50:00", 1048576)
+    + id_list(
+        root_item(),
+        volume_item("C:\\"),
+        file_entry_item("Users", "Users", FILE_ATTRIBUTE_DIRECTORY, 9),
+        file_entry_item("alice", "", FILE_ATTRIBUTE_DIRECTORY, None),
+        file_entry_item("DOWNLO~1", "Downloads", FILE_ATTRIBUTE_DIRECTORY, 8),
+        file_entry_item("SETUP_~1.EXE", "setup_x64.exe", FILE_ATTRIBUTE_ARCHIVE, 3,
+                        unicode=True),
+    )
+    + string_data("/S")
+    + tracker("ws02")
+    + TERMINAL_BLOCK
+)
+
+# a link to a program named only through an environment variable
+ENVIRONMENT = (
+    header(HAS_EXP_STRING | IS_UNICODE, 0, "2024-01-02 03:00:00", "2024-01-02 03:00:00",
+           "2024-01-02 03:00:00", 0)
+    + environment("%windir%\\system32\\mspaint.exe")
+    + TERMINAL_BLOCK
+)
+
+for name, link in [("local.lnk", LOCAL), ("network.lnk", NETWORK), ("idlist.lnk", IDLIST),
+                   ("environment.lnk", ENVIRONMENT)]:
+    with open(name, "wb") as file:
+        file.write(link)
+
+
+def directory_entry(name, object_type, child, right, start, size):
+    encoded = utf16(name) + b"\0\0" if name else b""
+    return struct.pack("<64sHBBIII16sIQQIQ", encoded, len(encoded), object_type, 1, NO_STREAM,
+                       right, child, bytes(16), 0, 0, 0, start, size)
+
+
+def compound_file(streams):
+    # a version 3 compound file with every stream in the mini stream, the directory entries
+    # are a chain of right siblings in the order the names compare in
+    names = sorted(streams, key=lambda name: (len(name), name.upper()))
+    mini_stream = b""
+    mini_fat = []
+    starts = {}
+    for name in names:
+        data = streams[name]
+        sectors = -(-len(data) // MINI_SECTOR_SIZE)
+        starts[name] = len(mini_fat)
+        mini_fat += [len(mini_fat) + index + 1 for index in range(sectors - 1)] + [END_OF_CHAIN]
+        mini_stream += data.ljust(sectors * MINI_SECTOR_SIZE, b"\0")
+
+    entries = [directory_entry("Root Entry", 5, 1, NO_STREAM, 0, len(mini_stream))]
+    for index, name in enumerate(names):
+        right = index + 2 if index + 1 < len(names) else NO_STREAM
+        entries.append(directory_entry(name, 2, NO_STREAM, right, starts[name],
+                                       len(streams[name])))
+    while len(entries) % 4:
+        entries.append(directory_entry("", 0, NO_STREAM, NO_STREAM, 0, 0))
+    directory = b"".join(entries)
+    mini_fat_data = struct.pack(f"<{len(mini_fat)}I", *mini_fat)
+    mini_fat_data = mini_fat_data.ljust(-(-len(mini_fat_data) // SECTOR_SIZE) * SECTOR_SIZE,
+                                        b"\xff")
+
+    # the FAT sector comes first, then the directory, the mini FAT and the mini stream
+    chains = []
+    for data in (directory, mini_fat_data, mini_stream):
+        chains.append(-(-len(data) // SECTOR_SIZE))
+    fat = [FAT_SECTOR]
+    starts = []
+    for sectors in chains:
+        starts.append(len(fat))
+        fat += [len(fat) + index + 1 for index in range(sectors - 1)] + [END_OF_CHAIN]
+    fat += [FREE_SECTOR] * (SECTOR_SIZE // 4 - len(fat))
+    entries = bytearray(directory)
+    struct.pack_into("<I", entries, 116, starts[2])
+
+    file_header = struct.pack("<8s16sHHHHH6sIIIIIIIII", bytes.fromhex("d0cf11e0a1b11ae1"),
+                              bytes(16), 0x3E, 3, 0xFFFE, 9, 6, bytes(6), 0, 1, starts[0], 0,
+                              4096, starts[1], chains[1], END_OF_CHAIN, 0)
+    file_header += struct.pack("<I", 0) + b"\xff" * (108 * 4)
+    body = struct.pack(f"<{len(fat)}I", *fat) + bytes(entries) + mini_fat_data
+    body += mini_stream.ljust(chains[2] * SECTOR_SIZE, b"\0")
+    return file_header + body
+
+
+def dest_list(version, entries):
+    # entries of (entry number, hostname, last accessed, pinned position, access count, path),
+    # the most recently used first
+    data = struct.pack("<IIIfII8x", version, len(entries),
+                       sum(1 for entry in entries if entry[3] is not None), 0,
+                       max(entry[0] for entry in entries), len(entries))
+    assert len(data) == DEST_LIST_HEADER_SIZE
+    for number, hostname, accessed, pinned, access_count, path in entries:
+        entry = struct.pack("<Q16s16s16s16s16sIIfQI", 0, bytes(range(16)), bytes(range(16)),
+                            bytes(range(16)), bytes(range(16)), hostname.encode().ljust(16, b"\0"),
+                            number, 0, 1.0, filetime(accessed),
+                            NOT_PINNED if pinned is None else pinned)
+        if version > 1:
+            entry += struct.pack("<iIQ", -1, access_count, 0)
+        entry += struct.pack("<H", len(path)) + utf16(path)
+        if version > 1:
+            entry += bytes(4)
+        data += entry
+    return data
+
+
+# Windows 7 Explorer, the DestList lists an entry whose stream is gone, stream 4 is not listed
+# and stream 5 is not a link
+with open("1b4dd67f29cb1962.automaticDestinations-ms", "wb") as file:
+    file.write(compound_file({
+        "1": LOCAL,
+        "2": NETWORK,
+        "4": IDLIST,
+        "5": b"not a link",
+        "DestList": dest_list(1, [
+            (2, "fileserver", "2024-01-02 03:40:00", 0, None, "\\\\FILESERVER\\SHARE\\reports\\q1.xlsx"),
+            (3, "ws01", "2024-01-02 03:35:00", None, None, "C:\\Users\\alice\\Documents\\gone.txt"),
+            (1, "ws01", "2024-01-02 03:30:00", None, None, "C:\\Users\\alice\\Documents\\R\u00e9sum\u00e9.docx"),
+        ]),
+    }))
+
+# Windows 11 Notepad, with access counts
+with open("9b9cdc69c1c24e2b.automaticDestinations-ms", "wb") as file:
+    file.write(compound_file({
+        "1": IDLIST,
+        "2": ENVIRONMENT,
+        "DestList": dest_list(4, [
+            (2, "ws03", "2024-01-02 03:50:00", 0, 3, "C:\\Windows\\system32\\mspaint.exe"),
+            (1, "ws03", "2024-01-02 02:55:00", None, 7, "C:\\Users\\alice\\Downloads\\setup_x64.exe"),
+        ]),
+    }))
+
+
+def category(category_type, links, name=None):
+    # a custom category is named, the known and task categories are not
+    data = struct.pack("<I", category_type)
+    if name is not None:
+        data += struct.pack("<H", len(name)) + utf16(name)
+    data += struct.pack("<I", len(links))
+    for link in links:
+        data += LINK_CLSID + link
+    return data + struct.pack("<I", 0xBABFFBAB)
+
+
+# PowerShell, a custom category and the tasks
+with open("590aee7bdd69b59b.customDestinations-ms", "wb") as file:
+    file.write(struct.pack("<III", 2, 2, 0))
+    file.write(category(0, [LOCAL, NETWORK], "Recent Documents"))
+    file.write(category(2, [ENVIRONMENT]))
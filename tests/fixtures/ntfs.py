@@ -0,0 +1,506 @@
+#!/usr/bin/env python3
+"""Builds ntfs.img, a small NTFS volume with live, deleted and unusual files, see README.md.
+
+    python3 ntfs.py && gzip -9 -n ntfs.img
+"""
+import struct
+
+SECTOR = 512
+CLUSTER = 4096
+RECORD = 1024
+INDEX_RECORD = 4096
+CLUSTERS = 1024
+MFT_LCN = 4
+MFT_RECORDS = 64
+
+ROOT = 5
+# 2024-01-02 03:04:05 UTC and the three seconds after it
+T0 = (1704164645 + 11644473600) * 10**7
+TIMES = (T0, T0 + 10**7, T0 + 2 * 10**7, T0 + 3 * 10**7)
+
+READ_ONLY, HIDDEN, SYSTEM, ARCHIVE = 0x1, 0x2, 0x4, 0x20
+DIRECTORY_NAME = 0x10000000
+
+STANDARD_INFORMATION, ATTRIBUTE_LIST, FILE_NAME = 0x10, 0x20, 0x30
+VOLUME_NAME, VOLUME_INFORMATION, DATA = 0x60, 0x70, 0x80
+INDEX_ROOT, INDEX_ALLOCATION, BITMAP = 0x90, 0xA0, 0xB0
+
+image = bytearray(CLUSTERS * CLUSTER)
+bitmap = bytearray(CLUSTERS // 8)
+next_cluster = MFT_LCN
+
+
+def allocate(count, lcn=None):
+    """Marks clusters as in use in $Bitmap, from the next free one unless `lcn` is given."""
+    global next_cluster
+    if lcn is None:
+        lcn = next_cluster
+        next_cluster += count
+    for cluster in range(lcn, lcn + count):
+        bitmap[cluster // 8] |= 1 << (cluster % 8)
+    return lcn
+
+
+def free(lcn, count):
+    for cluster in range(lcn, lcn + count):
+        bitmap[cluster // 8] &= ~(1 << (cluster % 8))
+
+
+def write_clusters(lcn, data):
+    image[lcn * CLUSTER : lcn * CLUSTER + len(data)] = data
+
+
+def utf16(name):
+    return name.encode("utf-16-le")
+
+
+def upcase(unit):
+    upper = chr(unit).upper() if not 0xD800 <= unit < 0xE000 else chr(unit)
+    return ord(upper) if len(upper) == 1 and ord(upper) < 0x10000 else unit
+
+
+UPCASE = [upcase(unit) for unit in range(0x10000)]
+
+
+def collation_key(name):
+    return [UPCASE[unit] for unit in struct.unpack("<%dH" % len(name), utf16(name))]
+
+
+def reference(record, sequence):
+    return record | sequence << 48
+
+
+def pad8(data):
+    return data + bytes(-len(data) % 8)
+
+
+def runlist(runs):
+    """Encodes (lcn or None for a hole, cluster count) pairs as NTFS data runs."""
+    out = b""
+    previous = 0
+    for lcn, count in runs:
+        length = count.to_bytes((count.bit_length() + 8) // 8, "little")
+        if lcn is None:
+            out += bytes([len(length)]) + length
+            continue
+        delta = lcn - previous
+        size = 1
+        while not -(1 << (8 * size - 1)) <= delta < 1 << (8 * size - 1):
+            size += 1
+        out += bytes([size << 4 | len(length)]) + length + delta.to_bytes(size, "little", signed=True)
+        previous = lcn
+    return out + b"\0"
+
+
+def fixup(record, usa_offset, sequence_number=1):
+    record = bytearray(record)
+    sectors = len(record) // SECTOR
+    usa = struct.pack("<H", sequence_number)
+    for sector in range(sectors):
+        end = (sector + 1) * SECTOR
+        usa += bytes(record[end - 2 : end])
+        record[end - 2 : end] = struct.pack("<H", sequence_number)
+    record[usa_offset : usa_offset + len(usa)] = usa
+    return bytes(record)
+
+
+class Attribute:
+    def __init__(self, kind, value=b"", name="", runs=None, size=None, flags=0, indexed=False):
+        self.kind = kind
+        self.value = value
+        self.name = name
+        self.runs = runs
+        self.size = size
+        self.flags = flags
+        self.indexed = indexed
+        self.id = 0
+
+    def encode(self):
+        name = utf16(self.name)
+        if self.runs is None:
+            value_offset = (24 + len(name) + 7) // 8 * 8
+            header = struct.pack(
+                "<IIBBHHHIHBB",
+                self.kind,
+                0,
+                0,
+                len(self.name),
+                24,
+                self.flags,
+                self.id,
+                len(self.value),
+                value_offset,
+                1 if self.indexed else 0,
+                0,
+            )
+            body = pad8(header + name + bytes(value_offset - 24 - len(name)) + self.value)
+        else:
+            clusters = sum(count for _, count in self.runs)
+            mapping = pad8(runlist(self.runs))
+            runs_offset = (64 + len(name) + 7) // 8 * 8
+            header = struct.pack(
+                "<IIBBHHHQQHH4xQQQ",
+                self.kind,
+                0,
+                1,
+                len(self.name),
+                64,
+                self.flags,
+                self.id,
+                0,
+                clusters - 1,
+                runs_offset,
+                0,
+                clusters * CLUSTER,
+                self.size,
+                self.size,
+            )
+            body = header + name + bytes(runs_offset - 64 - len(name)) + mapping
+        return body[:4] + struct.pack("<I", len(body)) + body[8:]
+
+
+def standard_information(attributes):
+    return Attribute(STANDARD_INFORMATION, struct.pack("<4QI12xIIQQ", *TIMES, attributes, 0, 0x100, 0, 0))
+
+
+def file_name_value(parent, name, attributes, size=0, namespace=1):
+    return struct.pack(
+        "<Q4QQQIIBB", parent, *TIMES, (size + CLUSTER - 1) // CLUSTER * CLUSTER, size, attributes, 0, len(name), namespace
+    ) + utf16(name)
+
+
+class File:
+    def __init__(self, number, sequence=1, in_use=True, directory=False, base=0):
+        self.number = number
+        self.sequence = sequence
+        self.in_use = in_use
+        self.directory = directory
+        self.base = base
+        self.attributes = []
+        self.links = 0
+
+    def add(self, attribute):
+        attribute.id = len(self.attributes)
+        self.attributes.append(attribute)
+        return attribute
+
+    def encode(self):
+        attributes = b"".join(attribute.encode() for attribute in sorted(self.attributes, key=lambda a: (a.kind, a.name)))
+        used = 56 + len(attributes) + 8
+        flags = (1 if self.in_use else 0) | (2 if self.directory else 0)
+        header = struct.pack(
+            "<4sHHQHHHHIIQHHI",
+            b"FILE",
+            48,
+            RECORD // SECTOR + 1,
+            0,
+            self.sequence,
+            self.links,
+            56,
+            flags,
+            used,
+            RECORD,
+            self.base,
+            len(self.attributes),
+            0,
+            self.number,
+        )
+        record = (header + bytes(8) + attributes + b"\xff\xff\xff\xff").ljust(RECORD, b"\0")
+        return fixup(record, 48)
+
+
+records = {}
+
+
+def new_file(number, parent, name, attributes=ARCHIVE, sequence=1, in_use=True, directory=False, parent_sequence=None):
+    file = File(number, sequence, in_use, directory)
+    file.add(standard_information(attributes))
+    if name is not None:
+        link(file, parent, name, parent_sequence)
+    records[number] = file
+    return file
+
+
+def link(file, parent, name, parent_sequence=None):
+    if parent_sequence is None:
+        parent_sequence = records[parent].sequence if parent in records else parent
+    file.add(Attribute(FILE_NAME, b"", indexed=True))
+    file.attributes[-1].link = (parent, parent_sequence, name)
+    file.links += 1
+
+
+def set_data(file, data, name="", runs=None, size=None, flags=0):
+    if runs is None and len(data) > 600:
+        clusters = (len(data) + CLUSTER - 1) // CLUSTER
+        lcn = allocate(clusters)
+        write_clusters(lcn, data)
+        runs = [(lcn, clusters)]
+    if runs is not None:
+        file.add(Attribute(DATA, name=name, runs=runs, size=len(data) if size is None else size, flags=flags))
+    else:
+        file.add(Attribute(DATA, data, name=name))
+
+
+def data_size(file):
+    for attribute in file.attributes:
+        if attribute.kind == DATA and attribute.name == "":
+            return attribute.size if attribute.runs is not None else len(attribute.value)
+    return 0
+
+
+def finish_names():
+    """Fills in the $FILE_NAME values now that every data size is known."""
+    for file in records.values():
+        for attribute in file.attributes:
+            if attribute.kind == FILE_NAME and hasattr(attribute, "link"):
+                parent, sequence, name = attribute.link
+                owner = records[file.base & 0xFFFFFFFFFFFF] if file.base else file
+                info = owner.attributes[0].value
+                flags = struct.unpack_from("<I", info, 32)[0]
+                if owner.directory:
+                    flags |= DIRECTORY_NAME
+                attribute.value = file_name_value(reference(parent, sequence), name, flags, data_size(owner))
+
+
+def index_entry(file_reference, key, subnode=None, last=False):
+    flags = (1 if subnode is not None else 0) | (2 if last else 0)
+    length = 16 + len(pad8(key)) + (8 if subnode is not None else 0)
+    entry = struct.pack("<QHHI", file_reference, length, len(key), flags) + pad8(key)
+    if subnode is not None:
+        entry += struct.pack("<Q", subnode)
+    return entry
+
+
+def directory_entries(directory):
+    entries = []
+    for file in records.values():
+        if not file.in_use or file.base:
+            continue
+        for attribute in file.attributes + [a for f in records.values() if f.base == reference(file.number, file.sequence) for a in f.attributes]:
+            if attribute.kind == FILE_NAME and attribute.link[0] == directory.number:
+                name = attribute.link[2]
+                entries.append((collation_key(name), reference(file.number, file.sequence), attribute.value))
+    return [(reference, key) for _, reference, key in sorted(entries)]
+
+
+def build_index(directory):
+    entries = directory_entries(directory)
+    node = b"".join(index_entry(reference, key) for reference, key in entries)
+    if len(node) + 16 <= 400:
+        node += index_entry(0, b"", last=True)
+        header = struct.pack("<IIIB3x", 16, 16 + len(node), 16 + len(node), 0)
+        root = struct.pack("<IIIb3x", FILE_NAME, 1, INDEX_RECORD, INDEX_RECORD // CLUSTER) + header + node
+        directory.add(Attribute(INDEX_ROOT, root, name="$I30"))
+        return
+
+    # too many entries for the record, they go in a single index record under the root
+    node += index_entry(0, b"", last=True)
+    entries_offset = 0x40 - 0x18
+    header = struct.pack("<IIIB3x", entries_offset, entries_offset + len(node), INDEX_RECORD - 0x18, 0)
+    record = struct.pack("<4sHHQQ", b"INDX", 0x28, INDEX_RECORD // SECTOR + 1, 0, 0) + header
+    record = (record.ljust(0x40, b"\0") + node).ljust(INDEX_RECORD, b"\0")
+    lcn = allocate(1)
+    write_clusters(lcn, fixup(record, 0x28))
+    node = index_entry(0, b"", subnode=0, last=True)
+    header = struct.pack("<IIIB3x", 16, 16 + len(node), 16 + len(node), 1)
+    root = struct.pack("<IIIb3x", FILE_NAME, 1, INDEX_RECORD, INDEX_RECORD // CLUSTER) + header + node
+    directory.add(Attribute(INDEX_ROOT, root, name="$I30"))
+    directory.add(Attribute(INDEX_ALLOCATION, name="$I30", runs=[(lcn, 1)], size=INDEX_RECORD))
+    directory.add(Attribute(BITMAP, b"\x01" + bytes(7), name="$I30"))
+
+
+# the system files, $MFT and $Bitmap get their data once everything else is allocated
+allocate(2, 0)  # $Boot
+mft_lcn = allocate(MFT_RECORDS * RECORD // CLUSTER)
+mirror_lcn = allocate(1)
+upcase_lcn = allocate(32)
+write_clusters(upcase_lcn, struct.pack("<65536H", *UPCASE))
+bitmap_lcn = allocate(1)
+
+system = [
+    (0, "$MFT", 1),
+    (1, "$MFTMirr", 1),
+    (2, "$LogFile", 2),
+    (3, "$Volume", 3),
+    (4, "$AttrDef", 4),
+    (5, ".", 5),
+    (6, "$Bitmap", 6),
+    (7, "$Boot", 7),
+    (8, "$BadClus", 8),
+    (9, "$Secure", 9),
+    (10, "$UpCase", 10),
+    (11, "$Extend", 11),
+]
+for number, name, sequence in system:
+    new_file(number, ROOT, name, HIDDEN | SYSTEM, sequence, directory=number in (ROOT, 11), parent_sequence=ROOT)
+
+set_data(records[0], b"", runs=[(mft_lcn, MFT_RECORDS * RECORD // CLUSTER)], size=MFT_RECORDS * RECORD)
+set_data(records[1], b"", runs=[(mirror_lcn, 1)], size=4 * RECORD)
+set_data(records[2], b"")
+records[3].add(Attribute(VOLUME_NAME, utf16("fixture")))
+records[3].add(Attribute(VOLUME_INFORMATION, struct.pack("<8xBBH", 3, 1, 0)))
+set_data(records[3], b"")
+set_data(records[4], b"")
+set_data(records[6], b"", runs=[(bitmap_lcn, 1)], size=len(bitmap))
+set_data(records[7], b"", runs=[(0, 2)], size=2 * CLUSTER)
+set_data(records[8], b"")
+set_data(records[10], b"", runs=[(upcase_lcn, 32)], size=0x20000)
+
+# $Extend\$UsnJrnl, $J starts with a hole like a journal whose oldest records were freed and
+# has another in the middle
+usn = new_file(24, 11, "$UsnJrnl", HIDDEN | SYSTEM | ARCHIVE)
+
+
+def usn_record(record, parent, usn_number, reason, name, attributes=ARCHIVE):
+    name = utf16(name)
+    body = struct.pack(
+        "<IHHQQqqIIII HH", 0, 2, 0, record, parent, usn_number, T0, reason, 0, 0, attributes, len(name), 60
+    ) + name
+    body = pad8(body)
+    return struct.pack("<I", len(body)) + body[4:]
+
+
+journal_start = 2 * CLUSTER
+first = usn_record(reference(29, 1), reference(ROOT, ROOT), journal_start, 0x100, "notes.txt")
+first += usn_record(reference(29, 1), reference(ROOT, ROOT), journal_start + len(first), 0x80000100, "notes.txt")
+second_start = 4 * CLUSTER
+second = usn_record(reference(36, 1), reference(ROOT, ROOT), second_start, 0x80000200, "deleted.txt")
+journal_lcn = allocate(1)
+write_clusters(journal_lcn, first)
+journal_lcn2 = allocate(1)
+write_clusters(journal_lcn2, second)
+set_data(usn, b"", name="$J", runs=[(None, 2), (journal_lcn, 1), (None, 1), (journal_lcn2, 1)], size=second_start + len(second), flags=0x8000)
+set_data(usn, struct.pack("<QQqq", 0x2000000, 0x800000, 0x1D6A5F1C0E6B5E0, 0), name="$Max")
+
+# Windows\System32\config\SYSTEM marks the volume as holding Windows
+new_file(25, ROOT, "Windows", directory=True, attributes=0)
+new_file(26, 25, "System32", directory=True, attributes=0)
+new_file(27, 26, "config", directory=True, attributes=0)
+set_data(new_file(28, 27, "SYSTEM"), b"regf" + bytes(60))
+
+notes = new_file(29, ROOT, "notes.txt")
+set_data(notes, b"hello from ntfs\n")
+set_data(notes, b"[ZoneTransfer]\r\nZoneId=3\r\n", name="Zone.Identifier")
+
+new_file(30, ROOT, "docs", directory=True, attributes=0)
+
+# a non-resident file in two fragments, also linked from the root
+fragmented = new_file(31, 30, "fragmented.bin")
+link(fragmented, ROOT, "fragment-link.bin")
+contents = bytes(i * 13 % 251 for i in range(10000))
+first_lcn = allocate(2)
+free(allocate(1), 1)  # leaves a free cluster between the fragments
+second_lcn = allocate(1)
+write_clusters(first_lcn, contents[: 2 * CLUSTER])
+write_clusters(second_lcn, contents[2 * CLUSTER :])
+set_data(fragmented, contents, runs=[(first_lcn, 2), (second_lcn, 1)])
+
+set_data(new_file(32, ROOT, "hidden.txt", HIDDEN | SYSTEM | ARCHIVE | READ_ONLY), b"hidden\n")
+
+# holes at the start and in the middle that are part of the file, unlike those of $J
+sparse = new_file(33, ROOT, "sparse.bin", ARCHIVE | 0x200)
+sparse_a, sparse_b = allocate(1), allocate(1)
+write_clusters(sparse_a, b"A" * CLUSTER)
+write_clusters(sparse_b, b"B" * 1000)
+set_data(sparse, b"", runs=[(None, 1), (sparse_a, 1), (None, 2), (sparse_b, 1)], size=4 * CLUSTER + 1000, flags=0x8000)
+
+# a file whose name is in an extension record, found through its $ATTRIBUTE_LIST
+extended = File(34)
+extended.add(standard_information(ARCHIVE))
+extended_list = extended.add(Attribute(ATTRIBUTE_LIST))
+set_data(extended, b"named in another record\n")
+records[34] = extended
+extension = File(35, base=reference(34, 1))
+link(extension, ROOT, "extended.txt")
+extended.links = 1
+extension.links = 0
+records[35] = extension
+
+# deleted files, their records are no longer in use and their sequence numbers were increased
+deleted_contents = bytes(b"deleted " * 625)
+deleted = new_file(36, ROOT, "deleted.txt", sequence=2, in_use=False, parent_sequence=ROOT)
+deleted_lcn = allocate(2)
+write_clusters(deleted_lcn, deleted_contents)
+free(deleted_lcn, 2)
+set_data(deleted, deleted_contents, runs=[(deleted_lcn, 2)])
+
+# the cluster of overwritten.bin was given to reused.bin after it was deleted
+overwritten = new_file(37, ROOT, "overwritten.bin", sequence=2, in_use=False, parent_sequence=ROOT)
+reused_lcn = allocate(1)
+write_clusters(reused_lcn, b"R" * CLUSTER)
+set_data(overwritten, b"O" * CLUSTER, runs=[(reused_lcn, 1)])
+set_data(new_file(38, ROOT, "reused.bin"), b"R" * CLUSTER, runs=[(reused_lcn, 1)])
+
+# olddir was deleted and its record reused for newdir, so the file deleted with it must not
+# be placed in newdir
+new_file(39, ROOT, "newdir", sequence=2, directory=True, attributes=0)
+set_data(new_file(40, 39, "gone.txt", sequence=2, in_use=False, parent_sequence=1), b"gone\n")
+
+# removed is a deleted directory whose record was not reused, the file in it keeps its path
+new_file(41, ROOT, "removed", sequence=2, in_use=False, directory=True, attributes=0, parent_sequence=ROOT)
+set_data(new_file(42, 41, "inside.txt", sequence=2, in_use=False, parent_sequence=1), b"inside\n")
+
+finish_names()
+
+# the attribute list of extended.txt names every attribute and the record it is in
+list_value = b""
+listed = [(attribute, file) for file in (extended, extension) for attribute in file.attributes]
+for attribute, file in sorted(listed, key=lambda item: (item[0].kind, item[0].name)):
+    if attribute.kind == ATTRIBUTE_LIST:
+        continue
+    name = utf16(attribute.name)
+    entry = struct.pack("<IHBBQQH", attribute.kind, 0, len(attribute.name), 26, 0, reference(file.number, 1), attribute.id) + name
+    entry = pad8(entry)
+    list_value += entry[:4] + struct.pack("<H", len(entry)) + entry[6:]
+extended_list.value = list_value
+
+for number in sorted(records):
+    file = records[number]
+    if file.directory and file.in_use:
+        build_index(file)
+# a deleted directory keeps its index, which no longer matters
+build_index(records[41])
+
+in_use = bytearray(MFT_RECORDS // 8)
+for number, file in records.items():
+    if file.in_use:
+        in_use[number // 8] |= 1 << (number % 8)
+records[0].add(Attribute(BITMAP, bytes(in_use)))
+write_clusters(bitmap_lcn, bitmap)
+
+mft = bytearray(MFT_RECORDS * RECORD)
+for number, file in records.items():
+    mft[number * RECORD : (number + 1) * RECORD] = file.encode()
+write_clusters(mft_lcn, mft)
+write_clusters(mirror_lcn, mft[: 4 * RECORD])
+
+boot = struct.pack(
+    "<3s8sHB7sB2sHHI4sBBBBQqqb3xb3xQI",
+    b"\xebR\x90",
+    b"NTFS    ",
+    SECTOR,
+    CLUSTER // SECTOR,
+    bytes(7),
+    0xF8,
+    bytes(2),
+    63,
+    255,
+    0,
+    bytes(4),
+    0x80,
+    0,
+    0x80,
+    0,
+    len(image) // SECTOR - 1,
+    mft_lcn,
+    mirror_lcn,
+    -10,
+    1,
+    0x1234567890ABCDEF,
+    0,
+)
+boot = boot.ljust(510, b"\0") + b"\x55\xaa"
+image[0:SECTOR] = boot
+image[-SECTOR:] = boot
+
+open("ntfs.img", "wb").write(image)
@@ -0,0 +1,204 @@
+#!/usr/bin/env python3
+"""Builds a prefetch file for every format version, those from Windows 10 on compressed, see
+README.md.
+
+    python3 prefetch.py && gzip -9 -n *.pf
+"""
+import heapq
+import struct
+
+BLOCK = 65536
+WINDOW = 4096
+SYMBOLS = 512
+MAX_CODE_LENGTH = 15
+HASH = 0x0BD30981
+
+
+def filetime(hours):
+    return 132000000000000000 + hours * 3600 * 10**7
+
+
+def utf16(text):
+    return text.encode("utf-16-le")
+
+
+def code_lengths(counts):
+    # Huffman code lengths, with the counts halved until no code is longer than MS-XCA allows
+    used = [symbol for symbol in range(SYMBOLS) if counts[symbol]]
+    if len(used) == 1:
+        used.append(0 if used[0] else 1)
+    while True:
+        heap = [(max(counts[symbol], 1), [symbol]) for symbol in used]
+        heapq.heapify(heap)
+        lengths = [0] * SYMBOLS
+        while len(heap) > 1:
+            first_count, first = heapq.heappop(heap)
+            second_count, second = heapq.heappop(heap)
+            for symbol in first + second:
+                lengths[symbol] += 1
+            heapq.heappush(heap, (first_count + second_count, first + second))
+        if max(lengths) <= MAX_CODE_LENGTH:
+            return lengths
+        counts = [(count + 1) // 2 for count in counts]
+
+
+def canonical_codes(lengths):
+    # codes are handed out by length and then by symbol, as the decoder builds its table
+    codes = [0] * SYMBOLS
+    next_code = 0
+    for length in range(1, MAX_CODE_LENGTH + 1):
+        for symbol in range(SYMBOLS):
+            if lengths[symbol] == length:
+                codes[symbol] = next_code >> (MAX_CODE_LENGTH - length)
+                next_code += 1 << (MAX_CODE_LENGTH - length)
+    return codes
+
+
+def find_match(data, position, end, chains):
+    best_length, best_offset = 0, 0
+    for candidate in reversed(chains.get(data[position:position + 3], [])[-16:]):
+        if position - candidate > WINDOW:
+            break
+        length = 0
+        while position + length < end and data[candidate + length] == data[position + length]:
+            length += 1
+        if length > best_length:
+            best_length, best_offset = length, position - candidate
+    return best_length, best_offset
+
+
+def encode_block(data, start, chains):
+    # a list of ("symbol", value), ("bits", count, value), ("byte", value) and ("word", value)
+    end = min(start + BLOCK, len(data))
+    events = []
+    position = start
+    while position < end:
+        length, offset = find_match(data, position, end, chains)
+        if length >= 3:
+            offset_bits = offset.bit_length() - 1
+            extra = length - 3
+            events.append(("symbol", 256 + (offset_bits << 4) + min(extra, 15)))
+            if extra >= 15:
+                if extra - 15 < 255:
+                    events.append(("byte", extra - 15))
+                else:
+                    events += [("byte", 255), ("word", extra)]
+            if offset_bits:
+                events.append(("bits", offset_bits, offset - (1 << offset_bits)))
+        else:
+            length = 1
+            events.append(("symbol", data[position]))
+        for skipped in range(position, position + length):
+            chains.setdefault(data[skipped:skipped + 3], []).append(skipped)
+        position += length
+    return layout(events)
+
+
+def layout(events):
+    counts = [0] * SYMBOLS
+    for event in events:
+        if event[0] == "symbol":
+            counts[event[1]] += 1
+    lengths = code_lengths(counts)
+    codes = canonical_codes(lengths)
+
+    # bits are read sixteen at a time from the next two bytes of the stream whenever fewer than
+    # sixteen are left, so the extra length bytes land between those words
+    table = bytes(lengths[index] | lengths[index + 1] << 4 for index in range(0, SYMBOLS, 2))
+    body = bytearray(4)
+    slots = [0, 2]
+    bits = []
+    for event in events:
+        if event[0] in ("symbol", "bits"):
+            count, value = (lengths[event[1]], codes[event[1]]) if event[0] == "symbol" else event[1:]
+            bits += [(value >> (count - 1 - index)) & 1 for index in range(count)]
+            while len(bits) > 16 * (len(slots) - 1):
+                slots.append(len(body))
+                body += bytes(2)
+        elif event[0] == "byte":
+            body.append(event[1])
+        else:
+            body += struct.pack("<H", event[1])
+    bits += [0] * (-len(bits) % 16)
+    for index in range(0, len(bits), 16):
+        word = int("".join(map(str, bits[index:index + 16])), 2)
+        struct.pack_into("<H", body, slots[index // 16], word)
+    return table + body
+
+
+def xpress_huffman(data):
+    chains = {}
+    return b"".join(encode_block(data, start, chains) for start in range(0, len(data), BLOCK))
+
+
+def mam(data, checksum=False):
+    # the checksum is left zero, the parser does not check it
+    if checksum:
+        return b"MAM\x84" + struct.pack("<II", len(data), 0) + xpress_huffman(data)
+    return b"MAM\x04" + struct.pack("<I", len(data)) + xpress_huffman(data)
+
+
+def scca(version, name, runs, run_count, short_metrics=False):
+    # where the file information ends, the run times start and the run count is, and the size of
+    # a volume information entry
+    if version == 17:
+        information_end, run_times, run_count_offset, volume_size = 152, 120, 144, 40
+    elif version == 23:
+        information_end, run_times, run_count_offset, volume_size = 240, 128, 152, 104
+    elif version == 26:
+        information_end, run_times, run_count_offset, volume_size = 308, 128, 208, 104
+    elif short_metrics:
+        information_end, run_times, run_count_offset, volume_size = 0x128, 128, 200, 96
+    else:
+        information_end, run_times, run_count_offset, volume_size = 0x130, 128, 208, 96
+
+    files = ["\\VOLUME{01d2}\\WINDOWS\\SYSTEM32\\NTDLL.DLL", "\\VOLUME{01d2}\\TOOLS\\" + name]
+    file_names = b"".join(utf16(file) + b"\0\0" for file in files)
+    device = utf16("\\VOLUME{01d2a1b2c3d4e5f6-a4b19c3e}")
+    directories = ["\\VOLUME{01d2}\\WINDOWS", "\\VOLUME{01d2}\\TOOLS"]
+    directory_strings = b"".join(
+        struct.pack("<H", len(directory)) + utf16(directory) + b"\0\0" for directory in directories
+    )
+    volume = bytearray(volume_size)
+    struct.pack_into("<IIQI", volume, 0, volume_size, len(device) // 2, filetime(1), 0xA4B19C3E)
+    struct.pack_into("<II", volume, 28, volume_size + len(device) + 2, len(directories))
+    volumes = bytes(volume) + device + b"\0\0" + directory_strings
+
+    files_offset = information_end
+    volumes_offset = files_offset + len(file_names)
+    data = bytearray(volumes_offset + len(volumes))
+    struct.pack_into("<I4sI", data, 0, version, b"SCCA", 0)
+    struct.pack_into("<I", data, 12, len(data))
+    data[16:16 + len(utf16(name))] = utf16(name)
+    struct.pack_into("<III", data, 76, HASH, 0, information_end)
+    struct.pack_into("<IIIII", data, 100, files_offset, len(file_names), volumes_offset, 1, len(volumes))
+    for index, hours in enumerate(runs):
+        struct.pack_into("<Q", data, run_times + 8 * index, filetime(hours))
+    struct.pack_into("<I", data, run_count_offset, run_count)
+    data[files_offset:volumes_offset] = file_names
+    data[volumes_offset:] = volumes
+    return bytes(data)
+
+
+def noise(length, seed):
+    # bytes from a linear congruential generator, which Huffman coding cannot shrink
+    output = bytearray()
+    for _ in range(length):
+        seed = (seed * 1103515245 + 12345) & 0x7FFFFFFF
+        output.append(seed >> 16 & 0xFF)
+    return bytes(output)
+
+
+def write(name, data):
+    with open(name + "-%08X.pf" % HASH, "wb") as file:
+        file.write(data)
+
+
+write("XP.EXE", scca(17, "XP.EXE", [5], 3))
+write("SEVEN.EXE", scca(23, "SEVEN.EXE", [6], 12))
+write("EIGHT.EXE", scca(26, "EIGHT.EXE", [9, 8, 7], 4))
+write("TEN.EXE", mam(scca(30, "TEN.EXE", [20, 19, 18, 17, 16, 15, 14, 13], 42)))
+write("ELEVEN.EXE", mam(scca(31, "ELEVEN.EXE", [30, 29], 7, short_metrics=True), checksum=True))
+# padded out over three blocks with matches of every length encoding and uncompressible runs
+padding = noise(3000, 1) * 40 + noise(8000, 2) + b"A" * 300 + b"AB" * 4000 + noise(2000, 3) * 3
+write("PADDED.EXE", mam(scca(30, "PADDED.EXE", [1], 1000) + padding))
@@ -0,0 +1,533 @@
+#!/usr/bin/env python3
+"""Builds the SYSTEM, SYSTEM.win7, SOFTWARE, NTUSER.DAT and Amcache.hve hives and the
+SOFTWARE.LOG1 and SOFTWARE.LOG2 transaction logs, see README.md.
+
+    python3 registry.py && gzip -9 -n SYSTEM SYSTEM.win7 SOFTWARE SOFTWARE.LOG1 SOFTWARE.LOG2 NTUSER.DAT Amcache.hve
+"""
+import struct
+from datetime import datetime, timezone
+
+BASE_BLOCK_SIZE = 4096
+LOG_BASE_BLOCK_SIZE = 512
+HBIN_SIZE = 4096
+HBIN_HEADER_SIZE = 32
+BIG_DATA_SEGMENT_SIZE = 16344
+MARVIN32_SEED = 0x82EF4D887A4E55C5
+NO_OFFSET = 0xFFFFFFFF
+
+KEY_HIVE_ENTRY = 0x0004
+KEY_COMP_NAME = 0x0020
+VALUE_COMP_NAME = 0x0001
+DATA_IS_INLINE = 0x80000000
+
+REG_SZ = 1
+REG_EXPAND_SZ = 2
+REG_BINARY = 3
+REG_DWORD = 4
+REG_MULTI_SZ = 7
+REG_QWORD = 11
+
+# the file types of the base block of a hive and of a new format transaction log
+FILE_TYPE_PRIMARY = 0
+FILE_TYPE_LOG_NEW = 6
+
+
+def filetime(text):
+    # a FILETIME of a UTC time written as 2024-01-02 03:04:05
+    time = datetime.strptime(text, "%Y-%m-%d %H:%M:%S").replace(tzinfo=timezone.utc)
+    return (int(time.timestamp()) + 11644473600) * 10_000_000
+
+
+def utf16(text):
+    return text.encode("utf-16-le")
+
+
+def sz(text):
+    return (REG_SZ, utf16(text) + b"\0\0")
+
+
+def expand_sz(text):
+    return (REG_EXPAND_SZ, utf16(text) + b"\0\0")
+
+
+def multi_sz(strings):
+    return (REG_MULTI_SZ, b"".join(utf16(string) + b"\0\0" for string in strings) + b"\0\0")
+
+
+def dword(number):
+    return (REG_DWORD, struct.pack("<I", number))
+
+
+def qword(number):
+    return (REG_QWORD, struct.pack("<Q", number))
+
+
+def binary(data):
+    return (REG_BINARY, data)
+
+
+def compressible(name):
+    # names that fit in Latin-1 are stored a byte per character
+    return all(ord(c) < 256 for c in name)
+
+
+def stored_name(name):
+    return name.encode("latin-1") if compressible(name) else utf16(name)
+
+
+def lh_hash(name):
+    hash = 0
+    for c in name.upper():
+        hash = (hash * 37 + ord(c)) & 0xFFFFFFFF
+    return hash
+
+
+def marvin32(data):
+    # the Marvin32 hash of the registry, with its fixed seed
+    lo = MARVIN32_SEED & 0xFFFFFFFF
+    hi = MARVIN32_SEED >> 32
+
+    def rotl(x, n):
+        return ((x << n) | (x >> (32 - n))) & 0xFFFFFFFF
+
+    def block(lo, hi):
+        hi ^= lo
+        lo = rotl(lo, 20)
+        lo = (lo + hi) & 0xFFFFFFFF
+        hi = rotl(hi, 9)
+        hi ^= lo
+        lo = rotl(lo, 27)
+        lo = (lo + hi) & 0xFFFFFFFF
+        hi = rotl(hi, 19)
+        return lo, hi
+
+    whole = len(data) // 4 * 4
+    for offset in range(0, whole, 4):
+        lo = (lo + struct.unpack_from("<I", data, offset)[0]) & 0xFFFFFFFF
+        lo, hi = block(lo, hi)
+    tail = data[whole:]
+    last = 0x80
+    if len(tail) == 1:
+        last = 0x8000 | tail[0]
+    elif len(tail) == 2:
+        last = 0x800000 | struct.unpack("<H", tail)[0]
+    elif len(tail) == 3:
+        last = 0x80000000 | struct.unpack_from("<H", tail)[0] | tail[2] << 16
+    lo = (lo + last) & 0xFFFFFFFF
+    lo, hi = block(lo, hi)
+    lo, hi = block(lo, hi)
+    return hi << 32 | lo
+
+
+class Key:
+    def __init__(self, name, time, values=(), subkeys=(), subkey_list="lf"):
+        self.name = name
+        self.time = filetime(time)
+        # (name, (type, data)) in the order they are listed
+        self.values = list(values)
+        self.subkeys = list(subkeys)
+        # lf, lh or li, or ri for an index root over two lh lists
+        self.subkey_list = subkey_list
+
+
+class Hive:
+    def __init__(self):
+        self.bins = bytearray()
+        self.bin_start = 0
+        # the offset of the nk cell of every key by its path below the root
+        self.keys = {}
+        self.start_bin()
+
+    def start_bin(self):
+        self.bin_start = len(self.bins)
+        self.bins += bytes(HBIN_HEADER_SIZE)
+
+    def close_bin(self):
+        # a bin is a whole number of pages, what is left of it is a single free cell
+        size = -(-(len(self.bins) - self.bin_start) // HBIN_SIZE) * HBIN_SIZE
+        free = self.bin_start + size - len(self.bins)
+        if free:
+            self.bins += struct.pack("<i", free) + bytes(free - 4)
+        struct.pack_into("<4sII8sQI", self.bins, self.bin_start, b"hbin", self.bin_start, size,
+                         bytes(8), filetime("2024-01-02 03:04:05"), 0)
+
+    def alloc(self, data):
+        size = -(-(4 + len(data)) // 8) * 8
+        offset = len(self.bins)
+        self.bins += struct.pack("<i", -size) + data + bytes(size - 4 - len(data))
+        return offset
+
+    def patch(self, offset, field, fmt, *values):
+        # writes into the data of the cell at offset, past its size
+        struct.pack_into(fmt, self.bins, offset + 4 + field, *values)
+
+    def value_data(self, data):
+        if len(data) <= BIG_DATA_SEGMENT_SIZE:
+            return self.alloc(data)
+        segments = [self.alloc(data[start:start + BIG_DATA_SEGMENT_SIZE])
+                    for start in range(0, len(data), BIG_DATA_SEGMENT_SIZE)]
+        segment_list = self.alloc(struct.pack(f"<{len(segments)}I", *segments))
+        return self.alloc(struct.pack("<2sHI", b"db", len(segments), segment_list))
+
+    def value(self, name, value_type, data):
+        if len(data) <= 4:
+            size = len(data) | DATA_IS_INLINE
+            data_offset = struct.unpack("<I", data.ljust(4, b"\0"))[0]
+        else:
+            size = len(data)
+            data_offset = self.value_data(data)
+        flags = VALUE_COMP_NAME if compressible(name) else 0
+        stored = stored_name(name)
+        return self.alloc(struct.pack("<2sHIIIHH", b"vk", len(stored), size, data_offset,
+                                      value_type, flags, 0) + stored)
+
+    def value_list(self, offsets):
+        return self.alloc(struct.pack(f"<{len(offsets)}I", *offsets))
+
+    def subkey_list(self, kind, keys):
+        keys = sorted(keys, key=lambda key: key[0].upper())
+        if kind == "ri":
+            half = (len(keys) + 1) // 2
+            lists = [self.subkey_list("lh", keys[:half]), self.subkey_list("lh", keys[half:])]
+            return self.alloc(struct.pack("<2sH2I", b"ri", 2, *lists))
+        body = b""
+        for name, offset in keys:
+            if kind == "lf":
+                body += struct.pack("<I4s", offset, stored_name(name)[:4].ljust(4, b"\0"))
+            elif kind == "lh":
+                body += struct.pack("<II", offset, lh_hash(name))
+            else:
+                body += struct.pack("<I", offset)
+        return self.alloc(struct.pack("<2sH", kind.encode(), len(keys)) + body)
+
+    def key(self, key, parent=NO_OFFSET, path=""):
+        stored = stored_name(key.name)
+        flags = (KEY_COMP_NAME if compressible(key.name) else 0)
+        if parent == NO_OFFSET:
+            flags |= KEY_HIVE_ENTRY
+        # the cell is written first so its subkeys can point back at it
+        nk = self.alloc(bytes(76) + stored)
+        self.keys[path] = nk
+        self.patch(nk, 0, "<2sHQII", b"nk", flags, key.time, 0, parent)
+        self.patch(nk, 44, "<II", NO_OFFSET, NO_OFFSET)
+        self.patch(nk, 72, "<HH", len(stored), 0)
+
+        values = [self.value(name, value_type, data) for name, (value_type, data) in key.values]
+        value_list = self.value_list(values) if values else NO_OFFSET
+        subkeys = [(subkey.name, self.key(subkey, nk, f"{path}\\{subkey.name}".lstrip("\\")))
+                   for subkey in key.subkeys]
+        subkey_list = self.subkey_list(key.subkey_list, subkeys) if subkeys else NO_OFFSET
+
+        self.patch(nk, 20, "<IIII", len(subkeys), 0, subkey_list, NO_OFFSET)
+        self.patch(nk, 36, "<II", len(values), value_list)
+        self.patch(nk, 52, "<IIII", max((len(name) * 2 for name, _ in subkeys), default=0), 0,
+                   max((len(name) * 2 for name, _ in key.values), default=0),
+                   max((len(data) for _, (_, data) in key.values), default=0))
+        return nk
+
+    def value_count(self, path, count, value_list):
+        self.patch(self.keys[path], 36, "<II", count, value_list)
+
+    def last_written(self, path, time):
+        self.patch(self.keys[path], 4, "<Q", filetime(time))
+
+
+def base_block(name, root, bins_size, sequence, file_type=FILE_TYPE_PRIMARY):
+    # sequence is the primary and secondary sequence numbers, which differ while the hive is
+    # being written to
+    block = bytearray(BASE_BLOCK_SIZE)
+    struct.pack_into("<4sIIQIIIIIII", block, 0, b"regf", sequence[0], sequence[1],
+                     filetime("2024-01-02 03:04:05"), 1, 6, file_type, 1, root, bins_size, 1)
+    name = utf16(name)[:64]
+    block[48:48 + len(name)] = name
+    checksum = 0
+    for offset in range(0, 508, 4):
+        checksum ^= struct.unpack_from("<I", block, offset)[0]
+    checksum = {0: 1, 0xFFFFFFFF: 0xFFFFFFFE}.get(checksum, checksum)
+    struct.pack_into("<I", block, 508, checksum)
+    return bytes(block)
+
+
+def write(file_name, name, root):
+    hive = Hive()
+    offset = hive.key(root)
+    hive.close_bin()
+    with open(file_name, "wb") as file:
+        file.write(base_block(name, offset, len(hive.bins), (1, 1)) + hive.bins)
+
+
+def log_entry(sequence, bins_size, pages, torn=False):
+    # a new format log entry rewriting whole pages of the hive bins, padded to a sector
+    references = b"".join(struct.pack("<II", offset, len(page)) for offset, page in pages)
+    body = references + b"".join(page for _, page in pages)
+    body += bytes(-(40 + len(body)) % 512)
+    header = struct.pack("<4sIIIII", b"HvLE", 40 + len(body), 0, sequence, bins_size,
+                         len(pages))
+    header += struct.pack("<Q", marvin32(body) ^ (1 if torn else 0))
+    return header + struct.pack("<Q", marvin32(header)) + body
+
+
+def run(time, values):
+    return Key("ROOT", time, subkeys=[
+        Key("Microsoft", time, subkeys=[
+            Key("Windows", time, subkeys=[
+                Key("CurrentVersion", time, subkeys=[
+                    Key("Run", time, values),
+                ]),
+            ]),
+        ]),
+    ])
+
+
+# SYSTEM of Windows 10, with the services listed through an index root
+
+def shimcache_win10(entries):
+    data = struct.pack("<I", 0x34) + bytes(0x30)
+    for path, modified in entries:
+        entry = struct.pack("<H", len(utf16(path))) + utf16(path)
+        entry += struct.pack("<QI", filetime(modified), 4) + b"\x01\x02\x03\x04"
+        data += struct.pack("<4sII", b"10ts", 0x12345678, len(entry)) + entry
+    return data
+
+
+SID = "S-1-5-21-1004336348-1177238915-682003330-1001"
+bam_values = [
+    ("Version", dword(1)),
+    ("SequenceNumber", dword(4)),
+    ("\\Device\\HarddiskVolume3\\Windows\\System32\\cmd.exe",
+     binary(struct.pack("<QQII", filetime("2024-01-02 03:10:00"), 0, 2, 0))),
+    ("\\Device\\HarddiskVolume3\\Users\\Public\\evil.exe",
+     binary(struct.pack("<QQII", filetime("2024-01-02 03:11:00"), 0, 0, 0))),
+]
+services = Key("Services", "2024-01-02 03:00:00", subkey_list="ri", subkeys=[
+    Key("bam", "2024-01-01 10:00:00", [
+        ("ImagePath", expand_sz("system32\\drivers\\bam.sys")),
+        ("Start", dword(1)),
+        ("Type", dword(1)),
+    ], subkeys=[
+        Key("State", "2024-01-02 03:11:00", subkeys=[
+            Key("UserSettings", "2024-01-02 03:11:00", subkeys=[
+                Key(SID, "2024-01-02 03:11:00", bam_values),
+            ]),
+        ]),
+    ]),
+    Key("dam", "2024-01-01 10:00:00", [
+        ("ImagePath", expand_sz("system32\\drivers\\dam.sys")),
+        ("Start", dword(1)),
+        ("Type", dword(1)),
+    ], subkeys=[
+        Key("UserSettings", "2024-01-02 03:12:00", subkeys=[
+            Key(SID, "2024-01-02 03:12:00", [
+                ("\\Device\\HarddiskVolume3\\Windows\\explorer.exe",
+                 binary(struct.pack("<QQ", filetime("2024-01-02 03:12:00"), 0))),
+            ]),
+        ]),
+    ]),
+    Key("EvilSvc", "2024-01-02 03:09:00", [
+        ("DisplayName", sz("Evil Service")),
+        ("ImagePath", expand_sz("C:\\Users\\Public\\evil.exe")),
+        ("ObjectName", sz("LocalSystem")),
+        ("Start", dword(2)),
+        ("Type", dword(16)),
+    ]),
+    Key("Schedule", "2024-01-01 10:00:00", [
+        ("Description", sz("Runs scheduled tasks")),
+        ("ImagePath", expand_sz("%systemroot%\\system32\\svchost.exe -k netsvcs -p")),
+        ("ObjectName", sz("LocalSystem")),
+        ("Start", dword(2)),
+        ("Type", dword(32)),
+    ], subkeys=[
+        Key("Parameters", "2024-01-01 10:00:00", [
+            ("ServiceDll", expand_sz("%systemroot%\\system32\\schedsvc.dll")),
+        ]),
+    ]),
+    Key("Unusual", "2024-01-01 10:00:00", [
+        ("ImagePath", sz("C:\\Windows\\unusual.exe")),
+        ("Start", dword(7)),
+    ]),
+])
+write("SYSTEM", "\\REGISTRY\\MACHINE\\SYSTEM", Key("ROOT", "2024-01-02 03:00:00", subkeys=[
+    Key("ControlSet001", "2024-01-01 10:00:00", subkeys=[
+        Key("Control", "2024-01-01 10:00:00", subkeys=[
+            Key("Session Manager", "2024-01-01 10:00:00", subkeys=[
+                Key("AppCompatCache", "2024-01-02 03:15:00", [
+                    ("AppCompatCache", binary(shimcache_win10([
+                        ("C:\\Users\\Public\\evil.exe", "2024-01-02 03:08:00"),
+                        ("C:\\Windows\\system32\\cmd.exe", "2019-12-07 09:09:00"),
+                    ]))),
+                ]),
+            ]),
+        ]),
+        services,
+    ]),
+    Key("Select", "2024-01-01 10:00:00", [
+        ("Current", dword(1)),
+        ("Default", dword(1)),
+        ("LastKnownGood", dword(1)),
+    ]),
+]))
+
+
+# SYSTEM of 64 bit Windows 7, for its ShimCache format
+
+def shimcache_win7(entries):
+    header = struct.pack("<II", 0xBADC0FEE, len(entries)).ljust(128, b"\0")
+    table = b""
+    paths = b""
+    paths_start = len(header) + 48 * len(entries)
+    for path, modified, flags in entries:
+        path = utf16(path)
+        table += struct.pack("<HHIQQIIQQ", len(path), len(path) + 2, 0,
+                             paths_start + len(paths), filetime(modified), flags, 0, 0, 0)
+        paths += path + b"\0\0"
+    return header + table + paths
+
+
+write("SYSTEM.win7", "\\REGISTRY\\MACHINE\\SYSTEM", Key("ROOT", "2024-01-02 03:00:00", subkeys=[
+    Key("ControlSet001", "2024-01-01 10:00:00", subkeys=[
+        Key("Control", "2024-01-01 10:00:00", subkeys=[
+            Key("Session Manager", "2024-01-01 10:00:00", subkeys=[
+                Key("AppCompatCache", "2024-01-02 03:15:00", [
+                    ("AppCompatCache", binary(shimcache_win7([
+                        ("\\??\\C:\\Users\\Public\\evil.exe", "2024-01-02 03:08:00", 0x2),
+                        ("\\??\\C:\\Windows\\system32\\notepad.exe", "2009-07-14 01:39:00", 0),
+                    ]))),
+                ]),
+            ]),
+        ]),
+    ]),
+    Key("Select", "2024-01-01 10:00:00", [("Current", dword(1))]),
+]))
+
+
+# NTUSER.DAT, with value and key names stored as UTF-16 and data split into segments
+
+def rot13(text):
+    return text.translate(str.maketrans(
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz",
+        "NOPQRSTUVWXYZABCDEFGHIJKLMnopqrstuvwxyzabcdefghijklm"))
+
+
+def user_assist_win7(run_count, focus_count, focus_milliseconds, last_run):
+    data = bytearray(72)
+    struct.pack_into("<III", data, 4, run_count, focus_count, focus_milliseconds)
+    struct.pack_into("<Q", data, 60, filetime(last_run))
+    return binary(bytes(data))
+
+
+BLOB = bytes((index * 7) % 251 for index in range(40000))
+write("NTUSER.DAT", "\\??\\C:\\Users\\alice\\ntuser.dat", Key("ROOT", "2024-01-02 03:00:00", subkeys=[
+    Key("Software", "2024-01-02 03:00:00", subkeys=[
+        Key("Microsoft", "2024-01-02 03:00:00", subkeys=[
+            Key("Windows", "2024-01-02 03:00:00", subkeys=[
+                Key("CurrentVersion", "2024-01-02 03:00:00", subkeys=[
+                    Key("Explorer", "2024-01-02 03:00:00", subkeys=[
+                        Key("UserAssist", "2024-01-02 03:00:00", subkeys=[
+                            Key("{CEBFF5CD-ACE2-4F4F-9178-9926F41749EA}", "2024-01-02 03:00:00", subkeys=[
+                                Key("Count", "2024-01-02 03:05:00", [
+                                    (rot13("{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\\cmd.exe"),
+                                     user_assist_win7(4, 6, 83000, "2024-01-02 03:05:00")),
+                                    (rot13("UEME_CTLSESSION"), binary(bytes(1612))),
+                                    (rot13("UEME_RUNPATH:C:\\Tools\\mimikatz.exe"),
+                                     binary(struct.pack("<IIQ", 0, 7,
+                                                        filetime("2024-01-02 03:06:00")))),
+                                ]),
+                            ]),
+                        ]),
+                    ]),
+                    Key("Run", "2024-01-02 03:07:00", [
+                        ("OneDrive", sz("\"C:\\Users\\alice\\AppData\\Local\\Microsoft\\OneDrive\\OneDrive.exe\" /background")),
+                        ("Odd", dword(1)),
+                    ]),
+                    Key("RunOnce", "2024-01-02 03:07:00", [
+                        ("Cleanup", expand_sz("%TEMP%\\cleanup.cmd")),
+                    ]),
+                ]),
+            ]),
+        ]),
+        Key("Yoink", "2024-01-02 03:04:05", [
+            ("", sz("default")),
+            ("Blob", binary(BLOB)),
+            ("Expand", expand_sz("%USERPROFILE%\\yoink")),
+            ("Multi", multi_sz(["one", "two"])),
+            ("Quad", qword(0x0123456789ABCDEF)),
+            ("Zähler", dword(0xDEADBEEF)),
+            ("\u540d\u524d", sz("\u5024")),
+        ], subkeys=[
+            Key("\u30ad\u30fc", "2024-01-02 03:04:05"),
+        ]),
+    ]),
+]))
+
+
+# Amcache.hve with the file entries of Windows 10 1709 and later, and of earlier versions
+
+write("Amcache.hve", "\\??\\C:\\Windows\\AppCompat\\Programs\\Amcache.hve", Key("{11517B7C-E79D-4e20-961B-75A811715ADD}", "2024-01-02 03:00:00", subkeys=[
+    Key("Root", "2024-01-02 03:00:00", subkey_list="lh", subkeys=[
+        Key("InventoryApplicationFile", "2024-01-02 03:00:00", subkey_list="lh", subkeys=[
+            Key("evil.exe|1a2b3c4d5e6f7a8b", "2024-01-02 03:08:30", [
+                ("FileId", sz("0000" + "da39a3ee5e6b4b0d3255bfef95601890afd80709")),
+                ("LinkDate", sz("01/02/2024 03:04:05")),
+                ("LowerCaseLongPath", sz("c:\\users\\public\\evil.exe")),
+                ("Name", sz("evil.exe")),
+                ("ProductName", sz("")),
+                ("ProgramId", sz("0000f519feec486de87ed73cb92d3cac802400000000")),
+                ("Publisher", sz("")),
+                ("Size", qword(73802)),
+                ("Version", sz("1.0.0.0")),
+            ]),
+        ]),
+        Key("File", "2024-01-02 03:00:00", subkeys=[
+            Key("{8bd2e9c5-3b2f-11ee-a7a9-806e6f6e6963}", "2024-01-02 03:00:00", subkey_list="li", subkeys=[
+                Key("1a2b", "2024-01-02 03:08:40", [
+                    ("0", sz("Tools")),
+                    ("1", sz("Gentil Kiwi")),
+                    ("6", dword(1250056)),
+                    ("15", sz("C:\\Tools\\mimikatz.exe")),
+                    ("17", qword(filetime("2024-01-02 02:00:00"))),
+                    ("101", sz("0000" + "e3b0c44298fc1c149afbf4c8996fb92427ae41e4")),
+                ]),
+            ]),
+        ]),
+    ]),
+]))
+
+
+# SOFTWARE left dirty, the log entries add a Run value in a new bin and touch the Run key,
+# an entry before the hive's sequence number and one with a bad hash remove the values again
+
+software = Hive()
+root = software.key(run("2024-01-02 03:00:00", [
+    ("SecurityHealth", expand_sz("%windir%\\system32\\SecurityHealthSystray.exe")),
+]), path="")
+software.close_bin()
+RUN = "Microsoft\\Windows\\CurrentVersion\\Run"
+before = bytes(software.bins)
+first_value = struct.unpack_from("<I", software.bins,
+                                 struct.unpack_from("<I", software.bins,
+                                                    software.keys[RUN] + 4 + 40)[0] + 4)[0]
+
+software.start_bin()
+added = software.value("Updater", *sz("C:\\Users\\Public\\evil.exe"))
+value_list = software.value_list([first_value, added])
+software.close_bin()
+software.value_count(RUN, 2, value_list)
+grown = bytes(software.bins)
+software.last_written(RUN, "2024-01-02 03:20:00")
+touched = bytes(software.bins)
+software.value_count(RUN, 0, NO_OFFSET)
+emptied = bytes(software.bins)
+
+with open("SOFTWARE", "wb") as file:
+    file.write(base_block("\\REGISTRY\\MACHINE\\SOFTWARE", root, len(before), (11, 10)))
+    file.write(before)
+log_base_block = base_block("\\REGISTRY\\MACHINE\\SOFTWARE", root, len(before), (10, 10),
+                            FILE_TYPE_LOG_NEW)[:LOG_BASE_BLOCK_SIZE]
+with open("SOFTWARE.LOG1", "wb") as file:
+    file.write(log_base_block)
+    file.write(log_entry(9, len(before), [(0, emptied[:HBIN_SIZE])]))
+    file.write(log_entry(10, len(grown), [(0, grown[:HBIN_SIZE]), (HBIN_SIZE, grown[HBIN_SIZE:])]))
+with open("SOFTWARE.LOG2", "wb") as file:
+    file.write(log_base_block)
+    file.write(log_entry(11, len(touched), [(0, touched[:HBIN_SIZE])]))
+    file.write(log_entry(12, len(emptied), [(0, emptied[:HBIN_SIZE])], torn=True))
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use yoink::collection::parsers::history_parser::{parse_history, HistoryEntry};
+use yoink::collection::parsers::login_parser::parse_passwd;
+
+fn parse(history: &[u8], shell: &str) -> Vec<HistoryEntry> {
+    parse_history(history, "/home/alice/history", shell, None, &HashMap::new()).unwrap()
+}
+
+/// The line, time and command of every entry.
+fn commands(entries: &[HistoryEntry]) -> Vec<(usize, Option<&str>, &str)> {
+    entries
+        .iter()
+        .map(|entry| {
+            (
+                entry.line,
+                entry.timestamp.as_deref(),
+                entry.command.as_str(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn groups_bash_commands_under_their_timestamps() {
+    let history = b"ls -la\n\
+        #1704164645\n\
+        cd /tmp\n\
+        #1704164650\n\
+        for f in *; do\n  echo \"$f\"\ndone\n\
+        #1704164655\n\
+        #not a timestamp\n";
+    let entries = parse(history, "bash");
+    assert_eq!(
+        commands(&entries),
+        [
+            (1, None, "ls -la"),
+            (3, Some("2024-01-02T03:04:05.000000000Z"), "cd /tmp"),
+            (
+                5,
+                Some("2024-01-02T03:04:10.000000000Z"),
+                "for f in *; do\n  echo \"$f\"\ndone"
+            ),
+            // a comment is kept as a command of its own
+            (
+                9,
+                Some("2024-01-02T03:04:15.000000000Z"),
+                "#not a timestamp"
+            ),
+        ]
+    );
+    assert!(entries.iter().all(|entry| entry.shell == "bash"));
+
+    // without any timestamps every line is a command
+    let entries = parse(b"ls\n\n#comment\npwd\n", "bash");
+    assert_eq!(
+        commands(&entries),
+        [(1, None, "ls"), (3, None, "#comment"), (4, None, "pwd")]
+    );
+}
+
+#[test]
+fn reads_zsh_extended_history() {
+    // "à" is 0xC3 0xA0, zsh writes the second byte as the meta byte and 0xA0 ^ 0x20
+    let mut history = b": 1704164645:3;echo caf\xc3\x83\x80\n".to_vec();
+    history.extend_from_slice(b": 1704164650:0;cat <<EOF\\\nfirst\\\nEOF\n");
+    history.extend_from_slice(b"plain command\n");
+    let entries = parse(&history, "zsh");
+    assert_eq!(
+        commands(&entries),
+        [
+            (1, Some("2024-01-02T03:04:05.000000000Z"), "echo cafà"),
+            (
+                2,
+                Some("2024-01-02T03:04:10.000000000Z"),
+                "cat <<EOF\nfirst\nEOF"
+            ),
+            (5, None, "plain command"),
+        ]
+    );
+    let durations: Vec<Option<u64>> = entries.iter().map(|entry| entry.duration).collect();
+    assert_eq!(durations, [Some(3), Some(0), None]);
+}
+
+#[test]
+fn reads_fish_yaml_history() {
+    let history = b"- cmd: git status\n  when: 1704164645\n\
+        - cmd: echo one\\ntwo \\\\n\n  when: 1704164650\n  paths:\n    - two\n\
+        - cmd: ls\n";
+    assert_eq!(
+        commands(&parse(history, "fish")),
+        [
+            (1, Some("2024-01-02T03:04:05.000000000Z"), "git status"),
+            (
+                3,
+                Some("2024-01-02T03:04:10.000000000Z"),
+                "echo one\ntwo \\n"
+            ),
+            (7, None, "ls"),
+        ]
+    );
+}
+
+#[test]
+fn unescapes_mysql_spaces() {
+    let history = b"_HiStOrY_V2_\nselect\\040*\\040from\\040users;\n";
+    assert_eq!(
+        commands(&parse(history, "mysql")),
+        [(1, None, "_HiStOrY_V2_"), (2, None, "select * from users;")]
+    );
+}
+
+#[test]
+fn names_the_owner_from_passwd() {
+    let users =
+        parse_passwd("root:x:0:0:root:/root:/bin/bash\nalice:x:1000:1000::/home/alice:/bin/zsh\n");
+    let entries = parse_history(
+        b"id\n".as_slice(),
+        "/home/alice/.bash_history",
+        "bash",
+        Some(1000),
+        &users,
+    )
+    .unwrap();
+    assert_eq!(entries[0].uid, Some(1000));
+    assert_eq!(entries[0].user.as_deref(), Some("alice"));
+    assert_eq!(entries[0].source, "/home/alice/.bash_history");
+
+    // a uid that is not in passwd is kept without a name
+    let entries = parse_history(b"id\n".as_slice(), "history", "sh", Some(1001), &users).unwrap();
+    assert_eq!(
+        (entries[0].uid, entries[0].user.as_deref()),
+        (Some(1001), None)
+    );
+}
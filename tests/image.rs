@@ -0,0 +1,118 @@
+use flate2::read::GzDecoder;
+use std::io::Read;
+use std::path::PathBuf;
+use yoink::collection::image::DiskImage;
+
+const SECTOR: usize = 512;
+const ALIGNMENT: usize = 1024 * 1024;
+
+/// Reads a filesystem image from `tests/fixtures`, see the README there for how each was made.
+fn fixture(name: &str) -> Vec<u8> {
+    let mut image = Vec::new();
+    GzDecoder::new(std::fs::File::open(format!("tests/fixtures/{}.img.gz", name)).unwrap())
+        .read_to_end(&mut image)
+        .unwrap();
+    image
+}
+
+/// The NTFS fixture without `Windows\System32\config\SYSTEM`, a volume that only holds data.
+/// The name is only stored in its record and in the index of `config`, both resident.
+fn data_volume() -> Vec<u8> {
+    let mut image = fixture("ntfs");
+    let name: Vec<u8> = "SYSTEM".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let renamed: Vec<u8> = "SYSTEX".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let mut found = 0;
+    for offset in 0..image.len() - name.len() {
+        if image[offset..offset + name.len()] == name[..] {
+            image[offset..offset + name.len()].copy_from_slice(&renamed);
+            found += 1;
+        }
+    }
+    assert_eq!(found, 2);
+    image
+}
+
+/// Writes a disk with an MBR partition table holding `volumes` in order to the temp directory.
+fn disk(name: &str, volumes: &[(u8, Vec<u8>)]) -> PathBuf {
+    let mut disk = vec![0u8; ALIGNMENT];
+    for (index, (partition_type, volume)) in volumes.iter().enumerate() {
+        let entry = 446 + index * 16;
+        let start = (disk.len() / SECTOR) as u32;
+        disk[entry + 4] = *partition_type;
+        disk[entry + 8..entry + 12].copy_from_slice(&start.to_le_bytes());
+        disk[entry + 12..entry + 16]
+            .copy_from_slice(&((volume.len() / SECTOR) as u32).to_le_bytes());
+        disk.extend_from_slice(volume);
+        disk.resize(disk.len().div_ceil(ALIGNMENT) * ALIGNMENT, 0);
+    }
+    disk[510] = 0x55;
+    disk[511] = 0xAA;
+    let path = std::env::temp_dir().join(format!("yoink-{}-{}.img", std::process::id(), name));
+    std::fs::write(&path, disk).unwrap();
+    path
+}
+
+fn contents(image: &mut DiskImage, path: &str) -> Option<Vec<u8>> {
+    let file = image.open_file(path).ok()?;
+    let mut data = Vec::new();
+    image.copy_file(&file, &mut data, false).unwrap();
+    Some(data)
+}
+
+#[test]
+fn gives_c_to_the_windows_volume() {
+    let path = disk(
+        "windows-second",
+        &[(0x07, data_volume()), (0x07, fixture("ntfs"))],
+    );
+    let mut image = DiskImage::open(path.to_str().unwrap()).unwrap();
+    assert_eq!(image.platform(), "windows");
+    // the second partition holds Windows, the first one is lettered after it
+    assert!(contents(&mut image, "C:\\Windows\\System32\\config\\SYSTEM").is_some());
+    assert!(contents(&mut image, "D:\\Windows\\System32\\config\\SYSTEX").is_some());
+    assert_eq!(
+        contents(&mut image, "D:\\notes.txt").as_deref(),
+        Some(&b"hello from ntfs\n"[..])
+    );
+    assert!(contents(&mut image, "E:\\notes.txt").is_none());
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn chooses_the_platform_of_the_installed_system() {
+    // Windows is installed, so the ext volume before it is left out
+    let path = disk(
+        "windows-and-ext",
+        &[(0x83, fixture("ext4")), (0x07, fixture("ntfs"))],
+    );
+    let mut image = DiskImage::open(path.to_str().unwrap()).unwrap();
+    assert_eq!(image.platform(), "windows");
+    assert!(contents(&mut image, "C:\\notes.txt").is_some());
+    assert!(contents(&mut image, "partition1:/tiny.txt").is_none());
+    std::fs::remove_file(path).ok();
+
+    // without an installed system a tie goes to the first volume
+    let path = disk(
+        "data-and-ext",
+        &[(0x83, fixture("ext4")), (0x07, data_volume())],
+    );
+    let mut image = DiskImage::open(path.to_str().unwrap()).unwrap();
+    assert_eq!(image.platform(), "linux");
+    assert_eq!(
+        contents(&mut image, "partition1:/tiny.txt").as_deref(),
+        Some(&b"tiny\n"[..])
+    );
+    assert!(contents(&mut image, "D:\\notes.txt").is_none());
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn opens_unpartitioned_ntfs_as_c() {
+    let path = std::env::temp_dir().join(format!("yoink-{}-ntfs.img", std::process::id()));
+    std::fs::write(&path, fixture("ntfs")).unwrap();
+    let mut image = DiskImage::open(path.to_str().unwrap()).unwrap();
+    assert_eq!(image.platform(), "windows");
+    assert_eq!(image.archive_path("C:\\notes.txt"), "C/notes.txt");
+    assert!(contents(&mut image, "C:\\notes.txt").is_some());
+    std::fs::remove_file(path).ok();
+}
@@ -0,0 +1,164 @@
+use flate2::read::GzDecoder;
+use serde_json::json;
+use std::io::Read;
+use yoink::collection::parsers::journal_parser::{JournalEntry, JournalFile};
+use yoink::collection::timeline::{parse_time_bound, parse_time_end};
+
+/// Reads a journal from `tests/fixtures`, see the README there for how they were made.
+fn fixture(name: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    GzDecoder::new(std::fs::File::open(format!("tests/fixtures/{}.gz", name)).unwrap())
+        .read_to_end(&mut data)
+        .unwrap();
+    data
+}
+
+fn entries(journal: &JournalFile) -> Vec<JournalEntry> {
+    journal
+        .entries()
+        .into_iter()
+        .map(|entry| entry.unwrap())
+        .collect()
+}
+
+fn message(entry: &JournalEntry) -> &str {
+    entry.fields["MESSAGE"].as_str().unwrap()
+}
+
+/// Returns the offset of the first data object compressed with `method`.
+fn compressed_object(data: &[u8], method: u8) -> usize {
+    let mut offset = u64::from_le_bytes(data[88..96].try_into().unwrap()) as usize;
+    loop {
+        let size = u64::from_le_bytes(data[offset + 8..offset + 16].try_into().unwrap()) as usize;
+        if data[offset] == 1 && data[offset + 1] == method {
+            return offset;
+        }
+        offset += size.div_ceil(8) * 8;
+    }
+}
+
+#[test]
+fn reads_the_regular_format() {
+    let journal =
+        JournalFile::from_reader(&fixture("system.journal")[..], "system.journal").unwrap();
+    let entries = entries(&journal);
+    assert_eq!(entries.len(), 4);
+
+    let first = &entries[0];
+    assert_eq!(first.source, "system.journal");
+    assert_eq!(first.seqnum, 1);
+    assert_eq!(first.realtime, 1704164645000000);
+    assert_eq!(
+        first.timestamp.as_deref(),
+        Some("2024-01-02T03:04:05.000000000Z")
+    );
+    assert_eq!(first.monotonic, 1_000_000);
+    assert_eq!(first.boot_id, "101112131415161718191a1b1c1d1e1f");
+    assert_eq!(
+        serde_json::to_value(&first.fields).unwrap(),
+        json!({
+            "MESSAGE": "Started Session 1 of User alice.",
+            "SYSLOG_IDENTIFIER": "systemd",
+            "_HOSTNAME": "ws01",
+            "_PID": "1",
+        })
+    );
+    // a field that appears twice is given as a list
+    assert_eq!(entries[1].fields["TAG"], json!(["first", "second"]));
+}
+
+#[test]
+fn decompresses_xz_lz4_and_zstd_data() {
+    let journal =
+        JournalFile::from_reader(&fixture("system.journal")[..], "system.journal").unwrap();
+    let entries = entries(&journal);
+    let long = "Accepted publickey for alice from 10.0.0.5 port 50022 ssh2 ".repeat(8);
+    assert_eq!(message(&entries[1]), format!("{}(xz)", long));
+    assert_eq!(message(&entries[2]), format!("{}(lz4)", long));
+    assert_eq!(message(&entries[3]), format!("{}(zstd)", long));
+}
+
+#[test]
+fn reads_the_compact_format() {
+    let journal =
+        JournalFile::from_reader(&fixture("user-1000.journal")[..], "user-1000.journal").unwrap();
+    let entries = entries(&journal);
+    let read: Vec<(u64, &str, &str)> = entries
+        .iter()
+        .map(|entry| {
+            (
+                entry.seqnum,
+                message(entry),
+                entry.fields["_UID"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        read,
+        [
+            (1, "Reached target Default.", "1000"),
+            (2, "Startup finished.", "1000"),
+        ]
+    );
+}
+
+#[test]
+fn filters_entries_by_time() {
+    let journal =
+        JournalFile::from_reader(&fixture("system.journal")[..], "system.journal").unwrap();
+    let seqnums = |start: Option<&str>, end: Option<&str>| -> Vec<u64> {
+        journal
+            .entries_between(
+                start.map(|start| parse_time_bound(start).unwrap()),
+                end.map(|end| parse_time_end(end).unwrap()),
+            )
+            .into_iter()
+            .map(|entry| entry.unwrap().seqnum)
+            .collect()
+    };
+    assert_eq!(seqnums(None, None), [1, 2, 3, 4]);
+    // a date as the end takes in the whole day, up to 23:59:59.5
+    assert_eq!(seqnums(Some("2024-01-03"), Some("2024-01-03")), [2, 3]);
+    assert_eq!(seqnums(Some("2024-01-03T10:00:00Z"), None), [2, 3, 4]);
+    assert_eq!(seqnums(None, Some("2024-01-03T10:00:00Z")), [1, 2]);
+}
+
+#[test]
+fn gives_the_entries_before_damage() {
+    let mut data = fixture("system.journal");
+    // the zstd entry is the last object, cut it short as if the file was still being written
+    let length = data.len();
+    data.truncate(length - 20);
+    let journal = JournalFile::from_reader(&data[..], "system.journal~").unwrap();
+    let entries = journal.entries();
+    assert_eq!(entries.len(), 4);
+    assert!(entries[..3].iter().all(|entry| entry.is_ok()));
+    let error = entries[3].as_ref().unwrap_err().to_string();
+    assert!(
+        error.starts_with("system.journal~ has a damaged object at offset"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn rejects_data_claiming_an_enormous_size() {
+    let mut data = fixture("system.journal");
+    // the LZ4 payload starts with the size of the data it holds
+    let payload = compressed_object(&data, 2) + 64;
+    data[payload..payload + 8].copy_from_slice(&(1u64 << 40).to_le_bytes());
+    let journal = JournalFile::from_reader(&data[..], "system.journal").unwrap();
+    let entries = journal.entries();
+    assert_eq!(entries.len(), 4);
+    let error = entries[2].as_ref().unwrap_err().to_string();
+    assert_eq!(error, "LZ4 data claims a size of 1099511627776 bytes");
+    assert!(entries[3].is_ok());
+}
+
+#[test]
+fn rejects_other_files() {
+    let error = JournalFile::from_reader(&b"not a journal"[..], "other.journal")
+        .err()
+        .unwrap();
+    assert_eq!(error.to_string(), "other.journal is not a journal file");
+}
@@ -0,0 +1,173 @@
+use flate2::read::GzDecoder;
+use std::io::Read;
+use yoink::collection::parsers::jumplist_parser::{is_jumplist_file, parse_jumplist, LinkResult};
+use yoink::collection::parsers::lnk_parser::LnkRecord;
+
+/// Reads a JumpList from `tests/fixtures`, see the README there for how each was made.
+fn fixture(name: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    GzDecoder::new(std::fs::File::open(format!("tests/fixtures/{}.gz", name)).unwrap())
+        .read_to_end(&mut data)
+        .unwrap();
+    data
+}
+
+/// Parses a JumpList as it would be found in a user's profile.
+fn jumplist(name: &str) -> Vec<LinkResult> {
+    let source = format!(
+        "C:\\Users\\alice\\AppData\\Roaming\\Microsoft\\Windows\\Recent\\{}",
+        name
+    );
+    parse_jumplist(fixture(name).as_slice(), &source).unwrap()
+}
+
+/// The entry number, target, pin status and access count of a link.
+type Summary<'a> = (Option<u32>, Option<&'a str>, Option<bool>, Option<u32>);
+
+fn summary(links: &[LnkRecord]) -> Vec<Summary<'_>> {
+    links
+        .iter()
+        .map(|link| {
+            (
+                link.entry,
+                link.target_path.as_deref(),
+                link.pinned,
+                link.access_count,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn names_jumplist_files() {
+    assert!(is_jumplist_file(
+        "1b4dd67f29cb1962.automaticDestinations-ms"
+    ));
+    assert!(is_jumplist_file("590AEE7BDD69B59B.CUSTOMDESTINATIONS-MS"));
+    assert!(!is_jumplist_file("local.lnk"));
+}
+
+#[test]
+fn reads_a_windows_7_dest_list() {
+    let mut links = jumplist("1b4dd67f29cb1962.automaticDestinations-ms");
+    // stream 5 is not a link, it is given last
+    let error = links.pop().unwrap().unwrap_err().to_string();
+    assert!(error.starts_with("Stream 5: "), "{}", error);
+    let links: Vec<LnkRecord> = links.into_iter().map(|link| link.unwrap()).collect();
+
+    // the most recently used first, then the links the DestList does not list
+    assert_eq!(
+        summary(&links),
+        [
+            (
+                Some(2),
+                Some("\\\\FILESERVER\\SHARE\\reports\\q1.xlsx"),
+                Some(true),
+                None
+            ),
+            (
+                Some(3),
+                Some("C:\\Users\\alice\\Documents\\gone.txt"),
+                Some(false),
+                None
+            ),
+            (
+                Some(1),
+                Some("C:\\Users\\alice\\Documents\\Résumé.docx"),
+                Some(false),
+                None
+            ),
+            (
+                Some(4),
+                Some("C:\\Users\\alice\\Downloads\\setup_x64.exe"),
+                None,
+                None
+            ),
+        ]
+    );
+    assert_eq!(
+        links[0].last_accessed.as_deref(),
+        Some("2024-01-02T03:40:00.000000000Z")
+    );
+    assert_eq!(
+        links[0].network_share.as_deref(),
+        Some("\\\\FILESERVER\\SHARE")
+    );
+    // an entry whose stream is gone is known only from the DestList
+    assert_eq!(links[1].machine_id.as_deref(), Some("ws01"));
+    assert_eq!(links[1].target_created, None);
+    assert_eq!(links[2].volume_serial.as_deref(), Some("1A2B-3C4D"));
+    assert_eq!(links[3].last_accessed, None);
+    assert!(links
+        .iter()
+        .all(|link| link.app_id.as_deref() == Some("1b4dd67f29cb1962")));
+}
+
+#[test]
+fn reads_access_counts_from_a_later_dest_list() {
+    let links: Vec<LnkRecord> = jumplist("9b9cdc69c1c24e2b.automaticDestinations-ms")
+        .into_iter()
+        .map(|link| link.unwrap())
+        .collect();
+    assert_eq!(
+        summary(&links),
+        [
+            (
+                Some(2),
+                Some("%windir%\\system32\\mspaint.exe"),
+                Some(true),
+                Some(3)
+            ),
+            (
+                Some(1),
+                Some("C:\\Users\\alice\\Downloads\\setup_x64.exe"),
+                Some(false),
+                Some(7)
+            ),
+        ]
+    );
+    assert_eq!(
+        links[1].last_accessed.as_deref(),
+        Some("2024-01-02T02:55:00.000000000Z")
+    );
+    // the hostname of the DestList is only used when the link has no tracker block
+    assert_eq!(links[0].machine_id.as_deref(), Some("ws03"));
+    assert_eq!(links[1].machine_id.as_deref(), Some("ws02"));
+}
+
+#[test]
+fn finds_the_links_of_custom_destinations() {
+    let links: Vec<LnkRecord> = jumplist("590aee7bdd69b59b.customDestinations-ms")
+        .into_iter()
+        .map(|link| link.unwrap())
+        .collect();
+    assert_eq!(
+        summary(&links),
+        [
+            (
+                Some(1),
+                Some("C:\\Users\\alice\\Documents\\Résumé.docx"),
+                None,
+                None
+            ),
+            (
+                Some(2),
+                Some("\\\\FILESERVER\\SHARE\\reports\\q1.xlsx"),
+                None,
+                None
+            ),
+            (Some(3), Some("%windir%\\system32\\mspaint.exe"), None, None),
+        ]
+    );
+    assert!(links
+        .iter()
+        .all(|link| link.app_id.as_deref() == Some("590aee7bdd69b59b")
+            && link.last_accessed.is_none()));
+    assert_eq!(links[0].arguments.as_deref(), Some("/safe"));
+
+    let error = parse_jumplist(&b"\x02\x00\x00\x00"[..], "empty.customDestinations-ms")
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(error.contains("does not hold any links"), "{}", error);
+}
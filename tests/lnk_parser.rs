@@ -0,0 +1,117 @@
+use flate2::read::GzDecoder;
+use std::io::Read;
+use yoink::collection::parsers::lnk_parser::{parse_lnk, LnkRecord};
+
+/// Reads a link from `tests/fixtures`, see the README there for how each was made.
+fn fixture(name: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    GzDecoder::new(std::fs::File::open(format!("tests/fixtures/{}.gz", name)).unwrap())
+        .read_to_end(&mut data)
+        .unwrap();
+    data
+}
+
+fn link(name: &str) -> LnkRecord {
+    parse_lnk(&fixture(name), name).unwrap()
+}
+
+#[test]
+fn reads_local_link_info() {
+    let link = link("local.lnk");
+    // the Unicode path is preferred over the one in the code page
+    assert_eq!(
+        link.target_path.as_deref(),
+        Some("C:\\Users\\alice\\Documents\\Résumé.docx")
+    );
+    assert_eq!(link.drive_type.as_deref(), Some("fixed"));
+    assert_eq!(link.volume_serial.as_deref(), Some("1A2B-3C4D"));
+    assert_eq!(link.volume_label.as_deref(), Some("OS"));
+    assert_eq!(link.network_share, None);
+    assert_eq!(
+        link.target_created.as_deref(),
+        Some("2024-01-02T03:00:00.000000000Z")
+    );
+    assert_eq!(
+        link.target_accessed.as_deref(),
+        Some("2024-01-02T03:30:00.000000000Z")
+    );
+    assert_eq!(
+        link.target_modified.as_deref(),
+        Some("2024-01-02T03:15:00.000000000Z")
+    );
+    assert_eq!(link.target_size, 24576);
+    assert_eq!(link.target_attributes, ["archive"]);
+
+    assert_eq!(link.description.as_deref(), Some("My résumé"));
+    assert_eq!(
+        link.relative_path.as_deref(),
+        Some("..\\Documents\\Résumé.docx")
+    );
+    assert_eq!(
+        link.working_directory.as_deref(),
+        Some("C:\\Users\\alice\\Documents")
+    );
+    assert_eq!(link.arguments.as_deref(), Some("/safe"));
+    assert_eq!(
+        link.icon_location.as_deref(),
+        Some("%ProgramFiles%\\Microsoft Office\\root\\Office16\\WINWORD.EXE")
+    );
+    assert_eq!(link.machine_id.as_deref(), Some("ws01"));
+    assert_eq!((link.app_id, link.entry, link.pinned), (None, None, None));
+}
+
+#[test]
+fn reads_network_link_info() {
+    let link = link("network.lnk");
+    assert_eq!(
+        link.target_path.as_deref(),
+        Some("\\\\FILESERVER\\SHARE\\reports\\q1.xlsx")
+    );
+    assert_eq!(link.network_share.as_deref(), Some("\\\\FILESERVER\\SHARE"));
+    assert_eq!(link.drive_type, None);
+    assert_eq!(link.volume_serial, None);
+    assert_eq!(link.target_attributes, ["read_only", "archive"]);
+    assert_eq!(link.machine_id.as_deref(), Some("fileserver"));
+}
+
+#[test]
+fn builds_the_path_from_the_id_list() {
+    // long names come from the extension blocks of versions 3, 8 and 9, a directory without
+    // one keeps its short name
+    let link = link("idlist.lnk");
+    assert_eq!(
+        link.target_path.as_deref(),
+        Some("C:\\Users\\alice\\Downloads\\setup_x64.exe")
+    );
+    assert_eq!(link.arguments.as_deref(), Some("/S"));
+    assert_eq!(link.description, None);
+    assert_eq!(link.volume_serial, None);
+    assert_eq!(link.machine_id.as_deref(), Some("ws02"));
+}
+
+#[test]
+fn reads_the_target_from_the_environment_block() {
+    let link = link("environment.lnk");
+    assert_eq!(
+        link.target_path.as_deref(),
+        Some("%windir%\\system32\\mspaint.exe")
+    );
+    assert!(link.target_attributes.is_empty());
+    assert_eq!(link.machine_id, None);
+}
+
+#[test]
+fn rejects_damaged_links() {
+    assert!(parse_lnk(b"not a link", "text.lnk").is_err());
+
+    // a LinkInfo claiming to be larger than the link
+    let mut data = fixture("network.lnk");
+    data[0x4C..0x50].copy_from_slice(&u32::MAX.to_le_bytes());
+    let error = parse_lnk(&data, "network.lnk").unwrap_err().to_string();
+    assert!(error.contains("LinkInfo"), "{}", error);
+
+    // string data cut short
+    let data = fixture("local.lnk");
+    let tracker = data.len() - 4 - 0x60;
+    assert!(parse_lnk(&data[..tracker - 10], "local.lnk").is_err());
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::io::{self, Read};
+use yoink::collection::parsers::login_parser::{parse_lastlog, parse_utmp, LoginRecord};
+
+const LASTLOG_SIZE: usize = 292;
+const UTMP_SIZE: usize = 384;
+
+/// Hands out at most 100 bytes per read, like a decompressing reader might.
+struct ShortReads<R>(R);
+
+impl<R: Read> Read for ShortReads<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = buf.len().min(100);
+        self.0.read(&mut buf[..length])
+    }
+}
+
+fn lastlog_record(seconds: i32, tty: &str, host: &str) -> Vec<u8> {
+    let mut record = vec![0u8; LASTLOG_SIZE];
+    record[0..4].copy_from_slice(&seconds.to_le_bytes());
+    record[4..4 + tty.len()].copy_from_slice(tty.as_bytes());
+    record[36..36 + host.len()].copy_from_slice(host.as_bytes());
+    record
+}
+
+#[test]
+fn reads_lastlog_a_record_at_a_time() {
+    // only uid 0 and uid 100000 logged in, every uid between them has a zeroed record
+    let lastlog = io::Cursor::new(lastlog_record(1704164645, "tty1", ""))
+        .chain(io::repeat(0).take(99_999 * LASTLOG_SIZE as u64))
+        .chain(io::Cursor::new(lastlog_record(
+            1704164646, "pts/0", "10.0.0.1",
+        )))
+        .chain(io::Cursor::new(vec![1u8; 10]));
+    let users = HashMap::from([(100_000, "alice".to_string())]);
+    let records = parse_lastlog(ShortReads(lastlog), "/var/log/lastlog", &users).unwrap();
+
+    assert_eq!(records.len(), 3);
+    assert_eq!((records[0].uid, records[0].tty.as_str()), (0, "tty1"));
+    assert_eq!(records[0].user, None);
+    assert_eq!(records[1].uid, 100_000);
+    assert_eq!(records[1].user.as_deref(), Some("alice"));
+    assert_eq!(records[1].host, "10.0.0.1");
+    assert_eq!(
+        records[1].time.as_deref(),
+        Some("2024-01-02T03:04:06.000000000Z")
+    );
+    assert_eq!(records[2].uid, 100_001);
+    assert_eq!(
+        records[2].warnings,
+        vec!["10 trailing bytes do not make up a whole record"]
+    );
+}
+
+/// A `struct utmp` of the given type, `address` is written to `ut_addr_v6`.
+fn utmp_record(record_type: i16, tty: &str, user: &str, seconds: i32, address: &[u8]) -> Vec<u8> {
+    let mut record = vec![0u8; UTMP_SIZE];
+    record[0..2].copy_from_slice(&record_type.to_le_bytes());
+    record[4..8].copy_from_slice(&1234i32.to_le_bytes());
+    record[8..8 + tty.len()].copy_from_slice(tty.as_bytes());
+    record[44..44 + user.len()].copy_from_slice(user.as_bytes());
+    record[340..344].copy_from_slice(&seconds.to_le_bytes());
+    record[348..348 + address.len()].copy_from_slice(address);
+    record
+}
+
+fn parse(records: &[Vec<u8>]) -> Vec<LoginRecord> {
+    parse_utmp(records.concat().as_slice(), "/var/log/wtmp").unwrap()
+}
+
+#[test]
+fn warns_about_zeroed_and_truncated_records() {
+    let mut wtmp = [
+        utmp_record(7, "pts/0", "alice", 1704164645, &[]),
+        vec![0u8; UTMP_SIZE],
+        utmp_record(7, "pts/1", "bob", 1704164700, &[]),
+    ]
+    .concat();
+    wtmp.truncate(2 * UTMP_SIZE + 100);
+    let records = parse_utmp(wtmp.as_slice(), "/var/log/wtmp").unwrap();
+
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].user, "alice");
+    assert!(records[0].warnings.is_empty(), "{:?}", records[0].warnings);
+    assert_eq!(
+        (records[1].offset, records[1].record_type.as_str()),
+        (UTMP_SIZE as u64, "empty")
+    );
+    assert_eq!(records[1].warnings, ["the record has been zeroed"]);
+    assert_eq!(
+        (records[2].offset, records[2].record_type.as_str()),
+        (2 * UTMP_SIZE as u64, "truncated")
+    );
+    assert_eq!(
+        records[2].warnings,
+        ["100 trailing bytes do not make up a whole record"]
+    );
+}
+
+#[test]
+fn warns_when_time_goes_backwards() {
+    let records = parse(&[
+        utmp_record(7, "pts/0", "alice", 1704164645, &[]),
+        utmp_record(7, "pts/1", "bob", 1704160000, &[]),
+        utmp_record(7, "pts/2", "carol", 0, &[]),
+        utmp_record(7, "pts/3", "dave", 1704164700, &[]),
+    ]);
+    assert!(records[0].warnings.is_empty());
+    assert_eq!(
+        records[1].warnings,
+        ["the time is earlier than the previous record's"]
+    );
+    // a record without a time is not compared, the next one is compared with bob's
+    assert_eq!(records[2].warnings, ["the record has no time"]);
+    assert_eq!(records[2].time, None);
+    assert!(records[3].warnings.is_empty(), "{:?}", records[3].warnings);
+}
+
+#[test]
+fn ends_sessions_at_logout_reboot_and_shutdown() {
+    let records = parse(&[
+        utmp_record(7, "pts/0", "alice", 1704164645, &[]),
+        utmp_record(7, "pts/1", "bob", 1704164650, &[]),
+        utmp_record(8, "pts/0", "", 1704164660, &[]),
+        utmp_record(2, "~", "reboot", 1704164670, &[]),
+        utmp_record(7, "tty1", "carol", 1704164680, &[]),
+        utmp_record(1, "~", "shutdown", 1704164690, &[]),
+        utmp_record(7, "tty1", "dave", 1704164700, &[]),
+    ]);
+    let logouts: Vec<(&str, Option<&str>)> = records
+        .iter()
+        .filter(|record| record.record_type == "user_process")
+        .map(|record| (record.user.as_str(), record.logout.as_deref()))
+        .collect();
+    assert_eq!(
+        logouts,
+        [
+            ("alice", Some("2024-01-02T03:04:20.000000000Z")),
+            ("bob", Some("2024-01-02T03:04:30.000000000Z")),
+            ("carol", Some("2024-01-02T03:04:50.000000000Z")),
+            ("dave", None),
+        ]
+    );
+    assert_eq!(records[2].record_type, "dead_process");
+    assert_eq!(records[3].record_type, "boot_time");
+    assert_eq!(records[5].record_type, "run_level");
+}
+
+#[test]
+fn reads_ipv4_and_ipv6_addresses() {
+    let ipv6 = [
+        0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01,
+    ];
+    let records = parse(&[
+        utmp_record(7, "pts/0", "alice", 1704164645, &[10, 0, 0, 5]),
+        utmp_record(7, "pts/1", "bob", 1704164650, &ipv6),
+        utmp_record(7, "tty1", "carol", 1704164655, &[]),
+    ]);
+    assert_eq!(records[0].address.as_deref(), Some("10.0.0.5"));
+    assert_eq!(records[1].address.as_deref(), Some("2001:db8::1"));
+    assert_eq!(records[2].address, None);
+}
@@ -0,0 +1,103 @@
+use flate2::read::GzDecoder;
+use std::io::{Cursor, Read};
+use yoink::collection::parsers::mft_parser::{parse_mft, MftEntry};
+use yoink::collection::readers::ntfs_reader::Drive;
+
+const IN_USE: u16 = 0x01;
+const DIRECTORY: u16 = 0x02;
+
+/// Builds a 512-byte file record holding a single `$FILE_NAME`, protected by an update
+/// sequence array like on disk.
+fn record(sequence: u16, flags: u16, parent: (u64, u16), name: &str) -> Vec<u8> {
+    let name: Vec<u8> = name.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let mut value = vec![0u8; 66];
+    value[0..8].copy_from_slice(&(parent.0 | (parent.1 as u64) << 48).to_le_bytes());
+    value[64] = (name.len() / 2) as u8;
+    value[65] = 1;
+    value.extend(name);
+    let mut attribute = vec![0u8; 24];
+    attribute[0..4].copy_from_slice(&0x30u32.to_le_bytes());
+    attribute[16..20].copy_from_slice(&(value.len() as u32).to_le_bytes());
+    attribute[20..22].copy_from_slice(&24u16.to_le_bytes());
+    attribute.extend(value);
+    attribute.resize(attribute.len().div_ceil(8) * 8, 0);
+    let length = attribute.len() as u32;
+    attribute[4..8].copy_from_slice(&length.to_le_bytes());
+
+    let mut record = vec![0u8; 512];
+    record[0..4].copy_from_slice(b"FILE");
+    record[4..6].copy_from_slice(&48u16.to_le_bytes());
+    record[6..8].copy_from_slice(&2u16.to_le_bytes());
+    record[16..18].copy_from_slice(&sequence.to_le_bytes());
+    record[20..22].copy_from_slice(&56u16.to_le_bytes());
+    record[22..24].copy_from_slice(&flags.to_le_bytes());
+    record[28..32].copy_from_slice(&512u32.to_le_bytes());
+    let end = 56 + attribute.len();
+    record[56..end].copy_from_slice(&attribute);
+    record[end..end + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    record[24..28].copy_from_slice(&(end as u32 + 8).to_le_bytes());
+    // the last two bytes of the sector are kept in the update sequence array
+    record.copy_within(510..512, 50);
+    record[48..50].copy_from_slice(&[1, 0]);
+    record[510..512].copy_from_slice(&[1, 0]);
+    record
+}
+
+fn entry<'a>(entries: &'a [MftEntry], name: &str) -> &'a MftEntry {
+    entries.iter().find(|entry| entry.name == name).unwrap()
+}
+
+#[test]
+fn reads_small_records() {
+    let mut mft = record(1, IN_USE, (5, 5), "$MFT");
+    mft.resize(5 * 512, 0);
+    mft.extend(record(5, IN_USE | DIRECTORY, (5, 5), "."));
+    mft.extend(record(3, IN_USE | DIRECTORY, (5, 5), "dir"));
+    mft.extend(record(1, IN_USE, (6, 3), "a.txt"));
+    // deleted while record 6 held an earlier directory
+    mft.extend(record(2, 0, (6, 2), "b.txt"));
+    let entries = parse_mft(Cursor::new(mft)).unwrap();
+
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entry(&entries, "$MFT").record, 0);
+    assert_eq!(entry(&entries, "dir").record, 6);
+    let a = entry(&entries, "a.txt");
+    assert_eq!((a.record, a.path.as_str()), (7, "dir\\a.txt"));
+    let b = entry(&entries, "b.txt");
+    assert_eq!((b.record, b.in_use, b.path.as_str()), (8, false, "b.txt"));
+}
+
+#[test]
+fn reads_the_mft_of_the_fixture() {
+    let mut image = Vec::new();
+    GzDecoder::new(std::fs::File::open("tests/fixtures/ntfs.img.gz").unwrap())
+        .read_to_end(&mut image)
+        .unwrap();
+    let mut drive = Drive::new(Cursor::new(image)).unwrap();
+    let mut mft = Vec::new();
+    drive.copy_file("C:\\$MFT", &mut mft, false).unwrap();
+    let entries = parse_mft(Cursor::new(mft)).unwrap();
+
+    // the name is in extension record 35, merged into its base record
+    let extended = entry(&entries, "extended.txt");
+    assert_eq!(
+        (extended.record, extended.path.as_str()),
+        (34, "extended.txt")
+    );
+    assert_eq!(extended.size, 24);
+    assert_eq!(
+        entry(&entries, "SYSTEM").path,
+        "Windows\\System32\\config\\SYSTEM"
+    );
+    assert_eq!(
+        entry(&entries, "notes.txt").streams,
+        vec!["Zone.Identifier"]
+    );
+    // record 39 now holds newdir, not the directory gone.txt was deleted from
+    let gone = entry(&entries, "gone.txt");
+    assert_eq!(
+        (gone.record, gone.in_use, gone.path.as_str()),
+        (40, false, "gone.txt")
+    );
+    assert_eq!(entry(&entries, "inside.txt").path, "removed\\inside.txt");
+}
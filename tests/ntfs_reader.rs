@@ -0,0 +1,257 @@
+use flate2::read::GzDecoder;
+use std::io::{Cursor, Read};
+use yoink::collection::manifest::RecoveryConfidence;
+use yoink::collection::readers::ntfs_reader::Drive;
+
+/// Opens the NTFS image from `tests/fixtures`, see the README there for how it was made.
+fn fixture() -> Drive<Cursor<Vec<u8>>> {
+    let mut image = Vec::new();
+    GzDecoder::new(std::fs::File::open("tests/fixtures/ntfs.img.gz").unwrap())
+        .read_to_end(&mut image)
+        .unwrap();
+    Drive::new(Cursor::new(image)).unwrap()
+}
+
+fn read(drive: &mut Drive<Cursor<Vec<u8>>>, path: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    let copied = drive.copy_file(path, &mut data, false).unwrap();
+    assert_eq!(copied.written, data.len() as u64);
+    data
+}
+
+#[test]
+fn walks_the_directory_tree() {
+    let mut drive = fixture();
+    let mut paths = Vec::new();
+    drive
+        .walk("C:", 10, &mut |path: &str| paths.push(path.to_string()))
+        .unwrap();
+    for expected in [
+        "C:\\$MFT",
+        "C:\\notes.txt",
+        "C:\\hidden.txt",
+        "C:\\docs\\fragmented.bin",
+        "C:\\fragment-link.bin",
+        "C:\\extended.txt",
+        "C:\\$Extend\\$UsnJrnl",
+        "C:\\Windows\\System32\\config\\SYSTEM",
+    ] {
+        assert!(
+            paths.iter().any(|path| path == expected),
+            "{} was not found",
+            expected
+        );
+    }
+    // deleted files are not in any directory index
+    assert!(!paths.iter().any(|path| path.ends_with("deleted.txt")));
+    assert!(!paths
+        .iter()
+        .any(|path| path == "C:\\." || path.ends_with("\\newdir")));
+
+    let mut shallow = Vec::new();
+    drive
+        .walk("C:", 1, &mut |path: &str| shallow.push(path.to_string()))
+        .unwrap();
+    assert!(shallow.contains(&"C:\\notes.txt".to_string()));
+    assert!(!shallow.iter().any(|path| path.starts_with("C:\\docs\\")));
+}
+
+#[test]
+fn copies_files_and_streams() {
+    let mut drive = fixture();
+    assert_eq!(read(&mut drive, "C:\\notes.txt"), b"hello from ntfs\n");
+    // lookups ignore case like Windows does
+    assert_eq!(read(&mut drive, "C:\\NOTES.TXT"), b"hello from ntfs\n");
+    assert_eq!(
+        read(&mut drive, "C:\\notes.txt:Zone.Identifier"),
+        b"[ZoneTransfer]\r\nZoneId=3\r\n"
+    );
+    assert_eq!(
+        drive.list_streams("C:\\notes.txt").unwrap(),
+        vec!["Zone.Identifier".to_string()]
+    );
+
+    // two data runs with a free cluster between them
+    let fragmented: Vec<u8> = (0..10000).map(|i| (i * 13 % 251) as u8).collect();
+    assert!(read(&mut drive, "C:\\docs\\fragmented.bin") == fragmented);
+    assert!(read(&mut drive, "C:\\fragment-link.bin") == fragmented);
+    assert_eq!(
+        read(&mut drive, "C:\\extended.txt"),
+        b"named in another record\n"
+    );
+
+    assert!(drive.does_file_exist("C:\\Windows\\System32\\config\\SYSTEM"));
+    assert!(!drive.does_file_exist("C:\\Windows\\System32\\config"));
+    assert!(!drive.does_file_exist("C:\\missing.txt"));
+    assert!(drive
+        .copy_file("C:\\notes.txt:missing", Vec::new(), false)
+        .is_err());
+}
+
+#[test]
+fn reads_metadata() {
+    let mut drive = fixture();
+    let metadata = drive.get_metadata("C:\\hidden.txt").unwrap();
+    assert_eq!(metadata.inode, Some(32));
+    assert_eq!(metadata.links, Some(1));
+    assert_eq!(
+        metadata.attributes,
+        vec!["read_only", "hidden", "system", "archive"]
+    );
+    assert_eq!(metadata.mode, None);
+    assert_eq!(
+        metadata.created.as_deref(),
+        Some("2024-01-02T03:04:05.000000000Z")
+    );
+    assert_eq!(
+        metadata.modified.as_deref(),
+        Some("2024-01-02T03:04:06.000000000Z")
+    );
+    assert_eq!(
+        metadata.changed.as_deref(),
+        Some("2024-01-02T03:04:07.000000000Z")
+    );
+    assert_eq!(
+        metadata.accessed.as_deref(),
+        Some("2024-01-02T03:04:08.000000000Z")
+    );
+
+    let metadata = drive.get_metadata("C:\\docs\\fragmented.bin").unwrap();
+    assert_eq!(metadata.links, Some(2));
+    assert_eq!(metadata.attributes, vec!["archive"]);
+
+    assert_eq!(
+        drive
+            .get_lastmodified("C:\\notes.txt")
+            .unwrap()
+            .and_utc()
+            .timestamp(),
+        1704164646
+    );
+}
+
+#[test]
+fn writes_holes_as_zeros() {
+    let mut drive = fixture();
+    let mut expected = vec![0u8; 4 * 4096 + 1000];
+    expected[4096..8192].fill(b'A');
+    expected[16384..].fill(b'B');
+    for keep_sparse in [false, true] {
+        let mut data = Vec::new();
+        let copied = drive
+            .copy_file("C:\\sparse.bin", &mut data, keep_sparse)
+            .unwrap();
+        assert_eq!(copied.start_offset, 0);
+        assert!(data == expected);
+    }
+}
+
+#[test]
+fn leaves_out_the_freed_start_of_the_change_journal() {
+    let mut drive = fixture();
+    let mut kept = Vec::new();
+    let copied = drive
+        .copy_file("C:\\$Extend\\$UsnJrnl:$J", &mut kept, true)
+        .unwrap();
+    assert_eq!((copied.written, copied.start_offset), (16472, 0));
+    assert!(kept[..8192].iter().all(|byte| *byte == 0));
+
+    let mut trimmed = Vec::new();
+    let copied = drive
+        .copy_file("C:\\$Extend\\$UsnJrnl:$J", &mut trimmed, false)
+        .unwrap();
+    assert_eq!((copied.written, copied.start_offset), (8280, 8192));
+    // the hole between the two clusters of records is kept so every record stays at its USN
+    assert!(trimmed == kept[8192..]);
+    assert!(trimmed[4096..8192].iter().all(|byte| *byte == 0));
+}
+
+#[test]
+fn walks_the_mft() {
+    let mut drive = fixture();
+    let mut paths = Vec::new();
+    drive
+        .walk_mft("C:", 10, &mut |path: &str| paths.push(path.to_string()))
+        .unwrap();
+    // the name of extended.txt is only in its extension record, found through $ATTRIBUTE_LIST
+    assert_eq!(
+        paths
+            .iter()
+            .filter(|path| *path == "C:\\extended.txt")
+            .count(),
+        1
+    );
+    for expected in [
+        "C:\\docs\\fragmented.bin",
+        "C:\\fragment-link.bin",
+        "C:\\Windows\\System32\\config\\SYSTEM",
+    ] {
+        assert!(paths.iter().any(|path| path == expected), "{}", expected);
+    }
+    assert!(!paths.iter().any(|path| path.ends_with("deleted.txt")));
+}
+
+#[test]
+fn walks_deleted_files() {
+    let mut drive = fixture();
+    let mut found = Vec::new();
+    drive
+        .walk_deleted("C:", 10, &mut |path: &str, collect_path: &str| {
+            found.push((path.to_string(), collect_path.to_string()))
+        })
+        .unwrap();
+    found.sort();
+    let expected = [
+        // its directory was deleted and the record reused for newdir, so it is not put there
+        ("C:\\gone.txt", "C:\\$Deleted\\40\\gone.txt"),
+        (
+            "C:\\removed\\inside.txt",
+            "C:\\$Deleted\\42\\removed\\inside.txt",
+        ),
+        ("C:\\deleted.txt", "C:\\$Deleted\\36\\deleted.txt"),
+    ];
+    for (path, collect_path) in expected {
+        assert!(
+            found
+                .iter()
+                .any(|(found_path, found_collect)| found_path == path
+                    && found_collect == collect_path),
+            "{} was not found in {:?}",
+            path,
+            found
+        );
+    }
+    assert!(!found.iter().any(|(path, _)| path.contains("newdir")));
+}
+
+#[test]
+fn rates_the_recovery_of_deleted_files() {
+    let mut drive = fixture();
+    let mut found = Vec::new();
+    drive
+        .walk_deleted("C:", 10, &mut |_: &str, collect_path: &str| {
+            found.push(collect_path.to_string())
+        })
+        .unwrap();
+    let confidence =
+        |drive: &mut Drive<Cursor<Vec<u8>>>, path: &str| drive.recovery_confidence(path).unwrap();
+    // its only cluster was given to reused.bin
+    assert!(found.contains(&"C:\\$Deleted\\37\\overwritten.bin".to_string()));
+    assert_eq!(
+        confidence(&mut drive, "C:\\$Deleted\\37\\overwritten.bin"),
+        Some(RecoveryConfidence::Low)
+    );
+    for path in [
+        "C:\\$Deleted\\36\\deleted.txt",
+        "C:\\$Deleted\\40\\gone.txt",
+        "C:\\$Deleted\\42\\removed\\inside.txt",
+    ] {
+        assert_eq!(
+            confidence(&mut drive, path),
+            Some(RecoveryConfidence::High),
+            "{}",
+            path
+        );
+    }
+    assert_eq!(confidence(&mut drive, "C:\\notes.txt"), None);
+}
@@ -0,0 +1,115 @@
+use std::io::Cursor;
+use yoink::collection::readers::partition_reader::read_partition_table;
+
+const SECTOR: usize = 512;
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+/// A GPT partition entry with a non-zero type GUID.
+fn entry(first_lba: u64, last_lba: u64, name: &str) -> Vec<u8> {
+    let mut entry = vec![0u8; 128];
+    entry[0..16].fill(0xAF);
+    entry[32..40].copy_from_slice(&first_lba.to_le_bytes());
+    entry[40..48].copy_from_slice(&last_lba.to_le_bytes());
+    for (index, unit) in name.encode_utf16().enumerate() {
+        entry[56 + index * 2..58 + index * 2].copy_from_slice(&unit.to_le_bytes());
+    }
+    entry
+}
+
+/// Builds a 64 KiB disk with a protective MBR and a GPT holding `entries`, each `entry_size`
+/// bytes, and lets `edit` change the header before its checksum is computed.
+fn gpt_disk(entries: &[Vec<u8>], entry_size: u32, edit: impl Fn(&mut [u8])) -> Vec<u8> {
+    let mut disk = vec![0u8; 128 * SECTOR];
+    disk[446 + 4] = 0xEE;
+    disk[510] = 0x55;
+    disk[511] = 0xAA;
+    for (index, entry) in entries.iter().enumerate() {
+        let offset = 2 * SECTOR + index * entry_size as usize;
+        disk[offset..offset + entry.len()].copy_from_slice(entry);
+    }
+
+    let header = &mut disk[SECTOR..SECTOR + 92];
+    header[0..8].copy_from_slice(b"EFI PART");
+    header[8..12].copy_from_slice(&0x10000u32.to_le_bytes());
+    header[12..16].copy_from_slice(&92u32.to_le_bytes());
+    header[72..80].copy_from_slice(&2u64.to_le_bytes());
+    header[80..84].copy_from_slice(&(entries.len() as u32).to_le_bytes());
+    header[84..88].copy_from_slice(&entry_size.to_le_bytes());
+    edit(header);
+    let checksum = crc32(header);
+    header[16..20].copy_from_slice(&checksum.to_le_bytes());
+    disk
+}
+
+#[test]
+fn reads_gpt_partitions() {
+    let disk = gpt_disk(
+        &[
+            entry(34, 2081, "EFI system"),
+            entry(2082, 4129, "Basic data"),
+        ],
+        128,
+        |_| {},
+    );
+    let partitions = read_partition_table(&mut Cursor::new(disk)).unwrap();
+    let read: Vec<(usize, u64, u64, &str)> = partitions
+        .iter()
+        .map(|p| (p.number, p.offset, p.size, p.description.as_str()))
+        .collect();
+    assert_eq!(
+        read,
+        [
+            (1, 34 * 512, 2048 * 512, "EFI system"),
+            (2, 2082 * 512, 2048 * 512, "Basic data"),
+        ]
+    );
+
+    // larger entries are read at their own stride
+    let disk = gpt_disk(&[entry(34, 2081, "EFI system")], 256, |_| {});
+    let partitions = read_partition_table(&mut Cursor::new(disk)).unwrap();
+    assert_eq!(partitions.len(), 1);
+    assert_eq!(partitions[0].description, "EFI system");
+}
+
+#[test]
+fn rejects_a_gpt_header_with_a_bad_checksum() {
+    let mut disk = gpt_disk(&[entry(34, 2081, "EFI system")], 128, |_| {});
+    disk[SECTOR + 72] = 3;
+    assert!(read_partition_table(&mut Cursor::new(disk)).is_err());
+}
+
+#[test]
+fn rejects_invalid_gpt_entry_sizes() {
+    // none of these may be used to size the read of the entry array
+    for entry_size in [0, 100, 192, 8192, u32::MAX] {
+        let disk = gpt_disk(&[entry(34, 2081, "EFI system")], 128, |header| {
+            header[84..88].copy_from_slice(&entry_size.to_le_bytes())
+        });
+        assert!(
+            read_partition_table(&mut Cursor::new(disk)).is_err(),
+            "entry size {}",
+            entry_size
+        );
+    }
+}
+
+#[test]
+fn skips_gpt_entries_whose_extent_overflows() {
+    let disk = gpt_disk(
+        &[
+            entry(u64::MAX / 256, u64::MAX / 256 + 10, "Past the end"),
+            entry(0, u64::MAX, "Everything"),
+            entry(34, 2081, "EFI system"),
+        ],
+        128,
+        |_| {},
+    );
+    let partitions = read_partition_table(&mut Cursor::new(disk)).unwrap();
+    assert_eq!(partitions.len(), 1);
+    assert_eq!(partitions[0].number, 3);
+}
@@ -0,0 +1,126 @@
+use flate2::read::GzDecoder;
+use std::io::Read;
+use yoink::collection::parsers::prefetch_parser::{parse_prefetch, PrefetchRecord};
+
+/// Reads a prefetch file from `tests/fixtures`, see the README there for how they were made.
+fn read(executable: &str) -> Vec<u8> {
+    let path = format!("tests/fixtures/{executable}-0BD30981.pf.gz");
+    let mut data = Vec::new();
+    GzDecoder::new(std::fs::File::open(path).unwrap())
+        .read_to_end(&mut data)
+        .unwrap();
+    data
+}
+
+fn parse(executable: &str) -> PrefetchRecord {
+    let source = format!("{executable}-0BD30981.pf");
+    parse_prefetch(read(executable).as_slice(), &source).unwrap()
+}
+
+/// Checks what every fixture shares, the hash, the volume and the files it loaded.
+fn assert_common(record: &PrefetchRecord, executable: &str) {
+    assert_eq!(record.executable, executable);
+    assert_eq!(record.hash, "0BD30981");
+    assert_eq!(record.volumes.len(), 1);
+    let volume = &record.volumes[0];
+    assert_eq!(volume.device_path, r"\VOLUME{01d2a1b2c3d4e5f6-a4b19c3e}");
+    assert_eq!(volume.serial, "A4B1-9C3E");
+    assert_eq!(
+        volume.created.as_deref(),
+        Some("2019-04-17T19:40:00.000000000Z")
+    );
+    assert_eq!(
+        volume.directories,
+        [r"\VOLUME{01d2}\WINDOWS", r"\VOLUME{01d2}\TOOLS"]
+    );
+    assert_eq!(
+        record.files,
+        [
+            r"\VOLUME{01d2}\WINDOWS\SYSTEM32\NTDLL.DLL".to_string(),
+            format!(r"\VOLUME{{01d2}}\TOOLS\{executable}"),
+        ]
+    );
+}
+
+#[test]
+fn reads_windows_xp_prefetch() {
+    let record = parse("XP.EXE");
+    assert_common(&record, "XP.EXE");
+    assert_eq!(record.version, 17);
+    assert_eq!(record.run_count, 3);
+    assert_eq!(record.last_run_times, ["2019-04-17T23:40:00.000000000Z"]);
+}
+
+#[test]
+fn reads_windows_7_prefetch() {
+    let record = parse("SEVEN.EXE");
+    assert_common(&record, "SEVEN.EXE");
+    assert_eq!(record.version, 23);
+    assert_eq!(record.run_count, 12);
+    assert_eq!(record.last_run_times, ["2019-04-18T00:40:00.000000000Z"]);
+}
+
+#[test]
+fn reads_windows_8_prefetch() {
+    let record = parse("EIGHT.EXE");
+    assert_common(&record, "EIGHT.EXE");
+    assert_eq!(record.version, 26);
+    assert_eq!(record.run_count, 4);
+    assert_eq!(
+        record.last_run_times,
+        [
+            "2019-04-18T03:40:00.000000000Z",
+            "2019-04-18T02:40:00.000000000Z",
+            "2019-04-18T01:40:00.000000000Z",
+        ]
+    );
+}
+
+#[test]
+fn reads_compressed_windows_10_prefetch() {
+    assert!(read("TEN.EXE").starts_with(b"MAM\x04"));
+    let record = parse("TEN.EXE");
+    assert_common(&record, "TEN.EXE");
+    assert_eq!(record.version, 30);
+    assert_eq!(record.run_count, 42);
+    assert_eq!(record.last_run_times.len(), 8);
+    assert_eq!(record.last_run_times[0], "2019-04-18T14:40:00.000000000Z");
+    assert_eq!(record.last_run_times[7], "2019-04-18T07:40:00.000000000Z");
+}
+
+#[test]
+fn reads_compressed_prefetch_with_a_checksum_and_short_metrics() {
+    assert!(read("ELEVEN.EXE").starts_with(b"MAM\x84"));
+    let record = parse("ELEVEN.EXE");
+    assert_common(&record, "ELEVEN.EXE");
+    assert_eq!(record.version, 31);
+    assert_eq!(record.run_count, 7);
+    assert_eq!(
+        record.last_run_times,
+        [
+            "2019-04-19T00:40:00.000000000Z",
+            "2019-04-18T23:40:00.000000000Z",
+        ]
+    );
+}
+
+#[test]
+fn decompresses_every_block() {
+    let record = parse("PADDED.EXE");
+    assert_common(&record, "PADDED.EXE");
+    assert_eq!(record.run_count, 1000);
+
+    // losing the end of the last of the three blocks has to be noticed
+    let data = read("PADDED.EXE");
+    let error = parse_prefetch(&data[..data.len() - 2000], "PADDED.EXE-0BD30981.pf").unwrap_err();
+    assert!(
+        error.to_string().contains("ends before its data"),
+        "{error}"
+    );
+}
+
+#[test]
+fn rejects_other_files() {
+    let error = parse_prefetch(&b"not a prefetch file"[..], "other.pf").unwrap_err();
+    assert_eq!(error.to_string(), "other.pf is not a prefetch file");
+}
@@ -0,0 +1,341 @@
+use flate2::read::GzDecoder;
+use serde_json::{json, Value};
+use std::io::Read;
+use yoink::collection::parsers::registry_artefacts::{extract_artefacts, RegistryArtefact};
+use yoink::collection::parsers::registry_parser::Hive;
+
+/// Reads a hive or log from `tests/fixtures`, see the README there for how each was made.
+fn fixture(name: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    GzDecoder::new(std::fs::File::open(format!("tests/fixtures/{}.gz", name)).unwrap())
+        .read_to_end(&mut data)
+        .unwrap();
+    data
+}
+
+/// The artefacts of one kind that a hive holds.
+fn artefacts(hive: &Hive, artefact: &str) -> Vec<RegistryArtefact> {
+    extract_artefacts(hive)
+        .into_iter()
+        .filter(|found| found.artefact == artefact)
+        .collect()
+}
+
+fn hive(name: &str) -> Hive {
+    Hive::from_reader(fixture(name).as_slice(), name).unwrap()
+}
+
+/// The name, path and timestamp of every artefact.
+fn summary(artefacts: &[RegistryArtefact]) -> Vec<(&str, Option<&str>, Option<&str>)> {
+    artefacts
+        .iter()
+        .map(|artefact| {
+            (
+                artefact.name.as_str(),
+                artefact.path.as_deref(),
+                artefact.timestamp.as_deref(),
+            )
+        })
+        .collect()
+}
+
+fn details(artefact: &RegistryArtefact) -> Value {
+    serde_json::to_value(&artefact.details).unwrap()
+}
+
+#[test]
+fn extracts_run_keys() {
+    let ntuser = hive("NTUSER.DAT");
+    let run = artefacts(&ntuser, "run_key");
+    assert_eq!(
+        summary(&run),
+        [
+            (
+                "OneDrive",
+                Some("\"C:\\Users\\alice\\AppData\\Local\\Microsoft\\OneDrive\\OneDrive.exe\" /background"),
+                None
+            ),
+            ("Odd", None, None),
+            ("Cleanup", Some("%TEMP%\\cleanup.cmd"), None),
+        ]
+    );
+    assert_eq!(run[0].source, "NTUSER.DAT");
+    assert_eq!(
+        run[0].key,
+        "Software\\Microsoft\\Windows\\CurrentVersion\\Run"
+    );
+    assert_eq!(
+        run[0].key_last_written.as_deref(),
+        Some("2024-01-02T03:07:00.000000000Z")
+    );
+    // data that is not a string is kept as it was decoded
+    assert_eq!(details(&run[1]), json!({"data": 1}));
+    assert_eq!(
+        run[2].key,
+        "Software\\Microsoft\\Windows\\CurrentVersion\\RunOnce"
+    );
+
+    // the Run key of SOFTWARE only holds the value the transaction logs add once replayed
+    let mut software = hive("SOFTWARE");
+    software
+        .recover(&[fixture("SOFTWARE.LOG1"), fixture("SOFTWARE.LOG2")])
+        .unwrap();
+    let run = artefacts(&software, "run_key");
+    assert_eq!(
+        summary(&run),
+        [
+            (
+                "SecurityHealth",
+                Some("%windir%\\system32\\SecurityHealthSystray.exe"),
+                None
+            ),
+            ("Updater", Some("C:\\Users\\Public\\evil.exe"), None),
+        ]
+    );
+}
+
+#[test]
+fn extracts_services() {
+    let system = hive("SYSTEM");
+    let services = artefacts(&system, "service");
+    assert_eq!(
+        summary(&services),
+        [
+            (
+                "bam",
+                Some("system32\\drivers\\bam.sys"),
+                Some("2024-01-01T10:00:00.000000000Z")
+            ),
+            (
+                "dam",
+                Some("system32\\drivers\\dam.sys"),
+                Some("2024-01-01T10:00:00.000000000Z")
+            ),
+            (
+                "EvilSvc",
+                Some("C:\\Users\\Public\\evil.exe"),
+                Some("2024-01-02T03:09:00.000000000Z")
+            ),
+            (
+                "Schedule",
+                Some("%systemroot%\\system32\\svchost.exe -k netsvcs -p"),
+                Some("2024-01-01T10:00:00.000000000Z")
+            ),
+            (
+                "Unusual",
+                Some("C:\\Windows\\unusual.exe"),
+                Some("2024-01-01T10:00:00.000000000Z")
+            ),
+        ]
+    );
+    assert_eq!(services[2].key, "ControlSet001\\Services\\EvilSvc");
+    assert_eq!(
+        details(&services[2]),
+        json!({
+            "account": "LocalSystem",
+            "display_name": "Evil Service",
+            "start": "automatic",
+            "type": 16,
+        })
+    );
+    assert_eq!(
+        details(&services[3]),
+        json!({
+            "account": "LocalSystem",
+            "description": "Runs scheduled tasks",
+            "service_dll": "%systemroot%\\system32\\schedsvc.dll",
+            "start": "automatic",
+            "type": 32,
+        })
+    );
+    // a start type past the known ones is given as its number
+    assert_eq!(details(&services[4]), json!({"start": "7"}));
+}
+
+#[test]
+fn extracts_user_assist() {
+    let ntuser = hive("NTUSER.DAT");
+    let user_assist = artefacts(&ntuser, "userassist");
+    // UEME_CTLSESSION is neither an XP nor a Windows 7 entry
+    assert_eq!(
+        summary(&user_assist),
+        [
+            (
+                "{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\\cmd.exe",
+                Some("{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\\cmd.exe"),
+                Some("2024-01-02T03:05:00.000000000Z")
+            ),
+            (
+                "UEME_RUNPATH:C:\\Tools\\mimikatz.exe",
+                Some("UEME_RUNPATH:C:\\Tools\\mimikatz.exe"),
+                Some("2024-01-02T03:06:00.000000000Z")
+            ),
+        ]
+    );
+    assert_eq!(
+        details(&user_assist[0]),
+        json!({"focus_count": 6, "focus_milliseconds": 83000, "run_count": 4})
+    );
+    // XP starts counting runs at 5
+    assert_eq!(details(&user_assist[1]), json!({"run_count": 2}));
+}
+
+#[test]
+fn extracts_the_shimcache_of_windows_10() {
+    let system = hive("SYSTEM");
+    let shimcache = artefacts(&system, "shimcache");
+    assert_eq!(
+        summary(&shimcache),
+        [
+            (
+                "C:\\Users\\Public\\evil.exe",
+                Some("C:\\Users\\Public\\evil.exe"),
+                Some("2024-01-02T03:08:00.000000000Z")
+            ),
+            (
+                "C:\\Windows\\system32\\cmd.exe",
+                Some("C:\\Windows\\system32\\cmd.exe"),
+                Some("2019-12-07T09:09:00.000000000Z")
+            ),
+        ]
+    );
+    assert_eq!(
+        shimcache[0].key,
+        "ControlSet001\\Control\\Session Manager\\AppCompatCache"
+    );
+    // Windows 10 does not record whether a file was executed
+    assert_eq!(details(&shimcache[1]), json!({"position": 1}));
+}
+
+/// Reads `SYSTEM.win7`, or when `damaged` a copy whose ShimCache claims `u32::MAX` entries and
+/// has its entries and paths zeroed, so every entry read past the real ones looks valid.
+fn win7_system(damaged: bool) -> Hive {
+    let mut data = fixture("SYSTEM.win7");
+    if damaged {
+        let header = data
+            .windows(4)
+            .position(|window| window == 0xBADC0FEEu32.to_le_bytes())
+            .unwrap();
+        data[header + 4..header + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+        let paths = [
+            "\\??\\C:\\Users\\Public\\evil.exe",
+            "\\??\\C:\\Windows\\system32\\notepad.exe",
+        ];
+        let entries = 2 * 48 + paths.iter().map(|path| path.len() * 2 + 2).sum::<usize>();
+        data[header + 128..header + 128 + entries].fill(0);
+    }
+    Hive::from_reader(data.as_slice(), "SYSTEM").unwrap()
+}
+
+#[test]
+fn extracts_the_shimcache_of_windows_7() {
+    let shimcache = artefacts(&win7_system(false), "shimcache");
+    assert_eq!(
+        summary(&shimcache),
+        [
+            (
+                "\\??\\C:\\Users\\Public\\evil.exe",
+                Some("\\??\\C:\\Users\\Public\\evil.exe"),
+                Some("2024-01-02T03:08:00.000000000Z")
+            ),
+            (
+                "\\??\\C:\\Windows\\system32\\notepad.exe",
+                Some("\\??\\C:\\Windows\\system32\\notepad.exe"),
+                Some("2009-07-14T01:39:00.000000000Z")
+            ),
+        ]
+    );
+    assert_eq!(
+        details(&shimcache[0]),
+        json!({"executed": true, "position": 0})
+    );
+    assert_eq!(
+        details(&shimcache[1]),
+        json!({"executed": false, "position": 1})
+    );
+
+    // the entries stop where the data does rather than where the count says
+    let shimcache = artefacts(&win7_system(true), "shimcache");
+    assert!(shimcache.len() <= 5, "{} entries", shimcache.len());
+}
+
+#[test]
+fn extracts_amcache_files() {
+    let amcache = hive("Amcache.hve");
+    let files = artefacts(&amcache, "amcache");
+    assert_eq!(
+        summary(&files),
+        [
+            (
+                "evil.exe|1a2b3c4d5e6f7a8b",
+                Some("c:\\users\\public\\evil.exe"),
+                Some("2024-01-02T03:08:30.000000000Z")
+            ),
+            (
+                "1a2b",
+                Some("C:\\Tools\\mimikatz.exe"),
+                Some("2024-01-02T03:08:40.000000000Z")
+            ),
+        ]
+    );
+    // empty values are left out and the SHA1 loses its leading zeros
+    assert_eq!(
+        details(&files[0]),
+        json!({
+            "link_date": "01/02/2024 03:04:05",
+            "name": "evil.exe",
+            "program_id": "0000f519feec486de87ed73cb92d3cac802400000000",
+            "sha1": "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+            "size": 73802,
+            "version": "1.0.0.0",
+        })
+    );
+    assert_eq!(
+        details(&files[1]),
+        json!({
+            "last_modified": "2024-01-02T02:00:00.000000000Z",
+            "product": "Tools",
+            "publisher": "Gentil Kiwi",
+            "sha1": "e3b0c44298fc1c149afbf4c8996fb92427ae41e4",
+            "size": 1250056,
+        })
+    );
+}
+
+#[test]
+fn extracts_bam_and_dam() {
+    let system = hive("SYSTEM");
+    let sid = "S-1-5-21-1004336348-1177238915-682003330-1001";
+    let bam = artefacts(&system, "bam");
+    // the Version and SequenceNumber values are not programs
+    assert_eq!(
+        summary(&bam),
+        [
+            (
+                "\\Device\\HarddiskVolume3\\Windows\\System32\\cmd.exe",
+                Some("\\Device\\HarddiskVolume3\\Windows\\System32\\cmd.exe"),
+                Some("2024-01-02T03:10:00.000000000Z")
+            ),
+            (
+                "\\Device\\HarddiskVolume3\\Users\\Public\\evil.exe",
+                Some("\\Device\\HarddiskVolume3\\Users\\Public\\evil.exe"),
+                Some("2024-01-02T03:11:00.000000000Z")
+            ),
+        ]
+    );
+    assert_eq!(
+        bam[0].key,
+        format!("ControlSet001\\Services\\bam\\State\\UserSettings\\{}", sid)
+    );
+    assert_eq!(details(&bam[0]), json!({ "sid": sid }));
+
+    let dam = artefacts(&system, "dam");
+    assert_eq!(
+        summary(&dam),
+        [(
+            "\\Device\\HarddiskVolume3\\Windows\\explorer.exe",
+            Some("\\Device\\HarddiskVolume3\\Windows\\explorer.exe"),
+            Some("2024-01-02T03:12:00.000000000Z")
+        )]
+    );
+}
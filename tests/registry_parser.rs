@@ -0,0 +1,192 @@
+use flate2::read::GzDecoder;
+use std::io::Read;
+use yoink::collection::parsers::registry_parser::{
+    Hive, REG_BINARY, REG_DWORD, REG_EXPAND_SZ, REG_MULTI_SZ, REG_QWORD, REG_SZ,
+};
+
+/// Reads a hive or log from `tests/fixtures`, see the README there for how each was made.
+fn fixture(name: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    GzDecoder::new(std::fs::File::open(format!("tests/fixtures/{}.gz", name)).unwrap())
+        .read_to_end(&mut data)
+        .unwrap();
+    data
+}
+
+fn hive(name: &str) -> Hive {
+    Hive::from_reader(fixture(name).as_slice(), name).unwrap()
+}
+
+/// The names of the subkeys of a key.
+fn subkey_names(hive: &Hive, path: &str) -> Vec<String> {
+    let key = hive.open(path).unwrap();
+    hive.subkeys(&key)
+        .unwrap()
+        .into_iter()
+        .map(|key| key.name)
+        .collect()
+}
+
+#[test]
+fn reads_keys_through_every_subkey_list() {
+    let system = hive("SYSTEM");
+    assert!(!system.is_dirty());
+    assert_eq!(subkey_names(&system, ""), ["ControlSet001", "Select"]);
+    // the services are split over two lh lists under an ri index root
+    assert_eq!(
+        subkey_names(&system, "ControlSet001\\Services"),
+        ["bam", "dam", "EvilSvc", "Schedule", "Unusual"]
+    );
+    let amcache = hive("Amcache.hve");
+    assert_eq!(
+        subkey_names(
+            &amcache,
+            "Root\\File\\{8bd2e9c5-3b2f-11ee-a7a9-806e6f6e6963}"
+        ),
+        ["1a2b"]
+    );
+    assert_eq!(
+        subkey_names(&amcache, "Root"),
+        ["File", "InventoryApplicationFile"]
+    );
+
+    let ntuser = hive("NTUSER.DAT");
+    // names are matched case-insensitively
+    let key = ntuser
+        .open("software\\MICROSOFT\\windows\\currentversion\\run")
+        .unwrap();
+    assert_eq!(key.name, "Run");
+    assert_eq!(
+        key.path,
+        "Software\\Microsoft\\Windows\\CurrentVersion\\Run"
+    );
+    assert_eq!(
+        key.last_written.as_deref(),
+        Some("2024-01-02T03:07:00.000000000Z")
+    );
+    assert!(ntuser.open("Software\\Missing").is_none());
+    // a name that does not fit in Latin-1 is stored as UTF-16
+    assert_eq!(
+        subkey_names(&ntuser, "Software\\Yoink"),
+        ["\u{30ad}\u{30fc}"]
+    );
+}
+
+#[test]
+fn reads_values_of_every_type() {
+    let ntuser = hive("NTUSER.DAT");
+    let key = ntuser.open("Software\\Yoink").unwrap();
+    let values = ntuser.values(&key).unwrap();
+    let names: Vec<&str> = values.iter().map(|value| value.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "",
+            "Blob",
+            "Expand",
+            "Multi",
+            "Quad",
+            "Zähler",
+            "\u{540d}\u{524d}"
+        ]
+    );
+
+    assert_eq!(values[0].value_type, REG_SZ);
+    assert_eq!(values[0].string().as_deref(), Some("default"));
+    assert_eq!(values[2].value_type, REG_EXPAND_SZ);
+    assert_eq!(values[2].json(), "%USERPROFILE%\\yoink");
+    assert_eq!(values[3].value_type, REG_MULTI_SZ);
+    assert_eq!(values[3].json(), serde_json::json!(["one", "two"]));
+    assert_eq!(values[4].value_type, REG_QWORD);
+    assert_eq!(values[4].qword(), Some(0x0123456789ABCDEF));
+    // four bytes of data are kept in the value's own cell
+    assert_eq!(values[5].value_type, REG_DWORD);
+    assert_eq!(values[5].dword(), Some(0xDEADBEEF));
+    assert_eq!(values[5].string(), None);
+    assert_eq!(values[6].string().as_deref(), Some("\u{5024}"));
+
+    let value = ntuser.value(&key, "zähler").unwrap();
+    assert_eq!(value.json(), 0xDEADBEEFu32);
+    assert!(ntuser.value(&key, "Missing").is_none());
+}
+
+#[test]
+fn joins_the_segments_of_big_data() {
+    let ntuser = hive("NTUSER.DAT");
+    let key = ntuser.open("Software\\Yoink").unwrap();
+    let blob = ntuser.value(&key, "Blob").unwrap();
+    let expected: Vec<u8> = (0..40000).map(|index| (index * 7 % 251) as u8).collect();
+    assert_eq!(blob.value_type, REG_BINARY);
+    assert_eq!(blob.data.len(), expected.len());
+    assert!(blob.data == expected, "the db segments should be joined");
+}
+
+/// The names of the values of the SOFTWARE Run key and when the key was last written.
+fn run_values(hive: &Hive) -> (Vec<String>, Option<String>) {
+    let key = hive
+        .open("Microsoft\\Windows\\CurrentVersion\\Run")
+        .unwrap();
+    let names = hive
+        .values(&key)
+        .unwrap()
+        .into_iter()
+        .map(|value| value.name)
+        .collect();
+    (names, key.last_written)
+}
+
+#[test]
+fn replays_transaction_logs_into_a_dirty_hive() {
+    let mut software = hive("SOFTWARE");
+    assert!(software.is_dirty());
+    assert_eq!(
+        run_values(&software),
+        (
+            vec!["SecurityHealth".to_string()],
+            Some("2024-01-02T03:00:00.000000000Z".to_string())
+        )
+    );
+
+    // the stale entry in LOG1 and the torn one at the end of LOG2 are not replayed, and the
+    // order the logs are given in does not matter
+    let applied = software
+        .recover(&[fixture("SOFTWARE.LOG2"), fixture("SOFTWARE.LOG1")])
+        .unwrap();
+    assert_eq!(applied, 2);
+    assert!(!software.is_dirty());
+    assert_eq!(
+        run_values(&software),
+        (
+            vec!["SecurityHealth".to_string(), "Updater".to_string()],
+            Some("2024-01-02T03:20:00.000000000Z".to_string())
+        )
+    );
+    let key = software
+        .open("Microsoft\\Windows\\CurrentVersion\\Run")
+        .unwrap();
+    assert_eq!(
+        software.value(&key, "Updater").unwrap().string().as_deref(),
+        Some("C:\\Users\\Public\\evil.exe")
+    );
+
+    // a clean hive is left alone
+    assert_eq!(software.recover(&[fixture("SOFTWARE.LOG1")]).unwrap(), 0);
+}
+
+#[test]
+fn leaves_a_dirty_hive_as_it_is_without_usable_logs() {
+    let mut software = hive("SOFTWARE");
+    let mut torn = fixture("SOFTWARE.LOG1");
+    // a changed byte in the pages of the entry that follows on from the hive breaks its hash
+    let last = torn.len() - 1;
+    torn[last] ^= 0xFF;
+    assert_eq!(software.recover(&[torn, b"regf".to_vec()]).unwrap(), 0);
+    assert!(software.is_dirty());
+    assert_eq!(run_values(&software).0, ["SecurityHealth"]);
+}
+
+#[test]
+fn rejects_files_that_are_not_hives() {
+    assert!(Hive::from_reader(&b"regf"[..], "short").is_err());
+    assert!(Hive::from_reader(vec![0u8; 8192].as_slice(), "zeroes").is_err());
+}
@@ -0,0 +1,93 @@
+use std::io::Write;
+use yoink::collection::uploaders::sftp_uploader::{SftpDestination, SftpUploader};
+
+#[test]
+fn parses_host_and_port() {
+    let destination = SftpDestination::parse("sftp://collector@evidence:2222/incoming/host.zip")
+        .expect("destination should parse");
+    assert_eq!(destination.user, "collector");
+    assert_eq!(destination.host, "evidence");
+    assert_eq!(destination.port, 2222);
+    assert_eq!(destination.path, "/incoming/host.zip");
+
+    let destination = SftpDestination::parse("sftp://collector@evidence/host.zip").unwrap();
+    assert_eq!(destination.host, "evidence");
+    assert_eq!(destination.port, 22);
+}
+
+#[test]
+fn parses_ipv6_hosts() {
+    let destination = SftpDestination::parse("sftp://collector@[2001:db8::1]:2222/host.zip")
+        .expect("bracketed address with a port should parse");
+    assert_eq!(destination.host, "2001:db8::1");
+    assert_eq!(destination.port, 2222);
+
+    let destination = SftpDestination::parse("sftp://collector@[::1]/host.zip").unwrap();
+    assert_eq!(destination.host, "::1");
+    assert_eq!(destination.port, 22);
+
+    // without brackets the whole address is the host
+    let destination = SftpDestination::parse("sftp://collector@fe80::1/host.zip").unwrap();
+    assert_eq!(destination.host, "fe80::1");
+    assert_eq!(destination.port, 22);
+}
+
+#[test]
+fn rejects_malformed_destinations() {
+    for destination in [
+        "sftp://collector@[::1/host.zip",
+        "sftp://collector@[::1]2222/host.zip",
+        "sftp://collector@evidence:port/host.zip",
+        "sftp://evidence/host.zip",
+        "sftp://collector@evidence/",
+        "https://evidence/host.zip",
+    ] {
+        assert!(
+            SftpDestination::parse(destination).is_err(),
+            "{} should be rejected",
+            destination
+        );
+    }
+}
+
+/// Uploads to a real OpenSSH server named by `YOINK_SFTP_TEST_DESTINATION`,
+/// `YOINK_SFTP_TEST_KEY` and `YOINK_SFTP_TEST_HOST_KEY`, so it only runs when asked for, e.g.
+/// against a local sshd:
+///
+/// ```text
+/// YOINK_SFTP_TEST_DESTINATION=sftp://$USER@127.0.0.1/tmp/yoink-sftp-test.zip \
+/// YOINK_SFTP_TEST_KEY=~/.ssh/id_ed25519 \
+/// YOINK_SFTP_TEST_HOST_KEY=$(ssh-keygen -lf /etc/ssh/ssh_host_ed25519_key.pub | cut -d' ' -f2) \
+/// cargo test --test sftp_uploader -- --ignored
+/// ```
+#[test]
+#[ignore = "needs an OpenSSH server, see the doc comment"]
+fn uploads_to_openssh_server() {
+    let variable = |name| std::env::var(name).unwrap_or_else(|_| panic!("{} is not set", name));
+    let destination = variable("YOINK_SFTP_TEST_DESTINATION");
+    let key = variable("YOINK_SFTP_TEST_KEY");
+    let host_key = variable("YOINK_SFTP_TEST_HOST_KEY");
+
+    let archive = std::env::temp_dir().join(format!("yoink-sftp-{}.zip", std::process::id()));
+    let contents: Vec<u8> = (0..3 * 1024 * 1024)
+        .map(|byte| (byte % 251) as u8)
+        .collect();
+    std::fs::File::create(&archive)
+        .and_then(|mut file| file.write_all(&contents))
+        .expect("the test archive should be written");
+
+    let uploader = SftpUploader::connect(&destination, &key, &host_key)
+        .expect("the server should accept the pinned host key and the private key");
+    let written = uploader
+        .upload(archive.to_str().unwrap())
+        .expect("the archive should be uploaded");
+    assert_eq!(written, contents.len() as u64);
+    uploader
+        .remove()
+        .expect("the uploaded archive should be removed");
+    std::fs::remove_file(&archive).ok();
+
+    // a host key that does not match must stop the upload before authenticating
+    let wrong_host_key = "SHA256:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+    assert!(SftpUploader::connect(&destination, &key, wrong_host_key).is_err());
+}
@@ -0,0 +1,73 @@
+use yoink::collection::manifest::ManifestEntry;
+use yoink::collection::metadata::FileMetadata;
+use yoink::collection::timeline::{mactime, parse_time_bound, parse_time_end, BodyfileEntry};
+
+fn entry(original_path: &str, metadata: FileMetadata) -> ManifestEntry {
+    ManifestEntry {
+        rule: "test".to_string(),
+        other_rules: Vec::new(),
+        archive_path: String::new(),
+        original_path: original_path.to_string(),
+        size: 0,
+        sha256: String::new(),
+        last_modified: 0,
+        atime_preserved: true,
+        metadata,
+        recovery_confidence: None,
+        start_offset: None,
+    }
+}
+
+#[test]
+fn windows_modes_come_from_the_attributes() {
+    let directory = FileMetadata {
+        attributes: vec!["hidden".to_string(), "directory".to_string()],
+        ..Default::default()
+    };
+    let file = FileMetadata {
+        attributes: vec!["archive".to_string()],
+        // attribute bits are not a Unix mode
+        mode: Some(0x20),
+        ..Default::default()
+    };
+    let mode = |platform: &str, metadata: FileMetadata| {
+        BodyfileEntry::from_manifest(platform, &entry("C:\\Windows:", metadata)).mode
+    };
+    assert_eq!(mode("windows", directory), "d/drwxrwxrwx");
+    assert_eq!(mode("windows", file), "r/rrwxrwxrwx");
+
+    let passwd = FileMetadata {
+        mode: Some(0o100644),
+        ..Default::default()
+    };
+    assert_eq!(mode("linux", passwd), "-rw-r--r--");
+    assert_eq!(mode("linux", FileMetadata::default()), "0");
+}
+
+#[test]
+fn an_end_date_takes_in_the_whole_day() {
+    let end = parse_time_end("2024-02-01").unwrap();
+    assert_eq!(end.timestamp(), 1706831999);
+    assert_eq!(
+        parse_time_bound("2024-02-01").unwrap().timestamp(),
+        1706745600
+    );
+    assert_eq!(
+        parse_time_end("2024-02-01 12:00:00").unwrap().timestamp(),
+        1706788800
+    );
+
+    let evening = BodyfileEntry {
+        name: "evening".to_string(),
+        mtime: 1706828400,
+        ..Default::default()
+    };
+    let next_day = BodyfileEntry {
+        name: "next day".to_string(),
+        mtime: 1706832000,
+        ..Default::default()
+    };
+    let rows = mactime(&[evening, next_day], None, Some(end));
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].file_name, "evening");
+}
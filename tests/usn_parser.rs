@@ -0,0 +1,81 @@
+use flate2::read::GzDecoder;
+use std::io::Read;
+use std::path::PathBuf;
+use yoink::collection::archive::CollectionArchive;
+use yoink::collection::collecter::Collecter;
+use yoink::collection::parsers::mft_parser::parse_collection_mft;
+use yoink::collection::parsers::usn_parser::parse_collection_usn;
+
+/// Collects `paths` from the NTFS image in `tests/fixtures` into a collection in `dir`.
+fn collect(dir: &PathBuf, paths: &str) -> CollectionArchive {
+    std::fs::create_dir_all(dir).unwrap();
+    let image = dir.join("ntfs.img");
+    let mut volume = Vec::new();
+    GzDecoder::new(std::fs::File::open("tests/fixtures/ntfs.img.gz").unwrap())
+        .read_to_end(&mut volume)
+        .unwrap();
+    std::fs::write(&image, volume).unwrap();
+    let rule = dir.join("rule.yaml");
+    std::fs::write(
+        &rule,
+        format!(
+            "name: ntfs\ndescription: test\nplatform: windows\nrule_type: file\npaths: [{}]\nrecursion_depth: 0\n",
+            paths
+        ),
+    )
+    .unwrap();
+    let mut collecter = Collecter::from_image(image.to_str().unwrap(), None).unwrap();
+    collecter
+        .add_rule_from_file(rule.to_str().unwrap())
+        .unwrap();
+    collecter.collect_by_rulename("ntfs").unwrap();
+    let output = dir.join("collection.zip");
+    collecter
+        .compress_collection(output.to_str().unwrap())
+        .unwrap();
+    CollectionArchive::open(output.to_str().unwrap(), None).unwrap()
+}
+
+fn journal_paths(collection: &mut CollectionArchive) -> Vec<(String, Option<String>)> {
+    let mut paths = Vec::new();
+    parse_collection_usn(collection, None, |entry| {
+        paths.push((entry.name, entry.path));
+        Ok(())
+    })
+    .unwrap();
+    paths
+}
+
+#[test]
+fn resolves_journal_paths_against_the_mft_of_the_volume() {
+    let dir = std::env::temp_dir().join(format!("yoink-usn-{}", std::process::id()));
+    let mut collection = collect(&dir, "\"C:\\\\$MFT\", \"C:\\\\$Extend\\\\$UsnJrnl:$J\"");
+    assert_eq!(
+        journal_paths(&mut collection),
+        vec![
+            ("notes.txt".to_string(), Some("C:\\notes.txt".to_string())),
+            ("notes.txt".to_string(), Some("C:\\notes.txt".to_string())),
+            (
+                "deleted.txt".to_string(),
+                Some("C:\\deleted.txt".to_string())
+            ),
+        ]
+    );
+
+    let mut records = Vec::new();
+    parse_collection_mft(&mut collection, |entry| {
+        records.push(entry.path);
+        Ok(())
+    })
+    .unwrap();
+    assert!(records.contains(&"C:\\docs\\fragmented.bin".to_string()));
+    std::fs::remove_dir_all(&dir).ok();
+
+    // without the $MFT only the names are known
+    let dir = std::env::temp_dir().join(format!("yoink-usn-only-{}", std::process::id()));
+    let mut collection = collect(&dir, "\"C:\\\\$Extend\\\\$UsnJrnl:$J\"");
+    let paths = journal_paths(&mut collection);
+    assert_eq!(paths.len(), 3);
+    assert!(paths.iter().all(|(_, path)| path.is_none()));
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,119 @@
+use flate2::read::GzDecoder;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use yoink::collection::image::DiskImage;
+use yoink::collection::readers::vhd_reader::VhdReader;
+
+/// Unpacks a fixture from `tests/fixtures` into the temp directory, see the README there for
+/// how each was made.
+fn unpack(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("yoink-{}-{}", std::process::id(), name));
+    let mut data = Vec::new();
+    GzDecoder::new(std::fs::File::open(format!("tests/fixtures/{}.gz", name)).unwrap())
+        .read_to_end(&mut data)
+        .unwrap();
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+fn disk() -> Vec<u8> {
+    let mut disk = Vec::new();
+    GzDecoder::new(std::fs::File::open("tests/fixtures/ext4.img.gz").unwrap())
+        .read_to_end(&mut disk)
+        .unwrap();
+    disk
+}
+
+#[test]
+fn reads_fixed_disks() {
+    let path = unpack("fixed.vhd");
+    let disk = disk();
+    let mut reader = VhdReader::open(path.to_str().unwrap()).unwrap();
+    assert_eq!(reader.size(), disk.len() as u64);
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).unwrap();
+    assert!(data == disk, "the fixed VHD should read back as the disk");
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn reads_unallocated_blocks_as_zeros() {
+    let path = unpack("dynamic.vhd");
+    let disk = disk();
+    let mut reader = VhdReader::open(path.to_str().unwrap()).unwrap();
+    assert_eq!(reader.size(), disk.len() as u64);
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).unwrap();
+    assert!(data == disk, "the dynamic VHD should read back as the disk");
+
+    // a read across the 64 KiB block boundary
+    let mut buf = vec![0u8; 4096];
+    reader.seek(SeekFrom::Start(0x10000 - 1000)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert!(buf == disk[0x10000 - 1000..0x10000 + 3096]);
+
+    let mut image = DiskImage::open(path.to_str().unwrap()).unwrap();
+    let file = image.open_file("/tiny.txt").unwrap();
+    let mut data = Vec::new();
+    image.copy_file(&file, &mut data, false).unwrap();
+    assert_eq!(data, b"tiny\n");
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn names_the_parent_of_differencing_disks() {
+    let path = unpack("differencing.vhd");
+    let error = VhdReader::open(path.to_str().unwrap())
+        .err()
+        .expect("differencing VHDs should be refused")
+        .to_string();
+    assert!(error.contains(path.to_str().unwrap()), "{}", error);
+    assert!(
+        error.contains("differencing VHD of parent.vhd"),
+        "{}",
+        error
+    );
+    std::fs::remove_file(path).ok();
+}
+
+/// Unpacks `dynamic.vhd` with `edit` applied to its dynamic disk header.
+fn with_dynamic_header(name: &str, edit: impl Fn(&mut [u8])) -> PathBuf {
+    let damaged = std::env::temp_dir().join(format!("yoink-{}-{}", std::process::id(), name));
+    let mut data = Vec::new();
+    GzDecoder::new(std::fs::File::open("tests/fixtures/dynamic.vhd.gz").unwrap())
+        .read_to_end(&mut data)
+        .unwrap();
+    let footer = data.len() - 512;
+    let header = u64::from_be_bytes(data[footer + 16..footer + 24].try_into().unwrap()) as usize;
+    edit(&mut data[header..header + 1024]);
+    std::fs::write(&damaged, data).unwrap();
+    damaged
+}
+
+#[test]
+fn reads_only_the_table_entries_of_the_disk_size() {
+    let path = with_dynamic_header("many-entries.vhd", |header| {
+        header[28..32].copy_from_slice(&u32::MAX.to_be_bytes())
+    });
+    let mut reader = VhdReader::open(path.to_str().unwrap()).unwrap();
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).unwrap();
+    assert!(
+        data == disk(),
+        "the dynamic VHD should read back as the disk"
+    );
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn rejects_a_table_past_the_end_of_the_file() {
+    let path = with_dynamic_header("far-table.vhd", |header| {
+        header[16..24].copy_from_slice(&(1u64 << 40).to_be_bytes())
+    });
+    let error = VhdReader::open(path.to_str().unwrap())
+        .err()
+        .expect("the table is not in the file")
+        .to_string();
+    assert!(error.contains("past the end of the file"), "{}", error);
+    std::fs::remove_file(path).ok();
+}
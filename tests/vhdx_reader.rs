@@ -0,0 +1,110 @@
+use flate2::read::GzDecoder;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use yoink::collection::image::DiskImage;
+use yoink::collection::readers::vhdx_reader::VhdxReader;
+
+/// Unpacks a fixture from `tests/fixtures` into the temp directory, see the README there for
+/// how each was made.
+fn unpack(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("yoink-{}-{}", std::process::id(), name));
+    let mut data = Vec::new();
+    GzDecoder::new(std::fs::File::open(format!("tests/fixtures/{}.gz", name)).unwrap())
+        .read_to_end(&mut data)
+        .unwrap();
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+fn disk() -> Vec<u8> {
+    let mut disk = Vec::new();
+    GzDecoder::new(std::fs::File::open("tests/fixtures/ext4.img.gz").unwrap())
+        .read_to_end(&mut disk)
+        .unwrap();
+    disk
+}
+
+#[test]
+fn reads_dynamic_disks() {
+    let path = unpack("dynamic.vhdx");
+    let disk = disk();
+    let mut reader = VhdxReader::open(path.to_str().unwrap()).unwrap();
+    // the virtual disk is 4 MiB, the blocks past the filesystem are not present
+    assert_eq!(reader.size(), 4 * 1024 * 1024);
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).unwrap();
+    assert!(
+        data[..disk.len()] == disk,
+        "the VHDX should read back as the disk"
+    );
+    assert!(data[disk.len()..].iter().all(|byte| *byte == 0));
+
+    let mut buf = vec![0xffu8; 100];
+    reader.seek(SeekFrom::End(-100)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, vec![0u8; 100]);
+    assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+    let mut image = DiskImage::open(path.to_str().unwrap()).unwrap();
+    let file = image.open_file("/tiny.txt").unwrap();
+    let mut data = Vec::new();
+    image.copy_file(&file, &mut data, false).unwrap();
+    assert_eq!(data, b"tiny\n");
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn names_the_parent_of_differencing_disks() {
+    let path = unpack("differencing.vhdx");
+    let error = VhdxReader::open(path.to_str().unwrap())
+        .err()
+        .expect("differencing VHDXs should be refused")
+        .to_string();
+    assert!(error.contains(path.to_str().unwrap()), "{}", error);
+    assert!(
+        error.contains("differencing VHDX of parent.vhdx"),
+        "{}",
+        error
+    );
+    std::fs::remove_file(path).ok();
+}
+
+/// Unpacks `dynamic.vhdx` with the length of its metadata or block allocation table region
+/// changed to `length`.
+fn with_region_length(name: &str, metadata: bool, length: u32) -> PathBuf {
+    const METADATA_REGION: [u8; 16] = [
+        0x06, 0xA2, 0x7C, 0x8B, 0x90, 0x47, 0x9A, 0x4B, 0xB8, 0xFE, 0x57, 0x5F, 0x05, 0x0F, 0x88,
+        0x6E,
+    ];
+    let damaged = std::env::temp_dir().join(format!("yoink-{}-{}", std::process::id(), name));
+    let mut data = Vec::new();
+    GzDecoder::new(std::fs::File::open("tests/fixtures/dynamic.vhdx.gz").unwrap())
+        .read_to_end(&mut data)
+        .unwrap();
+    let table = 0x30000;
+    let count = u32::from_le_bytes(data[table + 8..table + 12].try_into().unwrap()) as usize;
+    let entry = (0..count)
+        .map(|index| table + 16 + index * 32)
+        .find(|entry| (data[*entry..*entry + 16] == METADATA_REGION) == metadata)
+        .unwrap();
+    data[entry + 24..entry + 28].copy_from_slice(&length.to_le_bytes());
+    std::fs::write(&damaged, data).unwrap();
+    damaged
+}
+
+#[test]
+fn rejects_damaged_region_tables() {
+    for (name, metadata, length) in [
+        ("short-metadata.vhdx", true, 16),
+        ("huge-metadata.vhdx", true, u32::MAX),
+        ("huge-table.vhdx", false, u32::MAX),
+    ] {
+        let path = with_region_length(name, metadata, length);
+        assert!(
+            VhdxReader::open(path.to_str().unwrap()).is_err(),
+            "{}",
+            name
+        );
+        std::fs::remove_file(path).ok();
+    }
+}